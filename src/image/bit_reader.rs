@@ -1,11 +1,18 @@
 use anyhow::Result;
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::fmt::Debug;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+pub trait Source: Read + Seek + Debug {}
+
+impl<T: Read + Seek + Debug> Source for T {}
+
+// Files and in-memory buffers alike, so that embedded images can be decoded
+pub type Stream = BufReader<Box<dyn Source>>;
 
 pub trait BitReader {
-    fn new(stream: BufReader<File>) -> Self;
+    fn new(stream: Stream) -> Self;
     fn set_next_bit(&mut self, next_bit: usize) -> ();
-    fn stream(&mut self) -> &mut BufReader<File>;
+    fn stream(&mut self) -> &mut Stream;
     fn read_bit(&mut self) -> Result<u8>;
 
     fn read_byte(&mut self) -> Result<u8> {
@@ -40,6 +47,30 @@ pub trait BitReader {
         Ok(bits)
     }
 
+    fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>> {
+        self.set_next_bit(0);
+        let mut buffer: Vec<u8> = vec![0; length];
+        self.stream().read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn position(&mut self) -> Result<u64> {
+        Ok(self.stream().stream_position()?)
+    }
+
+    fn length(&mut self) -> Result<u64> {
+        let position: u64 = self.position()?;
+        let length: u64 = self.stream().seek(SeekFrom::End(0))?;
+        self.stream().seek(SeekFrom::Start(position))?;
+        Ok(length)
+    }
+
+    fn seek(&mut self, position: u64) -> Result<()> {
+        self.set_next_bit(0);
+        self.stream().seek(SeekFrom::Start(position))?;
+        Ok(())
+    }
+
     fn align(&mut self) -> () {
         self.set_next_bit(0);
     }
@@ -1,18 +1,16 @@
 use anyhow::Result;
-use std::fs::File;
-use std::io::BufReader;
 
-use crate::image::bit_reader::BitReader;
+use crate::image::bit_reader::{BitReader, Stream};
 
 #[derive(Debug)]
 pub struct BmpBitReader {
     next_bit: usize,
     current_byte: u8,
-    stream: BufReader<File>,
+    stream: Stream,
 }
 
 impl BitReader for BmpBitReader {
-    fn new(stream: BufReader<File>) -> Self {
+    fn new(stream: Stream) -> Self {
         Self {
             next_bit: 0,
             current_byte: 0,
@@ -36,7 +34,7 @@ impl BitReader for BmpBitReader {
         self.next_bit = next_bit;
     }
 
-    fn stream(&mut self) -> &mut BufReader<File> {
+    fn stream(&mut self) -> &mut Stream {
         &mut self.stream
     }
 }
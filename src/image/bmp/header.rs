@@ -1,21 +1,111 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::fmt;
 
 use crate::image::bit_reader::BitReader;
 use super::bmp_bit_reader::BmpBitReader;
 
+pub const CORE_HEADER_SIZE: u32 = 12;
+pub const INFO_HEADER_SIZE: u32 = 40;
+pub const V2_HEADER_SIZE: u32 = 52;
+pub const V3_HEADER_SIZE: u32 = 56;
+pub const V4_HEADER_SIZE: u32 = 108;
+pub const V5_HEADER_SIZE: u32 = 124;
+pub const OS2_HEADER_SIZE: u32 = 64;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BMPCompression {
+    #[default]
+    RGB,
+    RLE8,
+    RLE4,
+    BITFIELDS,
+    JPEG,
+    PNG,
+    ALPHABITFIELDS,
+    // OS/2 2.x only, sharing their values with BITFIELDS and JPEG
+    HUFFMAN1D,
+    RLE24,
+}
+
+impl BMPCompression {
+    fn from_value(value: u32, os2: bool) -> Result<Self> {
+        match (value, os2) {
+            (0, _)     => Ok(BMPCompression::RGB),
+            (1, _)     => Ok(BMPCompression::RLE8),
+            (2, _)     => Ok(BMPCompression::RLE4),
+            (3, false) => Ok(BMPCompression::BITFIELDS),
+            (3, true)  => Ok(BMPCompression::HUFFMAN1D),
+            (4, false) => Ok(BMPCompression::JPEG),
+            (4, true)  => Ok(BMPCompression::RLE24),
+            (5, false) => Ok(BMPCompression::PNG),
+            (6, false) => Ok(BMPCompression::ALPHABITFIELDS),
+            _          => bail!("BMP compression {value} not supported"),
+        }
+    }
+}
+
+// Color space of V4 and V5 headers
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ColorSpace {
+    // Endpoints of the red, green and blue primaries as CIE XYZ coordinates, followed by the gammas
+    Calibrated {
+        endpoints: [[f32; 3]; 3],
+        gammas: [f32; 3],
+    },
+    #[default]
+    SRGB,
+    Windows,
+    // ICC profile stored in a separate file
+    Linked(String),
+    Embedded(Vec<u8>),
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct BMPHeader {
     pub bmp_size: u32,
     pub header_size: u32,
-    pub height: u16,
-    pub width: u16,
+    pub height: u32,
+    pub width: u32,
+    // Negative height of the info headers, the first row of the pixel array being the top one
+    pub top_down: bool,
     pub components_number: u16,
     pub starting_offset: u32,
     pub bits_per_pixel: u16,
+    pub compression: BMPCompression,
+    pub image_size: u32,
+    pub horizontal_resolution: i32,
+    pub vertical_resolution: i32,
+    pub colors_used: u32,
+    pub colors_important: u32,
+    pub red_mask: u32,
+    pub green_mask: u32,
+    pub blue_mask: u32,
+    pub alpha_mask: u32,
+    pub color_space: ColorSpace,
+    pub intent: u32,
+    // Colors of indexed images, as red, green and blue
+    pub palette: Vec<(u8, u8, u8)>,
 }
 
 impl BMPHeader {
+    pub fn new(width: u32, height: u32) -> Self {
+        let padding_size: u64 = width as u64 % 4;
+        // Saturated, as the size field can't hold the size of the largest images
+        let bmp_size: u64 = 14u64 + 12u64 + width as u64 * height as u64 * 3 + padding_size * height as u64;
+        let bmp_size: u32 = bmp_size.min(u32::MAX as u64) as u32;
+
+        Self {
+            bmp_size,
+            header_size: CORE_HEADER_SIZE,
+            height,
+            width,
+            components_number: 1u16,
+            starting_offset: 0x1Au32,
+            bits_per_pixel: 24u16,
+            ..Self::default()
+        }
+    }
+
     pub fn from_binary(reader: &mut BmpBitReader) -> Result<Self> {
         let mut header: BMPHeader = BMPHeader::default();
         let mut count: u32 = 0;
@@ -26,33 +116,247 @@ impl BMPHeader {
         header.bmp_size = reader.read_double()?.swap_bytes();
         count += 4;
 
-        assert_eq!(0u32, reader.read_double()?);
+        // Reserved, used by OS/2 icons and pointers for their hotspot
+        reader.read_double()?;
         count += 4;
 
         header.starting_offset = reader.read_double()?.swap_bytes();
         count += 4;
 
         header.header_size = reader.read_double()?.swap_bytes();
-        assert_eq!(12u32, header.header_size);
         count += 4;
 
-        header.width = reader.read_word()?.swap_bytes();
-        count += 2;
-
-        header.height = reader.read_word()?.swap_bytes();
-        count += 2;
+        count += match header.header_size {
+            CORE_HEADER_SIZE => header.read_core_header(reader)?,
+            INFO_HEADER_SIZE |
+            V2_HEADER_SIZE |
+            V3_HEADER_SIZE |
+            V4_HEADER_SIZE |
+            V5_HEADER_SIZE => header.read_info_header(reader)?,
+            16..=OS2_HEADER_SIZE => header.read_os2_header(reader)?,
+            size => bail!("BMP header of {size} bytes not supported"),
+        };
 
-        header.components_number = reader.read_word()?.swap_bytes();
         assert_eq!(1u16, header.components_number);
-        count += 2;
 
-        header.bits_per_pixel = reader.read_word()?.swap_bytes();
-        count += 2;
+        // Masks following an info header, they are part of the V2 and later headers
+        if header.header_size == INFO_HEADER_SIZE {
+            if header.compression == BMPCompression::BITFIELDS || header.compression == BMPCompression::ALPHABITFIELDS {
+                header.red_mask = reader.read_double()?.swap_bytes();
+                header.green_mask = reader.read_double()?.swap_bytes();
+                header.blue_mask = reader.read_double()?.swap_bytes();
+                count += 12;
+            }
+
+            if header.compression == BMPCompression::ALPHABITFIELDS {
+                header.alpha_mask = reader.read_double()?.swap_bytes();
+                count += 4;
+            }
+        }
+
+        count += header.read_palette(reader)?;
 
-        assert_eq!(header.starting_offset, count);
+        if header.starting_offset < count {
+            bail!("BMP pixel array at {:X} overlaps the header", header.starting_offset);
+        }
 
         Ok(header)
     }
+
+    // RGBTRIPLE entries after a core header, RGBQUAD entries after the others
+    fn read_palette(&mut self, reader: &mut BmpBitReader) -> Result<u32> {
+        if self.bits_per_pixel > 8 {
+            return Ok(0);
+        }
+
+        let entry_size: u32 = if self.header_size == CORE_HEADER_SIZE { 3 } else { 4 };
+        let max_colors: u32 = 1 << self.bits_per_pixel;
+        let mut colors: u32 = match self.colors_used {
+            0 => max_colors,
+            colors_used => colors_used.min(max_colors),
+        };
+
+        // Some encoders announce more colors than they have room for before the pixel array
+        let position: u32 = reader.position()? as u32;
+        colors = colors.min(self.starting_offset.saturating_sub(position) / entry_size);
+
+        for _ in 0..colors {
+            let blue: u8 = reader.read_byte()?;
+            let green: u8 = reader.read_byte()?;
+            let red: u8 = reader.read_byte()?;
+
+            if entry_size == 4 {
+                reader.read_byte()?;
+            }
+
+            self.palette.push((red, green, blue));
+        }
+
+        Ok(colors * entry_size)
+    }
+
+    // Red, green, blue and alpha masks of 16 and 32 bits pixels, the color masks of the header being used only with bitfields
+    pub fn color_masks(&self) -> (u32, u32, u32, u32) {
+        match (self.compression, self.bits_per_pixel) {
+            (BMPCompression::BITFIELDS | BMPCompression::ALPHABITFIELDS, _) => (self.red_mask, self.green_mask, self.blue_mask, self.alpha_mask),
+            (_, 16) => (0x7C00, 0x03E0, 0x001F, 0),
+            // Alpha mask of V3 and later headers still applies to uncompressed pixels
+            _ if self.header_size >= V3_HEADER_SIZE => (0x00FF0000, 0x0000FF00, 0x000000FF, self.alpha_mask),
+            _ => (0x00FF0000, 0x0000FF00, 0x000000FF, 0),
+        }
+    }
+
+    // OS/2 1.x BITMAPCOREHEADER, with 16 bits dimensions
+    fn read_core_header(&mut self, reader: &mut BmpBitReader) -> Result<u32> {
+        self.width = reader.read_word()?.swap_bytes() as u32;
+        self.height = reader.read_word()?.swap_bytes() as u32;
+        self.components_number = reader.read_word()?.swap_bytes();
+        self.bits_per_pixel = reader.read_word()?.swap_bytes();
+
+        Ok(8)
+    }
+
+    // BITMAPINFOHEADER and its V2 to V5 extensions
+    fn read_info_header(&mut self, reader: &mut BmpBitReader) -> Result<u32> {
+        let mut count: u32 = self.read_common_fields(reader, INFO_HEADER_SIZE, false)?;
+
+        if self.header_size >= V2_HEADER_SIZE {
+            self.red_mask = reader.read_double()?.swap_bytes();
+            self.green_mask = reader.read_double()?.swap_bytes();
+            self.blue_mask = reader.read_double()?.swap_bytes();
+            count += 12;
+        }
+
+        if self.header_size >= V3_HEADER_SIZE {
+            self.alpha_mask = reader.read_double()?.swap_bytes();
+            count += 4;
+        }
+
+        if self.header_size >= V4_HEADER_SIZE {
+            count += self.read_color_space(reader)?;
+        }
+
+        if self.header_size >= V5_HEADER_SIZE {
+            self.intent = reader.read_double()?.swap_bytes();
+            let profile_data: u32 = reader.read_double()?.swap_bytes();
+            let profile_size: u32 = reader.read_double()?.swap_bytes();
+            reader.read_double()?;
+            count += 16;
+
+            self.read_profile(reader, profile_data, profile_size)?;
+        }
+
+        Ok(count)
+    }
+
+    // OS/2 2.x BITMAPINFOHEADER2, which can be truncated after any field
+    fn read_os2_header(&mut self, reader: &mut BmpBitReader) -> Result<u32> {
+        let mut count: u32 = self.read_common_fields(reader, self.header_size.min(INFO_HEADER_SIZE), true)?;
+
+        // Units, reserved, recording algorithm, halftoning algorithm and parameters, color encoding and identifier
+        while count < self.header_size - 4 {
+            reader.read_byte()?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    // Fields shared by the info headers, up to the given header size
+    fn read_common_fields(&mut self, reader: &mut BmpBitReader, size: u32, os2: bool) -> Result<u32> {
+        let width: i32 = reader.read_double()?.swap_bytes() as i32;
+        let height: i32 = reader.read_double()?.swap_bytes() as i32;
+
+        if width <= 0 {
+            bail!("BMP width {width} is invalid");
+        }
+
+        if height == 0 {
+            bail!("BMP height {height} is invalid");
+        }
+
+        self.width = width as u32;
+        self.height = height.unsigned_abs();
+        self.top_down = height < 0;
+        self.components_number = reader.read_word()?.swap_bytes();
+        self.bits_per_pixel = reader.read_word()?.swap_bytes();
+        let mut count: u32 = 12;
+
+        let mut fields: [u32; 6] = [0; 6];
+
+        for field in fields.iter_mut() {
+            if count + 4 + 4 > size {
+                break;
+            }
+
+            *field = reader.read_double()?.swap_bytes();
+            count += 4;
+        }
+
+        let [compression, image_size, horizontal_resolution, vertical_resolution, colors_used, colors_important] = fields;
+        self.compression = BMPCompression::from_value(compression, os2)?;
+        self.image_size = image_size;
+        self.horizontal_resolution = horizontal_resolution as i32;
+        self.vertical_resolution = vertical_resolution as i32;
+        self.colors_used = colors_used;
+        self.colors_important = colors_important;
+
+        Ok(count)
+    }
+
+    fn read_color_space(&mut self, reader: &mut BmpBitReader) -> Result<u32> {
+        let color_space_type: u32 = reader.read_double()?.swap_bytes();
+
+        // FXPT2DOT30 endpoints and 16.16 fixed point gammas
+        let mut endpoints: [[f32; 3]; 3] = [[0f32; 3]; 3];
+
+        for endpoint in endpoints.iter_mut() {
+            for coordinate in endpoint.iter_mut() {
+                *coordinate = reader.read_double()?.swap_bytes() as i32 as f32 / (1 << 30) as f32;
+            }
+        }
+
+        let mut gammas: [f32; 3] = [0f32; 3];
+
+        for gamma in gammas.iter_mut() {
+            *gamma = reader.read_double()?.swap_bytes() as f32 / (1 << 16) as f32;
+        }
+
+        self.color_space = match &color_space_type.to_be_bytes() {
+            [0, 0, 0, 0] => ColorSpace::Calibrated { endpoints, gammas },
+            b"Win " => ColorSpace::Windows,
+            // Profiles are read with the rest of the V5 header
+            b"LINK" => ColorSpace::Linked(String::new()),
+            b"MBED" => ColorSpace::Embedded(Vec::new()),
+            _ => ColorSpace::SRGB,
+        };
+
+        Ok(4 + 36 + 12)
+    }
+
+    // Profile data is located from the start of the info header, possibly after the pixel array
+    fn read_profile(&mut self, reader: &mut BmpBitReader, profile_data: u32, profile_size: u32) -> Result<()> {
+        let embedded: bool = match &self.color_space {
+            ColorSpace::SRGB | ColorSpace::Windows | ColorSpace::Calibrated { .. } => return Ok(()),
+            ColorSpace::Embedded(_) => true,
+            ColorSpace::Linked(_) => false,
+        };
+
+        let position: u64 = reader.position()?;
+        reader.seek(14 + profile_data as u64)?;
+        let profile: Vec<u8> = reader.read_bytes(profile_size as usize)?;
+        reader.seek(position)?;
+
+        self.color_space = if embedded {
+            ColorSpace::Embedded(profile)
+        } else {
+            // Windows-1252 file name, null terminated
+            let name: String = profile.iter().take_while(|&&c| c != 0).map(|&c| c as char).collect();
+            ColorSpace::Linked(name)
+        };
+
+        Ok(())
+    }
 }
 
 impl fmt::Display for BMPHeader {
@@ -62,8 +366,25 @@ impl fmt::Display for BMPHeader {
         write!(f, "header_size: {:04X}\n", self.header_size)?;
         write!(f, "width: {:02X}\n", self.width)?;
         write!(f, "height: {:02X}\n", self.height)?;
+        write!(f, "top_down: {}\n", self.top_down)?;
         write!(f, "components_number: {:02X}\n", self.components_number)?;
         write!(f, "bits_per_pixel: {:02X}\n", self.bits_per_pixel)?;
+        write!(f, "compression: {:?}\n", self.compression)?;
+        write!(f, "image_size: {:04X}\n", self.image_size)?;
+        write!(f, "resolution: {}x{}\n", self.horizontal_resolution, self.vertical_resolution)?;
+        write!(f, "colors_used: {}\n", self.colors_used)?;
+        write!(f, "colors_important: {}\n", self.colors_important)?;
+        write!(f, "masks: {:08X} {:08X} {:08X} {:08X}\n", self.red_mask, self.green_mask, self.blue_mask, self.alpha_mask)?;
+
+        match &self.color_space {
+            ColorSpace::Calibrated { endpoints, gammas } => write!(f, "color_space: calibrated {endpoints:?} {gammas:?}\n")?,
+            ColorSpace::Linked(name) => write!(f, "color_space: linked profile \"{name}\"\n")?,
+            ColorSpace::Embedded(profile) => write!(f, "color_space: embedded profile of {} bytes\n", profile.len())?,
+            color_space => write!(f, "color_space: {color_space:?}\n")?,
+        }
+
+        write!(f, "intent: {}\n", self.intent)?;
+        write!(f, "palette: {} colors\n", self.palette.len())?;
         Ok(())
     }
 }
@@ -0,0 +1,143 @@
+use anyhow::{bail, Result};
+
+use crate::image::bit_reader::BitReader;
+use super::BMP;
+use super::bmp_bit_reader::BmpBitReader;
+
+// Lengths and codes of the white runs of 0 to 63 pixels
+const WHITE_TERMINATING: [(u8, u16); 64] = [
+    (8, 0b00110101), (6, 0b000111), (4, 0b0111), (4, 0b1000),
+    (4, 0b1011), (4, 0b1100), (4, 0b1110), (4, 0b1111),
+    (5, 0b10011), (5, 0b10100), (5, 0b00111), (5, 0b01000),
+    (6, 0b001000), (6, 0b000011), (6, 0b110100), (6, 0b110101),
+    (6, 0b101010), (6, 0b101011), (7, 0b0100111), (7, 0b0001100),
+    (7, 0b0001000), (7, 0b0010111), (7, 0b0000011), (7, 0b0000100),
+    (7, 0b0101000), (7, 0b0101011), (7, 0b0010011), (7, 0b0100100),
+    (7, 0b0011000), (8, 0b00000010), (8, 0b00000011), (8, 0b00011010),
+    (8, 0b00011011), (8, 0b00010010), (8, 0b00010011), (8, 0b00010100),
+    (8, 0b00010101), (8, 0b00010110), (8, 0b00010111), (8, 0b00101000),
+    (8, 0b00101001), (8, 0b00101010), (8, 0b00101011), (8, 0b00101100),
+    (8, 0b00101101), (8, 0b00000100), (8, 0b00000101), (8, 0b00001010),
+    (8, 0b00001011), (8, 0b01010010), (8, 0b01010011), (8, 0b01010100),
+    (8, 0b01010101), (8, 0b00100100), (8, 0b00100101), (8, 0b01011000),
+    (8, 0b01011001), (8, 0b01011010), (8, 0b01011011), (8, 0b01001010),
+    (8, 0b01001011), (8, 0b00110010), (8, 0b00110011), (8, 0b00110100),
+];
+
+// Lengths and codes of the white runs of 64 to 1728 pixels, by steps of 64
+const WHITE_MAKEUP: [(u8, u16); 27] = [
+    (5, 0b11011), (5, 0b10010), (6, 0b010111), (7, 0b0110111),
+    (8, 0b00110110), (8, 0b00110111), (8, 0b01100100), (8, 0b01100101),
+    (8, 0b01101000), (8, 0b01100111), (9, 0b011001100), (9, 0b011001101),
+    (9, 0b011010010), (9, 0b011010011), (9, 0b011010100), (9, 0b011010101),
+    (9, 0b011010110), (9, 0b011010111), (9, 0b011011000), (9, 0b011011001),
+    (9, 0b011011010), (9, 0b011011011), (9, 0b010011000), (9, 0b010011001),
+    (9, 0b010011010), (6, 0b011000), (9, 0b010011011),
+];
+
+// Lengths and codes of the black runs of 0 to 63 pixels
+const BLACK_TERMINATING: [(u8, u16); 64] = [
+    (10, 0b0000110111), (3, 0b010), (2, 0b11), (2, 0b10),
+    (3, 0b011), (4, 0b0011), (4, 0b0010), (5, 0b00011),
+    (6, 0b000101), (6, 0b000100), (7, 0b0000100), (7, 0b0000101),
+    (7, 0b0000111), (8, 0b00000100), (8, 0b00000111), (9, 0b000011000),
+    (10, 0b0000010111), (10, 0b0000011000), (10, 0b0000001000), (11, 0b00001100111),
+    (11, 0b00001101000), (11, 0b00001101100), (11, 0b00000110111), (11, 0b00000101000),
+    (11, 0b00000010111), (11, 0b00000011000), (12, 0b000011001010), (12, 0b000011001011),
+    (12, 0b000011001100), (12, 0b000011001101), (12, 0b000001101000), (12, 0b000001101001),
+    (12, 0b000001101010), (12, 0b000001101011), (12, 0b000011010010), (12, 0b000011010011),
+    (12, 0b000011010100), (12, 0b000011010101), (12, 0b000011010110), (12, 0b000011010111),
+    (12, 0b000001101100), (12, 0b000001101101), (12, 0b000011011010), (12, 0b000011011011),
+    (12, 0b000001010100), (12, 0b000001010101), (12, 0b000001010110), (12, 0b000001010111),
+    (12, 0b000001100100), (12, 0b000001100101), (12, 0b000001010010), (12, 0b000001010011),
+    (12, 0b000000100100), (12, 0b000000110111), (12, 0b000000111000), (12, 0b000000100111),
+    (12, 0b000000101000), (12, 0b000001011000), (12, 0b000001011001), (12, 0b000000101011),
+    (12, 0b000000101100), (12, 0b000001011010), (12, 0b000001100110), (12, 0b000001100111),
+];
+
+// Lengths and codes of the black runs of 64 to 1728 pixels, by steps of 64
+const BLACK_MAKEUP: [(u8, u16); 27] = [
+    (10, 0b0000001111), (12, 0b000011001000), (12, 0b000011001001), (12, 0b000001011011),
+    (12, 0b000000110011), (12, 0b000000110100), (12, 0b000000110101), (13, 0b0000001101100),
+    (13, 0b0000001101101), (13, 0b0000001001010), (13, 0b0000001001011), (13, 0b0000001001100),
+    (13, 0b0000001001101), (13, 0b0000001110010), (13, 0b0000001110011), (13, 0b0000001110100),
+    (13, 0b0000001110101), (13, 0b0000001110110), (13, 0b0000001110111), (13, 0b0000001010010),
+    (13, 0b0000001010011), (13, 0b0000001010100), (13, 0b0000001010101), (13, 0b0000001011010),
+    (13, 0b0000001011011), (13, 0b0000001100100), (13, 0b0000001100101),
+];
+
+// Lengths and codes of the runs of 1792 to 2560 pixels shared by both colors
+const EXTENDED_MAKEUP: [(u8, u16); 13] = [
+    (11, 0b00000001000), (11, 0b00000001100), (11, 0b00000001101), (12, 0b000000010010),
+    (12, 0b000000010011), (12, 0b000000010100), (12, 0b000000010101), (12, 0b000000010110),
+    (12, 0b000000010111), (12, 0b000000011100), (12, 0b000000011101), (12, 0b000000011110),
+    (12, 0b000000011111),
+];
+
+impl BMP {
+    // OS/2 bitmaps of 1 bit per pixel coded as CCITT T.4 one-dimensional runs
+    // Each row starts with a white run, of the first color of the table, alternating with black runs
+    pub(super) fn read_huffman_1d(&mut self, reader: &mut BmpBitReader) -> Result<()> {
+        let white: (u8, u8, u8) = self.palette_color(0);
+        let black: (u8, u8, u8) = self.palette_color(1);
+
+        for row in 0..self.header.height {
+            let y: u32 = self.row_y(row);
+            let mut x: u32 = 0;
+            let mut is_white: bool = true;
+
+            while x < self.header.width {
+                let run: u32 = read_run(reader, is_white)?;
+                let end: u32 = (x + run).min(self.header.width);
+
+                for column in x..end {
+                    self.set_pixel(column, y, if is_white { white } else { black });
+                }
+
+                x += run;
+                is_white = !is_white;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Reads make-up codes up to a terminating code, skipping end of line codes and their fill bits
+fn read_run(reader: &mut BmpBitReader, is_white: bool) -> Result<u32> {
+    let terminating: &[(u8, u16)] = if is_white { &WHITE_TERMINATING } else { &BLACK_TERMINATING };
+    let makeup: &[(u8, u16)] = if is_white { &WHITE_MAKEUP } else { &BLACK_MAKEUP };
+
+    let mut run: u32 = 0;
+    let mut length: u8 = 0;
+    let mut code: u16 = 0;
+
+    loop {
+        code = (code << 1) | reader.read_bit()? as u16;
+        length += 1;
+
+        // End of line, eleven zeros or more followed by a one
+        if length == 11 && code == 0 {
+            while reader.read_bit()? == 0 {}
+            length = 0;
+            continue;
+        }
+
+        if let Some(index) = terminating.iter().position(|&entry| entry == (length, code)) {
+            return Ok(run + index as u32);
+        }
+
+        if let Some(index) = makeup.iter().position(|&entry| entry == (length, code)) {
+            run += 64 * (index as u32 + 1);
+        } else if let Some(index) = EXTENDED_MAKEUP.iter().position(|&entry| entry == (length, code)) {
+            run += 1792 + 64 * index as u32;
+        } else if length < 13 {
+            continue;
+        } else {
+            bail!("Invalid Huffman 1D code {code:0width$b}", width = length as usize);
+        }
+
+        length = 0;
+        code = 0;
+    }
+}
@@ -1,17 +1,17 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::fmt;
-use std::fs::File;
-use std::io::{BufReader, Write};
-use std::path::PathBuf;
 
 use bmp_bit_reader::BmpBitReader;
-use crate::image::bit_reader::BitReader;
-use header::BMPHeader;
-use super::Image;
+use crate::image::bit_reader::{BitReader, Stream};
+use header::{BMPCompression, BMPHeader};
+use super::{from_bytes, pixel_count, Image, ImageType};
 use super::mcu::MCU;
 
 mod bmp_bit_reader;
 pub mod header;
+mod huffman_1d;
+mod rle;
+pub mod writer;
 
 #[derive(Clone, Debug)]
 pub struct BMP {
@@ -27,105 +27,211 @@ impl BMP {
         }
     }
 
-    pub fn write_to_file(&self, filename: PathBuf) -> Result<()> {
-        let header: &BMPHeader = &self.header;
-        let mcus: &Vec<MCU> = &self.mcus;
-        let mcu_width: u32 = ((header.width + 7) / 8) as u32;
-        let padding_size: u32 = (header.width % 4) as u32;
-        let size: u32 = 14u32 + 12u32 + header.width as u32 * header.height as u32 * 3 + padding_size * header.height as u32;
-        let mut buffer: Vec<u8> = Vec::with_capacity(size as usize);
-
-        buffer.push(b'B');
-        buffer.push(b'M');
-        buffer.extend_from_slice(&size.to_le_bytes());
-        buffer.extend_from_slice(&0u32.to_le_bytes());
-        buffer.extend_from_slice(&0x1Au32.to_le_bytes());
-        buffer.extend_from_slice(&12u32.to_le_bytes());
-        buffer.extend_from_slice(&header.width.to_le_bytes());
-        buffer.extend_from_slice(&header.height.to_le_bytes());
-        buffer.extend_from_slice(&1u16.to_le_bytes());
-        buffer.extend_from_slice(&24u16.to_le_bytes());
-
-        for y in (0..header.height).rev() {
-            let mcu_row: u32 = y as u32 / 8;
-            let pixel_row: u32 = y as u32 % 8;
-
-            for x in 0..header.width {
-                let mcu_column: u32 = x as u32 / 8;
-                let pixel_column: u32 = x as u32 % 8;
-                let mcu_index: usize = (mcu_row * mcu_width + mcu_column) as usize;
-                let pixel_index: usize = (pixel_row * 8 + pixel_column) as usize;
-
-                buffer.push(mcus[mcu_index].component(2).expect("Should exist")[pixel_index] as u8);
-                buffer.push(mcus[mcu_index].component(1).expect("Should exist")[pixel_index] as u8);
-                buffer.push(mcus[mcu_index].component(0).expect("Should exist")[pixel_index] as u8);
-            }
+    // Builds the image from the color of each pixel, rows going from top to bottom
+    pub fn from_pixels(width: u32, height: u32, pixel: impl Fn(usize, usize) -> (u8, u8, u8)) -> Self {
+        Self::from_rgba_pixels(width, height, |x, y| {
+            let (r, g, b): (u8, u8, u8) = pixel(x, y);
+            (r, g, b, u8::MAX)
+        })
+    }
+
+    // Builds the image from the color and opacity of each pixel, rows going from top to bottom
+    pub fn from_rgba_pixels(width: u32, height: u32, pixel: impl Fn(usize, usize) -> (u8, u8, u8, u8)) -> Self {
+        let mcu_width: usize = (width as usize).div_ceil(8);
+        let mcu_height: usize = (height as usize).div_ceil(8);
+        let mut mcus: Vec<MCU> = vec![MCU::default(); mcu_width * mcu_height];
 
-            for _ in 0..padding_size {
-                buffer.push(0u8);
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let mcu: &mut MCU = &mut mcus[(y / 8) * mcu_width + x / 8];
+                let pixel_index: usize = (y % 8) * 8 + x % 8;
+                let (r, g, b, a): (u8, u8, u8, u8) = pixel(x, y);
+
+                mcu.component_mut(0).expect("Should exist")[pixel_index] = r as i32;
+                mcu.component_mut(1).expect("Should exist")[pixel_index] = g as i32;
+                mcu.component_mut(2).expect("Should exist")[pixel_index] = b as i32;
+                mcu.set_alpha(pixel_index, a);
             }
         }
 
-        let mut file: File = File::create(filename)?;
-        file.write_all(&buffer)?;
-
-        Ok(())
+        Self::new(BMPHeader::new(width, height), mcus)
     }
 
     fn read_components(&mut self, reader: &mut BmpBitReader) -> Result<()> {
-        let mut count: u32 = 0;
+        match (self.header.compression, self.header.bits_per_pixel) {
+            (BMPCompression::RGB, 1 | 2 | 4 | 8 | 16 | 24 | 32) => (),
+            (BMPCompression::BITFIELDS | BMPCompression::ALPHABITFIELDS, 16 | 32) => (),
+            (BMPCompression::RLE4, 4) | (BMPCompression::RLE8, 8) | (BMPCompression::RLE24, 24) => (),
+            (BMPCompression::HUFFMAN1D, 1) => (),
+            (BMPCompression::JPEG | BMPCompression::PNG, 0) => (),
+            (compression, bits_per_pixel) => bail!("BMP of {bits_per_pixel} bits per pixel with {compression:?} compression not supported"),
+        }
+
+        if (1..=8).contains(&self.header.bits_per_pixel) && self.header.palette.is_empty() {
+            bail!("Indexed BMP without color table");
+        }
 
-        let padding_size: u32 = (self.header.width % 4) as u32;
-        let mcu_width: u32 = ((self.header.width + 7) / 8) as u32;
-        let mcu_height: u32 = ((self.header.height + 7) / 8) as u32;
+        // Pixel array may be preceded by a gap after the header
+        reader.seek(self.header.starting_offset as u64)?;
+
+        // Sizes are checked before any allocation, compressed pixel arrays being only bounded by the pixel count
+        pixel_count("BMP", self.header.width, self.header.height)?;
+        let remaining: u64 = reader.length()?.saturating_sub(self.header.starting_offset as u64);
+        // Rows are padded to a multiple of 4 bytes
+        let row_size: usize = (self.header.width as usize * self.header.bits_per_pixel as usize).div_ceil(32) * 4;
+
+        if matches!(self.header.compression, BMPCompression::RGB | BMPCompression::BITFIELDS | BMPCompression::ALPHABITFIELDS) {
+            match (row_size as u64).checked_mul(self.header.height as u64) {
+                Some(size) if size <= remaining => (),
+                _ => bail!("BMP pixel array of {}x{} runs past the end of the file", self.header.width, self.header.height),
+            }
+        }
+
+        let mcu_width: u32 = self.header.width.div_ceil(8);
+        let mcu_height: u32 = self.header.height.div_ceil(8);
 
         self.mcus.resize(mcu_height as usize * mcu_width as usize, MCU::default());
 
-        for y in (0..self.header.height).rev() {
-            let mcu_row: u32 = y as u32 / 8;
-            let pixel_row: u32 = y as u32 % 8;
+        match self.header.compression {
+            BMPCompression::RLE4 | BMPCompression::RLE8 | BMPCompression::RLE24 => return self.read_rle(reader),
+            BMPCompression::HUFFMAN1D => return self.read_huffman_1d(reader),
+            BMPCompression::JPEG | BMPCompression::PNG => return self.read_payload(reader),
+            _ => (),
+        }
+
+        let masks: (u32, u32, u32, u32) = self.header.color_masks();
+
+        for row in 0..self.header.height {
+            let y: u32 = self.row_y(row);
+            let pixels: Vec<u8> = reader.read_bytes(row_size)?;
 
             for x in 0..self.header.width {
-                let mcu_column: u32 = x as u32 / 8;
-                let pixel_column: u32 = x as u32 % 8;
-                let mcu_index: usize = (mcu_row * mcu_width + mcu_column) as usize;
-                let pixel_index: usize = (pixel_row * 8 + pixel_column) as usize;
-
-                self.mcus[mcu_index]
-                    .component_mut(2)
-                    .expect("Should exist")
-                    [pixel_index] = reader.read_byte()? as i32;
-                count += 1;
-
-                self.mcus[mcu_index]
-                    .component_mut(1)
-                    .expect("Should exist")
-                    [pixel_index] = reader.read_byte()? as i32;
-                count += 1;
-
-                self.mcus[mcu_index]
-                    .component_mut(0)
-                    .expect("Should exist")
-                    [pixel_index] = reader.read_byte()? as i32;
-                count += 1;
+                let (r, g, b, a): (u8, u8, u8, u8) = self.read_pixel(&pixels, x as usize, masks);
+                self.set_pixel(x, y, (r, g, b));
+                self.set_alpha(x, y, a);
             }
+        }
 
-            for _ in 0..padding_size {
-                reader.read_byte()?;
-                count += 1;
-            }
+        // Alpha channel left to zero by encoders unaware of it
+        if (0..self.header.height).all(|y| (0..self.header.width).all(|x| self.pixel_alpha(x, y) == 0)) {
+            self.mcus.iter_mut().for_each(MCU::clear_alpha);
+        }
+
+        Ok(())
+    }
+
+    // Color and opacity of a pixel from its row of the pixel array
+    fn read_pixel(&self, row: &[u8], x: usize, masks: (u32, u32, u32, u32)) -> (u8, u8, u8, u8) {
+        let (red_mask, green_mask, blue_mask, alpha_mask) = masks;
+
+        match self.header.bits_per_pixel as usize {
+            // Leftmost pixel in the most significant bits
+            bits @ (1 | 2 | 4 | 8) => {
+                let bit: usize = x * bits;
+                let index: usize = (row[bit / 8] as usize >> (8 - bits - bit % 8)) & ((1 << bits) - 1);
+
+                let (r, g, b): (u8, u8, u8) = self.palette_color(index);
+                (r, g, b, u8::MAX)
+            },
+            24 => (row[x * 3 + 2], row[x * 3 + 1], row[x * 3], u8::MAX),
+            bits => {
+                let value: u32 = match bits {
+                    16 => u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]) as u32,
+                    _ => u32::from_le_bytes([row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]]),
+                };
+                let alpha: u8 = if alpha_mask == 0 { u8::MAX } else { mask_value(value, alpha_mask) };
+
+                (mask_value(value, red_mask), mask_value(value, green_mask), mask_value(value, blue_mask), alpha)
+            },
+        }
+    }
+
+    // Pixel array holding a whole JPEG or PNG file, mostly found in printer bitmaps
+    fn read_payload(&mut self, reader: &mut BmpBitReader) -> Result<()> {
+        let size: u64 = match self.header.image_size {
+            0 => reader.length()?.saturating_sub(self.header.starting_offset as u64),
+            image_size => image_size as u64,
+        };
+
+        if size > reader.length()?.saturating_sub(self.header.starting_offset as u64) {
+            bail!("BMP payload of {size} bytes runs past the end of the file");
         }
 
-        assert_eq!(self.header.bmp_size - self.header.starting_offset, count);
+        let bytes: Vec<u8> = reader.read_bytes(size as usize)?;
+
+        let image: Box<BMP> = match self.header.compression {
+            BMPCompression::JPEG => from_bytes(bytes, ImageType::JPEG, false)?.to_bmp(),
+            _ => from_bytes(bytes, ImageType::PNG, false)?.to_bmp(),
+        };
+
+        if image.width() != self.header.width || image.height() != self.header.height {
+            bail!("BMP payload of {}x{} doesn't match the {}x{} header", image.width(), image.height(), self.header.width, self.header.height);
+        }
+
+        self.mcus = image.mcus;
 
         Ok(())
     }
 
-    pub fn width(&self) -> u16 {
+    // Image row of a row of the pixel array, which goes from bottom to top unless the height is negative
+    fn row_y(&self, row: u32) -> u32 {
+        if self.header.top_down {
+            row
+        } else {
+            self.header.height - 1 - row
+        }
+    }
+
+    // Indexes outside of the color table are black
+    fn palette_color(&self, index: usize) -> (u8, u8, u8) {
+        self.header.palette.get(index).copied().unwrap_or((0, 0, 0))
+    }
+
+    pub fn set_alpha(&mut self, x: u32, y: u32, alpha: u8) {
+        let mcu_width: u32 = self.header.width.div_ceil(8);
+        let mcu_index: usize = ((y / 8) * mcu_width + x / 8) as usize;
+        let pixel_index: usize = ((y % 8) * 8 + x % 8) as usize;
+
+        self.mcus[mcu_index].set_alpha(pixel_index, alpha);
+    }
+
+    pub fn pixel_color(&self, x: u32, y: u32) -> (u8, u8, u8) {
+        let mcu_width: u32 = self.header.width.div_ceil(8);
+        let mcu_index: usize = ((y / 8) * mcu_width + x / 8) as usize;
+        let pixel_index: usize = ((y % 8) * 8 + x % 8) as usize;
+        let mcu: &MCU = &self.mcus[mcu_index];
+
+        (
+            mcu.component(0).expect("Should exist")[pixel_index] as u8,
+            mcu.component(1).expect("Should exist")[pixel_index] as u8,
+            mcu.component(2).expect("Should exist")[pixel_index] as u8,
+        )
+    }
+
+    pub fn pixel_alpha(&self, x: u32, y: u32) -> u8 {
+        let mcu_width: u32 = self.header.width.div_ceil(8);
+        let mcu_index: usize = ((y / 8) * mcu_width + x / 8) as usize;
+        let pixel_index: usize = ((y % 8) * 8 + x % 8) as usize;
+
+        self.mcus[mcu_index].alpha(pixel_index)
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: (u8, u8, u8)) {
+        let mcu_width: u32 = self.header.width.div_ceil(8);
+        let mcu_index: usize = ((y / 8) * mcu_width + x / 8) as usize;
+        let pixel_index: usize = ((y % 8) * 8 + x % 8) as usize;
+        let (r, g, b): (u8, u8, u8) = color;
+
+        self.mcus[mcu_index].component_mut(0).expect("Should exist")[pixel_index] = r as i32;
+        self.mcus[mcu_index].component_mut(1).expect("Should exist")[pixel_index] = g as i32;
+        self.mcus[mcu_index].component_mut(2).expect("Should exist")[pixel_index] = b as i32;
+    }
+
+    pub fn width(&self) -> u32 {
         self.header.width
     }
 
-    pub fn height(&self) -> u16 {
+    pub fn height(&self) -> u32 {
         self.header.height
     }
 
@@ -135,13 +241,17 @@ impl BMP {
 }
 
 impl Image for BMP {
-    fn from_stream(stream: BufReader<File>, _debug: bool) -> Result<Self> {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
         let mut reader: BmpBitReader = BmpBitReader::new(stream);
         let mut bmp: Self = Self {
             header: BMPHeader::from_binary(&mut reader)?,
             mcus: Vec::new(),
         };
 
+        if debug {
+            println!("{bmp}");
+        }
+
         bmp.read_components(&mut reader)?;
 
         Ok(bmp)
@@ -157,3 +267,124 @@ impl fmt::Display for BMP {
         write!(f, "Header:\n{}", self.header)
     }
 }
+
+// Extracts the bits of a mask, scaled to 8 bits
+fn mask_value(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+
+    let shift: u32 = mask.trailing_zeros();
+    let max: u64 = (mask >> shift) as u64;
+    let value: u64 = ((value & mask) >> shift) as u64;
+
+    ((value * 255 + max / 2) / max) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    // File and info headers followed by the color table and the pixel array
+    pub(super) fn file(width: i32, height: i32, bits_per_pixel: u16, compression: u32, palette: &[u8], pixels: &[u8]) -> Vec<u8> {
+        let offset: u32 = 14 + 40 + palette.len() as u32;
+        let mut bytes: Vec<u8> = b"BM".to_vec();
+        bytes.extend_from_slice(&(offset + pixels.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&bits_per_pixel.to_le_bytes());
+        bytes.extend_from_slice(&compression.to_le_bytes());
+        bytes.extend_from_slice(&(pixels.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(palette);
+        bytes.extend_from_slice(pixels);
+        bytes
+    }
+
+    pub(super) fn decode(bytes: Vec<u8>) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        BMP::from_stream(BufReader::new(source), false)
+    }
+
+    pub(super) fn colors(bmp: &BMP) -> Vec<(u8, u8, u8)> {
+        (0..bmp.height()).flat_map(|y| (0..bmp.width()).map(move |x| bmp.pixel_color(x, y))).collect()
+    }
+
+    #[test]
+    fn rows_go_from_bottom_to_top() {
+        // Blue and green bottom row, red and white top row, padded to 8 bytes
+        let pixels: [u8; 16] = [255, 0, 0, 0, 255, 0, 0, 0, 0, 0, 255, 255, 255, 255, 0, 0];
+        let bmp: BMP = decode(file(2, 2, 24, 0, &[], &pixels)).unwrap();
+
+        assert_eq!(colors(&bmp), [(255, 0, 0), (255, 255, 255), (0, 0, 255), (0, 255, 0)]);
+        assert_eq!(bmp.pixel_alpha(1, 1), u8::MAX);
+    }
+
+    #[test]
+    fn negative_heights_go_from_top_to_bottom() {
+        let pixels: [u8; 16] = [255, 0, 0, 0, 255, 0, 0, 0, 0, 0, 255, 255, 255, 255, 0, 0];
+        let bmp: BMP = decode(file(2, -2, 24, 0, &[], &pixels)).unwrap();
+
+        assert_eq!(colors(&bmp), [(0, 0, 255), (0, 255, 0), (255, 0, 0), (255, 255, 255)]);
+    }
+
+    #[test]
+    fn indexed_pixels_use_the_color_table() {
+        // Black and white entries, the leftmost pixel being in the most significant bit
+        let palette: [u8; 8] = [0, 0, 0, 0, 255, 255, 255, 0];
+        let bmp: BMP = decode(file(3, 1, 1, 0, &palette, &[0b1010_0000, 0, 0, 0])).unwrap();
+        assert_eq!(colors(&bmp), [(255, 255, 255), (0, 0, 0), (255, 255, 255)]);
+
+        // Indexes past the color table are black
+        let palette: [u8; 8] = [255, 0, 0, 0, 0, 255, 0, 0];
+        let bmp: BMP = decode(file(3, 1, 8, 0, &palette, &[1, 0, 7, 0])).unwrap();
+        assert_eq!(colors(&bmp), [(0, 255, 0), (0, 0, 255), (0, 0, 0)]);
+    }
+
+    #[test]
+    fn bitfields_are_scaled_to_8_bits() {
+        let masks: Vec<u8> = [0xF800u32, 0x07E0, 0x001F].iter().flat_map(|mask| mask.to_le_bytes()).collect();
+        let bmp: BMP = decode(file(2, 1, 16, 3, &masks, &[0x00, 0xF8, 0x1F, 0x00])).unwrap();
+        assert_eq!(colors(&bmp), [(255, 0, 0), (0, 0, 255)]);
+
+        let masks: Vec<u8> = [0x00FF0000u32, 0x0000FF00, 0x000000FF, 0xFF000000].iter().flat_map(|mask| mask.to_le_bytes()).collect();
+        let bmp: BMP = decode(file(1, 1, 32, 6, &masks, &0x80FF0000u32.to_le_bytes())).unwrap();
+        assert_eq!(colors(&bmp), [(255, 0, 0)]);
+        assert_eq!(bmp.pixel_alpha(0, 0), 0x80);
+    }
+
+    #[test]
+    fn jpeg_payloads_are_decoded() {
+        let jpeg: &[u8] = include_bytes!("../../../tests/jpeg/cat.jpg");
+        let image: Box<BMP> = from_bytes(jpeg.to_vec(), ImageType::JPEG, false).unwrap().to_bmp();
+        let (width, height): (i32, i32) = (image.width() as i32, image.height() as i32);
+
+        let bmp: BMP = decode(file(width, height, 0, 4, &[], jpeg)).unwrap();
+        assert_eq!(colors(&bmp), colors(&image));
+
+        let error: String = decode(file(width + 1, height, 0, 4, &[], jpeg)).unwrap_err().to_string();
+        assert!(error.contains("doesn't match"), "{error}");
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        for (width, height) in [(i32::MAX, i32::MAX), (100000, 100000), (1, i32::MIN)] {
+            let error: String = decode(file(width, height, 24, 0, &[], &[])).unwrap_err().to_string();
+            assert!(error.contains("too large"), "{error}");
+        }
+    }
+
+    #[test]
+    fn truncated_pixel_arrays_are_rejected() {
+        let error: String = decode(file(1000, 1000, 24, 0, &[], &[0; 4])).unwrap_err().to_string();
+
+        assert!(error.contains("runs past the end of the file"), "{error}");
+    }
+}
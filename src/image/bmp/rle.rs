@@ -0,0 +1,169 @@
+use anyhow::{bail, Result};
+
+use crate::image::bit_reader::BitReader;
+use super::BMP;
+use super::bmp_bit_reader::BmpBitReader;
+use super::header::BMPCompression;
+
+impl BMP {
+    // Run length encoded pixel array of RLE4, RLE8 and OS/2 RLE24 bitmaps
+    // Pixels skipped by the end of line and delta escapes are left transparent, runs going past the end of their row are rejected
+    pub(super) fn read_rle(&mut self, reader: &mut BmpBitReader) -> Result<()> {
+        for mcu in self.mcus.iter_mut() {
+            (0..64).for_each(|i| mcu.set_alpha(i, 0));
+        }
+
+        let length: u64 = reader.length()?;
+        let data: Vec<u8> = reader.read_bytes(length.saturating_sub(self.header.starting_offset as u64) as usize)?;
+        let compression: BMPCompression = self.header.compression;
+
+        let width: usize = self.header.width as usize;
+        let mut x: usize = 0;
+        let mut row: usize = 0;
+        let mut position: usize = 0;
+
+        // Files are sometimes missing their end of bitmap escape
+        while row < self.header.height as usize && position + 1 < data.len() {
+            let count: u8 = data[position];
+            let value: u8 = data[position + 1];
+            position += 2;
+
+            match (count, value) {
+                // End of line
+                (0, 0) => {
+                    x = 0;
+                    row += 1;
+                },
+                // End of bitmap
+                (0, 1) => break,
+                // Delta, moving right and up
+                (0, 2) => {
+                    x = x.saturating_add(byte(&data, position)? as usize);
+                    row = row.saturating_add(byte(&data, position + 1)? as usize);
+                    position += 2;
+                },
+                // Absolute mode, padded to 16 bits
+                (0, count) => {
+                    let count: usize = count as usize;
+                    check_run(x, row, count, width)?;
+
+                    for i in 0..count {
+                        let color: (u8, u8, u8) = match compression {
+                            BMPCompression::RLE4 => self.palette_color(nibble(byte(&data, position + i / 2)?, i)),
+                            BMPCompression::RLE8 => self.palette_color(byte(&data, position + i)? as usize),
+                            _ => (byte(&data, position + i * 3 + 2)?, byte(&data, position + i * 3 + 1)?, byte(&data, position + i * 3)?),
+                        };
+
+                        self.set_rle_pixel(x, row, color);
+                        x += 1;
+                    }
+
+                    let size: usize = match compression {
+                        BMPCompression::RLE4 => count.div_ceil(2),
+                        BMPCompression::RLE8 => count,
+                        _ => count * 3,
+                    };
+
+                    position += size + size % 2;
+                },
+                // Encoded mode, RLE24 runs being followed by the green and red values
+                (count, value) => {
+                    let rgb: (u8, u8, u8) = match compression {
+                        BMPCompression::RLE24 => {
+                            position += 2;
+                            (byte(&data, position - 1)?, byte(&data, position - 2)?, value)
+                        },
+                        _ => (0, 0, 0),
+                    };
+
+                    check_run(x, row, count as usize, width)?;
+
+                    for i in 0..count as usize {
+                        let color: (u8, u8, u8) = match compression {
+                            BMPCompression::RLE4 => self.palette_color(nibble(value, i)),
+                            BMPCompression::RLE8 => self.palette_color(value as usize),
+                            _ => rgb,
+                        };
+
+                        self.set_rle_pixel(x, row, color);
+                        x += 1;
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    // Runs being checked against the row, pixels are always inside of the image
+    fn set_rle_pixel(&mut self, x: usize, row: usize, color: (u8, u8, u8)) {
+        let y: u32 = self.row_y(row as u32);
+        self.set_pixel(x as u32, y, color);
+        self.set_alpha(x as u32, y, u8::MAX);
+    }
+}
+
+fn check_run(x: usize, row: usize, count: usize, width: usize) -> Result<()> {
+    match x.checked_add(count) {
+        Some(end) if end <= width => Ok(()),
+        _ => bail!("RLE run of {count} pixels at {x},{row} goes past the end of the row"),
+    }
+}
+
+fn byte(data: &[u8], index: usize) -> Result<u8> {
+    match data.get(index) {
+        Some(&value) => Ok(value),
+        None => bail!("RLE pixel array truncated at {index:X}"),
+    }
+}
+
+// High nibble for even pixels, low nibble for odd ones
+fn nibble(value: u8, index: usize) -> usize {
+    if index.is_multiple_of(2) {
+        (value >> 4) as usize
+    } else {
+        (value & 0x0F) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::bmp::tests::{colors, decode, file};
+    use super::*;
+
+    // Black, red and green entries
+    const PALETTE: [u8; 12] = [0, 0, 0, 0, 0, 0, 255, 0, 0, 255, 0, 0];
+
+    #[test]
+    fn rle8_runs_and_escapes_are_decoded() {
+        // Two red pixels, a delta to the last pixel of the top row, a green pixel and the end of bitmap
+        let data: [u8; 10] = [2, 1, 0, 2, 1, 1, 1, 2, 0, 1];
+        let bmp: BMP = decode(file(4, 2, 8, 1, &PALETTE, &data)).unwrap();
+
+        assert_eq!(colors(&bmp)[3], (0, 255, 0));
+        assert_eq!(colors(&bmp)[4..6], [(255, 0, 0), (255, 0, 0)]);
+
+        let alphas: Vec<u8> = (0..2).flat_map(|y| (0..4).map(move |x| (x, y))).map(|(x, y)| bmp.pixel_alpha(x, y)).collect();
+        assert_eq!(alphas, [0, 0, 0, 255, 255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn rle8_absolute_runs_are_padded() {
+        // Absolute run of 3 pixels and its padding byte, then an end of line and a run on the top row
+        let data: [u8; 10] = [0, 3, 1, 2, 1, 0, 0, 0, 3, 2];
+        let bmp: BMP = decode(file(3, 2, 8, 1, &PALETTE, &data)).unwrap();
+
+        assert_eq!(colors(&bmp), [(0, 255, 0), (0, 255, 0), (0, 255, 0), (255, 0, 0), (0, 255, 0), (255, 0, 0)]);
+    }
+
+    #[test]
+    fn runs_past_the_end_of_the_row_are_rejected() {
+        // Encoded and absolute runs of 3 pixels, and a delta followed by a single pixel, in rows of 2
+        let runs: [&[u8]; 3] = [&[3, 1, 0, 1], &[0, 3, 1, 1, 1, 0, 0, 1], &[0, 2, 2, 0, 1, 1, 0, 1]];
+
+        for data in runs {
+            let error: String = decode(file(2, 1, 8, 1, &PALETTE, data)).unwrap_err().to_string();
+            assert!(error.contains("past the end of the row"), "{error}");
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::image::write_bytes;
+use super::BMP;
+use super::header::{CORE_HEADER_SIZE, INFO_HEADER_SIZE, V5_HEADER_SIZE};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Serialize)]
+pub enum BMPVersion {
+    // OS/2 1.x BITMAPCOREHEADER
+    #[default]
+    CORE,
+    // BITMAPINFOHEADER, pixels with an alpha channel being written with a V5 header
+    INFO,
+    // BITMAPV5HEADER, in the sRGB color space
+    V5,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Serialize)]
+pub enum BMPPixels {
+    #[default]
+    RGB24,
+    // Bitfields with an alpha mask
+    RGBA32,
+    // Color table computed from the image
+    INDEXED8,
+    // Indexed pixels compressed by runs
+    RLE8,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BMPWriteOptions {
+    pub version: BMPVersion,
+    pub pixels: BMPPixels,
+}
+
+impl BMP {
+    pub fn write_to_file(&self, filename: PathBuf) -> Result<()> {
+        self.write_to_file_with(filename, BMPWriteOptions::default())
+    }
+
+    pub fn write_to_file_with(&self, filename: PathBuf, options: BMPWriteOptions) -> Result<()> {
+        let width: u32 = self.header.width;
+        let height: u32 = self.header.height;
+
+        if let (BMPVersion::CORE, BMPPixels::RGBA32 | BMPPixels::RLE8) = (options.version, options.pixels) {
+            bail!("{:?} pixels can't be written with a core header", options.pixels);
+        }
+
+        // The core header only holds 16 bits dimensions
+        if matches!(options.version, BMPVersion::CORE) && (width > u16::MAX as u32 || height > u16::MAX as u32) {
+            bail!("Image of {width}x{height} too large to be written");
+        }
+
+        let (palette, indexes): (Vec<(u8, u8, u8)>, Vec<u8>) = match options.pixels {
+            BMPPixels::INDEXED8 | BMPPixels::RLE8 => self.quantize(),
+            BMPPixels::RGB24 | BMPPixels::RGBA32 => (Vec::new(), Vec::new()),
+        };
+
+        // Alpha masks are part of the V3 and later headers only
+        let version: BMPVersion = match (options.version, options.pixels) {
+            (BMPVersion::INFO, BMPPixels::RGBA32) => BMPVersion::V5,
+            (version, _) => version,
+        };
+
+        let (bits_per_pixel, compression): (u16, u32) = match options.pixels {
+            BMPPixels::RGB24 => (24, 0),
+            BMPPixels::RGBA32 => (32, 3),
+            BMPPixels::INDEXED8 => (8, 0),
+            BMPPixels::RLE8 => (8, 1),
+        };
+
+        let pixel_array: Vec<u8> = self.pixel_array(options.pixels, &indexes);
+
+        let header_size: u32 = match version {
+            BMPVersion::CORE => CORE_HEADER_SIZE,
+            BMPVersion::INFO => INFO_HEADER_SIZE,
+            BMPVersion::V5 => V5_HEADER_SIZE,
+        };
+        let entry_size: u32 = if header_size == CORE_HEADER_SIZE { 3 } else { 4 };
+        let starting_offset: u32 = 14 + header_size + palette.len() as u32 * entry_size;
+        let size: u64 = starting_offset as u64 + pixel_array.len() as u64;
+
+        // Sizes of the file and of its pixel array are stored on 32 bits
+        if size > u32::MAX as u64 {
+            bail!("BMP file of {size} bytes too large to be written");
+        }
+
+        let size: u32 = size as u32;
+        let mut buffer: Vec<u8> = Vec::with_capacity(size as usize);
+
+        buffer.push(b'B');
+        buffer.push(b'M');
+        buffer.extend_from_slice(&size.to_le_bytes());
+        buffer.extend_from_slice(&0u32.to_le_bytes());
+        buffer.extend_from_slice(&starting_offset.to_le_bytes());
+        buffer.extend_from_slice(&header_size.to_le_bytes());
+
+        if header_size == CORE_HEADER_SIZE {
+            buffer.extend_from_slice(&(width as u16).to_le_bytes());
+            buffer.extend_from_slice(&(height as u16).to_le_bytes());
+            buffer.extend_from_slice(&1u16.to_le_bytes());
+            buffer.extend_from_slice(&bits_per_pixel.to_le_bytes());
+        } else {
+            buffer.extend_from_slice(&width.to_le_bytes());
+            buffer.extend_from_slice(&height.to_le_bytes());
+            buffer.extend_from_slice(&1u16.to_le_bytes());
+            buffer.extend_from_slice(&bits_per_pixel.to_le_bytes());
+            buffer.extend_from_slice(&compression.to_le_bytes());
+            buffer.extend_from_slice(&(pixel_array.len() as u32).to_le_bytes());
+            // 72 DPI
+            buffer.extend_from_slice(&2835u32.to_le_bytes());
+            buffer.extend_from_slice(&2835u32.to_le_bytes());
+            buffer.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(&0u32.to_le_bytes());
+        }
+
+        if header_size == V5_HEADER_SIZE {
+            let masks: [u32; 4] = match compression {
+                0 | 1 => [0; 4],
+                _ => [0x00FF0000, 0x0000FF00, 0x000000FF, 0xFF000000],
+            };
+
+            for mask in masks {
+                buffer.extend_from_slice(&mask.to_le_bytes());
+            }
+        }
+
+        if header_size == V5_HEADER_SIZE {
+            buffer.extend_from_slice(b"BGRs");
+            // Endpoints and gammas, unused by sRGB
+            buffer.extend_from_slice(&[0u8; 36 + 12]);
+            // LCS_GM_IMAGES intent, no profile
+            buffer.extend_from_slice(&4u32.to_le_bytes());
+            buffer.extend_from_slice(&[0u8; 12]);
+        }
+
+        for &(r, g, b) in palette.iter() {
+            buffer.extend_from_slice(&[b, g, r]);
+
+            if entry_size == 4 {
+                buffer.push(0u8);
+            }
+        }
+
+        buffer.extend_from_slice(&pixel_array);
+
+        write_bytes(filename, &buffer)
+    }
+
+    // Rows from bottom to top, padded to a multiple of 4 bytes unless compressed
+    fn pixel_array(&self, pixels: BMPPixels, indexes: &[u8]) -> Vec<u8> {
+        let width: u32 = self.header.width;
+        let height: u32 = self.header.height;
+        let mut pixel_array: Vec<u8> = Vec::new();
+
+        for y in (0..height).rev() {
+            let row_start: usize = pixel_array.len();
+            let row_indexes: &[u8] = indexes.get((y * width) as usize..((y + 1) * width) as usize).unwrap_or(&[]);
+
+            match pixels {
+                BMPPixels::RGB24 | BMPPixels::RGBA32 => {
+                    for x in 0..width {
+                        let (r, g, b): (u8, u8, u8) = self.pixel_color(x, y);
+                        pixel_array.extend_from_slice(&[b, g, r]);
+
+                        if matches!(pixels, BMPPixels::RGBA32) {
+                            pixel_array.push(self.pixel_alpha(x, y));
+                        }
+                    }
+                },
+                BMPPixels::INDEXED8 => pixel_array.extend_from_slice(row_indexes),
+                BMPPixels::RLE8 => {
+                    encode_rle8(row_indexes, &mut pixel_array);
+                    pixel_array.extend_from_slice(if y == 0 { &[0, 1] } else { &[0, 0] });
+                    continue;
+                },
+            }
+
+            while !(pixel_array.len() - row_start).is_multiple_of(4) {
+                pixel_array.push(0u8);
+            }
+        }
+
+        pixel_array
+    }
+
+    // Color table of the image colors when they fit in 256 entries, of their median cut otherwise,
+    // along with the index of every pixel, rows going from top to bottom
+    fn quantize(&self) -> (Vec<(u8, u8, u8)>, Vec<u8>) {
+        let pixels: Vec<(u8, u8, u8)> = (0..self.header.height)
+            .flat_map(|y| (0..self.header.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel_color(x, y))
+            .collect();
+
+        let mut colors: Vec<(u8, u8, u8)> = pixels.clone();
+        colors.sort_unstable();
+        colors.dedup();
+
+        let palette: Vec<(u8, u8, u8)> = if colors.len() <= 256 { colors } else { median_cut(colors, 256) };
+        let mut nearest_indexes: HashMap<(u8, u8, u8), u8> = HashMap::new();
+
+        let indexes: Vec<u8> = pixels
+            .iter()
+            .map(|&color| *nearest_indexes.entry(color).or_insert_with(|| nearest(&palette, color)))
+            .collect();
+
+        (palette, indexes)
+    }
+}
+
+// Splits the box of colors with the widest channel at its median until there are enough boxes
+fn median_cut(colors: Vec<(u8, u8, u8)>, count: usize) -> Vec<(u8, u8, u8)> {
+    let channel = |color: &(u8, u8, u8), channel: usize| [color.0, color.1, color.2][channel];
+    let widest = |colors: &Vec<(u8, u8, u8)>| {
+        (0..3)
+            .map(|i| {
+                let minimum: u8 = colors.iter().map(|color| channel(color, i)).min().unwrap_or(0);
+                let maximum: u8 = colors.iter().map(|color| channel(color, i)).max().unwrap_or(0);
+                (maximum - minimum, i)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes: Vec<Vec<(u8, u8, u8)>> = vec![colors];
+
+    while boxes.len() < count {
+        let Some((index, (range, widest_channel))) = boxes.iter().map(widest).enumerate().max_by_key(|&(_, range)| range) else {
+            break;
+        };
+
+        if range == 0 {
+            break;
+        }
+
+        let mut lower: Vec<(u8, u8, u8)> = boxes.swap_remove(index);
+        lower.sort_unstable_by_key(|color| channel(color, widest_channel));
+        let upper: Vec<(u8, u8, u8)> = lower.split_off(lower.len() / 2);
+
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|colors| {
+            let length: usize = colors.len().max(1);
+            let sum = |i: usize| colors.iter().map(|color| channel(color, i) as usize).sum::<usize>();
+            ((sum(0) / length) as u8, (sum(1) / length) as u8, (sum(2) / length) as u8)
+        })
+        .collect()
+}
+
+// Index of the closest palette color
+fn nearest(palette: &[(u8, u8, u8)], color: (u8, u8, u8)) -> u8 {
+    let distance = |entry: &(u8, u8, u8)| {
+        let dr: i32 = entry.0 as i32 - color.0 as i32;
+        let dg: i32 = entry.1 as i32 - color.1 as i32;
+        let db: i32 = entry.2 as i32 - color.2 as i32;
+        dr * dr + dg * dg + db * db
+    };
+
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map_or(0, |(index, _)| index as u8)
+}
+
+// Runs of at least two pixels are encoded, other pixels being grouped in absolute runs when there are at least three of them
+fn encode_rle8(row: &[u8], output: &mut Vec<u8>) {
+    let mut x: usize = 0;
+
+    while x < row.len() {
+        let run: usize = row[x..].iter().take(255).take_while(|&&index| index == row[x]).count();
+
+        if run >= 2 {
+            output.extend_from_slice(&[run as u8, row[x]]);
+            x += run;
+            continue;
+        }
+
+        let mut end: usize = x + 1;
+
+        while end < row.len() && end - x < 255 && !(end + 1 < row.len() && row[end] == row[end + 1]) {
+            end += 1;
+        }
+
+        let literal: &[u8] = &row[x..end];
+
+        if literal.len() < 3 {
+            for &index in literal {
+                output.extend_from_slice(&[1, index]);
+            }
+        } else {
+            output.extend_from_slice(&[0, literal.len() as u8]);
+            output.extend_from_slice(literal);
+
+            // Absolute runs are padded to 16 bits
+            if !literal.len().is_multiple_of(2) {
+                output.push(0u8);
+            }
+        }
+
+        x = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs;
+    use std::process;
+
+    use crate::image::bmp::tests::{colors, decode};
+    use super::*;
+
+    // Tests run in parallel, so each one writes its own files
+    fn write(name: &str, image: &BMP, version: BMPVersion, pixels: BMPPixels) -> Result<Vec<u8>> {
+        let path: PathBuf = temp_dir().join(format!("term_viewer_{}_{name}_{version:?}_{pixels:?}.bmp", process::id()));
+        image.write_to_file_with(path.clone(), BMPWriteOptions { version, pixels })?;
+        let bytes: Vec<u8> = fs::read(&path)?;
+        fs::remove_file(&path)?;
+        Ok(bytes)
+    }
+
+    fn image() -> BMP {
+        BMP::from_rgba_pixels(5, 3, |x, y| ((x * 50) as u8, (y * 80) as u8, (255 - x * 20) as u8, (x * 60) as u8))
+    }
+
+    fn alphas(bmp: &BMP) -> Vec<u8> {
+        (0..bmp.height()).flat_map(|y| (0..bmp.width()).map(move |x| bmp.pixel_alpha(x, y))).collect()
+    }
+
+    #[test]
+    fn every_header_and_pixel_format_round_trips() {
+        let image: BMP = image();
+        let formats: [(BMPVersion, BMPPixels); 10] = [
+            (BMPVersion::CORE, BMPPixels::RGB24),
+            (BMPVersion::CORE, BMPPixels::INDEXED8),
+            (BMPVersion::INFO, BMPPixels::RGB24),
+            (BMPVersion::INFO, BMPPixels::RGBA32),
+            (BMPVersion::INFO, BMPPixels::INDEXED8),
+            (BMPVersion::INFO, BMPPixels::RLE8),
+            (BMPVersion::V5, BMPPixels::RGB24),
+            (BMPVersion::V5, BMPPixels::RGBA32),
+            (BMPVersion::V5, BMPPixels::INDEXED8),
+            (BMPVersion::V5, BMPPixels::RLE8),
+        ];
+
+        for (version, pixels) in formats {
+            let bytes: Vec<u8> = write("round_trip", &image, version, pixels).unwrap();
+            let bmp: BMP = decode(bytes.clone()).unwrap();
+
+            assert_eq!(colors(&bmp), colors(&image), "{version:?} {pixels:?}");
+            assert_eq!(write("round_trip", &bmp, version, pixels).unwrap(), bytes, "{version:?} {pixels:?}");
+
+            if let BMPPixels::RGBA32 = pixels {
+                assert_eq!(alphas(&bmp), alphas(&image), "{version:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn alpha_is_written_with_bitfields_in_a_v5_header() {
+        let bytes: Vec<u8> = write("alpha", &image(), BMPVersion::INFO, BMPPixels::RGBA32).unwrap();
+
+        assert_eq!(bytes[14..18], V5_HEADER_SIZE.to_le_bytes());
+        assert_eq!(bytes[30..34], 3u32.to_le_bytes());
+        assert_eq!(bytes[66..70], 0xFF000000u32.to_le_bytes());
+    }
+
+    #[test]
+    fn core_headers_reject_alpha_and_compression() {
+        assert!(write("core", &image(), BMPVersion::CORE, BMPPixels::RGBA32).is_err());
+        assert!(write("core", &image(), BMPVersion::CORE, BMPPixels::RLE8).is_err());
+    }
+}
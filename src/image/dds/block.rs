@@ -0,0 +1,146 @@
+// Decoders of 4x4 blocks, pixels going from left to right and from top to bottom
+
+// 5 or 6 bits of a 565 color repeated in the lower bits
+fn expand_565(color: u16) -> (u8, u8, u8) {
+    let (r, g, b): (u8, u8, u8) = ((color >> 11) as u8, ((color >> 5) & 0x3F) as u8, (color & 0x1F) as u8);
+
+    ((r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2))
+}
+
+// Value a fraction of the way from one endpoint to the other, rounded
+fn interpolate(first: i32, second: i32, numerator: i32, denominator: i32) -> i32 {
+    let value: i32 = first * (denominator - numerator) + second * numerator;
+
+    if value >= 0 {
+        (value + denominator / 2) / denominator
+    } else {
+        (value - denominator / 2) / denominator
+    }
+}
+
+// Two 565 endpoints and 2 bits indices, a third color and transparent black replacing the two interpolated ones
+// when the first endpoint is not the greater, which only BC1 allows
+pub fn decode_color(block: &[u8], allow_transparent: bool) -> [(u8, u8, u8, u8); 16] {
+    let first: u16 = u16::from_le_bytes([block[0], block[1]]);
+    let second: u16 = u16::from_le_bytes([block[2], block[3]]);
+    let (r0, g0, b0): (u8, u8, u8) = expand_565(first);
+    let (r1, g1, b1): (u8, u8, u8) = expand_565(second);
+
+    let mix = |numerator: i32, denominator: i32| {
+        let channel = |a: u8, b: u8| interpolate(a as i32, b as i32, numerator, denominator) as u8;
+        (channel(r0, r1), channel(g0, g1), channel(b0, b1), u8::MAX)
+    };
+
+    let colors: [(u8, u8, u8, u8); 4] = if first > second || !allow_transparent {
+        [(r0, g0, b0, u8::MAX), (r1, g1, b1, u8::MAX), mix(1, 3), mix(2, 3)]
+    } else {
+        [(r0, g0, b0, u8::MAX), (r1, g1, b1, u8::MAX), mix(1, 2), (0, 0, 0, 0)]
+    };
+
+    let indices: u32 = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    std::array::from_fn(|i| colors[((indices >> (2 * i)) & 0x3) as usize])
+}
+
+// 4 bits alpha of each pixel, scaled to 8 bits
+pub fn decode_explicit_alpha(block: &[u8]) -> [u8; 16] {
+    std::array::from_fn(|i| {
+        let alpha: u8 = (block[i / 2] >> (4 * (i % 2))) & 0xF;
+        alpha * 17
+    })
+}
+
+// Two 8 bits endpoints and 3 bits indices, 6 interpolated values or 4 with both extremes,
+// signed values going from -127 to 127 and being brought from 0 to 255
+pub fn decode_interpolated(block: &[u8], signed: bool) -> [u8; 16] {
+    let endpoint = |byte: u8| if signed { (byte as i8).max(-127) as i32 } else { byte as i32 };
+    let (first, second): (i32, i32) = (endpoint(block[0]), endpoint(block[1]));
+    let (minimum, maximum): (i32, i32) = if signed { (-127, 127) } else { (0, 255) };
+
+    let values: [i32; 8] = if first > second {
+        std::array::from_fn(|i| match i {
+            0 => first,
+            1 => second,
+            _ => interpolate(first, second, i as i32 - 1, 7),
+        })
+    } else {
+        std::array::from_fn(|i| match i {
+            0 => first,
+            1 => second,
+            6 => minimum,
+            7 => maximum,
+            _ => interpolate(first, second, i as i32 - 1, 5),
+        })
+    };
+
+    let indices: u64 = u64::from_le_bytes([block[2], block[3], block[4], block[5], block[6], block[7], 0, 0]);
+
+    std::array::from_fn(|i| {
+        let value: i32 = values[((indices >> (3 * i)) & 0x7) as usize];
+        if signed { ((value + 127) * 255 + 127) / 254 } else { value }.clamp(0, 255) as u8
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Indices of 2 bits of the 16 pixels, the first one in the lowest bits
+    fn color_block(first: u16, second: u16, indices: [u32; 16]) -> Vec<u8> {
+        let packed: u32 = indices.iter().enumerate().fold(0, |packed, (i, &index)| packed | index << (2 * i));
+        [first.to_le_bytes(), second.to_le_bytes()].concat().into_iter().chain(packed.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn color_endpoints_are_expanded_from_565() {
+        assert_eq!(expand_565(0xFFFF), (255, 255, 255));
+        assert_eq!(expand_565(0xF800), (255, 0, 0));
+        assert_eq!(expand_565(0x0400), (0, 130, 0));
+    }
+
+    #[test]
+    fn opaque_blocks_interpolate_two_colors() {
+        // White then black, the interpolated colors being a third and two thirds of the way
+        let block: Vec<u8> = color_block(0xFFFF, 0x0000, [0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3]);
+        let colors: [(u8, u8, u8, u8); 16] = decode_color(&block, true);
+
+        assert_eq!(colors[..4], [(255, 255, 255, 255), (0, 0, 0, 255), (170, 170, 170, 255), (85, 85, 85, 255)]);
+        assert_eq!(colors[12..], colors[..4]);
+    }
+
+    #[test]
+    fn transparent_blocks_interpolate_one_color() {
+        let block: Vec<u8> = color_block(0x0000, 0xFFFF, [0, 1, 2, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3]);
+
+        assert_eq!(decode_color(&block, true)[..4], [(0, 0, 0, 255), (255, 255, 255, 255), (128, 128, 128, 255), (0, 0, 0, 0)]);
+        // Only BC1 has transparent pixels
+        assert_eq!(decode_color(&block, false)[..4], [(0, 0, 0, 255), (255, 255, 255, 255), (85, 85, 85, 255), (170, 170, 170, 255)]);
+    }
+
+    #[test]
+    fn explicit_alpha_is_scaled_from_4_bits() {
+        let alphas: [u8; 16] = decode_explicit_alpha(&[0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE]);
+
+        assert_eq!(alphas, std::array::from_fn(|i| i as u8 * 17));
+    }
+
+    #[test]
+    fn interpolated_values_have_8_or_6_steps() {
+        // Indices 0 to 7 for the first 8 pixels
+        let indices: [u8; 6] = [0x88, 0xC6, 0xFA, 0, 0, 0];
+
+        let eight: [u8; 16] = decode_interpolated(&[&[210, 140][..], &indices].concat(), false);
+        assert_eq!(eight[..8], [210, 140, 200, 190, 180, 170, 160, 150]);
+
+        let six: [u8; 16] = decode_interpolated(&[&[100, 200][..], &indices].concat(), false);
+        assert_eq!(six[..8], [100, 200, 120, 140, 160, 180, 0, 255]);
+    }
+
+    #[test]
+    fn signed_values_are_brought_to_unsigned() {
+        let values: [u8; 16] = decode_interpolated(&[0x7F, 0x81, 0, 0, 0, 0, 0, 0], true);
+
+        assert_eq!(values, [255; 16]);
+        assert_eq!(decode_interpolated(&[0x80, 0x7F, 0xF0, 0x01, 0, 0, 0, 0], true)[..3], [0, 0, 255]);
+    }
+}
@@ -0,0 +1,312 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::dds_bit_reader::DdsBitReader;
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+
+// Header flags
+const FLAG_MIPMAP_COUNT: u32 = 0x20000;
+const FLAG_DEPTH: u32 = 0x800000;
+
+// Pixel format flags
+const PIXEL_ALPHA_PIXELS: u32 = 0x1;
+const PIXEL_ALPHA: u32 = 0x2;
+const PIXEL_FOURCC: u32 = 0x4;
+const PIXEL_RGB: u32 = 0x40;
+const PIXEL_YUV: u32 = 0x200;
+const PIXEL_LUMINANCE: u32 = 0x20000;
+const PIXEL_BUMP_DUDV: u32 = 0x80000;
+
+// Second capabilities, six faces being the bits following the cubemap one
+const CAPS2_CUBEMAP: u32 = 0x200;
+const CAPS2_CUBEMAP_FACES: u32 = 0xFC00;
+const CAPS2_VOLUME: u32 = 0x200000;
+
+// DX10 extension
+const DIMENSION_TEXTURE3D: u32 = 4;
+const MISC_TEXTURECUBE: u32 = 0x4;
+const ALPHA_MODE_PREMULTIPLIED: u32 = 2;
+
+// D3DFMT_A16B16G16R16, stored as a number instead of four characters
+const FOURCC_A16B16G16R16: u32 = 36;
+
+// Longest mipmap chain of a 32 bits size
+const MAX_MIP_COUNT: u32 = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DDSFormat {
+    // DXT1, colors of 4x4 blocks with an optional transparent black
+    #[default]
+    BC1,
+    // DXT2 and DXT3, explicit 4 bits alpha
+    BC2,
+    // DXT4 and DXT5, interpolated alpha
+    BC3,
+    // ATI1, a single interpolated channel
+    BC4,
+    // ATI2, two interpolated channels
+    BC5,
+    // Pixels whose channels are picked by masks
+    UNCOMPRESSED,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DDSDimension {
+    #[default]
+    TEXTURE,
+    // Six faces for each element of the array
+    CUBEMAP,
+    // Slices whose count is halved with each mip level
+    VOLUME,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DDSHeader {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub mip_count: u32,
+    pub dimension: DDSDimension,
+    // Textures of an array, times the faces of cubemaps
+    pub array_size: u32,
+    // Four characters of the legacy pixel format, and the DXGI format of the DX10 extension
+    pub fourcc: String,
+    pub dxgi_format: Option<u32>,
+    pub format: DDSFormat,
+    // BC4 and BC5 values from -1 to 1
+    pub signed: bool,
+    // Colors multiplied by the alpha
+    pub premultiplied: bool,
+    pub bit_count: u32,
+    // Red, green, blue and alpha masks of uncompressed pixels
+    pub masks: [u64; 4],
+    // Red mask holding a gray level
+    pub luminance: bool,
+}
+
+impl DDSHeader {
+    pub fn from_binary(reader: &mut DdsBitReader) -> Result<Self> {
+        let mut header: DDSHeader = DDSHeader::default();
+
+        if &reader.read_bytes(4)?[..] != MAGIC {
+            bail!("Not a DDS file");
+        }
+
+        let size: u32 = reader.read_double()?.swap_bytes();
+
+        if size != HEADER_SIZE {
+            bail!("DDS header of {size} bytes is invalid");
+        }
+
+        let flags: u32 = reader.read_double()?.swap_bytes();
+        header.height = reader.read_double()?.swap_bytes();
+        header.width = reader.read_double()?.swap_bytes();
+        let _pitch_or_linear_size: u32 = reader.read_double()?.swap_bytes();
+        let depth: u32 = reader.read_double()?.swap_bytes();
+        let mip_count: u32 = reader.read_double()?.swap_bytes();
+        reader.read_bytes(44)?;
+
+        let pixel_format_size: u32 = reader.read_double()?.swap_bytes();
+
+        if pixel_format_size != PIXEL_FORMAT_SIZE {
+            bail!("DDS pixel format of {pixel_format_size} bytes is invalid");
+        }
+
+        let pixel_flags: u32 = reader.read_double()?.swap_bytes();
+        let fourcc: Vec<u8> = reader.read_bytes(4)?;
+        header.bit_count = reader.read_double()?.swap_bytes();
+
+        for mask in header.masks.iter_mut() {
+            *mask = reader.read_double()?.swap_bytes() as u64;
+        }
+
+        let _caps: u32 = reader.read_double()?.swap_bytes();
+        let caps2: u32 = reader.read_double()?.swap_bytes();
+        reader.read_bytes(12)?;
+
+        header.mip_count = if flags & FLAG_MIPMAP_COUNT != 0 { mip_count.max(1) } else { 1 };
+        header.depth = 1;
+        header.array_size = 1;
+
+        if pixel_flags & PIXEL_FOURCC != 0 {
+            header.fourcc = String::from_utf8_lossy(&fourcc).into_owned();
+
+            match &fourcc[..] {
+                b"DXT1" => header.format = DDSFormat::BC1,
+                b"DXT2" => (header.format, header.premultiplied) = (DDSFormat::BC2, true),
+                b"DXT3" => header.format = DDSFormat::BC2,
+                b"DXT4" => (header.format, header.premultiplied) = (DDSFormat::BC3, true),
+                b"DXT5" => header.format = DDSFormat::BC3,
+                b"ATI1" | b"BC4U" => header.format = DDSFormat::BC4,
+                b"BC4S" => (header.format, header.signed) = (DDSFormat::BC4, true),
+                b"ATI2" | b"BC5U" => header.format = DDSFormat::BC5,
+                b"BC5S" => (header.format, header.signed) = (DDSFormat::BC5, true),
+                b"DX10" => header.read_extension(reader)?,
+                _ if u32::from_le_bytes([fourcc[0], fourcc[1], fourcc[2], fourcc[3]]) == FOURCC_A16B16G16R16 => {
+                    header.format = DDSFormat::UNCOMPRESSED;
+                    header.bit_count = 64;
+                    header.masks = [0xFFFF, 0xFFFF << 16, 0xFFFF << 32, 0xFFFF << 48];
+                },
+                _ => bail!("DDS pixel format \"{}\" not supported", header.fourcc.escape_debug()),
+            }
+        } else if pixel_flags & (PIXEL_YUV | PIXEL_BUMP_DUDV) != 0 {
+            bail!("DDS YUV and bump map pixel formats not supported");
+        } else if pixel_flags & (PIXEL_RGB | PIXEL_LUMINANCE | PIXEL_ALPHA) != 0 {
+            header.format = DDSFormat::UNCOMPRESSED;
+            header.luminance = pixel_flags & PIXEL_LUMINANCE != 0;
+
+            // Alpha mask only counts when flagged, alpha-only formats having nothing but it
+            if pixel_flags & PIXEL_ALPHA != 0 && pixel_flags & (PIXEL_RGB | PIXEL_LUMINANCE) == 0 {
+                header.masks[..3].fill(0);
+            } else if pixel_flags & PIXEL_ALPHA_PIXELS == 0 {
+                header.masks[3] = 0;
+            }
+
+            if !matches!(header.bit_count, 8 | 16 | 24 | 32) {
+                bail!("DDS pixels of {} bits not supported", header.bit_count);
+            }
+        } else {
+            bail!("DDS pixel format flags {pixel_flags:#X} not supported");
+        }
+
+        // Legacy cubemaps and volumes, the DX10 extension having set them already
+        if header.dxgi_format.is_none() {
+            if caps2 & CAPS2_CUBEMAP != 0 {
+                header.dimension = DDSDimension::CUBEMAP;
+                header.array_size = (caps2 & CAPS2_CUBEMAP_FACES).count_ones();
+            } else if caps2 & CAPS2_VOLUME != 0 && flags & FLAG_DEPTH != 0 {
+                header.dimension = DDSDimension::VOLUME;
+                header.depth = depth.max(1);
+            }
+        } else if header.dimension == DDSDimension::VOLUME {
+            header.depth = depth.max(1);
+        }
+
+        if header.width == 0 || header.height == 0 || header.array_size == 0 {
+            bail!("DDS texture of {}x{} and {} slices is empty", header.width, header.height, header.array_size);
+        }
+
+        pixel_count("DDS", header.width, header.height)?;
+
+        if header.mip_count > MAX_MIP_COUNT {
+            bail!("DDS texture of {} mip levels is invalid", header.mip_count);
+        }
+
+        Ok(header)
+    }
+
+    // DXGI format, dimension and array size following the header
+    fn read_extension(&mut self, reader: &mut DdsBitReader) -> Result<()> {
+        let dxgi_format: u32 = reader.read_double()?.swap_bytes();
+        let dimension: u32 = reader.read_double()?.swap_bytes();
+        let misc_flags: u32 = reader.read_double()?.swap_bytes();
+        let array_size: u32 = reader.read_double()?.swap_bytes();
+        let misc_flags2: u32 = reader.read_double()?.swap_bytes();
+
+        self.dxgi_format = Some(dxgi_format);
+        self.premultiplied = misc_flags2 & 0x7 == ALPHA_MODE_PREMULTIPLIED;
+
+        (self.format, self.bit_count, self.masks) = match dxgi_format {
+            // R16G16B16A16_UNORM
+            11 => (DDSFormat::UNCOMPRESSED, 64, [0xFFFF, 0xFFFF << 16, 0xFFFF << 32, 0xFFFF << 48]),
+            // R10G10B10A2_UNORM
+            24 => (DDSFormat::UNCOMPRESSED, 32, [0x3FF, 0xFFC00, 0x3FF00000, 0xC0000000]),
+            // R8G8B8A8_TYPELESS, UNORM and UNORM_SRGB
+            27..=29 => (DDSFormat::UNCOMPRESSED, 32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000]),
+            // R16G16_UNORM
+            35 => (DDSFormat::UNCOMPRESSED, 32, [0xFFFF, 0xFFFF0000, 0, 0]),
+            // R8G8_UNORM
+            49 => (DDSFormat::UNCOMPRESSED, 16, [0xFF, 0xFF00, 0, 0]),
+            // R16_UNORM
+            56 => (DDSFormat::UNCOMPRESSED, 16, [0xFFFF, 0, 0, 0]),
+            // R8_UNORM
+            61 => (DDSFormat::UNCOMPRESSED, 8, [0xFF, 0, 0, 0]),
+            // A8_UNORM
+            65 => (DDSFormat::UNCOMPRESSED, 8, [0, 0, 0, 0xFF]),
+            70..=72 => (DDSFormat::BC1, 0, [0; 4]),
+            73..=75 => (DDSFormat::BC2, 0, [0; 4]),
+            76..=78 => (DDSFormat::BC3, 0, [0; 4]),
+            79..=81 => (DDSFormat::BC4, 0, [0; 4]),
+            82..=84 => (DDSFormat::BC5, 0, [0; 4]),
+            // B5G6R5_UNORM
+            85 => (DDSFormat::UNCOMPRESSED, 16, [0xF800, 0x7E0, 0x1F, 0]),
+            // B5G5R5A1_UNORM
+            86 => (DDSFormat::UNCOMPRESSED, 16, [0x7C00, 0x3E0, 0x1F, 0x8000]),
+            // B8G8R8A8 and B8G8R8X8, UNORM, TYPELESS and UNORM_SRGB
+            87 | 90 | 91 => (DDSFormat::UNCOMPRESSED, 32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000]),
+            88 | 92 | 93 => (DDSFormat::UNCOMPRESSED, 32, [0xFF0000, 0xFF00, 0xFF, 0]),
+            // B4G4R4A4_UNORM
+            115 => (DDSFormat::UNCOMPRESSED, 16, [0xF00, 0xF0, 0xF, 0xF000]),
+            value => bail!("DDS DXGI format {value} not supported"),
+        };
+
+        // BC4_SNORM and BC5_SNORM
+        self.signed = matches!(dxgi_format, 81 | 84);
+        self.luminance = matches!(dxgi_format, 56 | 61);
+
+        if dimension == DIMENSION_TEXTURE3D {
+            self.dimension = DDSDimension::VOLUME;
+        } else if misc_flags & MISC_TEXTURECUBE != 0 {
+            self.dimension = DDSDimension::CUBEMAP;
+        }
+
+        self.array_size = match self.dimension {
+            DDSDimension::TEXTURE => array_size,
+            DDSDimension::CUBEMAP => array_size.saturating_mul(6),
+            DDSDimension::VOLUME => 1,
+        };
+
+        Ok(())
+    }
+
+    // Bytes of a 4x4 block, none for uncompressed pixels
+    pub fn block_size(&self) -> u64 {
+        match self.format {
+            DDSFormat::BC1 | DDSFormat::BC4 => 8,
+            DDSFormat::BC2 | DDSFormat::BC3 | DDSFormat::BC5 => 16,
+            DDSFormat::UNCOMPRESSED => 0,
+        }
+    }
+
+    // Size of a mip level, which is at least one pixel
+    pub fn level_size(&self, level: u32) -> (u32, u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1), (self.depth >> level).max(1))
+    }
+
+    // Bytes of a 2D surface, block compressed ones being made of whole blocks
+    pub fn surface_size(&self, width: u32, height: u32) -> u64 {
+        match self.format {
+            DDSFormat::UNCOMPRESSED => width as u64 * height as u64 * (self.bit_count as u64 / 8),
+            _ => (width as u64).div_ceil(4) * (height as u64).div_ceil(4) * self.block_size(),
+        }
+    }
+}
+
+impl fmt::Display for DDSHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "depth: {}\n", self.depth)?;
+        write!(f, "mip_count: {}\n", self.mip_count)?;
+        write!(f, "dimension: {:?}\n", self.dimension)?;
+        write!(f, "array_size: {}\n", self.array_size)?;
+        write!(f, "fourcc: {}\n", self.fourcc.escape_debug())?;
+        write!(f, "dxgi_format: {:?}\n", self.dxgi_format)?;
+        write!(f, "format: {:?}\n", self.format)?;
+        write!(f, "signed: {}\n", self.signed)?;
+        write!(f, "premultiplied: {}\n", self.premultiplied)?;
+
+        if self.format == DDSFormat::UNCOMPRESSED {
+            write!(f, "bit_count: {}\n", self.bit_count)?;
+            write!(f, "masks: {:X?}\n", self.masks)?;
+            write!(f, "luminance: {}\n", self.luminance)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use dds_bit_reader::DdsBitReader;
+use header::{DDSDimension, DDSFormat, DDSHeader};
+use super::bmp::BMP;
+use super::{DecodeOptions, Image};
+
+mod block;
+mod dds_bit_reader;
+mod header;
+
+#[derive(Clone, Debug)]
+pub struct DDS {
+    header: DDSHeader,
+    mip_level: u32,
+    slice: u32,
+    image: BMP,
+}
+
+impl DDS {
+    pub fn from_stream_with(stream: Stream, options: DecodeOptions, debug: bool) -> Result<Self> {
+        let mut reader: DdsBitReader = DdsBitReader::new(stream);
+        let header: DDSHeader = DDSHeader::from_binary(&mut reader)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let mip_level: u32 = options.mip_level as u32;
+
+        if options.mip_level >= header.mip_count as usize {
+            bail!("No mip level {}, the texture has {} levels", options.mip_level, header.mip_count);
+        }
+
+        let (width, height, depth): (u32, u32, u32) = header.level_size(mip_level);
+
+        // Volume slices are those of the level, other textures having the same slices at each level
+        let slices: u32 = if header.dimension == DDSDimension::VOLUME { depth } else { header.array_size };
+
+        let slice: u32 = match options.entry {
+            Some(index) if index >= slices as usize => bail!("No slice {index}, the mip level has {slices} slices"),
+            Some(index) => index as u32,
+            None => 0,
+        };
+
+        let offset: u64 = reader.position()?.saturating_add(Self::surface_offset(&header, mip_level, slice));
+        let size: u64 = header.surface_size(width, height);
+
+        if offset.saturating_add(size) > reader.length()? {
+            bail!("DDS surface of {size} bytes at {offset} is out of the file");
+        }
+
+        reader.seek(offset)?;
+        let data: Vec<u8> = reader.read_bytes(size as usize)?;
+        let pixels: Vec<(u8, u8, u8, u8)> = decode_surface(&header, &data, width as usize, height as usize);
+
+        let image: BMP = BMP::from_rgba_pixels(width, height, |x, y| {
+            let (r, g, b, a): (u8, u8, u8, u8) = pixels[y * width as usize + x];
+
+            if header.premultiplied && a != 0 {
+                let unpremultiply = |color: u8| (color as u32 * 255 / a as u32).min(255) as u8;
+                (unpremultiply(r), unpremultiply(g), unpremultiply(b), a)
+            } else {
+                (r, g, b, a)
+            }
+        });
+
+        Ok(Self {
+            header,
+            mip_level,
+            slice,
+            image,
+        })
+    }
+
+    // Offset from the end of the headers, each texture of an array being followed by its mip levels
+    // while volumes store all the slices of a level before the next one
+    fn surface_offset(header: &DDSHeader, mip_level: u32, slice: u32) -> u64 {
+        let level_bytes = |level: u32| {
+            let (width, height, depth): (u32, u32, u32) = header.level_size(level);
+            let slices: u64 = if header.dimension == DDSDimension::VOLUME { depth as u64 } else { 1 };
+            header.surface_size(width, height).saturating_mul(slices)
+        };
+
+        // Sizes of corrupted headers saturate, the surface being then out of the file
+        let levels_before: u64 = (0..mip_level).map(level_bytes).fold(0, u64::saturating_add);
+        let (width, height, _): (u32, u32, u32) = header.level_size(mip_level);
+
+        if header.dimension == DDSDimension::VOLUME {
+            levels_before.saturating_add((slice as u64).saturating_mul(header.surface_size(width, height)))
+        } else {
+            let chain: u64 = (0..header.mip_count).map(level_bytes).fold(0, u64::saturating_add);
+            (slice as u64).saturating_mul(chain).saturating_add(levels_before)
+        }
+    }
+}
+
+// Pixels in raster order, blocks past the right and bottom edges being cut
+fn decode_surface(header: &DDSHeader, data: &[u8], width: usize, height: usize) -> Vec<(u8, u8, u8, u8)> {
+    let mut pixels: Vec<(u8, u8, u8, u8)> = vec![(0, 0, 0, 0); width * height];
+
+    if header.format == DDSFormat::UNCOMPRESSED {
+        let bytes: usize = header.bit_count as usize / 8;
+
+        for (pixel, chunk) in pixels.iter_mut().zip(data.chunks_exact(bytes)) {
+            let mut value_bytes: [u8; 8] = [0; 8];
+            value_bytes[..bytes].copy_from_slice(chunk);
+            *pixel = masked_pixel(header, u64::from_le_bytes(value_bytes));
+        }
+
+        return pixels;
+    }
+
+    let blocks_across: usize = width.div_ceil(4);
+
+    for (index, block) in data.chunks_exact(header.block_size() as usize).enumerate() {
+        let colors: [(u8, u8, u8, u8); 16] = match header.format {
+            DDSFormat::BC1 => block::decode_color(block, true),
+            DDSFormat::BC2 => {
+                let colors: [(u8, u8, u8, u8); 16] = block::decode_color(&block[8..], false);
+                let alphas: [u8; 16] = block::decode_explicit_alpha(&block[..8]);
+                std::array::from_fn(|i| (colors[i].0, colors[i].1, colors[i].2, alphas[i]))
+            },
+            DDSFormat::BC3 => {
+                let colors: [(u8, u8, u8, u8); 16] = block::decode_color(&block[8..], false);
+                let alphas: [u8; 16] = block::decode_interpolated(&block[..8], false);
+                std::array::from_fn(|i| (colors[i].0, colors[i].1, colors[i].2, alphas[i]))
+            },
+            // Single channel shown as gray, two channels as red and green
+            DDSFormat::BC4 => block::decode_interpolated(block, header.signed).map(|value| (value, value, value, u8::MAX)),
+            DDSFormat::BC5 => {
+                let reds: [u8; 16] = block::decode_interpolated(&block[..8], header.signed);
+                let greens: [u8; 16] = block::decode_interpolated(&block[8..], header.signed);
+                std::array::from_fn(|i| (reds[i], greens[i], 0, u8::MAX))
+            },
+            DDSFormat::UNCOMPRESSED => unreachable!("Uncompressed pixels have no blocks"),
+        };
+
+        let (block_x, block_y): (usize, usize) = (index % blocks_across * 4, index / blocks_across * 4);
+
+        for (i, &color) in colors.iter().enumerate() {
+            let (x, y): (usize, usize) = (block_x + i % 4, block_y + i / 4);
+
+            if x < width && y < height {
+                pixels[y * width + x] = color;
+            }
+        }
+    }
+
+    pixels
+}
+
+// Channels picked by the masks and scaled to 8 bits, missing colors being black and missing alpha opaque
+fn masked_pixel(header: &DDSHeader, value: u64) -> (u8, u8, u8, u8) {
+    let channel = |mask: u64, missing: u8| {
+        if mask == 0 {
+            return missing;
+        }
+
+        let maximum: u64 = mask >> mask.trailing_zeros();
+        let value: u64 = (value & mask) >> mask.trailing_zeros();
+        ((value * 255 + maximum / 2) / maximum) as u8
+    };
+
+    let [red, green, blue, alpha]: [u64; 4] = header.masks;
+    let a: u8 = channel(alpha, u8::MAX);
+
+    if header.luminance {
+        let gray: u8 = channel(red, 0);
+        (gray, gray, gray, a)
+    } else {
+        (channel(red, 0), channel(green, 0), channel(blue, 0), a)
+    }
+}
+
+impl Image for DDS {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        Self::from_stream_with(stream, DecodeOptions::default(), debug)
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for DDS {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)?;
+        write!(f, "mip_level: {}\n", self.mip_level)?;
+        write!(f, "slice: {}\n", self.slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    // Pixel formats of their flags, four characters code, bits and masks
+    const DXT1: (u32, &[u8; 4], u32, [u32; 4]) = (0x4, b"DXT1", 0, [0; 4]);
+    const BGRA: (u32, &[u8; 4], u32, [u32; 4]) = (0x41, &[0; 4], 32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000]);
+
+    // Headers of a texture of the given mip levels and pixel format
+    fn file(width: u32, height: u32, mip_count: u32, format: (u32, &[u8; 4], u32, [u32; 4]), data: &[u8]) -> Vec<u8> {
+        let (pixel_flags, fourcc, bit_count, masks): (u32, &[u8; 4], u32, [u32; 4]) = format;
+        let mut bytes: Vec<u8> = b"DDS ".to_vec();
+
+        for value in [124, 0x1007 | 0x20000, height, width, 0, 0, mip_count] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&[0; 44]);
+        bytes.extend_from_slice(&32u32.to_le_bytes());
+        bytes.extend_from_slice(&pixel_flags.to_le_bytes());
+        bytes.extend_from_slice(fourcc);
+        bytes.extend_from_slice(&bit_count.to_le_bytes());
+        masks.iter().for_each(|mask| bytes.extend_from_slice(&mask.to_le_bytes()));
+        bytes.extend_from_slice(&[0; 20]);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn decode(bytes: Vec<u8>, mip_level: usize) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        let options: DecodeOptions = DecodeOptions {
+            mip_level,
+            ..DecodeOptions::default()
+        };
+
+        Ok(DDS::from_stream_with(BufReader::new(source), options, false)?.image)
+    }
+
+    #[test]
+    fn uncompressed_pixels_are_picked_by_masks() {
+        let image: BMP = decode(file(2, 1, 1, BGRA, &[1, 2, 3, 4, 5, 6, 7, 8]), 0).unwrap();
+
+        assert_eq!((image.pixel_color(0, 0), image.pixel_alpha(0, 0)), ((3, 2, 1), 4));
+        assert_eq!((image.pixel_color(1, 0), image.pixel_alpha(1, 0)), ((7, 6, 5), 8));
+    }
+
+    #[test]
+    fn blocks_past_the_edges_are_cut() {
+        // Red block on the left, green one on the right, the second one being 1 pixel wide
+        let data: [u8; 16] = [0, 0xF8, 0, 0xF8, 0, 0, 0, 0, 0xE0, 0x07, 0xE0, 0x07, 0, 0, 0, 0];
+        let image: BMP = decode(file(5, 3, 1, DXT1, &data), 0).unwrap();
+
+        assert_eq!((image.width(), image.height()), (5, 3));
+        assert_eq!(image.pixel_color(3, 2), (255, 0, 0));
+        assert_eq!(image.pixel_color(4, 2), (0, 255, 0));
+    }
+
+    #[test]
+    fn mip_levels_follow_each_other() {
+        // Levels of 8x4, 4x2, 2x1 and 1x1 pixels, blocks holding one color each
+        let data: Vec<u8> = [[0, 0xF8, 0, 0xF8], [0xE0, 0x07, 0xE0, 0x07], [0x1F, 0, 0x1F, 0], [0xFF, 0xFF, 0xFF, 0xFF], [0, 0, 0, 0]]
+            .iter().flat_map(|colors| colors.iter().copied().chain([0; 4])).collect();
+        let image: BMP = decode(file(8, 4, 4, DXT1, &data), 2).unwrap();
+
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixel_color(1, 0), (255, 255, 255));
+    }
+
+    #[test]
+    fn missing_mip_levels_are_rejected() {
+        let error: String = decode(file(4, 4, 1, DXT1, &[0; 8]), 1).unwrap_err().to_string();
+
+        assert!(error.contains("No mip level 1"), "{error}");
+    }
+
+    #[test]
+    fn oversized_textures_are_rejected() {
+        let error: String = decode(file(1 << 20, 1 << 20, 1, DXT1, &[]), 0).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn surfaces_out_of_the_file_are_rejected() {
+        let error: String = decode(file(8, 8, 1, DXT1, &[0; 24]), 0).unwrap_err().to_string();
+
+        assert!(error.contains("DDS surface of 32 bytes at 128 is out of the file"), "{error}");
+    }
+}
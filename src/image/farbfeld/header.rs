@@ -0,0 +1,43 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::farbfeld_bit_reader::FarbfeldBitReader;
+
+pub const MAGIC: &[u8; 8] = b"farbfeld";
+
+#[derive(Clone, Debug, Default)]
+pub struct FARBFELDHeader {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FARBFELDHeader {
+    pub fn from_binary(reader: &mut FarbfeldBitReader) -> Result<Self> {
+        let mut header: FARBFELDHeader = FARBFELDHeader::default();
+
+        if reader.read_bytes(MAGIC.len())? != MAGIC {
+            bail!("Not a farbfeld file");
+        }
+
+        header.width = reader.read_double()?;
+        header.height = reader.read_double()?;
+
+        if header.width == 0 || header.height == 0 {
+            bail!("Farbfeld image of {}x{} is empty", header.width, header.height);
+        }
+
+        pixel_count("Farbfeld", header.width, header.height)?;
+
+        Ok(header)
+    }
+}
+
+impl fmt::Display for FARBFELDHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        Ok(())
+    }
+}
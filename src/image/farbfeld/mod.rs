@@ -0,0 +1,135 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use farbfeld_bit_reader::FarbfeldBitReader;
+use header::FARBFELDHeader;
+use super::bmp::BMP;
+use super::Image;
+
+mod farbfeld_bit_reader;
+mod header;
+pub mod writer;
+
+// Red, green, blue and alpha channels of 16 bits
+const PIXEL_SIZE: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct FARBFELD {
+    header: FARBFELDHeader,
+    image: BMP,
+}
+
+impl Image for FARBFELD {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: FarbfeldBitReader = FarbfeldBitReader::new(stream);
+        let header: FARBFELDHeader = FARBFELDHeader::from_binary(&mut reader)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let size: u64 = reader.length()? - reader.position()?;
+        let data: Vec<u8> = reader.read_bytes(size as usize)?;
+        let count: usize = header.width as usize * header.height as usize;
+
+        if data.len() < count * PIXEL_SIZE {
+            bail!("Farbfeld pixels hold {} bytes instead of {}", data.len(), count * PIXEL_SIZE);
+        }
+
+        // Big endian channels, scaled down to 8 bits with rounding
+        let channel = |position: usize| ((u16::from_be_bytes([data[position], data[position + 1]]) as u32 + 128) / 257) as u8;
+        let width: usize = header.width as usize;
+
+        let image: BMP = BMP::from_rgba_pixels(header.width, header.height, |x, y| {
+            let position: usize = (y * width + x) * PIXEL_SIZE;
+            (channel(position), channel(position + 2), channel(position + 4), channel(position + 6))
+        });
+
+        Ok(Self {
+            header,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for FARBFELD {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs;
+    use std::io::{BufReader, Cursor};
+    use std::path::PathBuf;
+    use std::process;
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn file(width: u32, height: u32, channels: &[u16]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = header::MAGIC.to_vec();
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend(channels.iter().flat_map(|channel| channel.to_be_bytes()));
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes.to_vec()));
+        Ok(FARBFELD::from_stream(BufReader::new(source), false)?.image)
+    }
+
+    fn write(image: &BMP, name: &str) -> Vec<u8> {
+        let path: PathBuf = temp_dir().join(format!("term_viewer_{}_{name}.ff", process::id()));
+        writer::write_to_file(image, path.clone()).unwrap();
+        let bytes: Vec<u8> = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn pixels(image: &BMP) -> Vec<(u8, u8, u8, u8)> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| (x, y))).map(|(x, y)| {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+            (r, g, b, image.pixel_alpha(x, y))
+        }).collect()
+    }
+
+    #[test]
+    fn channels_are_rounded_to_8_bits() {
+        let image: BMP = decode(&file(2, 1, &[0xFFFF, 0x8080, 0x0081, 0x0000, 0x007F, 0x1234, 0xFEFF, 0x8000])).unwrap();
+
+        assert_eq!(pixels(&image), [(255, 128, 1, 0), (0, 18, 254, 128)]);
+    }
+
+    #[test]
+    fn written_files_round_trip() {
+        let channels: Vec<u16> = [10, 20, 30, 255, 0, 128, 255, 0, 7, 7, 7, 100].iter().map(|&channel| channel * 257).collect();
+        let bytes: Vec<u8> = file(3, 1, &channels);
+        let image: BMP = decode(&bytes).unwrap();
+
+        assert_eq!(pixels(&image), [(10, 20, 30, 255), (0, 128, 255, 0), (7, 7, 7, 100)]);
+        assert_eq!(write(&image, "round_trip"), bytes);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode(&file(100_000, 100_000, &[])).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn truncated_pixels_are_rejected() {
+        let error: String = decode(&file(2, 1, &[0; 6])).unwrap_err().to_string();
+
+        assert!(error.contains("Farbfeld pixels hold 12 bytes instead of 16"), "{error}");
+    }
+}
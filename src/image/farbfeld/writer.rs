@@ -0,0 +1,28 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::image::bmp::BMP;
+use crate::image::write_bytes;
+use super::header::MAGIC;
+
+// Channels of 8 bits widened to 16 bits, alpha always included
+pub fn write_to_file(image: &BMP, filename: PathBuf) -> Result<()> {
+    let (width, height): (u32, u32) = (image.width(), image.height());
+
+    let mut buffer: Vec<u8> = Vec::new();
+    buffer.extend_from_slice(MAGIC);
+    buffer.extend_from_slice(&width.to_be_bytes());
+    buffer.extend_from_slice(&height.to_be_bytes());
+
+    for y in 0..height {
+        for x in 0..width {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+
+            for channel in [r, g, b, image.pixel_alpha(x, y)] {
+                buffer.extend_from_slice(&(channel as u16 * 257).to_be_bytes());
+            }
+        }
+    }
+
+    write_bytes(filename, &buffer)
+}
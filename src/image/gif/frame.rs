@@ -0,0 +1,114 @@
+use anyhow::Result;
+use std::fmt;
+use std::time::Duration;
+
+use super::lzw;
+
+// Interlaced rows as (first row, row step) of each pass
+const INTERLACE_PASSES: [(usize, usize); 4] = [(0, 8), (4, 8), (2, 4), (1, 2)];
+
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+// What becomes of the area of a frame once it is over
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DisposalMethod {
+    #[default]
+    NONE,
+    // Cleared to transparent, as browsers do, rather than to the background color
+    BACKGROUND,
+    // Restored to what it was before the frame
+    PREVIOUS,
+}
+
+// Graphic Control Extension, applying to the next image
+#[derive(Clone, Copy, Debug)]
+pub struct GraphicControl {
+    pub disposal: DisposalMethod,
+    pub delay: Duration,
+    pub transparent_index: Option<u8>,
+}
+
+// Images without extension are played like ones without delay
+impl Default for GraphicControl {
+    fn default() -> Self {
+        Self {
+            disposal: DisposalMethod::NONE,
+            delay: DEFAULT_DELAY,
+            transparent_index: None,
+        }
+    }
+}
+
+impl GraphicControl {
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let (Some(&packed), Some(&transparent_index)) = (data.first(), data.get(3)) else {
+            return Self::default();
+        };
+
+        // Delays of less than 20ms are played at 100ms by browsers, which such files rely on
+        let delay: Duration = match u16::from_le_bytes([data[1], data[2]]) {
+            0 | 1 => DEFAULT_DELAY,
+            delay => Duration::from_millis(delay as u64 * 10),
+        };
+
+        Self {
+            disposal: match (packed >> 2) & 0x07 {
+                2 => DisposalMethod::BACKGROUND,
+                3 => DisposalMethod::PREVIOUS,
+                _ => DisposalMethod::NONE,
+            },
+            delay,
+            transparent_index: if packed & 0x01 != 0 { Some(transparent_index) } else { None },
+        }
+    }
+}
+
+// Image descriptor with the LZW data of the image
+#[derive(Clone, Debug, Default)]
+pub struct GIFFrame {
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    pub interlaced: bool,
+    pub local_color_table: Vec<(u8, u8, u8)>,
+    pub control: GraphicControl,
+    pub minimum_code_size: u8,
+    pub data: Vec<u8>,
+}
+
+impl GIFFrame {
+    // Color index of each pixel, rows going from top to bottom, None past the end of truncated images
+    pub fn indices(&self) -> Result<Vec<Option<u8>>> {
+        let (width, height): (usize, usize) = (self.width as usize, self.height as usize);
+
+        // Empty frames hold no pixel to draw over the canvas
+        if width == 0 || height == 0 {
+            return Ok(Vec::new());
+        }
+
+        let decoded: Vec<u8> = lzw::decode(&self.data, self.minimum_code_size, width * height)?;
+        let mut indices: Vec<Option<u8>> = vec![None; width * height];
+
+        let rows: Vec<usize> = match self.interlaced {
+            true => INTERLACE_PASSES.iter().flat_map(|&(first, step)| (first..height).step_by(step)).collect(),
+            false => (0..height).collect(),
+        };
+
+        for (row, index) in decoded.chunks(width).zip(rows) {
+            for (x, &color) in row.iter().enumerate() {
+                indices[index * width + x] = Some(color);
+            }
+        }
+
+        Ok(indices)
+    }
+}
+
+impl fmt::Display for GIFFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{} at ({}, {}), ", self.width, self.height, self.left, self.top)?;
+        write!(f, "interlaced: {}, local colors: {}, ", self.interlaced, self.local_color_table.len())?;
+        write!(f, "{:?}, {:?}, transparent index: {:?}\n", self.control.delay, self.control.disposal, self.control.transparent_index)
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::gif_bit_reader::GifBitReader;
+
+// Header and logical screen descriptor, followed by the global color table
+#[derive(Clone, Debug, Default)]
+pub struct GIFHeader {
+    pub version: String,
+    pub width: u16,
+    pub height: u16,
+    pub color_resolution: u8,
+    pub background_index: u8,
+    pub aspect_ratio: u8,
+    pub global_color_table: Vec<(u8, u8, u8)>,
+}
+
+impl GIFHeader {
+    pub fn from_binary(reader: &mut GifBitReader) -> Result<Self> {
+        let mut header: GIFHeader = GIFHeader::default();
+
+        let signature: Vec<u8> = reader.read_bytes(6)?;
+
+        header.version = match &signature[..] {
+            b"GIF87a" | b"GIF89a" => String::from_utf8_lossy(&signature[3..]).into_owned(),
+            _ => bail!("Not a GIF file"),
+        };
+
+        header.width = reader.read_word()?.swap_bytes();
+        header.height = reader.read_word()?.swap_bytes();
+
+        let packed: u8 = reader.read_byte()?;
+        header.color_resolution = ((packed >> 4) & 0x07) + 1;
+        header.background_index = reader.read_byte()?;
+        header.aspect_ratio = reader.read_byte()?;
+
+        if packed & 0x80 != 0 {
+            header.global_color_table = read_color_table(reader, packed & 0x07)?;
+        }
+
+        if header.width == 0 || header.height == 0 {
+            bail!("GIF screen of {}x{} is empty", header.width, header.height);
+        }
+
+        pixel_count("GIF", header.width as u32, header.height as u32)?;
+
+        Ok(header)
+    }
+}
+
+// Table of 2^(size + 1) RGB triplets
+pub fn read_color_table(reader: &mut GifBitReader, size: u8) -> Result<Vec<(u8, u8, u8)>> {
+    let bytes: Vec<u8> = reader.read_bytes(3 << (size + 1))?;
+
+    Ok(bytes.chunks_exact(3).map(|color| (color[0], color[1], color[2])).collect())
+}
+
+impl fmt::Display for GIFHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "version: {}\n", self.version)?;
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "color_resolution: {}\n", self.color_resolution)?;
+        write!(f, "background_index: {}\n", self.background_index)?;
+        write!(f, "aspect_ratio: {}\n", self.aspect_ratio)?;
+        write!(f, "global_color_table: {} colors\n", self.global_color_table.len())?;
+        Ok(())
+    }
+}
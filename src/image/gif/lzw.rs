@@ -0,0 +1,142 @@
+use anyhow::{bail, Result};
+
+const MAX_CODE_SIZE: u32 = 12;
+const MAX_CODES: usize = 1 << MAX_CODE_SIZE;
+
+// Codes of variable size, packed from the least significant bit of each byte
+struct CodeReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl CodeReader<'_> {
+    fn read_code(&mut self, size: u32) -> Option<u16> {
+        if self.position + size as usize > self.data.len() * 8 {
+            return None;
+        }
+
+        let mut code: u16 = 0;
+
+        for i in 0..size as usize {
+            let bit: u8 = (self.data[(self.position + i) / 8] >> ((self.position + i) % 8)) & 1;
+            code |= (bit as u16) << i;
+        }
+
+        self.position += size as usize;
+        Some(code)
+    }
+}
+
+// Color indices of a frame, truncated streams giving fewer indices than pixels
+pub fn decode(data: &[u8], minimum_code_size: u8, pixel_count: usize) -> Result<Vec<u8>> {
+    if !(1..MAX_CODE_SIZE as u8).contains(&minimum_code_size) {
+        bail!("LZW minimum code size {minimum_code_size} is invalid");
+    }
+
+    let clear_code: u16 = 1 << minimum_code_size;
+    let end_code: u16 = clear_code + 1;
+
+    // Strings of the table are a prefix code followed by one byte
+    let mut prefixes: Vec<u16> = vec![0; MAX_CODES];
+    let mut suffixes: Vec<u8> = (0..MAX_CODES).map(|code| code as u8).collect();
+
+    let mut reader: CodeReader = CodeReader {
+        data,
+        position: 0,
+    };
+    // Sizes come from the file, the output only grows with the decoded data
+    let mut output: Vec<u8> = Vec::new();
+    let mut string: Vec<u8> = Vec::new();
+    let mut code_size: u32 = minimum_code_size as u32 + 1;
+    let mut next_code: u16 = end_code + 1;
+    let mut previous: Option<u16> = None;
+
+    while output.len() < pixel_count {
+        let Some(code) = reader.read_code(code_size) else {
+            break;
+        };
+
+        if code == clear_code {
+            code_size = minimum_code_size as u32 + 1;
+            next_code = end_code + 1;
+            previous = None;
+            continue;
+        }
+
+        if code == end_code {
+            break;
+        }
+
+        if let Some(previous) = previous {
+            // Code not yet in the table is the previous string followed by its own first byte
+            let first: u16 = match code {
+                _ if code < next_code => code,
+                _ if code == next_code => previous,
+                _ => bail!("LZW code {code} is out of the table of {next_code} codes"),
+            };
+
+            // Table stops growing once full, until the next clear code
+            if (next_code as usize) < MAX_CODES {
+                prefixes[next_code as usize] = previous;
+                suffixes[next_code as usize] = first_byte(&prefixes, &suffixes, first, clear_code);
+                next_code += 1;
+
+                if next_code == 1 << code_size && code_size < MAX_CODE_SIZE {
+                    code_size += 1;
+                }
+            }
+        } else if code > clear_code {
+            bail!("LZW stream starts with code {code}");
+        }
+
+        // Strings are walked from their last byte
+        string.clear();
+        let mut current: u16 = code;
+
+        while current > end_code {
+            string.push(suffixes[current as usize]);
+            current = prefixes[current as usize];
+        }
+
+        string.push(current as u8);
+        output.extend(string.iter().rev());
+        previous = Some(code);
+    }
+
+    output.truncate(pixel_count);
+    Ok(output)
+}
+
+fn first_byte(prefixes: &[u16], suffixes: &[u8], mut code: u16, clear_code: u16) -> u8 {
+    while code > clear_code + 1 {
+        code = prefixes[code as usize];
+    }
+
+    suffixes[code as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_not_yet_in_the_table_repeat_the_previous_string() {
+        // Clear, 1, then codes 6 and 7 used as soon as they are defined, and end, on 3 bits then 4
+        assert_eq!(decode(&[140, 95], 2, 10).unwrap(), [1; 6]);
+    }
+
+    #[test]
+    fn output_stops_at_the_pixel_count() {
+        assert_eq!(decode(&[140, 95], 2, 4).unwrap(), [1; 4]);
+
+        // Stream cut in the middle of the third code
+        assert_eq!(decode(&[140], 2, 10).unwrap(), [1]);
+    }
+
+    #[test]
+    fn invalid_codes_are_rejected() {
+        // Clear, 1, then code 7 before code 6 is defined
+        assert!(decode(&[204, 1], 2, 10).is_err());
+        assert!(decode(&[140, 95], 12, 10).is_err());
+    }
+}
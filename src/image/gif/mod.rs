@@ -0,0 +1,266 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use frame::{GIFFrame, GraphicControl};
+use gif_bit_reader::GifBitReader;
+use header::{read_color_table, GIFHeader};
+use super::bmp::BMP;
+use super::{pixel_count, Image};
+
+pub mod frame;
+pub mod gif_bit_reader;
+pub mod header;
+mod lzw;
+
+const EXTENSION_INTRODUCER: u8 = 0x21;
+const IMAGE_SEPARATOR: u8 = 0x2C;
+const TRAILER: u8 = 0x3B;
+
+const GRAPHIC_CONTROL_LABEL: u8 = 0xF9;
+const APPLICATION_LABEL: u8 = 0xFF;
+
+#[derive(Clone, Debug)]
+pub struct GIF {
+    pub header: GIFHeader,
+    pub frames: Vec<GIFFrame>,
+    // Repetitions after the first play asked by the NETSCAPE extension, zero standing for forever
+    pub loop_count: Option<u16>,
+    // Frames drawn so far, starting fully transparent
+    pub canvas: Vec<(u8, u8, u8, u8)>,
+}
+
+impl GIF {
+    pub fn from_binary(stream: Stream) -> Result<Self> {
+        let mut reader: GifBitReader = GifBitReader::new(stream);
+        let header: GIFHeader = GIFHeader::from_binary(&mut reader)?;
+        let mut frames: Vec<GIFFrame> = Vec::new();
+        let mut loop_count: Option<u16> = None;
+        let mut control: Option<GraphicControl> = None;
+
+        loop {
+            // Files cut before their trailer keep their complete frames
+            let Ok(introducer) = reader.read_byte() else {
+                break;
+            };
+
+            match introducer {
+                EXTENSION_INTRODUCER => {
+                    let label: u8 = reader.read_byte()?;
+                    let data: Vec<u8> = read_sub_blocks(&mut reader)?;
+
+                    match label {
+                        GRAPHIC_CONTROL_LABEL => control = Some(GraphicControl::from_bytes(&data)),
+                        // Identifier and authentication code, then a sub-block of ID 1 holding the loop count
+                        APPLICATION_LABEL if data.len() >= 14 && matches!(&data[..11], b"NETSCAPE2.0" | b"ANIMEXTS1.0") && data[11] == 1 => {
+                            loop_count = Some(u16::from_le_bytes([data[12], data[13]]));
+                        },
+                        _ => (),
+                    }
+                },
+                IMAGE_SEPARATOR => {
+                    let mut frame: GIFFrame = GIFFrame {
+                        left: reader.read_word()?.swap_bytes(),
+                        top: reader.read_word()?.swap_bytes(),
+                        width: reader.read_word()?.swap_bytes(),
+                        height: reader.read_word()?.swap_bytes(),
+                        control: control.take().unwrap_or_default(),
+                        ..GIFFrame::default()
+                    };
+
+                    // Frames may be larger than the logical screen, which only clips them
+                    pixel_count("GIF", frame.width as u32, frame.height as u32)?;
+
+                    let packed: u8 = reader.read_byte()?;
+                    frame.interlaced = packed & 0x40 != 0;
+
+                    if packed & 0x80 != 0 {
+                        frame.local_color_table = read_color_table(&mut reader, packed & 0x07)?;
+                    }
+
+                    frame.minimum_code_size = reader.read_byte()?;
+                    frame.data = read_sub_blocks(&mut reader)?;
+                    frames.push(frame);
+                },
+                TRAILER => break,
+                value => bail!("Unknown GIF block {value:02X}"),
+            }
+        }
+
+        if frames.is_empty() {
+            bail!("GIF file has no image");
+        }
+
+        Ok(Self {
+            canvas: vec![(0, 0, 0, 0); header.width as usize * header.height as usize],
+            header,
+            frames,
+            loop_count,
+        })
+    }
+
+    // Draws the frame over the canvas, its transparent pixels letting the canvas show through
+    pub fn draw_frame(&mut self, index: usize) -> Result<()> {
+        let frame: &GIFFrame = &self.frames[index];
+        let indices: Vec<Option<u8>> = frame.indices()?;
+
+        let colors: &[(u8, u8, u8)] = match frame.local_color_table.is_empty() {
+            true => &self.header.global_color_table,
+            false => &frame.local_color_table,
+        };
+
+        let canvas_width: usize = self.header.width as usize;
+        let canvas_height: usize = self.header.height as usize;
+
+        for y in 0..frame.height as usize {
+            for x in 0..frame.width as usize {
+                let (canvas_x, canvas_y): (usize, usize) = (frame.left as usize + x, frame.top as usize + y);
+
+                // Frames may go past the logical screen
+                if canvas_x >= canvas_width || canvas_y >= canvas_height {
+                    continue;
+                }
+
+                let Some(color) = indices[y * frame.width as usize + x] else {
+                    continue;
+                };
+
+                if frame.control.transparent_index == Some(color) {
+                    continue;
+                }
+
+                let (r, g, b): (u8, u8, u8) = colors.get(color as usize).copied().unwrap_or_default();
+                self.canvas[canvas_y * canvas_width + canvas_x] = (r, g, b, u8::MAX);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_image(&self) -> BMP {
+        let width: usize = self.header.width as usize;
+
+        BMP::from_rgba_pixels(self.header.width as u32, self.header.height as u32, |x, y| self.canvas[y * width + x])
+    }
+}
+
+// Data split in blocks of at most 255 bytes, each preceded by its size, up to an empty one
+fn read_sub_blocks(reader: &mut GifBitReader) -> Result<Vec<u8>> {
+    let mut data: Vec<u8> = Vec::new();
+
+    loop {
+        let size: u8 = reader.read_byte()?;
+
+        if size == 0 {
+            return Ok(data);
+        }
+
+        data.extend_from_slice(&reader.read_bytes(size as usize)?);
+    }
+}
+
+// Still image of the first frame
+impl Image for GIF {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut gif: Self = Self::from_binary(stream)?;
+
+        if debug {
+            println!("{gif}");
+        }
+
+        gif.draw_frame(0)?;
+
+        Ok(gif)
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.to_image())
+    }
+}
+
+impl fmt::Display for GIF {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)?;
+        write!(f, "loop_count: {:?}\n", self.loop_count)?;
+        write!(f, "frames: {}\n", self.frames.len())?;
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            write!(f, "frame {index}: {frame}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    // Clear code, then six pixels of color 1 with a minimum code size of 2
+    const LZW: [u8; 2] = [140, 95];
+
+    // Screen with a global table of black, white, red and green, one frame and an optional graphic control extension
+    fn file(screen: (u16, u16), frame: (u16, u16, u16, u16), control: Option<[u8; 4]>) -> Vec<u8> {
+        let mut bytes: Vec<u8> = b"GIF89a".to_vec();
+        bytes.extend_from_slice(&screen.0.to_le_bytes());
+        bytes.extend_from_slice(&screen.1.to_le_bytes());
+        bytes.extend_from_slice(&[0x81, 0, 0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 255, 0]);
+
+        if let Some(control) = control {
+            bytes.extend_from_slice(&[0x21, 0xF9, 4]);
+            bytes.extend_from_slice(&control);
+            bytes.push(0);
+        }
+
+        bytes.push(0x2C);
+
+        for field in [frame.0, frame.1, frame.2, frame.3] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&[0, 2, LZW.len() as u8]);
+        bytes.extend_from_slice(&LZW);
+        bytes.extend_from_slice(&[0, 0x3B]);
+        bytes
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        Ok(*Box::new(GIF::from_stream(BufReader::new(source), false)?).to_bmp())
+    }
+
+    #[test]
+    fn frames_are_drawn_at_their_position() {
+        let image: BMP = decode(file((4, 3), (1, 1, 3, 2), None)).unwrap();
+
+        assert_eq!((image.pixel_color(1, 1), image.pixel_alpha(1, 1)), ((255, 255, 255), 255));
+        assert_eq!((image.pixel_color(3, 2), image.pixel_alpha(3, 2)), ((255, 255, 255), 255));
+        assert_eq!(image.pixel_alpha(0, 0), 0);
+        assert_eq!(image.pixel_alpha(0, 1), 0);
+    }
+
+    #[test]
+    fn transparent_index_shows_the_canvas() {
+        let image: BMP = decode(file((3, 2), (0, 0, 3, 2), Some([0x01, 0, 0, 1]))).unwrap();
+
+        assert!((0..2).all(|y| (0..3).all(|x| image.pixel_alpha(x, y) == 0)));
+    }
+
+    #[test]
+    fn empty_frames_draw_nothing() {
+        let image: BMP = decode(file((3, 2), (0, 0, 0, 2), None)).unwrap();
+
+        assert!((0..2).all(|y| (0..3).all(|x| image.pixel_alpha(x, y) == 0)));
+    }
+
+    #[test]
+    fn oversized_screens_and_frames_are_rejected() {
+        for bytes in [file((u16::MAX, u16::MAX), (0, 0, 3, 2), None), file((3, 2), (0, 0, u16::MAX, u16::MAX), None)] {
+            let error: String = decode(bytes).unwrap_err().to_string();
+            assert!(error.contains("too large"), "{error}");
+        }
+    }
+}
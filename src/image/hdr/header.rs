@@ -0,0 +1,167 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::hdr_bit_reader::HdrBitReader;
+
+// Longest line of the header, anything longer being binary data
+const MAX_LINE_LENGTH: usize = 4096;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HDRFormat {
+    // Red, green and blue sharing an exponent
+    #[default]
+    RGBE,
+    // CIE XYZ sharing an exponent
+    XYZE,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HDRHeader {
+    // Name following the "#?" of the first line
+    pub program: String,
+    pub format: HDRFormat,
+    // Product of the exposures the pixels went through, which they already include
+    pub exposure: f32,
+    pub software: String,
+    pub width: u32,
+    pub height: u32,
+    // Scanlines are columns instead of rows
+    pub column_major: bool,
+    pub top_to_bottom: bool,
+    pub left_to_right: bool,
+}
+
+impl HDRHeader {
+    pub fn from_binary(reader: &mut HdrBitReader) -> Result<Self> {
+        let mut header: HDRHeader = HDRHeader {
+            exposure: 1.0,
+            ..HDRHeader::default()
+        };
+
+        let first_line: String = read_line(reader)?;
+
+        let Some(program) = first_line.strip_prefix("#?") else {
+            bail!("Not a Radiance HDR file");
+        };
+
+        header.program = program.to_owned();
+
+        // Variables end with an empty line
+        loop {
+            let line: String = read_line(reader)?;
+
+            if line.is_empty() {
+                break;
+            }
+
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            match name.trim() {
+                "FORMAT" => {
+                    header.format = match value.trim() {
+                        "32-bit_rle_rgbe" => HDRFormat::RGBE,
+                        "32-bit_rle_xyze" => HDRFormat::XYZE,
+                        format => bail!("HDR format {format} not supported"),
+                    };
+                },
+                "EXPOSURE" => {
+                    let Ok(exposure) = value.trim().parse::<f32>() else {
+                        bail!("HDR exposure {value} is invalid");
+                    };
+
+                    header.exposure *= exposure;
+                },
+                "SOFTWARE" => header.software = value.trim().to_owned(),
+                _ => (),
+            }
+        }
+
+        // Resolution string, as "-Y <height> +X <width>" for rows from top to bottom of pixels from left to right
+        let resolution: String = read_line(reader)?;
+        let fields: Vec<&str> = resolution.split_whitespace().collect();
+
+        let [first_axis, first_size, second_axis, second_size] = fields[..] else {
+            bail!("HDR resolution \"{resolution}\" is invalid");
+        };
+
+        let (Ok(first_size), Ok(second_size)) = (first_size.parse::<u32>(), second_size.parse::<u32>()) else {
+            bail!("HDR resolution \"{resolution}\" is invalid");
+        };
+
+        (header.column_major, header.width, header.height) = match (first_axis, second_axis) {
+            ("-Y" | "+Y", "-X" | "+X") => (false, second_size, first_size),
+            ("-X" | "+X", "-Y" | "+Y") => (true, first_size, second_size),
+            _ => bail!("HDR resolution \"{resolution}\" is invalid"),
+        };
+
+        let (y_axis, x_axis): (&str, &str) = if header.column_major { (second_axis, first_axis) } else { (first_axis, second_axis) };
+        header.top_to_bottom = y_axis.starts_with('-');
+        header.left_to_right = x_axis.starts_with('+');
+
+        if header.width == 0 || header.height == 0 {
+            bail!("HDR image of {}x{} is empty", header.width, header.height);
+        }
+
+        pixel_count("HDR", header.width, header.height)?;
+
+        Ok(header)
+    }
+
+    // Pixels of a scanline, and scanlines of the image
+    pub fn scanline_length(&self) -> usize {
+        if self.column_major { self.height as usize } else { self.width as usize }
+    }
+
+    pub fn scanline_count(&self) -> usize {
+        if self.column_major { self.width as usize } else { self.height as usize }
+    }
+
+    // Position in the image of the pixel at an index of a scanline
+    pub fn position(&self, scanline: usize, index: usize) -> (usize, usize) {
+        let (y, x): (usize, usize) = if self.column_major { (index, scanline) } else { (scanline, index) };
+        let x: usize = if self.left_to_right { x } else { self.width as usize - 1 - x };
+        let y: usize = if self.top_to_bottom { y } else { self.height as usize - 1 - y };
+
+        (x, y)
+    }
+}
+
+// Line ending with a newline, which is left out
+fn read_line(reader: &mut HdrBitReader) -> Result<String> {
+    let mut bytes: Vec<u8> = Vec::new();
+
+    loop {
+        let byte: u8 = reader.read_byte()?;
+
+        if byte == b'\n' {
+            break;
+        }
+
+        if bytes.len() == MAX_LINE_LENGTH {
+            bail!("HDR header line is too long");
+        }
+
+        bytes.push(byte);
+    }
+
+    Ok(String::from_utf8_lossy(&bytes).trim_end_matches('\r').to_owned())
+}
+
+impl fmt::Display for HDRHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "program: {}\n", self.program)?;
+        write!(f, "format: {:?}\n", self.format)?;
+        write!(f, "exposure: {}\n", self.exposure)?;
+        write!(f, "software: {}\n", self.software)?;
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "column_major: {}\n", self.column_major)?;
+        write!(f, "top_to_bottom: {}\n", self.top_to_bottom)?;
+        write!(f, "left_to_right: {}\n", self.left_to_right)?;
+        Ok(())
+    }
+}
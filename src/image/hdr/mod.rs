@@ -0,0 +1,279 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use hdr_bit_reader::HdrBitReader;
+use header::{HDRFormat, HDRHeader};
+use super::bmp::BMP;
+use super::tone_mapping::ToneMapping;
+use super::{DecodeOptions, Image};
+
+mod hdr_bit_reader;
+mod header;
+
+// Scanlines of this length can be run length encoded by component
+const MIN_RLE_LENGTH: usize = 8;
+const MAX_RLE_LENGTH: usize = 0x7FFF;
+
+#[derive(Clone, Debug)]
+pub struct HDR {
+    header: HDRHeader,
+    tone_mapping: ToneMapping,
+    exposure: f32,
+    image: BMP,
+}
+
+impl HDR {
+    pub fn from_stream_with(stream: Stream, options: DecodeOptions, debug: bool) -> Result<Self> {
+        let mut reader: HdrBitReader = HdrBitReader::new(stream);
+        let header: HDRHeader = HDRHeader::from_binary(&mut reader)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let size: u64 = reader.length()? - reader.position()?;
+        let data: Vec<u8> = reader.read_bytes(size as usize)?;
+
+        // Scanlines start with a pixel at least
+        if data.len() < header.scanline_count() * 4 {
+            bail!("HDR pixels truncated");
+        }
+        let (width, length): (usize, usize) = (header.width as usize, header.scanline_length());
+        let mut colors: Vec<(f32, f32, f32)> = vec![(0.0, 0.0, 0.0); width * header.height as usize];
+        let mut position: usize = 0;
+
+        for scanline in 0..header.scanline_count() {
+            let pixels: Vec<[u8; 4]> = read_scanline(&data, &mut position, length)?;
+
+            for (index, &pixel) in pixels.iter().enumerate() {
+                let (x, y): (usize, usize) = header.position(scanline, index);
+                colors[y * width + x] = to_linear(pixel, header.format);
+            }
+        }
+
+        let image: BMP = BMP::from_pixels(header.width, header.height, |x, y| options.tone_mapping.map(colors[y * width + x], options.exposure));
+
+        Ok(Self {
+            header,
+            tone_mapping: options.tone_mapping,
+            exposure: options.exposure,
+            image,
+        })
+    }
+}
+
+fn read_byte(data: &[u8], position: &mut usize) -> Result<u8> {
+    let Some(&byte) = data.get(*position) else {
+        bail!("HDR pixels truncated");
+    };
+
+    *position += 1;
+    Ok(byte)
+}
+
+// Pixels of a scanline, run length encoded by component when it starts with 2, 2 and its length
+fn read_scanline(data: &[u8], position: &mut usize, length: usize) -> Result<Vec<[u8; 4]>> {
+    let start: &[u8] = data.get(*position..*position + 4).unwrap_or_default();
+
+    if !(MIN_RLE_LENGTH..=MAX_RLE_LENGTH).contains(&length) || start.len() < 4 || start[0] != 2 || start[1] != 2 || start[2] & 0x80 != 0 {
+        return read_old_scanline(data, position, length);
+    }
+
+    let encoded_length: usize = u16::from_be_bytes([start[2], start[3]]) as usize;
+
+    if encoded_length != length {
+        bail!("HDR scanline of {encoded_length} pixels instead of {length}");
+    }
+
+    *position += 4;
+    let mut pixels: Vec<[u8; 4]> = vec![[0; 4]; length];
+
+    // Components follow each other, each made of runs of one value and of literal values
+    for component in 0..4 {
+        let mut index: usize = 0;
+
+        while index < length {
+            let count: u8 = read_byte(data, position)?;
+
+            let (run, literal): (usize, bool) = if count > 128 { (count as usize - 128, false) } else { (count as usize, true) };
+
+            if run == 0 || index + run > length {
+                bail!("HDR run of {run} pixels at {index} is out of the scanline of {length}");
+            }
+
+            if literal {
+                for pixel in pixels[index..index + run].iter_mut() {
+                    pixel[component] = read_byte(data, position)?;
+                }
+            } else {
+                let value: u8 = read_byte(data, position)?;
+                pixels[index..index + run].iter_mut().for_each(|pixel| pixel[component] = value);
+            }
+
+            index += run;
+        }
+    }
+
+    Ok(pixels)
+}
+
+// Pixels of the first format, where a pixel of 1, 1, 1 repeats the previous one, consecutive repeats being more significant
+fn read_old_scanline(data: &[u8], position: &mut usize, length: usize) -> Result<Vec<[u8; 4]>> {
+    let mut pixels: Vec<[u8; 4]> = Vec::with_capacity(length);
+    let mut shift: u32 = 0;
+
+    while pixels.len() < length {
+        let pixel: [u8; 4] = [read_byte(data, position)?, read_byte(data, position)?, read_byte(data, position)?, read_byte(data, position)?];
+
+        if pixel[..3] == [1, 1, 1] {
+            let Some(&previous) = pixels.last() else {
+                bail!("HDR scanline starts with a repeat");
+            };
+
+            let count: usize = ((pixel[3] as usize) << shift).min(length - pixels.len());
+            pixels.extend(std::iter::repeat_n(previous, count));
+            shift = (shift + 8).min(24);
+        } else {
+            pixels.push(pixel);
+            shift = 0;
+        }
+    }
+
+    Ok(pixels)
+}
+
+// Mantissas of a shared exponent, centered within their step like Radiance does
+fn to_linear(pixel: [u8; 4], format: HDRFormat) -> (f32, f32, f32) {
+    if pixel[3] == 0 {
+        return (0.0, 0.0, 0.0);
+    }
+
+    let step: f32 = (pixel[3] as f32 - 136.0).exp2();
+    let (a, b, c): (f32, f32, f32) = ((pixel[0] as f32 + 0.5) * step, (pixel[1] as f32 + 0.5) * step, (pixel[2] as f32 + 0.5) * step);
+
+    match format {
+        HDRFormat::RGBE => (a, b, c),
+        // CIE XYZ to the linear primaries of sRGB
+        HDRFormat::XYZE => (
+            3.2404542 * a - 1.5371385 * b - 0.4985314 * c,
+            -0.969266 * a + 1.8760108 * b + 0.041556 * c,
+            0.0556434 * a - 0.2040259 * b + 1.0572252 * c,
+        ),
+    }
+}
+
+impl Image for HDR {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        Self::from_stream_with(stream, DecodeOptions::default(), debug)
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for HDR {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)?;
+        write!(f, "tone_mapping: {:?}\n", self.tone_mapping)?;
+        write!(f, "exposure: {}\n", self.exposure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn file(resolution: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\nEXPOSURE=2\n\n{resolution}\n").into_bytes();
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    // Colors clipped without tone mapping, bright pixels becoming white
+    fn decode(bytes: Vec<u8>) -> Result<HDR> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        let options: DecodeOptions = DecodeOptions {
+            tone_mapping: ToneMapping::EXPOSURE,
+            ..DecodeOptions::default()
+        };
+
+        HDR::from_stream_with(BufReader::new(source), options, false)
+    }
+
+    fn reds(image: &BMP) -> Vec<u8> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| image.pixel_color(x, y).0)).collect()
+    }
+
+    #[test]
+    fn run_length_scanlines_are_split_by_component() {
+        // Red of 4 literal values then a run of 4, others being runs of 8
+        let data: [u8; 20] = [2, 2, 0, 8, 4, 1, 2, 3, 4, 132, 5, 136, 6, 136, 7, 136, 8, 0, 0, 0];
+        let mut position: usize = 0;
+        let pixels: Vec<[u8; 4]> = read_scanline(&data, &mut position, 8).unwrap();
+
+        assert_eq!(position, 17);
+        assert_eq!(pixels.iter().map(|pixel| pixel[0]).collect::<Vec<u8>>(), [1, 2, 3, 4, 5, 5, 5, 5]);
+        assert!(pixels.iter().all(|pixel| pixel[1..] == [6, 7, 8]));
+    }
+
+    #[test]
+    fn old_scanlines_repeat_the_previous_pixel() {
+        // Repeats of 1 then 1 << 8 pixels, cut to the scanline
+        let data: [u8; 12] = [10, 20, 30, 128, 1, 1, 1, 1, 1, 1, 1, 1];
+        let mut position: usize = 0;
+        let pixels: Vec<[u8; 4]> = read_scanline(&data, &mut position, 5).unwrap();
+
+        assert_eq!(pixels, [[10, 20, 30, 128]; 5]);
+    }
+
+    #[test]
+    fn mantissas_share_their_exponent() {
+        assert_eq!(to_linear([127, 255, 0, 129], HDRFormat::RGBE), (127.5 / 128.0, 255.5 / 128.0, 0.5 / 128.0));
+        assert_eq!(to_linear([255, 255, 255, 0], HDRFormat::RGBE), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn rows_may_go_from_bottom_to_top() {
+        let data: [u8; 8] = [255, 255, 255, 140, 0, 0, 0, 0];
+        let hdr: HDR = decode(file("+Y 2 +X 1", &data)).unwrap();
+
+        assert_eq!(hdr.header.exposure, 2.0);
+        assert_eq!(reds(&hdr.image), [0, 255]);
+    }
+
+    #[test]
+    fn scanlines_may_be_columns() {
+        let data: [u8; 8] = [255, 255, 255, 140, 0, 0, 0, 0];
+        let hdr: HDR = decode(file("-X 2 +Y 1", &data)).unwrap();
+
+        assert_eq!((hdr.image.width(), hdr.image.height()), (2, 1));
+        assert_eq!(reds(&hdr.image), [0, 255]);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode(file("-Y 100000 +X 100000", &[])).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn truncated_pixels_are_rejected() {
+        let error: String = decode(file("-Y 2 +X 1", &[255, 255, 255, 140])).unwrap_err().to_string();
+
+        assert!(error.contains("HDR pixels truncated"), "{error}");
+    }
+
+    #[test]
+    fn runs_out_of_the_scanline_are_rejected() {
+        let data: [u8; 6] = [2, 2, 0, 8, 137, 1];
+        let error: String = read_scanline(&data, &mut 0, 8).unwrap_err().to_string();
+
+        assert!(error.contains("HDR run of 9 pixels at 0 is out of the scanline of 8"), "{error}");
+    }
+}
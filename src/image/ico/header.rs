@@ -0,0 +1,120 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use super::ico_bit_reader::IcoBitReader;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ICOType {
+    #[default]
+    ICON,
+    CURSOR,
+}
+
+// ICONDIRENTRY, planes and bit count of icons being the hotspot of cursors
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ICOEntry {
+    pub width: u32,
+    pub height: u32,
+    pub colors: u8,
+    pub planes_or_hotspot_x: u16,
+    pub bit_count_or_hotspot_y: u16,
+    pub size: u32,
+    pub offset: u32,
+}
+
+impl ICOEntry {
+    // Area first, then bit count for icons
+    fn rank(&self, ico_type: ICOType) -> (u32, u16) {
+        match ico_type {
+            ICOType::ICON => (self.width * self.height, self.bit_count_or_hotspot_y),
+            ICOType::CURSOR => (self.width * self.height, 0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ICOHeader {
+    pub ico_type: ICOType,
+    pub entries: Vec<ICOEntry>,
+}
+
+impl ICOHeader {
+    pub fn from_binary(reader: &mut IcoBitReader) -> Result<Self> {
+        let mut header: ICOHeader = ICOHeader::default();
+
+        let reserved: u16 = reader.read_word()?;
+
+        if reserved != 0 {
+            bail!("Not an ICO file");
+        }
+
+        header.ico_type = match reader.read_word()?.swap_bytes() {
+            1 => ICOType::ICON,
+            2 => ICOType::CURSOR,
+            value => bail!("ICO type {value} is invalid"),
+        };
+
+        let count: u16 = reader.read_word()?.swap_bytes();
+
+        for _ in 0..count {
+            // Zero stands for 256 pixels
+            let width: u8 = reader.read_byte()?;
+            let height: u8 = reader.read_byte()?;
+            let colors: u8 = reader.read_byte()?;
+            reader.read_byte()?;
+
+            header.entries.push(ICOEntry {
+                width: if width == 0 { 256 } else { width as u32 },
+                height: if height == 0 { 256 } else { height as u32 },
+                colors,
+                planes_or_hotspot_x: reader.read_word()?.swap_bytes(),
+                bit_count_or_hotspot_y: reader.read_word()?.swap_bytes(),
+                size: reader.read_double()?.swap_bytes(),
+                offset: reader.read_double()?.swap_bytes(),
+            });
+        }
+
+        if header.entries.is_empty() {
+            bail!("ICO file has no image");
+        }
+
+        Ok(header)
+    }
+
+    // Largest entry fitting in the maximum size, or the smallest one when none fits
+    pub fn best_entry(&self, max_size: Option<(u32, u32)>) -> usize {
+        let fitting = self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| max_size.is_none_or(|(width, height)| entry.width <= width && entry.height <= height))
+            .max_by_key(|(_, entry)| entry.rank(self.ico_type));
+
+        match fitting {
+            Some((index, _)) => index,
+            None => self.entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| entry.width * entry.height)
+                .map_or(0, |(index, _)| index),
+        }
+    }
+}
+
+impl fmt::Display for ICOHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "type: {:?}\n", self.ico_type)?;
+        write!(f, "entries: {}\n", self.entries.len())?;
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            write!(f, "entry {index}: {}x{}, {} colors, {} bytes at {:04X}", entry.width, entry.height, entry.colors, entry.size, entry.offset)?;
+
+            match self.ico_type {
+                ICOType::ICON => write!(f, ", {} bits per pixel\n", entry.bit_count_or_hotspot_y)?,
+                ICOType::CURSOR => write!(f, ", hotspot at ({}, {})\n", entry.planes_or_hotspot_x, entry.bit_count_or_hotspot_y)?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use header::{ICOEntry, ICOHeader};
+use ico_bit_reader::IcoBitReader;
+use super::bmp::BMP;
+use super::bmp::header::{INFO_HEADER_SIZE, V3_HEADER_SIZE, V5_HEADER_SIZE};
+use super::{from_bytes, pixel_count, DecodeOptions, Image, ImageType};
+
+mod header;
+mod ico_bit_reader;
+
+#[derive(Clone, Debug)]
+pub struct ICO {
+    header: ICOHeader,
+    entry: usize,
+    image: BMP,
+}
+
+impl ICO {
+    pub fn from_stream_with(stream: Stream, options: DecodeOptions, debug: bool) -> Result<Self> {
+        let mut reader: IcoBitReader = IcoBitReader::new(stream);
+        let header: ICOHeader = ICOHeader::from_binary(&mut reader)?;
+
+        let entry: usize = match options.entry {
+            Some(index) if index >= header.entries.len() => bail!("No entry {index}, the file has {} entries", header.entries.len()),
+            Some(index) => index,
+            None => header.best_entry(options.max_size),
+        };
+
+        let ico_entry: ICOEntry = header.entries[entry];
+
+        if ico_entry.offset as u64 + ico_entry.size as u64 > reader.length()? {
+            bail!("ICO entry {entry} of {} bytes at {:X} runs past the end of the file", ico_entry.size, ico_entry.offset);
+        }
+
+        reader.seek(ico_entry.offset as u64)?;
+        let bytes: Vec<u8> = reader.read_bytes(ico_entry.size as usize)?;
+
+        let image: BMP = if bytes.starts_with(b"\x89PNG") {
+            *from_bytes(bytes, ImageType::PNG, false)?.to_bmp()
+        } else {
+            Self::decode_dib(bytes)?
+        };
+
+        let ico: Self = Self {
+            header,
+            entry,
+            image,
+        };
+
+        if debug {
+            println!("{ico}");
+        }
+
+        Ok(ico)
+    }
+
+    // Bitmap without file header, twice as high to hold the 1 bit AND mask after the pixels
+    fn decode_dib(dib: Vec<u8>) -> Result<BMP> {
+        if dib.len() < 40 {
+            bail!("Icon bitmap of {} bytes is too small", dib.len());
+        }
+
+        let field = |offset: usize| u32::from_le_bytes([dib[offset], dib[offset + 1], dib[offset + 2], dib[offset + 3]]);
+        let header_size: u32 = field(0);
+        let width: u32 = field(4);
+        let height: u32 = field(8) / 2;
+        let bits_per_pixel: u32 = u16::from_le_bytes([dib[14], dib[15]]) as u32;
+        let compression: u32 = field(16);
+        let colors_used: u32 = field(32);
+
+        if !(INFO_HEADER_SIZE..=V5_HEADER_SIZE).contains(&header_size) || header_size as usize > dib.len() {
+            bail!("Icon bitmap header of {header_size} bytes not supported");
+        }
+
+        pixel_count("Icon", width, height)?;
+
+        // Color tables are never larger than the pixels can index
+        let palette_size: u32 = match (bits_per_pixel, colors_used) {
+            (1..=8, 0) => (1 << bits_per_pixel) * 4,
+            (1..=8, colors_used) => colors_used.min(1 << bits_per_pixel) * 4,
+            _ => 0,
+        };
+        let mask_offset: Option<u32> = width
+            .checked_mul(bits_per_pixel)
+            .and_then(|bits| bits.div_ceil(32).checked_mul(4))
+            .and_then(|row_size| row_size.checked_mul(height))
+            .and_then(|pixels_size| pixels_size.checked_add(header_size + palette_size));
+
+        let Some(mask_offset) = mask_offset else {
+            bail!("Icon bitmap of {width}x{height} with {bits_per_pixel} bits per pixel is too large");
+        };
+
+        let mask: Vec<u8> = dib.get(mask_offset as usize..).unwrap_or_default().to_vec();
+        let mut header: Vec<u8> = dib[..header_size as usize].to_vec();
+        header[8..12].copy_from_slice(&height.to_le_bytes());
+
+        // Alpha of 32 bits icons is stored in the unused byte of the uncompressed pixels, which a V3 header describes with bitfields
+        if bits_per_pixel == 32 && compression == 0 && header_size == INFO_HEADER_SIZE {
+            header[0..4].copy_from_slice(&V3_HEADER_SIZE.to_le_bytes());
+            header[16..20].copy_from_slice(&3u32.to_le_bytes());
+
+            for mask in [0x00FF0000u32, 0x0000FF00, 0x000000FF, 0xFF000000] {
+                header.extend_from_slice(&mask.to_le_bytes());
+            }
+        }
+
+        let starting_offset: u32 = 14 + header.len() as u32 + palette_size;
+        let Some(size) = (dib.len() as u32 - header_size).checked_add(14 + header.len() as u32) else {
+            bail!("Icon bitmap of {} bytes is too large", dib.len());
+        };
+        let mut file: Vec<u8> = Vec::with_capacity(size as usize);
+
+        file.extend_from_slice(b"BM");
+        file.extend_from_slice(&size.to_le_bytes());
+        file.extend_from_slice(&0u32.to_le_bytes());
+        file.extend_from_slice(&starting_offset.to_le_bytes());
+        file.extend_from_slice(&header);
+        file.extend_from_slice(&dib[header_size as usize..]);
+
+        let mut image: Box<BMP> = from_bytes(file, ImageType::BMP, false)?.to_bmp();
+
+        // Rows of the mask go from bottom to top, a set bit making the pixel transparent
+        let stride: u32 = width.div_ceil(32) * 4;
+
+        for row in 0..height {
+            for x in 0..width {
+                let Some(&byte) = mask.get((row * stride + x / 8) as usize) else {
+                    continue;
+                };
+
+                if (byte >> (7 - x % 8)) & 1 == 1 {
+                    image.set_alpha(x, height - 1 - row, 0);
+                }
+            }
+        }
+
+        Ok(*image)
+    }
+}
+
+impl Image for ICO {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        Self::from_stream_with(stream, DecodeOptions::default(), debug)
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for ICO {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)?;
+        write!(f, "selected entry: {}\n", self.entry)?;
+        write!(f, "{}", self.image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    // Icon directory of a single entry, followed by its bitmap
+    fn file(dib: &[u8], size: u32) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![0, 0, 1, 0, 1, 0, 2, 1, 0, 0, 1, 0, 32, 0];
+        bytes.extend_from_slice(&size.to_le_bytes());
+        bytes.extend_from_slice(&22u32.to_le_bytes());
+        bytes.extend_from_slice(dib);
+        bytes
+    }
+
+    // Info header of twice the height, followed by the pixels and the AND mask
+    fn dib(width: u32, height: u32, bits_per_pixel: u16, pixels: &[u8], mask: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&(height * 2).to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&bits_per_pixel.to_le_bytes());
+        bytes.extend_from_slice(&[0; 24]);
+        bytes.extend_from_slice(pixels);
+        bytes.extend_from_slice(mask);
+        bytes
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<ICO> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        ICO::from_stream_with(BufReader::new(source), DecodeOptions::default(), false)
+    }
+
+    #[test]
+    fn alpha_of_32_bits_icons_is_kept() {
+        let dib: Vec<u8> = dib(2, 1, 32, &[0, 0, 255, 128, 0, 255, 0, 255], &[0; 4]);
+        let image: BMP = decode(file(&dib, dib.len() as u32)).unwrap().image;
+
+        assert_eq!((image.pixel_color(0, 0), image.pixel_alpha(0, 0)), ((255, 0, 0), 128));
+        assert_eq!((image.pixel_color(1, 0), image.pixel_alpha(1, 0)), ((0, 255, 0), 255));
+    }
+
+    #[test]
+    fn and_mask_makes_pixels_transparent() {
+        let dib: Vec<u8> = dib(2, 1, 24, &[0, 0, 255, 0, 255, 0, 0, 0], &[0b0100_0000, 0, 0, 0]);
+        let image: BMP = decode(file(&dib, dib.len() as u32)).unwrap().image;
+
+        assert_eq!((image.pixel_color(0, 0), image.pixel_alpha(0, 0)), ((255, 0, 0), 255));
+        assert_eq!(image.pixel_alpha(1, 0), 0);
+    }
+
+    #[test]
+    fn oversized_bitmaps_are_rejected() {
+        for (width, height) in [(u32::MAX, 1), (1 << 27, 0)] {
+            let dib: Vec<u8> = dib(width, height, 32, &[], &[]);
+            let error: String = decode(file(&dib, dib.len() as u32)).unwrap_err().to_string();
+            assert!(error.contains("too large"), "{error}");
+        }
+
+        let dib: Vec<u8> = dib(2, 1, 32, &[0; 8], &[0; 4]);
+        let error: String = decode(file(&dib, u32::MAX)).unwrap_err().to_string();
+        assert!(error.contains("runs past the end of the file"), "{error}");
+    }
+}
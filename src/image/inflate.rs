@@ -0,0 +1,380 @@
+use anyhow::{bail, Result};
+
+// Base lengths and extra bits of the length symbols 257 to 285
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+// Base distances and extra bits of the distance symbols 0 to 29
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+// Order in which the code length code lengths are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// Deflate streams are read from the least significant bit of each byte
+struct DeflateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+}
+
+impl<'a> DeflateReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    // Bytes past the end are read as zeros, consuming them being an error
+    fn fill(&mut self) {
+        while self.count <= 56 {
+            let byte: u8 = self.data.get(self.position).copied().unwrap_or(0);
+            self.buffer |= (byte as u64) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+    }
+
+    fn peek(&mut self, length: u32) -> u32 {
+        if self.count < length {
+            self.fill();
+        }
+
+        (self.buffer & ((1u64 << length) - 1)) as u32
+    }
+
+    fn consume(&mut self, length: u32) -> Result<()> {
+        self.buffer >>= length;
+        self.count -= length;
+
+        if self.position * 8 - self.count as usize > self.data.len() * 8 {
+            bail!("Deflate stream truncated");
+        }
+
+        Ok(())
+    }
+
+    fn read_bits(&mut self, length: u32) -> Result<u32> {
+        let value: u32 = self.peek(length);
+        self.consume(length)?;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        let skipped: u32 = self.count % 8;
+        self.buffer >>= skipped;
+        self.count -= skipped;
+    }
+
+    // Position of the next byte once aligned
+    fn byte_position(&self) -> usize {
+        self.position - (self.count / 8) as usize
+    }
+}
+
+// Canonical Huffman code, looked up by the next bits of the stream
+struct HuffmanCode {
+    // Symbol and code length, indexed by the reversed codes
+    table: Vec<(u16, u8)>,
+    bits: u32,
+}
+
+impl HuffmanCode {
+    fn from_lengths(lengths: &[u8]) -> Result<Self> {
+        let bits: u32 = lengths.iter().copied().max().unwrap_or(0) as u32;
+        let mut counts: [u16; 16] = [0; 16];
+
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        counts[0] = 0;
+        let mut next_codes: [u32; 16] = [0; 16];
+        let mut code: u32 = 0;
+
+        for length in 1..16 {
+            code = (code + counts[length - 1] as u32) << 1;
+            next_codes[length] = code;
+        }
+
+        let mut table: Vec<(u16, u8)> = vec![(0, 0); 1 << bits];
+
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length == 0 {
+                continue;
+            }
+
+            let code: u32 = next_codes[length as usize];
+            next_codes[length as usize] += 1;
+
+            if code >= 1 << length {
+                bail!("Over-subscribed Huffman code");
+            }
+
+            let reversed: usize = (code.reverse_bits() >> (32 - length as u32)) as usize;
+
+            for index in (reversed..table.len()).step_by(1 << length) {
+                table[index] = (symbol as u16, length);
+            }
+        }
+
+        Ok(Self {
+            table,
+            bits,
+        })
+    }
+
+    fn decode(&self, reader: &mut DeflateReader) -> Result<u16> {
+        let (symbol, length): (u16, u8) = self.table[reader.peek(self.bits) as usize];
+
+        if length == 0 {
+            bail!("Invalid Huffman code");
+        }
+
+        reader.consume(length as u32)?;
+        Ok(symbol)
+    }
+}
+
+// Zlib stream, as described by RFC 1950, wrapping a deflate stream described by RFC 1951
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < 2 {
+        bail!("Zlib stream too short");
+    }
+
+    let (cmf, flg): (u8, u8) = (data[0], data[1]);
+
+    if cmf & 0x0F != 8 || !(((cmf as u16) << 8) | flg as u16).is_multiple_of(31) {
+        bail!("Invalid zlib header {cmf:02X}{flg:02X}");
+    }
+
+    if flg & 0x20 != 0 {
+        bail!("Zlib preset dictionaries not supported");
+    }
+
+    let mut reader: DeflateReader = DeflateReader::new(&data[2..]);
+    let output: Vec<u8> = inflate_stream(&mut reader)?;
+
+    reader.align();
+    let position: usize = 2 + reader.byte_position();
+
+    if let Some(checksum) = data.get(position..position + 4) {
+        let expected: u32 = u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+
+        if adler32(&output) != expected {
+            bail!("Zlib checksum mismatch");
+        }
+    }
+
+    Ok(output)
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+
+    // Largest number of bytes before the sums may overflow
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+
+        a %= 65521;
+        b %= 65521;
+    }
+
+    (b << 16) | a
+}
+
+fn inflate_stream(reader: &mut DeflateReader) -> Result<Vec<u8>> {
+    let mut output: Vec<u8> = Vec::new();
+
+    loop {
+        let is_final: bool = reader.read_bits(1)? == 1;
+
+        match reader.read_bits(2)? {
+            0 => inflate_stored(reader, &mut output)?,
+            1 => {
+                let (literals, distances): (HuffmanCode, HuffmanCode) = fixed_codes()?;
+                inflate_block(reader, &mut output, &literals, &distances)?;
+            },
+            2 => {
+                let (literals, distances): (HuffmanCode, HuffmanCode) = dynamic_codes(reader)?;
+                inflate_block(reader, &mut output, &literals, &distances)?;
+            },
+            _ => bail!("Invalid deflate block type"),
+        }
+
+        if is_final {
+            return Ok(output);
+        }
+    }
+}
+
+fn inflate_stored(reader: &mut DeflateReader, output: &mut Vec<u8>) -> Result<()> {
+    reader.align();
+    let length: u32 = reader.read_bits(16)?;
+    let complement: u32 = reader.read_bits(16)?;
+
+    if length != !complement & 0xFFFF {
+        bail!("Stored block length {length:04X} doesn't match its complement {complement:04X}");
+    }
+
+    for _ in 0..length {
+        output.push(reader.read_bits(8)? as u8);
+    }
+
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(HuffmanCode, HuffmanCode)> {
+    let mut lengths: [u8; 288] = [8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+
+    Ok((HuffmanCode::from_lengths(&lengths)?, HuffmanCode::from_lengths(&[5; 30])?))
+}
+
+fn dynamic_codes(reader: &mut DeflateReader) -> Result<(HuffmanCode, HuffmanCode)> {
+    let literals_count: usize = reader.read_bits(5)? as usize + 257;
+    let distances_count: usize = reader.read_bits(5)? as usize + 1;
+    let code_lengths_count: usize = reader.read_bits(4)? as usize + 4;
+
+    let mut code_lengths: [u8; 19] = [0; 19];
+
+    for &index in CODE_LENGTH_ORDER.iter().take(code_lengths_count) {
+        code_lengths[index] = reader.read_bits(3)? as u8;
+    }
+
+    let code_lengths_code: HuffmanCode = HuffmanCode::from_lengths(&code_lengths)?;
+    let mut lengths: Vec<u8> = Vec::with_capacity(literals_count + distances_count);
+
+    // Literal and distance lengths form a single sequence, repetitions possibly crossing from one to the other
+    while lengths.len() < literals_count + distances_count {
+        let (value, repeat): (u8, u32) = match code_lengths_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let Some(&previous) = lengths.last() else {
+                    bail!("Repetition of a missing code length");
+                };
+                (previous, 3 + reader.read_bits(2)?)
+            },
+            17 => (0, 3 + reader.read_bits(3)?),
+            _ => (0, 11 + reader.read_bits(7)?),
+        };
+
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+
+    if lengths.len() > literals_count + distances_count {
+        bail!("Code lengths overflow the literal and distance codes");
+    }
+
+    if lengths[256] == 0 {
+        bail!("Missing end of block code");
+    }
+
+    Ok((
+        HuffmanCode::from_lengths(&lengths[..literals_count])?,
+        HuffmanCode::from_lengths(&lengths[literals_count..])?,
+    ))
+}
+
+fn inflate_block(reader: &mut DeflateReader, output: &mut Vec<u8>, literals: &HuffmanCode, distances: &HuffmanCode) -> Result<()> {
+    loop {
+        let symbol: u16 = literals.decode(reader)?;
+
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let index: usize = symbol as usize - 257;
+                let length: usize = LENGTH_BASES[index] as usize + reader.read_bits(LENGTH_EXTRA_BITS[index] as u32)? as usize;
+
+                let index: usize = distances.decode(reader)? as usize;
+
+                if index >= 30 {
+                    bail!("Invalid distance symbol {index}");
+                }
+
+                let distance: usize = DISTANCE_BASES[index] as usize + reader.read_bits(DISTANCE_EXTRA_BITS[index] as u32)? as usize;
+
+                if distance > output.len() {
+                    bail!("Distance {distance} goes before the start of the stream");
+                }
+
+                // Copies may overlap their own output
+                let start: usize = output.len() - distance;
+
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            },
+            _ => bail!("Invalid literal or length symbol {symbol}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Streams produced by Python's zlib module
+    const STORED: [u8; 16] = [120, 1, 1, 5, 0, 250, 255, 104, 101, 108, 108, 111, 6, 44, 2, 21];
+    const FIXED: [u8; 15] = [120, 218, 75, 76, 196, 4, 73, 201, 64, 4, 0, 123, 79, 9, 128];
+    const DYNAMIC: [u8; 54] = [
+        120, 218, 37, 140, 193, 17, 0, 64, 12, 1, 107, 181, 244, 95, 195, 113, 225, 145, 25, 54, 108, 125, 97, 2, 74, 109, 43,
+        137, 179, 84, 242, 29, 160, 25, 199, 54, 93, 61, 202, 171, 246, 130, 219, 226, 252, 189, 12, 58, 87, 15, 143, 161, 38, 74,
+    ];
+
+    #[test]
+    fn stored_blocks_are_copied() {
+        assert_eq!(zlib_decompress(&STORED).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn fixed_blocks_copy_overlapping_matches() {
+        assert_eq!(zlib_decompress(&FIXED).unwrap(), b"aaaaaaaaaaaaaaaaaaaabcabc");
+    }
+
+    #[test]
+    fn dynamic_blocks_are_decoded() {
+        // Letters of uneven frequencies picked by a linear congruential generator
+        let mut state: u32 = 1;
+        let expected: Vec<u8> = (0..100)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345) & 0x7FFFFFFF;
+                b"aaaabbcd"[(state >> 16) as usize % 8]
+            })
+            .collect();
+
+        assert_eq!(zlib_decompress(&DYNAMIC).unwrap(), expected);
+    }
+
+    #[test]
+    fn corrupted_streams_are_rejected() {
+        let mut checksum: [u8; 16] = STORED;
+        checksum[15] ^= 1;
+        assert!(zlib_decompress(&checksum).is_err());
+
+        assert!(zlib_decompress(&DYNAMIC[..20]).is_err());
+        assert!(zlib_decompress(&[0x78, 0x9C, 0x07]).is_err());
+    }
+}
@@ -31,18 +31,7 @@ pub struct JPEGHeader {
 
 impl JPEGHeader {
     pub fn to_bmp(&self) -> BMPHeader {
-        let padding_size: u32 = (self.width % 4) as u32;
-        let bmp_size: u32 = 14u32 + 12u32 + self.width as u32 * self.height as u32 * 3 + padding_size * self.height as u32;
-
-        BMPHeader {
-            bmp_size,
-            header_size: 12u32,
-            height: self.height,
-            width: self.width,
-            components_number: 1u16,
-            starting_offset: 0x1Au32,
-            bits_per_pixel: 24u16,
-        }
+        BMPHeader::new(self.width as u32, self.height as u32)
     }
 
     pub fn from_binary(reader: &mut JpegBitReader, debug: bool) -> Result<Self> {
@@ -77,6 +66,7 @@ impl JPEGHeader {
                 SegmentType::SOF0 => header.read_segment_sof0(reader)?,
                 SegmentType::SOS  => {
                     header.read_segment_sos(reader)?;
+                    header.fill_missing_huffman_tables();
                     break;
                 },
                 SegmentType::TEM  => (),
@@ -323,6 +313,21 @@ impl JPEGHeader {
         Ok(())
     }
 
+    fn fill_missing_huffman_tables(&mut self) -> () {
+        for i in 0..self.components_number as usize {
+            let ac_table_id: usize = self.color_components[i].huffman_ac_table_id() as usize;
+            let dc_table_id: usize = self.color_components[i].huffman_dc_table_id() as usize;
+
+            if !self.ac_tables[ac_table_id].is_set() {
+                self.ac_tables[ac_table_id] = HuffmanTable::standard(ac_table_id as u8, true);
+            }
+
+            if !self.dc_tables[dc_table_id].is_set() {
+                self.dc_tables[dc_table_id] = HuffmanTable::standard(dc_table_id as u8, false);
+            }
+        }
+    }
+
     pub fn quantization_tables(&self, index: usize) -> Option<&QuantizationTable> {
         self.quantization_tables.get(index)
     }
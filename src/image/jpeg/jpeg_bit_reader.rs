@@ -1,8 +1,6 @@
 use anyhow::{bail, Result};
-use std::fs::File;
-use std::io::BufReader;
 
-use crate::image::bit_reader::BitReader;
+use crate::image::bit_reader::{BitReader, Stream};
 use super::segment::SegmentType;
 
 #[derive(Debug)]
@@ -10,11 +8,11 @@ pub struct JpegBitReader {
     next_bit: usize,
     current_byte: u8,
     next_byte: u8,
-    stream: BufReader<File>,
+    stream: Stream,
 }
 
 impl BitReader for JpegBitReader {
-    fn new(stream: BufReader<File>) -> Self {
+    fn new(stream: Stream) -> Self {
         Self {
             next_bit: 0,
             current_byte: 0,
@@ -57,7 +55,7 @@ impl BitReader for JpegBitReader {
         self.next_bit = next_bit;
     }
 
-    fn stream(&mut self) -> &mut BufReader<File> {
+    fn stream(&mut self) -> &mut Stream {
         &mut self.stream
     }
 }
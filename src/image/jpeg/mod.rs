@@ -1,14 +1,12 @@
 use anyhow::Result;
 use std::f32::consts::PI;
 use std::fmt;
-use std::fs::File;
-use std::io::BufReader;
 
 use header::JPEGHeader;
 use jpeg_bit_reader::JpegBitReader;
 use super::huffman::HuffmanTable;
 use super::Image;
-use super::bit_reader::BitReader;
+use super::bit_reader::{BitReader, Stream};
 use super::bmp::BMP;
 use super::mcu::MCU;
 use super::quantization_table::QuantizationTable;
@@ -173,7 +171,7 @@ impl JPEG {
 }
 
 impl Image for JPEG {
-    fn from_stream(stream: BufReader<File>, debug: bool) -> Result<Self> {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
         let mut reader: JpegBitReader = JpegBitReader::new(stream);
         let mut jpeg: Self = Self {
             header: JPEGHeader::from_binary(&mut reader, debug)?,
@@ -1,12 +1,38 @@
 use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::Serialize;
 
 use super::bit_reader::BitReader;
 use super::huffman::HuffmanTable;
 use super::mcu_component::MCUComponent;
 
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+pub enum ColorMatrix {
+    BT601,
+    BT709,
+}
+
+impl ColorMatrix {
+    // Cr to R, Cb to G, Cr to G, Cb to B
+    fn coefficients(&self) -> [f32; 4] {
+        match self {
+            ColorMatrix::BT601 => [1.402, 0.344, 0.714, 1.772],
+            ColorMatrix::BT709 => [1.5748, 0.1873, 0.4681, 1.8556],
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+pub enum ColorRange {
+    FULL,
+    LIMITED,
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MCU {
     components: [MCUComponent; 3],
+    // Opacity of the pixels, only allocated once a pixel isn't opaque
+    alpha: Option<MCUComponent>,
 }
 
 impl MCU {
@@ -18,6 +44,22 @@ impl MCU {
         self.components.get_mut(i)
     }
 
+    pub fn alpha(&self, i: usize) -> u8 {
+        self.alpha.map_or(u8::MAX, |alpha| alpha[i] as u8)
+    }
+
+    pub fn set_alpha(&mut self, i: usize, value: u8) {
+        if self.alpha.is_none() && value == u8::MAX {
+            return;
+        }
+
+        self.alpha.get_or_insert(MCUComponent::from([u8::MAX as i32; 64]))[i] = value as i32;
+    }
+
+    pub fn clear_alpha(&mut self) {
+        self.alpha = None;
+    }
+
     fn next_symbol(reader: &mut impl BitReader, table: &HuffmanTable) -> Result<u8> {
         let mut code: u32 = 0;
 
@@ -101,10 +143,26 @@ impl MCU {
     }
 
     pub fn ycbcr_to_rgb(&mut self) -> () {
+        self.ycbcr_to_rgb_with(ColorMatrix::BT601, ColorRange::FULL);
+    }
+
+    // Components are expected centered on 0, as they are after the inverse DCT
+    pub fn ycbcr_to_rgb_with(&mut self, matrix: ColorMatrix, range: ColorRange) -> () {
+        let [cr_r, cb_g, cr_g, cb_b]: [f32; 4] = matrix.coefficients();
+
         for i in 0..64 {
-            let r: i32 = self.components[0][i] + (1.402 * self.components[2][i] as f32) as i32 + 128;
-            let g: i32 = self.components[0][i] - (0.344 * self.components[1][i] as f32) as i32 - (0.714 * self.components[2][i] as f32) as i32 + 128;
-            let b: i32 = self.components[0][i] + (1.772 * self.components[1][i] as f32) as i32 + 128;
+            let (y, cb, cr): (i32, i32, i32) = match range {
+                ColorRange::FULL => (self.components[0][i], self.components[1][i], self.components[2][i]),
+                ColorRange::LIMITED => (
+                    ((self.components[0][i] + 128 - 16) as f32 * 255.0 / 219.0) as i32 - 128,
+                    (self.components[1][i] as f32 * 255.0 / 224.0) as i32,
+                    (self.components[2][i] as f32 * 255.0 / 224.0) as i32,
+                ),
+            };
+
+            let r: i32 = y + (cr_r * cr as f32) as i32 + 128;
+            let g: i32 = y - (cb_g * cb as f32) as i32 - (cr_g * cr as f32) as i32 + 128;
+            let b: i32 = y + (cb_b * cb as f32) as i32 + 128;
 
             self.components[0][i] = r.max(0).min(255);
             self.components[1][i] = g.max(0).min(255);
@@ -1,39 +1,224 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::ValueEnum;
 use serde::Serialize;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{stdin, stdout, BufReader, Cursor, Read, StdoutLock, Write};
 use std::path::PathBuf;
 
+use bit_reader::{Source, Stream};
 use bmp::BMP;
+use dds::DDS;
+use farbfeld::FARBFELD;
+use gif::GIF;
+use hdr::HDR;
+use ico::ICO;
 use jpeg::JPEG;
+use pcx::PCX;
+use png::PNG;
+use pnm::header::PNMFormat;
+use pnm::PNM;
+use qoi::QOI;
+use raw::RawFormat;
+use tga::TGA;
+use tiff::TIFF;
+use tone_mapping::ToneMapping;
+use webp::WEBP;
+use xbm::XBM;
+use xpm::XPM;
 
-mod bit_reader;
+pub mod bit_reader;
+mod dds;
+mod farbfeld;
+mod hdr;
 mod huffman;
+mod ico;
+mod inflate;
 mod jpeg;
 mod mcu_component;
+mod pcx;
+mod qoi;
 mod quantization_table;
+mod raw;
+mod tga;
+mod tiff;
+mod xbm;
+mod xpm;
 pub mod bmp;
+pub mod gif;
 pub mod mcu;
+pub mod png;
+pub mod pnm;
+pub mod tone_mapping;
+pub mod webp;
 
 #[derive(ValueEnum, Clone, Debug, Serialize)]
 pub enum ImageType {
     BMP,
+    // DirectDraw Surface textures, any mip level and array slice
+    DDS,
+    // Suckless farbfeld, 16 bits per channel
+    FARBFELD,
     JPEG,
+    // First frame of animated files
+    GIF,
+    // Radiance RGBE, tone mapped to the display
+    HDR,
+    // Windows icons and cursors
+    ICO,
+    // ZSoft Paintbrush
+    PCX,
+    PNG,
+    // Netpbm PBM, PGM, PPM and PAM
+    PNM,
+    // Quite OK Image
+    QOI,
+    // Interleaved red, green and blue bytes, of the size given on the command line
+    RGB,
+    // Interleaved red, green, blue and alpha bytes, of the size given on the command line
+    RGBA,
+    // Truevision Targa
+    TGA,
+    // Any page of multi-page files
+    TIFF,
+    // Lossy and lossless WebP, first frame of animated files
+    WEBP,
+    // X11 bitmaps
+    XBM,
+    // X11 pixmaps
+    XPM,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+pub enum OutputType {
+    BMP,
+    PBM,
+    PGM,
+    PPM,
+    PAM,
+    QOI,
+    FARBFELD,
+    RGB,
+    RGBA,
+}
+
+impl OutputType {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputType::BMP => "bmp",
+            OutputType::PBM => "pbm",
+            OutputType::PGM => "pgm",
+            OutputType::PPM => "ppm",
+            OutputType::PAM => "pam",
+            OutputType::QOI => "qoi",
+            OutputType::FARBFELD => "ff",
+            OutputType::RGB => "rgb",
+            OutputType::RGBA => "rgba",
+        }
+    }
+}
+
+// Choices left to the user by formats holding several images, or more colors than the display
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DecodeOptions {
+    // Index of the image of a container
+    pub entry: Option<usize>,
+    // Mip level of a texture, the full size one being the first
+    pub mip_level: usize,
+    // Size of the display, in pixels, the largest image fitting in it being picked by default
+    pub max_size: Option<(u32, u32)>,
+    // Operator bringing high dynamic range images to the display
+    pub tone_mapping: ToneMapping,
+    // Stops added to high dynamic range images before their tone mapping
+    pub exposure: f32,
+    // Size of raw images, which hold nothing but their pixels
+    pub raw_size: Option<(u32, u32)>,
 }
 
 pub trait Image {
-    fn from_stream(reader: BufReader<File>, debug: bool) -> Result<Self> where Self: Sized;
+    fn from_stream(reader: Stream, debug: bool) -> Result<Self> where Self: Sized;
     fn to_bmp(self: Box<Self>) -> Box<BMP>;
 }
 
-pub fn from_file(filepath: &PathBuf, image_type: ImageType, debug: bool) -> Result<Box<dyn Image>> {
-    let file: File = File::open(filepath)?;
-    let reader: BufReader<File> = BufReader::new(file);
+// Decoded pixels take 16 bytes each, in blocks of 8x8 components and opacities of 32 bits
+const MAX_PIXELS: u64 = 100_000_000;
+
+// Number of pixels of an image, sizes read from a file being rejected before they exhaust the memory
+pub fn pixel_count(format: &str, width: u32, height: u32) -> Result<usize> {
+    let count: u64 = width as u64 * height as u64;
+
+    if count > MAX_PIXELS {
+        bail!("{format} image of {width}x{height} is too large");
+    }
+
+    Ok(count as usize)
+}
 
+// Standard input stands for the "-" path, so that images can be piped from other tools
+pub fn from_file(filepath: &PathBuf, image_type: ImageType, options: DecodeOptions, debug: bool) -> Result<Box<dyn Image>> {
+    let file: Box<dyn Source> = if filepath.as_os_str() == "-" {
+        // Formats need to seek, so the whole input is read first
+        let mut bytes: Vec<u8> = Vec::new();
+        stdin().lock().read_to_end(&mut bytes)?;
+        Box::new(Cursor::new(bytes))
+    } else {
+        Box::new(File::open(filepath)?)
+    };
+
+    from_stream(BufReader::new(file), image_type, options, debug)
+}
+
+pub fn from_bytes(bytes: Vec<u8>, image_type: ImageType, debug: bool) -> Result<Box<dyn Image>> {
+    let buffer: Box<dyn Source> = Box::new(Cursor::new(bytes));
+
+    from_stream(BufReader::new(buffer), image_type, DecodeOptions::default(), debug)
+}
+
+fn from_stream(reader: Stream, image_type: ImageType, options: DecodeOptions, debug: bool) -> Result<Box<dyn Image>> {
     match image_type {
         ImageType::BMP => Ok(Box::new(BMP::from_stream(reader, debug)?)),
+        ImageType::DDS => Ok(Box::new(DDS::from_stream_with(reader, options, debug)?)),
+        ImageType::FARBFELD => Ok(Box::new(FARBFELD::from_stream(reader, debug)?)),
         ImageType::JPEG => Ok(Box::new(JPEG::from_stream(reader, debug)?)),
+        ImageType::GIF => Ok(Box::new(GIF::from_stream(reader, debug)?)),
+        ImageType::HDR => Ok(Box::new(HDR::from_stream_with(reader, options, debug)?)),
+        ImageType::ICO => Ok(Box::new(ICO::from_stream_with(reader, options, debug)?)),
+        ImageType::PCX => Ok(Box::new(PCX::from_stream(reader, debug)?)),
+        ImageType::PNG => Ok(Box::new(PNG::from_stream(reader, debug)?)),
+        ImageType::PNM => Ok(Box::new(PNM::from_stream(reader, debug)?)),
+        ImageType::QOI => Ok(Box::new(QOI::from_stream(reader, debug)?)),
+        ImageType::RGB => Ok(Box::new(raw::decode(reader, RawFormat::RGB, options, debug)?)),
+        ImageType::RGBA => Ok(Box::new(raw::decode(reader, RawFormat::RGBA, options, debug)?)),
+        ImageType::TGA => Ok(Box::new(TGA::from_stream(reader, debug)?)),
+        ImageType::TIFF => Ok(Box::new(TIFF::from_stream_with(reader, options, debug)?)),
+        ImageType::WEBP => Ok(Box::new(WEBP::from_stream(reader, debug)?)),
+        ImageType::XBM => Ok(Box::new(XBM::from_stream(reader, debug)?)),
+        ImageType::XPM => Ok(Box::new(XPM::from_stream(reader, debug)?)),
     }
 }
 
+pub fn to_file(image: &BMP, filepath: PathBuf, output_type: OutputType) -> Result<()> {
+    match output_type {
+        OutputType::BMP => image.write_to_file(filepath),
+        OutputType::PBM => pnm::writer::write_to_file(image, filepath, PNMFormat::PBM),
+        OutputType::PGM => pnm::writer::write_to_file(image, filepath, PNMFormat::PGM),
+        OutputType::PPM => pnm::writer::write_to_file(image, filepath, PNMFormat::PPM),
+        OutputType::PAM => pnm::writer::write_to_file(image, filepath, PNMFormat::PAM),
+        OutputType::QOI => qoi::writer::write_to_file(image, filepath),
+        OutputType::FARBFELD => farbfeld::writer::write_to_file(image, filepath),
+        OutputType::RGB => raw::writer::write_to_file(image, filepath, RawFormat::RGB),
+        OutputType::RGBA => raw::writer::write_to_file(image, filepath, RawFormat::RGBA),
+    }
+}
+
+// Standard output stands for the "-" path, so that images can be piped to other tools
+pub fn write_bytes(filepath: PathBuf, bytes: &[u8]) -> Result<()> {
+    if filepath.as_os_str() == "-" {
+        let mut output: StdoutLock = stdout().lock();
+        output.write_all(bytes)?;
+        output.flush()?;
+    } else {
+        File::create(filepath)?.write_all(bytes)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::pcx_bit_reader::PcxBitReader;
+
+const MANUFACTURER: u8 = 0x0A;
+pub const HEADER_SIZE: u64 = 128;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PCXFormat {
+    // 1 bit plane, 0 standing for black
+    MONOCHROME,
+    // 16 colors of the header palette, in 4 planes of 1 bit or 1 plane of 2 or 4 bits
+    #[default]
+    EGA,
+    // 256 colors of the palette following the pixels
+    VGA,
+    // 256 gray levels of files missing their palette
+    GRAYSCALE,
+    // Red, green and blue planes, followed by an alpha one in 32 bits files
+    TRUECOLOR,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PCXHeader {
+    pub version: u8,
+    // Run length encoded scanlines
+    pub rle: bool,
+    pub bits_per_pixel: u8,
+    pub planes: u8,
+    pub x_min: u16,
+    pub y_min: u16,
+    pub width: u32,
+    pub height: u32,
+    pub horizontal_dpi: u16,
+    pub vertical_dpi: u16,
+    // Bytes of each plane of a scanline, rounded up to an even count by most writers
+    pub bytes_per_line: u16,
+    // 1 for colors and 2 for gray levels, the palette being used either way
+    pub palette_info: u16,
+    pub format: PCXFormat,
+    pub palette: Vec<(u8, u8, u8)>,
+}
+
+impl PCXHeader {
+    pub fn from_binary(reader: &mut PcxBitReader) -> Result<Self> {
+        let mut header: PCXHeader = PCXHeader::default();
+
+        if reader.read_byte()? != MANUFACTURER {
+            bail!("Not a PCX file");
+        }
+
+        header.version = reader.read_byte()?;
+
+        header.rle = match reader.read_byte()? {
+            0 => false,
+            1 => true,
+            value => bail!("PCX encoding {value} is invalid"),
+        };
+
+        header.bits_per_pixel = reader.read_byte()?;
+        header.x_min = reader.read_word()?.swap_bytes();
+        header.y_min = reader.read_word()?.swap_bytes();
+        let x_max: u16 = reader.read_word()?.swap_bytes();
+        let y_max: u16 = reader.read_word()?.swap_bytes();
+        header.horizontal_dpi = reader.read_word()?.swap_bytes();
+        header.vertical_dpi = reader.read_word()?.swap_bytes();
+
+        let colors: Vec<u8> = reader.read_bytes(48)?;
+        header.palette = colors.chunks_exact(3).map(|color| (color[0], color[1], color[2])).collect();
+
+        let _reserved: u8 = reader.read_byte()?;
+        header.planes = reader.read_byte()?;
+        header.bytes_per_line = reader.read_word()?.swap_bytes();
+        header.palette_info = reader.read_word()?.swap_bytes();
+
+        if x_max < header.x_min || y_max < header.y_min {
+            bail!("PCX window from {},{} to {x_max},{y_max} is invalid", header.x_min, header.y_min);
+        }
+
+        header.width = (x_max - header.x_min) as u32 + 1;
+        header.height = (y_max - header.y_min) as u32 + 1;
+        pixel_count("PCX", header.width, header.height)?;
+
+        header.format = match (header.bits_per_pixel, header.planes) {
+            (1, 1) => PCXFormat::MONOCHROME,
+            (1, 4) | (2, 1) | (4, 1) => PCXFormat::EGA,
+            (8, 1) => PCXFormat::VGA,
+            (8, 3 | 4) => PCXFormat::TRUECOLOR,
+            (bits, planes) => bail!("PCX images of {planes} planes of {bits} bits not supported"),
+        };
+
+        if (header.bytes_per_line as u32 * 8) < header.width * header.bits_per_pixel as u32 {
+            bail!("PCX scanlines of {} bytes are too short for {} pixels", header.bytes_per_line, header.width);
+        }
+
+        Ok(header)
+    }
+
+    // Bytes of a scanline, every plane included
+    pub fn scanline_size(&self) -> usize {
+        self.bytes_per_line as usize * self.planes as usize
+    }
+}
+
+impl fmt::Display for PCXHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "version: {}\n", self.version)?;
+        write!(f, "rle: {}\n", self.rle)?;
+        write!(f, "bits_per_pixel: {}\n", self.bits_per_pixel)?;
+        write!(f, "planes: {}\n", self.planes)?;
+        write!(f, "origin: {},{}\n", self.x_min, self.y_min)?;
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "dpi: {}x{}\n", self.horizontal_dpi, self.vertical_dpi)?;
+        write!(f, "bytes_per_line: {}\n", self.bytes_per_line)?;
+        write!(f, "palette_info: {}\n", self.palette_info)?;
+        write!(f, "format: {:?}\n", self.format)?;
+        Ok(())
+    }
+}
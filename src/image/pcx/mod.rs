@@ -0,0 +1,230 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use header::{PCXFormat, PCXHeader, HEADER_SIZE};
+use pcx_bit_reader::PcxBitReader;
+use super::bmp::BMP;
+use super::Image;
+
+mod header;
+mod pcx_bit_reader;
+
+// 256 colors palette at the end of the file, following its marker
+const VGA_PALETTE_MARKER: u8 = 0x0C;
+const VGA_PALETTE_SIZE: usize = 768;
+
+// Version 3 files have no palette, their 16 colors being those of the EGA
+const EGA_PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00), (0x00, 0x00, 0xAA), (0x00, 0xAA, 0x00), (0x00, 0xAA, 0xAA),
+    (0xAA, 0x00, 0x00), (0xAA, 0x00, 0xAA), (0xAA, 0x55, 0x00), (0xAA, 0xAA, 0xAA),
+    (0x55, 0x55, 0x55), (0x55, 0x55, 0xFF), (0x55, 0xFF, 0x55), (0x55, 0xFF, 0xFF),
+    (0xFF, 0x55, 0x55), (0xFF, 0x55, 0xFF), (0xFF, 0xFF, 0x55), (0xFF, 0xFF, 0xFF),
+];
+
+#[derive(Clone, Debug)]
+pub struct PCX {
+    header: PCXHeader,
+    image: BMP,
+}
+
+impl PCX {
+    // Scanlines of every plane, runs being allowed to go from one scanline to the next
+    fn decode(data: &[u8], rle: bool, size: usize) -> Result<Vec<u8>> {
+        if !rle {
+            if data.len() < size {
+                bail!("PCX pixels truncated");
+            }
+
+            return Ok(data[..size].to_vec());
+        }
+
+        // Runs of 63 bytes being the longest, truncated files are found before anything large is allocated
+        let mut bytes: Vec<u8> = Vec::with_capacity(size.min(data.len() * 32));
+        let mut position: usize = 0;
+
+        while bytes.len() < size {
+            let Some(&byte) = data.get(position) else {
+                bail!("PCX pixels truncated");
+            };
+
+            position += 1;
+
+            // Two upper bits set make a count of the byte that follows
+            if byte >= 0xC0 {
+                let Some(&value) = data.get(position) else {
+                    bail!("PCX pixels truncated");
+                };
+
+                position += 1;
+                let count: usize = ((byte & 0x3F) as usize).min(size - bytes.len());
+                bytes.extend(std::iter::repeat_n(value, count));
+            } else {
+                bytes.push(byte);
+            }
+        }
+
+        Ok(bytes)
+    }
+}
+
+impl Image for PCX {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: PcxBitReader = PcxBitReader::new(stream);
+        let mut header: PCXHeader = PCXHeader::from_binary(&mut reader)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let length: u64 = reader.length()?;
+
+        if length < HEADER_SIZE {
+            bail!("PCX file truncated");
+        }
+
+        reader.seek(HEADER_SIZE)?;
+        let data: Vec<u8> = reader.read_bytes((length - HEADER_SIZE) as usize)?;
+
+        if header.format == PCXFormat::VGA {
+            let start: usize = data.len().saturating_sub(VGA_PALETTE_SIZE + 1);
+
+            // Files missing the palette are shown in gray levels
+            if data.len() > VGA_PALETTE_SIZE && data[start] == VGA_PALETTE_MARKER {
+                header.palette = data[start + 1..].chunks_exact(3).map(|color| (color[0], color[1], color[2])).collect();
+            } else {
+                header.format = PCXFormat::GRAYSCALE;
+            }
+        } else if header.format == PCXFormat::EGA && header.version == 3 {
+            header.palette = EGA_PALETTE.to_vec();
+        }
+
+        let scanline_size: usize = header.scanline_size();
+        let bytes: Vec<u8> = Self::decode(&data, header.rle, scanline_size * header.height as usize)?;
+        let (bits, line): (usize, usize) = (header.bits_per_pixel as usize, header.bytes_per_line as usize);
+
+        let image: BMP = BMP::from_rgba_pixels(header.width, header.height, |x, y| {
+            let scanline: &[u8] = &bytes[y * scanline_size..(y + 1) * scanline_size];
+            // Value of a pixel in a plane, packed from the most significant bit
+            let value = |plane: usize| (scanline[plane * line + x * bits / 8] >> (8 - bits - x * bits % 8)) & ((1 << bits) - 1) as u8;
+
+            match header.format {
+                PCXFormat::MONOCHROME => {
+                    let level: u8 = value(0) * u8::MAX;
+                    (level, level, level, u8::MAX)
+                },
+                PCXFormat::EGA => {
+                    let index: u8 = if header.planes == 4 {
+                        value(0) | (value(1) << 1) | (value(2) << 2) | (value(3) << 3)
+                    } else {
+                        value(0)
+                    };
+                    let (r, g, b): (u8, u8, u8) = header.palette[index as usize];
+                    (r, g, b, u8::MAX)
+                },
+                PCXFormat::VGA => {
+                    let (r, g, b): (u8, u8, u8) = header.palette[value(0) as usize];
+                    (r, g, b, u8::MAX)
+                },
+                PCXFormat::GRAYSCALE => (value(0), value(0), value(0), u8::MAX),
+                PCXFormat::TRUECOLOR => {
+                    let alpha: u8 = if header.planes == 4 { value(3) } else { u8::MAX };
+                    (value(0), value(1), value(2), alpha)
+                },
+            }
+        });
+
+        Ok(Self {
+            header,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for PCX {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    // Header of the given version, encoding, planes of bits and window, with a 16 colors palette going from black to white
+    fn file(version: u8, rle: bool, (bits, planes): (u8, u8), (x_max, y_max): (u16, u16), bytes_per_line: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![0x0A, version, rle as u8, bits, 0, 0, 0, 0];
+        bytes.extend_from_slice(&x_max.to_le_bytes());
+        bytes.extend_from_slice(&y_max.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        (0..16).for_each(|index| bytes.extend_from_slice(&[index * 17; 3]));
+        bytes.extend_from_slice(&[0, planes]);
+        bytes.extend_from_slice(&bytes_per_line.to_le_bytes());
+        bytes.resize(HEADER_SIZE as usize, 0);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        Ok(PCX::from_stream(BufReader::new(source), false)?.image)
+    }
+
+    fn colors(image: &BMP) -> Vec<(u8, u8, u8)> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| image.pixel_color(x, y))).collect()
+    }
+
+    #[test]
+    fn runs_go_from_one_scanline_to_the_next() {
+        // Run of 3 over both scanlines, then a literal byte and a run of 1 of a byte with its upper bits set
+        let mut data: Vec<u8> = vec![0xC3, 10, 20, 0xC1, 0xC5];
+        data.push(VGA_PALETTE_MARKER);
+        data.extend((0..=255).flat_map(|index: u8| [index, index / 2, 255 - index]));
+        let image: BMP = decode(file(5, true, (8, 1), (1, 1), 2, &data)).unwrap();
+
+        assert_eq!(colors(&image), [(10, 5, 245), (10, 5, 245), (10, 5, 245), (20, 10, 235)]);
+    }
+
+    #[test]
+    fn files_missing_their_palette_are_gray() {
+        let image: BMP = decode(file(5, false, (8, 1), (1, 0), 2, &[30, 60])).unwrap();
+
+        assert_eq!(colors(&image), [(30, 30, 30), (60, 60, 60)]);
+    }
+
+    #[test]
+    fn ega_planes_make_the_bits_of_an_index() {
+        // Planes of the lowest to the highest bit, indices 5 and 10
+        let image: BMP = decode(file(5, false, (1, 4), (1, 0), 2, &[0x80, 0, 0x40, 0, 0x80, 0, 0x40, 0])).unwrap();
+
+        assert_eq!(colors(&image), [(85, 85, 85), (170, 170, 170)]);
+    }
+
+    #[test]
+    fn truecolor_planes_follow_each_other() {
+        let image: BMP = decode(file(5, false, (8, 3), (1, 0), 2, &[1, 2, 3, 4, 5, 6])).unwrap();
+
+        assert_eq!(colors(&image), [(1, 3, 5), (2, 4, 6)]);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode(file(5, true, (8, 1), (u16::MAX, u16::MAX), u16::MAX, &[])).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn truncated_pixels_are_rejected() {
+        let error: String = decode(file(5, true, (8, 3), (3, 3), 4, &[0xC2, 1])).unwrap_err().to_string();
+
+        assert!(error.contains("PCX pixels truncated"), "{error}");
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{bail, Result};
+
+use crate::image::bit_reader::BitReader;
+use super::png_bit_reader::PngBitReader;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+#[derive(Clone, Debug)]
+pub struct Chunk {
+    pub chunk_type: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+impl Chunk {
+    pub fn from_binary(reader: &mut PngBitReader) -> Result<Self> {
+        let length: u32 = reader.read_double()?;
+        let bytes: Vec<u8> = reader.read_bytes(4)?;
+        let chunk_type: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
+
+        // Lengths of 31 bits are only trusted as far as the file goes
+        if length as u64 > reader.length()? - reader.position()? {
+            bail!("PNG chunk {} of {length} bytes runs past the end of the file", String::from_utf8_lossy(&chunk_type));
+        }
+
+        let data: Vec<u8> = reader.read_bytes(length as usize)?;
+        let crc: u32 = reader.read_double()?;
+
+        let chunk: Self = Self {
+            chunk_type,
+            data,
+        };
+
+        // CRC covers the type and the data
+        let expected: u32 = !crc_update(crc_update(u32::MAX, &chunk.chunk_type), &chunk.data);
+
+        if crc != expected {
+            bail!("CRC of chunk {} is {crc:08X} instead of {expected:08X}", chunk.name());
+        }
+
+        Ok(chunk)
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.chunk_type).into_owned()
+    }
+
+    // Unknown ancillary chunks can be skipped, unknown critical ones can't
+    pub fn is_critical(&self) -> bool {
+        self.chunk_type[0].is_ascii_uppercase()
+    }
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table: [u32; 256] = [0; 256];
+    let mut n: usize = 0;
+
+    while n < 256 {
+        let mut value: u32 = n as u32;
+        let mut k: usize = 0;
+
+        while k < 8 {
+            value = if value & 1 == 1 { 0xEDB88320 ^ (value >> 1) } else { value >> 1 };
+            k += 1;
+        }
+
+        table[n] = value;
+        n += 1;
+    }
+
+    table
+}
+
+pub(super) fn crc_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for &byte in bytes {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+
+    crc
+}
@@ -0,0 +1,161 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::pixel_count;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorType {
+    #[default]
+    GRAYSCALE,
+    RGB,
+    INDEXED,
+    GRAYSCALEALPHA,
+    RGBA,
+}
+
+impl ColorType {
+    pub fn from_value(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ColorType::GRAYSCALE),
+            2 => Some(ColorType::RGB),
+            3 => Some(ColorType::INDEXED),
+            4 => Some(ColorType::GRAYSCALEALPHA),
+            6 => Some(ColorType::RGBA),
+            _ => None,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::GRAYSCALE | ColorType::INDEXED => 1,
+            ColorType::GRAYSCALEALPHA => 2,
+            ColorType::RGB => 3,
+            ColorType::RGBA => 4,
+        }
+    }
+}
+
+// IHDR, followed by the PLTE, tRNS and gAMA chunks describing the pixels
+#[derive(Clone, Debug, Default)]
+pub struct PNGHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub interlaced: bool,
+    pub palette: Vec<(u8, u8, u8)>,
+    // Opacity of the first palette entries
+    pub palette_alpha: Vec<u8>,
+    // Samples of the only transparent color of grayscale and RGB images, at the bit depth of the image
+    pub transparent_color: Option<(u16, u16, u16)>,
+    // Gamma of the image times 100000
+    pub gamma: Option<u32>,
+}
+
+impl PNGHeader {
+    pub fn from_ihdr(data: &[u8]) -> Result<Self> {
+        if data.len() != 13 {
+            bail!("IHDR chunk of {} bytes is invalid", data.len());
+        }
+
+        let width: u32 = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let height: u32 = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        let bit_depth: u8 = data[8];
+
+        let Some(color_type) = ColorType::from_value(data[9]) else {
+            bail!("PNG color type {} is invalid", data[9]);
+        };
+
+        let valid_depth: bool = match color_type {
+            ColorType::GRAYSCALE => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+            ColorType::INDEXED => matches!(bit_depth, 1 | 2 | 4 | 8),
+            _ => matches!(bit_depth, 8 | 16),
+        };
+
+        if !valid_depth {
+            bail!("Bit depth {bit_depth} is invalid for color type {color_type:?}");
+        }
+
+        if data[10] != 0 || data[11] != 0 {
+            bail!("PNG compression method {} or filter method {} not supported", data[10], data[11]);
+        }
+
+        let interlaced: bool = match data[12] {
+            0 => false,
+            1 => true,
+            value => bail!("PNG interlace method {value} is invalid"),
+        };
+
+        if width == 0 || height == 0 {
+            bail!("PNG image of {width}x{height} is empty");
+        }
+
+        pixel_count("PNG", width, height)?;
+
+        Ok(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced,
+            ..Self::default()
+        })
+    }
+
+    pub fn read_palette(&mut self, data: &[u8]) -> Result<()> {
+        if !data.len().is_multiple_of(3) || data.len() > 256 * 3 {
+            bail!("PLTE chunk of {} bytes is invalid", data.len());
+        }
+
+        self.palette = data.chunks_exact(3).map(|color| (color[0], color[1], color[2])).collect();
+
+        Ok(())
+    }
+
+    pub fn read_transparency(&mut self, data: &[u8]) -> Result<()> {
+        let sample = |index: usize| u16::from_be_bytes([data[2 * index], data[2 * index + 1]]);
+
+        match (self.color_type, data.len()) {
+            (ColorType::INDEXED, _) => self.palette_alpha = data.to_vec(),
+            (ColorType::GRAYSCALE, 2) => self.transparent_color = Some((sample(0), sample(0), sample(0))),
+            (ColorType::RGB, 6) => self.transparent_color = Some((sample(0), sample(1), sample(2))),
+            (color_type, length) => bail!("tRNS chunk of {length} bytes is invalid for color type {color_type:?}"),
+        }
+
+        Ok(())
+    }
+
+    pub fn read_gamma(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() != 4 {
+            bail!("gAMA chunk of {} bytes is invalid", data.len());
+        }
+
+        self.gamma = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]));
+
+        Ok(())
+    }
+
+    // Number of bits of a pixel, of which at least one byte is taken by the filters
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+}
+
+impl fmt::Display for PNGHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "bit_depth: {}\n", self.bit_depth)?;
+        write!(f, "color_type: {:?}\n", self.color_type)?;
+        write!(f, "interlaced: {}\n", self.interlaced)?;
+        write!(f, "palette: {} colors, {} with an alpha\n", self.palette.len(), self.palette_alpha.len())?;
+        write!(f, "transparent_color: {:?}\n", self.transparent_color)?;
+
+        match self.gamma {
+            Some(gamma) => write!(f, "gamma: {:.5}\n", gamma as f32 / 100000.0)?,
+            None => write!(f, "gamma: none\n")?,
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use chunk::Chunk;
+use header::{ColorType, PNGHeader};
+use pixels::decode_pixels;
+use png_bit_reader::PngBitReader;
+use super::bmp::BMP;
+use super::Image;
+
+pub mod chunk;
+pub mod header;
+pub mod pixels;
+pub mod png_bit_reader;
+
+pub const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Clone, Debug)]
+pub struct PNG {
+    header: PNGHeader,
+    image: BMP,
+}
+
+impl Image for PNG {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: PngBitReader = PngBitReader::new(stream);
+
+        if reader.read_bytes(SIGNATURE.len())? != SIGNATURE {
+            bail!("PNG signature is invalid");
+        }
+
+        let ihdr: Chunk = Chunk::from_binary(&mut reader)?;
+
+        if &ihdr.chunk_type != b"IHDR" {
+            bail!("PNG file starts with chunk {} instead of IHDR", ihdr.name());
+        }
+
+        let mut header: PNGHeader = PNGHeader::from_ihdr(&ihdr.data)?;
+        // Image data may be split over several consecutive IDAT chunks
+        let mut data: Vec<u8> = Vec::new();
+
+        loop {
+            let chunk: Chunk = Chunk::from_binary(&mut reader)?;
+
+            match &chunk.chunk_type {
+                b"PLTE" => header.read_palette(&chunk.data)?,
+                b"tRNS" => header.read_transparency(&chunk.data)?,
+                b"gAMA" => header.read_gamma(&chunk.data)?,
+                b"IDAT" => data.extend_from_slice(&chunk.data),
+                b"IEND" => break,
+                _ if chunk.is_critical() => bail!("Critical PNG chunk {} not supported", chunk.name()),
+                _ => (),
+            }
+        }
+
+        if header.color_type == ColorType::INDEXED && header.palette.is_empty() {
+            bail!("Indexed PNG without PLTE chunk");
+        }
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let pixels: Vec<(u8, u8, u8, u8)> = decode_pixels(&header, header.width, header.height, &data)?;
+        let width: usize = header.width as usize;
+        let image: BMP = BMP::from_rgba_pixels(header.width, header.height, |x, y| pixels[y * width + x]);
+
+        Ok(Self {
+            header,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for PNG {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::chunk::crc_update;
+    use super::*;
+
+    pub(crate) fn chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let crc: u32 = !crc_update(crc_update(u32::MAX, chunk_type), data);
+        [&(data.len() as u32).to_be_bytes(), chunk_type.as_slice(), data, &crc.to_be_bytes()].concat()
+    }
+
+    // Single stored block, without the optional checksum
+    pub(crate) fn zlib(data: &[u8]) -> Vec<u8> {
+        let length: u16 = data.len() as u16;
+        [[0x78, 0x01, 0x01].as_slice(), &length.to_le_bytes(), &(!length).to_le_bytes(), data].concat()
+    }
+
+    pub(crate) fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8, interlaced: bool) -> Vec<u8> {
+        chunk(b"IHDR", &[&width.to_be_bytes(), &height.to_be_bytes(), [bit_depth, color_type, 0, 0, interlaced as u8].as_slice()].concat())
+    }
+
+    fn decode(chunks: &[Vec<u8>]) -> Result<PNG> {
+        let bytes: Vec<u8> = [SIGNATURE.to_vec(), chunks.concat(), chunk(b"IEND", &[])].concat();
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        PNG::from_stream(BufReader::new(source), false)
+    }
+
+    fn pixels(png: &PNG) -> Vec<(u8, u8, u8, u8)> {
+        let image: &BMP = &png.image;
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| (x, y))).map(|(x, y)| {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+            (r, g, b, image.pixel_alpha(x, y))
+        }).collect()
+    }
+
+    #[test]
+    fn filtered_rows_are_decoded() {
+        // Sub filtered first row, Up, then Paeth filtered rows
+        let data: [u8; 21] = [1, 10, 20, 30, 5, 5, 5, 2, 1, 1, 1, 2, 2, 2, 4, 0, 0, 0, 0, 0, 0];
+        let png: PNG = decode(&[ihdr(2, 3, 8, 2, false), chunk(b"IDAT", &zlib(&data))]).unwrap();
+
+        assert_eq!(pixels(&png), [
+            (10, 20, 30, 255), (15, 25, 35, 255),
+            (11, 21, 31, 255), (17, 27, 37, 255),
+            (11, 21, 31, 255), (17, 27, 37, 255),
+        ]);
+    }
+
+    #[test]
+    fn palette_transparency_is_applied() {
+        let data: [u8; 2] = [0, 0b0001_1000];
+        let chunks: [Vec<u8>; 4] = [
+            ihdr(3, 1, 2, 3, false),
+            chunk(b"PLTE", &[255, 0, 0, 0, 255, 0]),
+            chunk(b"tRNS", &[128]),
+            chunk(b"IDAT", &zlib(&data)),
+        ];
+
+        // Indexes past the palette are opaque black
+        assert_eq!(pixels(&decode(&chunks).unwrap()), [(255, 0, 0, 128), (0, 255, 0, 255), (0, 0, 0, 255)]);
+    }
+
+    #[test]
+    fn interlaced_passes_are_placed() {
+        // Grayscale 3x3, the passes 1, 4, 5, 6 and 7 holding (0, 0), (2, 0), (0, 2) and (2, 2), (1, 0) and (1, 2), then the middle row
+        let data: [u8; 15] = [0, 1, 0, 3, 0, 7, 9, 0, 2, 0, 8, 0, 4, 5, 6];
+        let png: PNG = decode(&[ihdr(3, 3, 8, 0, true), chunk(b"IDAT", &zlib(&data))]).unwrap();
+        let grays: Vec<u8> = pixels(&png).iter().map(|&(r, _, _, _)| r).collect();
+
+        assert_eq!(grays, [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode(&[ihdr(100000, 100000, 8, 6, false), chunk(b"IDAT", &zlib(&[0; 8]))]).unwrap_err().to_string();
+        assert!(error.contains("too large"), "{error}");
+
+        let error: String = decode(&[ihdr(5000, 5000, 8, 6, false), chunk(b"IDAT", &zlib(&[0; 8]))]).unwrap_err().to_string();
+        assert!(error.contains("too short"), "{error}");
+
+        let mut ihdr: Vec<u8> = ihdr(1, 1, 8, 0, false);
+        ihdr[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let error: String = decode(&[ihdr]).unwrap_err().to_string();
+        assert!(error.contains("runs past the end of the file"), "{error}");
+    }
+}
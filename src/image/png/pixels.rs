@@ -0,0 +1,213 @@
+use anyhow::{bail, Result};
+
+use crate::image::inflate::zlib_decompress;
+use super::header::{ColorType, PNGHeader};
+
+// Adam7 passes as (first column, first row, column step, row step)
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+// Gamma of the display the decoded samples are meant for
+const DISPLAY_GAMMA: f32 = 2.2;
+
+// Decodes the zlib stream of the image data, of the size of the image or of an animation frame
+pub fn decode_pixels(header: &PNGHeader, width: u32, height: u32, compressed: &[u8]) -> Result<Vec<(u8, u8, u8, u8)>> {
+    let (width, height): (usize, usize) = (width as usize, height as usize);
+    let mut data: Vec<u8> = zlib_decompress(compressed)?;
+
+    let passes: &[(usize, usize, usize, usize)] = match header.interlaced {
+        true => &ADAM7_PASSES,
+        false => &[(0, 0, 1, 1)],
+    };
+
+    let bits_per_pixel: usize = header.bits_per_pixel();
+
+    // Sizes come from the header, the image data has to cover them before the pixels are allocated
+    match data_size(width, height, bits_per_pixel, passes) {
+        Some(size) if size <= data.len() => (),
+        _ => bail!("PNG image data of {} bytes is too short for {width}x{height} pixels", data.len()),
+    }
+
+    let mut pixels: Vec<(u8, u8, u8, u8)> = vec![(0, 0, 0, 0); width * height];
+    let mut offset: usize = 0;
+
+    for &(first_column, first_row, column_step, row_step) in passes {
+        let pass_width: usize = width.saturating_sub(first_column).div_ceil(column_step);
+        let pass_height: usize = height.saturating_sub(first_row).div_ceil(row_step);
+
+        // Empty passes have no filter byte
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        // Each row starts with its filter type
+        let row_size: usize = (pass_width * bits_per_pixel).div_ceil(8);
+        let size: usize = (row_size + 1) * pass_height;
+
+        let Some(pass) = data.get_mut(offset..offset + size) else {
+            bail!("PNG image data of {} bytes is too short", data.len());
+        };
+
+        unfilter(pass, row_size, bits_per_pixel.div_ceil(8))?;
+
+        for row in 0..pass_height {
+            let start: usize = offset + row * (row_size + 1) + 1;
+            let samples: &[u8] = &data[start..start + row_size];
+            let y: usize = first_row + row * row_step;
+
+            for column in 0..pass_width {
+                pixels[y * width + first_column + column * column_step] = read_pixel(header, samples, column);
+            }
+        }
+
+        offset += size;
+    }
+
+    if let Some(table) = header.gamma.and_then(gamma_table) {
+        for (r, g, b, _) in pixels.iter_mut() {
+            (*r, *g, *b) = (table[*r as usize], table[*g as usize], table[*b as usize]);
+        }
+    }
+
+    Ok(pixels)
+}
+
+// Size of the image data, rows of every pass starting with their filter type
+fn data_size(width: usize, height: usize, bits_per_pixel: usize, passes: &[(usize, usize, usize, usize)]) -> Option<usize> {
+    passes.iter().try_fold(0usize, |total, &(first_column, first_row, column_step, row_step)| {
+        let pass_width: usize = width.saturating_sub(first_column).div_ceil(column_step);
+        let pass_height: usize = height.saturating_sub(first_row).div_ceil(row_step);
+
+        // Empty passes have no filter byte
+        if pass_width == 0 || pass_height == 0 {
+            return Some(total);
+        }
+
+        let row_size: usize = pass_width.checked_mul(bits_per_pixel)?.div_ceil(8);
+        total.checked_add(row_size.checked_add(1)?.checked_mul(pass_height)?)
+    })
+}
+
+// Reverses the filter of each row in place, filters predicting bytes from the previous ones
+fn unfilter(data: &mut [u8], row_size: usize, bytes_per_pixel: usize) -> Result<()> {
+    let stride: usize = row_size + 1;
+
+    for row in 0..data.len() / stride {
+        let start: usize = row * stride + 1;
+        let filter: u8 = data[start - 1];
+
+        if filter > 4 {
+            bail!("PNG filter type {filter} is invalid");
+        }
+
+        for i in 0..row_size {
+            let position: usize = start + i;
+            let left: u8 = if i >= bytes_per_pixel { data[position - bytes_per_pixel] } else { 0 };
+            let up: u8 = if row > 0 { data[position - stride] } else { 0 };
+            let upper_left: u8 = if row > 0 && i >= bytes_per_pixel { data[position - stride - bytes_per_pixel] } else { 0 };
+
+            let prediction: u8 = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                _ => paeth(left, up, upper_left),
+            };
+
+            data[position] = data[position].wrapping_add(prediction);
+        }
+    }
+
+    Ok(())
+}
+
+// Neighbour closest to their gradient, preferring left, then up
+fn paeth(left: u8, up: u8, upper_left: u8) -> u8 {
+    let estimate: i16 = left as i16 + up as i16 - upper_left as i16;
+    let distance_left: i16 = (estimate - left as i16).abs();
+    let distance_up: i16 = (estimate - up as i16).abs();
+    let distance_upper_left: i16 = (estimate - upper_left as i16).abs();
+
+    if distance_left <= distance_up && distance_left <= distance_upper_left {
+        left
+    } else if distance_up <= distance_upper_left {
+        up
+    } else {
+        upper_left
+    }
+}
+
+// Sample of a row at the bit depth of the image, samples smaller than a byte starting from its high bits
+fn read_sample(samples: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([samples[2 * index], samples[2 * index + 1]]),
+        8 => samples[index] as u16,
+        _ => {
+            let bit: usize = index * bit_depth as usize;
+            let shift: usize = 8 - bit_depth as usize - bit % 8;
+            ((samples[bit / 8] >> shift) & ((1 << bit_depth) - 1)) as u16
+        },
+    }
+}
+
+fn scale_sample(value: u16, bit_depth: u8) -> u8 {
+    match bit_depth {
+        16 => ((value as u32 * 255 + 32767) / 65535) as u8,
+        8 => value as u8,
+        _ => (value as u32 * 255 / ((1 << bit_depth) - 1)) as u8,
+    }
+}
+
+fn read_pixel(header: &PNGHeader, samples: &[u8], column: usize) -> (u8, u8, u8, u8) {
+    let channels: usize = header.color_type.channels();
+    let sample = |channel: usize| read_sample(samples, column * channels + channel, header.bit_depth);
+    let scale = |value: u16| scale_sample(value, header.bit_depth);
+
+    // Grayscale and RGB images may have one color made transparent by tRNS
+    let opacity = |color: (u16, u16, u16)| if header.transparent_color == Some(color) { 0 } else { u8::MAX };
+
+    match header.color_type {
+        ColorType::GRAYSCALE => {
+            let gray: u16 = sample(0);
+            (scale(gray), scale(gray), scale(gray), opacity((gray, gray, gray)))
+        },
+        ColorType::RGB => {
+            let (r, g, b): (u16, u16, u16) = (sample(0), sample(1), sample(2));
+            (scale(r), scale(g), scale(b), opacity((r, g, b)))
+        },
+        ColorType::INDEXED => {
+            let index: usize = sample(0) as usize;
+            let (r, g, b): (u8, u8, u8) = header.palette.get(index).copied().unwrap_or_default();
+            (r, g, b, header.palette_alpha.get(index).copied().unwrap_or(u8::MAX))
+        },
+        ColorType::GRAYSCALEALPHA => {
+            let gray: u8 = scale(sample(0));
+            (gray, gray, gray, scale(sample(1)))
+        },
+        ColorType::RGBA => (scale(sample(0)), scale(sample(1)), scale(sample(2)), scale(sample(3))),
+    }
+}
+
+// Correction of the colors for the display, none when the image already matches it
+fn gamma_table(gamma: u32) -> Option<[u8; 256]> {
+    let exponent: f32 = 100000.0 / (gamma as f32 * DISPLAY_GAMMA);
+
+    if gamma == 0 || (exponent - 1.0).abs() < 0.01 {
+        return None;
+    }
+
+    let mut table: [u8; 256] = [0; 256];
+
+    for (value, corrected) in table.iter_mut().enumerate() {
+        *corrected = ((value as f32 / 255.0).powf(exponent) * 255.0).round() as u8;
+    }
+
+    Some(table)
+}
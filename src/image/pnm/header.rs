@@ -0,0 +1,124 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::pnm_bit_reader::PnmBitReader;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PNMFormat {
+    // Bitmap, 1 standing for black
+    PBM,
+    #[default]
+    PGM,
+    PPM,
+    // Arbitrary tuples described by the header
+    PAM,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PNMHeader {
+    pub format: PNMFormat,
+    // Samples written as ASCII decimal numbers
+    pub plain: bool,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub maxval: u32,
+    pub tuple_type: String,
+}
+
+impl PNMHeader {
+    pub fn from_binary(reader: &mut PnmBitReader) -> Result<Self> {
+        let mut header: PNMHeader = PNMHeader::default();
+
+        let magic: Vec<u8> = reader.read_bytes(2)?;
+
+        (header.format, header.plain) = match &magic[..] {
+            b"P1" => (PNMFormat::PBM, true),
+            b"P2" => (PNMFormat::PGM, true),
+            b"P3" => (PNMFormat::PPM, true),
+            b"P4" => (PNMFormat::PBM, false),
+            b"P5" => (PNMFormat::PGM, false),
+            b"P6" => (PNMFormat::PPM, false),
+            b"P7" => (PNMFormat::PAM, false),
+            _ => bail!("Not a Netpbm file"),
+        };
+
+        match header.format {
+            PNMFormat::PAM => header.read_pam_header(reader)?,
+            format => {
+                header.width = reader.read_number()?;
+                header.height = reader.read_number()?;
+                header.maxval = if format == PNMFormat::PBM { 1 } else { reader.read_number()? };
+                header.depth = if format == PNMFormat::PPM { 3 } else { 1 };
+                header.tuple_type = match format {
+                    PNMFormat::PBM => "BLACKANDWHITE",
+                    PNMFormat::PGM => "GRAYSCALE",
+                    _ => "RGB",
+                }.to_owned();
+            },
+        }
+
+        if header.width == 0 || header.height == 0 {
+            bail!("Netpbm image of {}x{} is empty", header.width, header.height);
+        }
+
+        pixel_count("Netpbm", header.width, header.height)?;
+
+        if !(1..=u16::MAX as u32).contains(&header.maxval) {
+            bail!("Netpbm maxval {} is invalid", header.maxval);
+        }
+
+        if header.depth == 0 {
+            bail!("PAM depth can't be zero");
+        }
+
+        Ok(header)
+    }
+
+    // "NAME value" lines up to ENDHDR
+    fn read_pam_header(&mut self, reader: &mut PnmBitReader) -> Result<()> {
+        loop {
+            match reader.read_token()?.as_str() {
+                "WIDTH" => self.width = reader.read_number()?,
+                "HEIGHT" => self.height = reader.read_number()?,
+                "DEPTH" => self.depth = reader.read_number()?,
+                "MAXVAL" => self.maxval = reader.read_number()?,
+                // Tuple type may be given over several lines, each adding a word
+                "TUPLTYPE" => {
+                    if !self.tuple_type.is_empty() {
+                        self.tuple_type.push(' ');
+                    }
+
+                    self.tuple_type.push_str(&reader.read_token()?);
+                },
+                "ENDHDR" => return Ok(()),
+                token => bail!("Unknown PAM header field \"{token}\""),
+            }
+        }
+    }
+
+    // Last sample of the tuples is an opacity
+    pub fn has_alpha(&self) -> bool {
+        self.tuple_type.ends_with("_ALPHA") && self.depth >= 2
+    }
+
+    // Bytes of each sample of binary rasters, bitmaps excepted
+    pub fn sample_size(&self) -> usize {
+        if self.maxval > u8::MAX as u32 { 2 } else { 1 }
+    }
+}
+
+impl fmt::Display for PNMHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "format: {:?}\n", self.format)?;
+        write!(f, "plain: {}\n", self.plain)?;
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "depth: {}\n", self.depth)?;
+        write!(f, "maxval: {}\n", self.maxval)?;
+        write!(f, "tuple_type: {}\n", self.tuple_type)?;
+        Ok(())
+    }
+}
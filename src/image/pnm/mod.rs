@@ -0,0 +1,194 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use header::{PNMFormat, PNMHeader};
+use pnm_bit_reader::PnmBitReader;
+use super::bmp::BMP;
+use super::Image;
+
+pub mod header;
+mod pnm_bit_reader;
+pub mod writer;
+
+// Netpbm PBM, PGM, PPM and PAM images
+#[derive(Clone, Debug)]
+pub struct PNM {
+    header: PNMHeader,
+    image: BMP,
+}
+
+impl PNM {
+    // Samples of every tuple, bitmaps being turned into a 0 for black and 1 for white grayscale
+    fn read_samples(reader: &mut PnmBitReader, header: &PNMHeader) -> Result<Vec<u32>> {
+        let (width, height): (usize, usize) = (header.width as usize, header.height as usize);
+        let count: Option<usize> = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(header.depth as usize));
+
+        // Fewest bytes the samples can be stored in, a plain sample taking at least one digit
+        let size: Option<usize> = match (header.format, header.plain) {
+            (PNMFormat::PBM, false) => width.div_ceil(8).checked_mul(height),
+            (_, true) => count,
+            (_, false) => count.and_then(|count| count.checked_mul(header.sample_size())),
+        };
+
+        let (Some(count), Some(size)) = (count, size) else {
+            bail!("PNM image of {}x{} and depth {} is too large", header.width, header.height, header.depth);
+        };
+
+        let remaining: u64 = reader.length()? - reader.position()?;
+
+        if size as u64 > remaining {
+            bail!("PNM pixels need at least {size} bytes, {remaining} found");
+        }
+
+        let mut samples: Vec<u32> = Vec::with_capacity(count);
+
+        match (header.format, header.plain) {
+            (PNMFormat::PBM, true) => {
+                for _ in 0..count {
+                    samples.push(1 - reader.read_digit()? as u32);
+                }
+            },
+            // Rows of bits, each row starting on a new byte
+            (PNMFormat::PBM, false) => {
+                for _ in 0..height {
+                    let row: Vec<u8> = reader.read_bytes(width.div_ceil(8))?;
+
+                    for x in 0..width {
+                        samples.push(1 - ((row[x / 8] >> (7 - x % 8)) & 1) as u32);
+                    }
+                }
+            },
+            (_, true) => {
+                for _ in 0..count {
+                    samples.push(reader.read_number()?);
+                }
+            },
+            (_, false) => {
+                let bytes: Vec<u8> = reader.read_bytes(count * header.sample_size())?;
+
+                match header.sample_size() {
+                    2 => samples.extend(bytes.chunks_exact(2).map(|sample| u16::from_be_bytes([sample[0], sample[1]]) as u32)),
+                    _ => samples.extend(bytes.iter().map(|&sample| sample as u32)),
+                }
+            },
+        }
+
+        Ok(samples)
+    }
+}
+
+impl Image for PNM {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: PnmBitReader = PnmBitReader::new(stream);
+        let header: PNMHeader = PNMHeader::from_binary(&mut reader)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let samples: Vec<u32> = Self::read_samples(&mut reader, &header)?;
+        let depth: usize = header.depth as usize;
+        let width: usize = header.width as usize;
+        let maxval: u32 = header.maxval;
+        let scale = |sample: u32| ((sample.min(maxval) * 255 + maxval / 2) / maxval) as u8;
+
+        // Unknown tuple types are read as grayscale or RGB from their first samples
+        let has_alpha: bool = header.has_alpha();
+        let colors: usize = depth - has_alpha as usize;
+
+        let image: BMP = BMP::from_rgba_pixels(header.width, header.height, |x, y| {
+            let tuple: &[u32] = &samples[(y * width + x) * depth..(y * width + x + 1) * depth];
+            let a: u8 = if has_alpha { scale(tuple[depth - 1]) } else { u8::MAX };
+
+            match colors {
+                0..=2 => (scale(tuple[0]), scale(tuple[0]), scale(tuple[0]), a),
+                _ => (scale(tuple[0]), scale(tuple[1]), scale(tuple[2]), a),
+            }
+        });
+
+        Ok(Self {
+            header,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for PNM {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs;
+    use std::io::{BufReader, Cursor};
+    use std::path::PathBuf;
+    use std::process;
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes.to_vec()));
+        Ok(PNM::from_stream(BufReader::new(source), false)?.image)
+    }
+
+    fn write(image: &BMP, format: PNMFormat) -> Vec<u8> {
+        let path: PathBuf = temp_dir().join(format!("term_viewer_{}_{format:?}.pnm", process::id()));
+        writer::write_to_file(image, path.clone(), format).unwrap();
+        let bytes: Vec<u8> = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn pixels(image: &BMP) -> Vec<(u8, u8, u8, u8)> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| (x, y))).map(|(x, y)| {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+            (r, g, b, image.pixel_alpha(x, y))
+        }).collect()
+    }
+
+    #[test]
+    fn pam_round_trips() {
+        let image: BMP = BMP::from_rgba_pixels(3, 2, |x, y| ((x * 80) as u8, (y * 200) as u8, 7, (255 - x * 100) as u8));
+        let bytes: Vec<u8> = write(&image, PNMFormat::PAM);
+        let decoded: BMP = decode(&bytes).unwrap();
+
+        assert!(bytes.starts_with(b"P7\nWIDTH 3\nHEIGHT 2\nDEPTH 4\n"));
+        assert_eq!(pixels(&decoded), pixels(&image));
+        assert_eq!(write(&decoded, PNMFormat::PAM), bytes);
+    }
+
+    #[test]
+    fn plain_bitmaps_ignore_whitespace_and_comments() {
+        let image: BMP = decode(b"P1\n# comment\n3 2\n1 0 1\n010").unwrap();
+        let grays: Vec<u8> = pixels(&image).iter().map(|&(r, _, _, _)| r).collect();
+
+        assert_eq!(grays, [0, 255, 0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn samples_are_scaled_from_maxval() {
+        let image: BMP = decode(b"P5 2 1 1000 \x00\x00\x03\xE8").unwrap();
+        assert_eq!(pixels(&image), [(0, 0, 0, 255), (255, 255, 255, 255)]);
+
+        let image: BMP = decode(b"P2 2 1 3 1 2").unwrap();
+        assert_eq!(pixels(&image), [(85, 85, 85, 255), (170, 170, 170, 255)]);
+    }
+
+    #[test]
+    fn oversized_and_truncated_images_are_rejected() {
+        let error: String = decode(b"P6 100000 100000 255 \x00").unwrap_err().to_string();
+        assert!(error.contains("too large"), "{error}");
+
+        let error: String = decode(b"P6 1000 1000 255 \x00").unwrap_err().to_string();
+        assert!(error.contains("3000000 bytes"), "{error}");
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{bail, Result};
+
+use crate::image::bit_reader::{BitReader, Stream};
+
+#[derive(Debug)]
+pub struct PnmBitReader {
+    next_bit: usize,
+    current_byte: u8,
+    stream: Stream,
+}
+
+impl BitReader for PnmBitReader {
+    fn new(stream: Stream) -> Self {
+        Self {
+            next_bit: 0,
+            current_byte: 0,
+            stream,
+        }
+    }
+
+    fn read_bit(&mut self) -> Result<u8> {
+        if self.next_bit == 0 {
+            self.current_byte = self.read_byte()?;
+        }
+
+        let bit: u8 = (self.current_byte >> (7 - self.next_bit)) & 0x1;
+        self.next_bit += 1;
+        self.next_bit %= 8;
+
+        Ok(bit)
+    }
+
+    fn set_next_bit(&mut self, next_bit: usize) -> () {
+        self.next_bit = next_bit;
+    }
+
+    fn stream(&mut self) -> &mut Stream {
+        &mut self.stream
+    }
+}
+
+impl PnmBitReader {
+    // Next token of the header or of a plain raster, separated by whitespace or comments running to the end of the line
+    pub fn read_token(&mut self) -> Result<String> {
+        let mut token: String = String::new();
+
+        loop {
+            let byte: u8 = match self.read_byte() {
+                Ok(byte) => byte,
+                // Last sample of a plain raster may end the file
+                Err(_) if !token.is_empty() => return Ok(token),
+                Err(error) => return Err(error),
+            };
+
+            if byte == b'#' {
+                while self.read_byte()? != b'\n' {}
+            }
+
+            if byte == b'#' || byte.is_ascii_whitespace() {
+                if !token.is_empty() {
+                    return Ok(token);
+                }
+
+                continue;
+            }
+
+            token.push(byte as char);
+        }
+    }
+
+    pub fn read_number(&mut self) -> Result<u32> {
+        let token: String = self.read_token()?;
+
+        let Ok(number) = token.parse() else {
+            bail!("Expected a number instead of \"{token}\"");
+        };
+
+        Ok(number)
+    }
+
+    // Digits of plain PBM rasters need no whitespace between them
+    pub fn read_digit(&mut self) -> Result<u8> {
+        loop {
+            match self.read_byte()? {
+                b'#' => while self.read_byte()? != b'\n' {},
+                byte @ (b'0' | b'1') => return Ok(byte - b'0'),
+                byte if byte.is_ascii_whitespace() => (),
+                byte => bail!("Unexpected character {:?} in PBM raster", byte as char),
+            }
+        }
+    }
+}
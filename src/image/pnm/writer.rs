@@ -0,0 +1,56 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::image::bmp::BMP;
+use crate::image::write_bytes;
+use super::header::PNMFormat;
+
+// Binary variants, the opacity being only kept by PAM
+pub fn write_to_file(image: &BMP, filename: PathBuf, format: PNMFormat) -> Result<()> {
+    let (width, height): (u32, u32) = (image.width(), image.height());
+    let has_alpha: bool = (0..height).any(|y| (0..width).any(|x| image.pixel_alpha(x, y) < u8::MAX));
+
+    let header: String = match format {
+        PNMFormat::PBM => format!("P4\n{width} {height}\n"),
+        PNMFormat::PGM => format!("P5\n{width} {height}\n255\n"),
+        PNMFormat::PPM => format!("P6\n{width} {height}\n255\n"),
+        PNMFormat::PAM if has_alpha => format!("P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n"),
+        PNMFormat::PAM => format!("P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH 3\nMAXVAL 255\nTUPLTYPE RGB\nENDHDR\n"),
+    };
+
+    let mut buffer: Vec<u8> = header.into_bytes();
+
+    for y in 0..height {
+        // Bits of a bitmap row, 1 standing for black
+        let mut bits: Vec<u8> = vec![0; width.div_ceil(8) as usize];
+
+        for x in 0..width {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+
+            match format {
+                PNMFormat::PBM if luma(r, g, b) < 128 => bits[x as usize / 8] |= 0x80 >> (x % 8),
+                PNMFormat::PBM => (),
+                PNMFormat::PGM => buffer.push(luma(r, g, b)),
+                PNMFormat::PPM => buffer.extend_from_slice(&[r, g, b]),
+                PNMFormat::PAM => {
+                    buffer.extend_from_slice(&[r, g, b]);
+
+                    if has_alpha {
+                        buffer.push(image.pixel_alpha(x, y));
+                    }
+                },
+            }
+        }
+
+        if format == PNMFormat::PBM {
+            buffer.extend_from_slice(&bits);
+        }
+    }
+
+    write_bytes(filename, &buffer)
+}
+
+// BT.601 luma
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000) as u8
+}
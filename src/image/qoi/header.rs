@@ -0,0 +1,67 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::qoi_bit_reader::QoiBitReader;
+
+pub const MAGIC: &[u8; 4] = b"qoif";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum QOIColorSpace {
+    // sRGB color channels, linear alpha
+    #[default]
+    SRGB,
+    // Every channel linear
+    LINEAR,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QOIHeader {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u8,
+    pub color_space: QOIColorSpace,
+}
+
+impl QOIHeader {
+    pub fn from_binary(reader: &mut QoiBitReader) -> Result<Self> {
+        let mut header: QOIHeader = QOIHeader::default();
+
+        if reader.read_bytes(MAGIC.len())? != MAGIC {
+            bail!("Not a QOI file");
+        }
+
+        header.width = reader.read_double()?;
+        header.height = reader.read_double()?;
+        header.channels = reader.read_byte()?;
+
+        header.color_space = match reader.read_byte()? {
+            0 => QOIColorSpace::SRGB,
+            1 => QOIColorSpace::LINEAR,
+            value => bail!("QOI color space {value} is invalid"),
+        };
+
+        if !matches!(header.channels, 3 | 4) {
+            bail!("QOI images of {} channels are invalid", header.channels);
+        }
+
+        if header.width == 0 || header.height == 0 {
+            bail!("QOI image of {}x{} is empty", header.width, header.height);
+        }
+
+        pixel_count("QOI", header.width, header.height)?;
+
+        Ok(header)
+    }
+}
+
+impl fmt::Display for QOIHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "channels: {}\n", self.channels)?;
+        write!(f, "color_space: {:?}\n", self.color_space)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use header::QOIHeader;
+use qoi_bit_reader::QoiBitReader;
+use super::bmp::BMP;
+use super::Image;
+
+mod header;
+mod qoi_bit_reader;
+pub mod writer;
+
+// 8 bits tags, then 2 bits tags of the other operations
+pub(super) const QOI_OP_RGB: u8 = 0xFE;
+pub(super) const QOI_OP_RGBA: u8 = 0xFF;
+pub(super) const QOI_OP_INDEX: u8 = 0x00;
+pub(super) const QOI_OP_DIFF: u8 = 0x40;
+pub(super) const QOI_OP_LUMA: u8 = 0x80;
+pub(super) const QOI_OP_RUN: u8 = 0xC0;
+
+pub(super) const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+// Longest run of a single operation, 63 and 64 being taken by the RGB and RGBA tags
+pub(super) const MAX_RUN: u8 = 62;
+
+// Position of a pixel in the array of previously seen pixels
+pub(super) fn index_position(pixel: (u8, u8, u8, u8)) -> usize {
+    let (r, g, b, a): (u8, u8, u8, u8) = pixel;
+
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+#[derive(Clone, Debug)]
+pub struct QOI {
+    header: QOIHeader,
+    image: BMP,
+}
+
+impl QOI {
+    fn decode(data: &[u8], count: usize) -> Result<Vec<(u8, u8, u8, u8)>> {
+        let mut pixels: Vec<(u8, u8, u8, u8)> = Vec::with_capacity(count);
+        let mut index: [(u8, u8, u8, u8); 64] = [(0, 0, 0, 0); 64];
+        let mut pixel: (u8, u8, u8, u8) = (0, 0, 0, u8::MAX);
+        let mut position: usize = 0;
+
+        let mut next_byte = || {
+            let byte: Option<u8> = data.get(position).copied();
+            position += 1;
+            byte
+        };
+
+        while pixels.len() < count {
+            let Some(tag) = next_byte() else {
+                bail!("QOI data ends after {} of {count} pixels", pixels.len());
+            };
+
+            let mut run: usize = 1;
+
+            match tag {
+                QOI_OP_RGB | QOI_OP_RGBA => {
+                    let mut channel = || next_byte().unwrap_or_default();
+                    pixel = (channel(), channel(), channel(), pixel.3);
+
+                    if tag == QOI_OP_RGBA {
+                        pixel.3 = channel();
+                    }
+                },
+                _ => match tag & 0xC0 {
+                    QOI_OP_INDEX => pixel = index[(tag & 0x3F) as usize],
+                    // Differences to the previous pixel, biased by 2
+                    QOI_OP_DIFF => {
+                        let difference = |shift: u8| ((tag >> shift) & 0x03).wrapping_sub(2);
+                        pixel = (pixel.0.wrapping_add(difference(4)), pixel.1.wrapping_add(difference(2)), pixel.2.wrapping_add(difference(0)), pixel.3);
+                    },
+                    // Green difference biased by 32, red and blue ones relative to it biased by 8
+                    QOI_OP_LUMA => {
+                        let green: u8 = (tag & 0x3F).wrapping_sub(32);
+                        let byte: u8 = next_byte().unwrap_or_default();
+                        let red: u8 = green.wrapping_sub(8).wrapping_add(byte >> 4);
+                        let blue: u8 = green.wrapping_sub(8).wrapping_add(byte & 0x0F);
+                        pixel = (pixel.0.wrapping_add(red), pixel.1.wrapping_add(green), pixel.2.wrapping_add(blue), pixel.3);
+                    },
+                    // Run of the previous pixel, biased by 1
+                    _ => run = (tag & 0x3F) as usize + 1,
+                },
+            }
+
+            index[index_position(pixel)] = pixel;
+            pixels.extend(std::iter::repeat_n(pixel, run.min(count - pixels.len())));
+        }
+
+        Ok(pixels)
+    }
+}
+
+impl Image for QOI {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: QoiBitReader = QoiBitReader::new(stream);
+        let header: QOIHeader = QOIHeader::from_binary(&mut reader)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let size: u64 = reader.length()? - reader.position()?;
+        let data: Vec<u8> = reader.read_bytes(size as usize)?;
+
+        if !data.ends_with(&END_MARKER) {
+            eprintln!("QOI file doesn't end with its end marker");
+        }
+
+        let count: usize = header.width as usize * header.height as usize;
+
+        // Every operation takes at least a byte for up to a run of pixels
+        if data.len() < count.div_ceil(MAX_RUN as usize) {
+            bail!("QOI data of {} bytes can't hold {count} pixels", data.len());
+        }
+
+        let pixels: Vec<(u8, u8, u8, u8)> = Self::decode(&data, count)?;
+        let width: usize = header.width as usize;
+
+        // Channels are only a hint of the encoder, alpha of 3 channels images being ignored
+        let opaque: bool = header.channels == 3;
+        let image: BMP = BMP::from_rgba_pixels(header.width, header.height, |x, y| {
+            let (r, g, b, a): (u8, u8, u8, u8) = pixels[y * width + x];
+            (r, g, b, if opaque { u8::MAX } else { a })
+        });
+
+        Ok(Self {
+            header,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for QOI {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs;
+    use std::io::{BufReader, Cursor};
+    use std::path::PathBuf;
+    use std::process;
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn file(width: u32, height: u32, channels: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = header::MAGIC.to_vec();
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&[channels, 0]);
+        bytes.extend_from_slice(data);
+        bytes.extend_from_slice(&END_MARKER);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes.to_vec()));
+        Ok(QOI::from_stream(BufReader::new(source), false)?.image)
+    }
+
+    fn write(image: &BMP, name: &str) -> Vec<u8> {
+        let path: PathBuf = temp_dir().join(format!("term_viewer_{}_{name}.qoi", process::id()));
+        writer::write_to_file(image, path.clone()).unwrap();
+        let bytes: Vec<u8> = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn pixels(image: &BMP) -> Vec<(u8, u8, u8, u8)> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| (x, y))).map(|(x, y)| {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+            (r, g, b, image.pixel_alpha(x, y))
+        }).collect()
+    }
+
+    #[test]
+    fn operations_are_decoded() {
+        // RGB, run of 2, difference of (-1, 0, 1), luma of green +10 with red +9 and blue +12, index of the first pixel
+        let data: [u8; 9] = [QOI_OP_RGB, 100, 50, 25, QOI_OP_RUN | 1, QOI_OP_DIFF | 0x1B, QOI_OP_LUMA | 42, 0x7A, QOI_OP_INDEX | 10];
+        let image: BMP = decode(&file(6, 1, 3, &data)).unwrap();
+        let colors: Vec<(u8, u8, u8)> = (0..6).map(|x| image.pixel_color(x, 0)).collect();
+
+        assert_eq!(index_position((100, 50, 25, 255)), 10);
+        assert_eq!(colors, [(100, 50, 25), (100, 50, 25), (100, 50, 25), (99, 50, 26), (108, 60, 38), (100, 50, 25)]);
+    }
+
+    #[test]
+    fn images_round_trip() {
+        let image: BMP = BMP::from_rgba_pixels(7, 5, |x, y| match (x + y) % 4 {
+            0 => (10, 20, 30, 255),
+            1 => ((x * 3) as u8, (y * 5) as u8, 40, 255),
+            2 => ((x * 40) as u8, 200, (y * 50) as u8, (x * 30) as u8),
+            _ => (11, 21, 31, 255),
+        });
+        let bytes: Vec<u8> = write(&image, "round_trip");
+        let decoded: BMP = decode(&bytes).unwrap();
+
+        assert_eq!(bytes[12], 4);
+        assert!(bytes.ends_with(&END_MARKER));
+        assert_eq!(pixels(&decoded), pixels(&image));
+        assert_eq!(write(&decoded, "rewrite"), bytes);
+    }
+
+    #[test]
+    fn long_runs_are_split() {
+        let image: BMP = BMP::from_rgba_pixels(200, 1, |_, _| (0, 0, 0, 255));
+        let bytes: Vec<u8> = write(&image, "long_runs");
+
+        assert_eq!(bytes[14..bytes.len() - END_MARKER.len()], [QOI_OP_RUN | 61, QOI_OP_RUN | 61, QOI_OP_RUN | 61, QOI_OP_RUN | 13]);
+        assert_eq!(pixels(&decode(&bytes).unwrap()), pixels(&image));
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode(&file(u32::MAX, u32::MAX, 4, &[])).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn truncated_data_is_rejected() {
+        let error: String = decode(&file(100, 100, 3, &[QOI_OP_RGB, 1, 2, 3])).unwrap_err().to_string();
+
+        assert!(error.contains("can't hold 10000 pixels"), "{error}");
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::image::bmp::BMP;
+use crate::image::write_bytes;
+use super::header::MAGIC;
+use super::{index_position, END_MARKER, MAX_RUN, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LUMA, QOI_OP_RGB, QOI_OP_RGBA, QOI_OP_RUN};
+
+// sRGB image, of 4 channels only when it has transparent pixels
+pub fn write_to_file(image: &BMP, filename: PathBuf) -> Result<()> {
+    let (width, height): (u32, u32) = (image.width(), image.height());
+    let has_alpha: bool = (0..height).any(|y| (0..width).any(|x| image.pixel_alpha(x, y) < u8::MAX));
+
+    let mut buffer: Vec<u8> = Vec::new();
+    buffer.extend_from_slice(MAGIC);
+    buffer.extend_from_slice(&width.to_be_bytes());
+    buffer.extend_from_slice(&height.to_be_bytes());
+    buffer.push(if has_alpha { 4 } else { 3 });
+    buffer.push(0);
+
+    let mut index: [(u8, u8, u8, u8); 64] = [(0, 0, 0, 0); 64];
+    let mut previous: (u8, u8, u8, u8) = (0, 0, 0, u8::MAX);
+    let mut run: u8 = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+            let pixel: (u8, u8, u8, u8) = (r, g, b, image.pixel_alpha(x, y));
+
+            if pixel == previous {
+                run += 1;
+
+                if run == MAX_RUN {
+                    buffer.push(QOI_OP_RUN | (run - 1));
+                    run = 0;
+                }
+
+                continue;
+            }
+
+            if run > 0 {
+                buffer.push(QOI_OP_RUN | (run - 1));
+                run = 0;
+            }
+
+            let position: usize = index_position(pixel);
+
+            if index[position] == pixel {
+                buffer.push(QOI_OP_INDEX | position as u8);
+            } else if pixel.3 != previous.3 {
+                buffer.extend_from_slice(&[QOI_OP_RGBA, r, g, b, pixel.3]);
+            } else {
+                let red: i8 = r.wrapping_sub(previous.0) as i8;
+                let green: i8 = g.wrapping_sub(previous.1) as i8;
+                let blue: i8 = b.wrapping_sub(previous.2) as i8;
+                let red_green: i8 = red.wrapping_sub(green);
+                let blue_green: i8 = blue.wrapping_sub(green);
+
+                if (-2..2).contains(&red) && (-2..2).contains(&green) && (-2..2).contains(&blue) {
+                    buffer.push(QOI_OP_DIFF | ((red + 2) as u8) << 4 | ((green + 2) as u8) << 2 | (blue + 2) as u8);
+                } else if (-32..32).contains(&green) && (-8..8).contains(&red_green) && (-8..8).contains(&blue_green) {
+                    buffer.push(QOI_OP_LUMA | (green + 32) as u8);
+                    buffer.push(((red_green + 8) as u8) << 4 | (blue_green + 8) as u8);
+                } else {
+                    buffer.extend_from_slice(&[QOI_OP_RGB, r, g, b]);
+                }
+            }
+
+            index[position] = pixel;
+            previous = pixel;
+        }
+    }
+
+    if run > 0 {
+        buffer.push(QOI_OP_RUN | (run - 1));
+    }
+
+    buffer.extend_from_slice(&END_MARKER);
+
+    write_bytes(filename, &buffer)
+}
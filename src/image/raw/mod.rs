@@ -0,0 +1,142 @@
+use anyhow::{bail, Result};
+
+use crate::image::bit_reader::{BitReader, Stream};
+use raw_bit_reader::RawBitReader;
+use super::bmp::BMP;
+use super::{pixel_count, DecodeOptions};
+
+mod raw_bit_reader;
+pub mod writer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RawFormat {
+    // Red, green and blue bytes
+    RGB,
+    // Red, green, blue and alpha bytes
+    RGBA,
+}
+
+impl RawFormat {
+    pub fn channels(&self) -> usize {
+        match self {
+            RawFormat::RGB => 3,
+            RawFormat::RGBA => 4,
+        }
+    }
+}
+
+// Interleaved pixels without any header, their size being given by the user
+pub fn decode(stream: Stream, format: RawFormat, options: DecodeOptions, debug: bool) -> Result<BMP> {
+    let Some((width, height)) = options.raw_size else {
+        bail!("Raw images need a width and a height");
+    };
+
+    if width == 0 || height == 0 {
+        bail!("Raw image of {width}x{height} is empty");
+    }
+
+    let count: usize = pixel_count("Raw", width, height)?;
+
+    if debug {
+        println!("Raw {format:?} pixels of {width}x{height}");
+    }
+
+    let mut reader: RawBitReader = RawBitReader::new(stream);
+    let length: u64 = reader.length()?;
+    let data: Vec<u8> = reader.read_bytes(length as usize)?;
+
+    let channels: usize = format.channels();
+    let size: usize = count * channels;
+
+    // Bytes past the image are left alone, so that the first frame of a stream can be shown
+    if data.len() < size {
+        bail!("Raw {format:?} image of {width}x{height} needs {size} bytes, {} found", data.len());
+    }
+
+    Ok(BMP::from_rgba_pixels(width, height, |x, y| {
+        let pixel: &[u8] = &data[(y * width as usize + x) * channels..][..channels];
+        (pixel[0], pixel[1], pixel[2], if format == RawFormat::RGBA { pixel[3] } else { u8::MAX })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs;
+    use std::io::{BufReader, Cursor};
+    use std::path::PathBuf;
+    use std::process;
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn read(bytes: &[u8], format: RawFormat, raw_size: Option<(u32, u32)>) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes.to_vec()));
+        let options: DecodeOptions = DecodeOptions {
+            raw_size,
+            ..DecodeOptions::default()
+        };
+        decode(BufReader::new(source), format, options, false)
+    }
+
+    fn write(image: &BMP, format: RawFormat, name: &str) -> Vec<u8> {
+        let path: PathBuf = temp_dir().join(format!("term_viewer_{}_{name}.raw", process::id()));
+        writer::write_to_file(image, path.clone(), format).unwrap();
+        let bytes: Vec<u8> = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn pixels(image: &BMP) -> Vec<(u8, u8, u8, u8)> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| (x, y))).map(|(x, y)| {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+            (r, g, b, image.pixel_alpha(x, y))
+        }).collect()
+    }
+
+    #[test]
+    fn rgb_pixels_are_opaque() {
+        let bytes: [u8; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        let image: BMP = read(&bytes, RawFormat::RGB, Some((2, 2))).unwrap();
+
+        assert_eq!(pixels(&image), [(1, 2, 3, 255), (4, 5, 6, 255), (7, 8, 9, 255), (10, 11, 12, 255)]);
+        assert_eq!(write(&image, RawFormat::RGB, "rgb"), bytes);
+    }
+
+    #[test]
+    fn rgba_pixels_round_trip() {
+        let bytes: [u8; 8] = [255, 0, 128, 0, 9, 8, 7, 200];
+        let image: BMP = read(&bytes, RawFormat::RGBA, Some((1, 2))).unwrap();
+
+        assert_eq!(pixels(&image), [(255, 0, 128, 0), (9, 8, 7, 200)]);
+        assert_eq!(write(&image, RawFormat::RGBA, "rgba"), bytes);
+    }
+
+    #[test]
+    fn trailing_bytes_are_ignored() {
+        let image: BMP = read(&[1, 2, 3, 4, 5, 6], RawFormat::RGB, Some((1, 1))).unwrap();
+
+        assert_eq!(pixels(&image), [(1, 2, 3, 255)]);
+    }
+
+    #[test]
+    fn sizes_are_required() {
+        let error: String = read(&[0; 3], RawFormat::RGB, None).unwrap_err().to_string();
+
+        assert!(error.contains("Raw images need a width and a height"), "{error}");
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = read(&[0; 4], RawFormat::RGBA, Some((100_000, 100_000))).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn short_data_is_rejected() {
+        let error: String = read(&[0; 11], RawFormat::RGBA, Some((3, 1))).unwrap_err().to_string();
+
+        assert!(error.contains("Raw RGBA image of 3x1 needs 12 bytes, 11 found"), "{error}");
+    }
+}
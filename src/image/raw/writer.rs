@@ -0,0 +1,25 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::image::bmp::BMP;
+use crate::image::write_bytes;
+use super::RawFormat;
+
+// Interleaved pixels only, the opacity being dropped by RGB
+pub fn write_to_file(image: &BMP, filename: PathBuf, format: RawFormat) -> Result<()> {
+    let (width, height): (u32, u32) = (image.width(), image.height());
+    let mut buffer: Vec<u8> = Vec::with_capacity(width as usize * height as usize * format.channels());
+
+    for y in 0..height {
+        for x in 0..width {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+            buffer.extend_from_slice(&[r, g, b]);
+
+            if format == RawFormat::RGBA {
+                buffer.push(image.pixel_alpha(x, y));
+            }
+        }
+    }
+
+    write_bytes(filename, &buffer)
+}
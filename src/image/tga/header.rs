@@ -0,0 +1,272 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::tga_bit_reader::TgaBitReader;
+
+const FOOTER_SIZE: u64 = 26;
+const FOOTER_SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+const EXTENSION_SIZE: u16 = 495;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TGAImageType {
+    #[default]
+    COLORMAPPED,
+    TRUECOLOR,
+    GRAYSCALE,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TGAHeader {
+    pub id_length: u8,
+    pub image_type: TGAImageType,
+    // Run length encoded pixels
+    pub rle: bool,
+    pub color_map_first_entry: u16,
+    pub color_map_length: u16,
+    pub color_map_entry_size: u8,
+    pub x_origin: u16,
+    pub y_origin: u16,
+    pub width: u16,
+    pub height: u16,
+    pub pixel_depth: u8,
+    pub alpha_bits: u8,
+    pub right_to_left: bool,
+    pub top_to_bottom: bool,
+    pub color_map: Vec<(u8, u8, u8, u8)>,
+    pub extension: Option<TGAExtension>,
+}
+
+impl TGAHeader {
+    pub fn from_binary(reader: &mut TgaBitReader) -> Result<Self> {
+        let id_length: u8 = reader.read_byte()?;
+        let color_map_type: u8 = reader.read_byte()?;
+        let image_type: u8 = reader.read_byte()?;
+
+        let (image_type, rle): (TGAImageType, bool) = match image_type {
+            1 => (TGAImageType::COLORMAPPED, false),
+            2 => (TGAImageType::TRUECOLOR, false),
+            3 => (TGAImageType::GRAYSCALE, false),
+            9 => (TGAImageType::COLORMAPPED, true),
+            10 => (TGAImageType::TRUECOLOR, true),
+            11 => (TGAImageType::GRAYSCALE, true),
+            0 => bail!("TGA file holds no image data"),
+            value => bail!("TGA image type {value} not supported"),
+        };
+
+        let mut header: TGAHeader = TGAHeader {
+            id_length,
+            image_type,
+            rle,
+            ..TGAHeader::default()
+        };
+
+        header.color_map_first_entry = reader.read_word()?.swap_bytes();
+        header.color_map_length = reader.read_word()?.swap_bytes();
+        header.color_map_entry_size = reader.read_byte()?;
+        header.x_origin = reader.read_word()?.swap_bytes();
+        header.y_origin = reader.read_word()?.swap_bytes();
+        header.width = reader.read_word()?.swap_bytes();
+        header.height = reader.read_word()?.swap_bytes();
+        header.pixel_depth = reader.read_byte()?;
+
+        let descriptor: u8 = reader.read_byte()?;
+        header.alpha_bits = descriptor & 0x0F;
+        header.right_to_left = descriptor & 0x10 != 0;
+        header.top_to_bottom = descriptor & 0x20 != 0;
+
+        let valid_depth: bool = match header.image_type {
+            TGAImageType::COLORMAPPED => matches!(header.pixel_depth, 8 | 16),
+            TGAImageType::TRUECOLOR => matches!(header.pixel_depth, 15 | 16 | 24 | 32),
+            TGAImageType::GRAYSCALE => matches!(header.pixel_depth, 8 | 16),
+        };
+
+        if !valid_depth {
+            bail!("{} bits pixels are invalid for {:?} images", header.pixel_depth, header.image_type);
+        }
+
+        if header.width == 0 || header.height == 0 {
+            bail!("TGA image of {}x{} is empty", header.width, header.height);
+        }
+
+        pixel_count("TGA", header.width as u32, header.height as u32)?;
+
+        // Image ID comes before the color map, which may be present without being used
+        reader.read_bytes(header.id_length as usize)?;
+
+        if color_map_type == 1 {
+            let entry_size: usize = (header.color_map_entry_size as usize).div_ceil(8);
+            let entries: Vec<u8> = reader.read_bytes(entry_size * header.color_map_length as usize)?;
+
+            // Entries of unused color maps are skipped whatever their size
+            if header.image_type == TGAImageType::COLORMAPPED {
+                if !matches!(header.color_map_entry_size, 15 | 16 | 24 | 32) {
+                    bail!("TGA color map entries of {} bits are invalid", header.color_map_entry_size);
+                }
+
+                header.color_map = entries
+                    .chunks_exact(entry_size)
+                    .map(|entry| color(little_endian(entry), header.color_map_entry_size, true))
+                    .collect();
+            }
+        }
+
+        if header.image_type == TGAImageType::COLORMAPPED && header.color_map.is_empty() {
+            bail!("Color-mapped TGA image without color map");
+        }
+
+        header.extension = TGAExtension::from_footer(reader)?;
+
+        Ok(header)
+    }
+
+    // Alpha channel is used unless the extension area says otherwise
+    pub fn use_alpha(&self) -> bool {
+        match &self.extension {
+            Some(extension) => matches!(extension.attributes_type, 3 | 4),
+            None => self.alpha_bits > 0,
+        }
+    }
+
+    pub fn premultiplied(&self) -> bool {
+        self.extension.as_ref().is_some_and(|extension| extension.attributes_type == 4)
+    }
+}
+
+// Extension area of TGA 2.0 files, found from their footer
+#[derive(Clone, Debug, Default)]
+pub struct TGAExtension {
+    pub author: String,
+    pub comments: String,
+    // Month, day, year, hour, minute and second
+    pub timestamp: [u16; 6],
+    pub job: String,
+    pub software: String,
+    pub key_color: u32,
+    pub aspect_ratio: (u16, u16),
+    pub gamma: (u16, u16),
+    // 0 for no alpha, 1 and 2 for alpha to ignore, 3 for alpha, 4 for premultiplied alpha
+    pub attributes_type: u8,
+}
+
+impl TGAExtension {
+    // Stream is left at the start of the image data
+    fn from_footer(reader: &mut TgaBitReader) -> Result<Option<Self>> {
+        let position: u64 = reader.position()?;
+        let length: u64 = reader.length()?;
+
+        if length < position + FOOTER_SIZE {
+            return Ok(None);
+        }
+
+        reader.seek(length - FOOTER_SIZE)?;
+        let extension_offset: u32 = reader.read_double()?.swap_bytes();
+        reader.read_double()?;
+        let signature: Vec<u8> = reader.read_bytes(FOOTER_SIGNATURE.len())?;
+
+        let extension: Option<Self> = if signature == FOOTER_SIGNATURE && extension_offset != 0 && extension_offset as u64 + EXTENSION_SIZE as u64 <= length {
+            reader.seek(extension_offset as u64)?;
+            Self::from_binary(reader)?
+        } else {
+            None
+        };
+
+        reader.seek(position)?;
+
+        Ok(extension)
+    }
+
+    fn from_binary(reader: &mut TgaBitReader) -> Result<Option<Self>> {
+        let mut extension: TGAExtension = TGAExtension::default();
+
+        if reader.read_word()?.swap_bytes() < EXTENSION_SIZE {
+            return Ok(None);
+        }
+
+        extension.author = read_string(reader, 41)?;
+        extension.comments = read_string(reader, 324)?;
+
+        for value in extension.timestamp.iter_mut() {
+            *value = reader.read_word()?.swap_bytes();
+        }
+
+        extension.job = read_string(reader, 41)?;
+        // Job time
+        reader.read_bytes(6)?;
+        extension.software = read_string(reader, 41)?;
+        // Software version
+        reader.read_bytes(3)?;
+        extension.key_color = reader.read_double()?.swap_bytes();
+        extension.aspect_ratio = (reader.read_word()?.swap_bytes(), reader.read_word()?.swap_bytes());
+        extension.gamma = (reader.read_word()?.swap_bytes(), reader.read_word()?.swap_bytes());
+        // Color correction, postage stamp and scan line offsets
+        reader.read_bytes(12)?;
+        extension.attributes_type = reader.read_byte()?;
+
+        Ok(Some(extension))
+    }
+}
+
+// Null terminated ASCII field of fixed size
+fn read_string(reader: &mut TgaBitReader, length: usize) -> Result<String> {
+    let bytes: Vec<u8> = reader.read_bytes(length)?;
+    let end: usize = bytes.iter().position(|&byte| byte == 0).unwrap_or(length);
+
+    Ok(String::from_utf8_lossy(&bytes[..end]).trim_end().to_owned())
+}
+
+pub fn little_endian(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0, |value, &byte| (value << 8) | byte as u32)
+}
+
+// Color of a true-color pixel or color map entry, 16 bits ones holding an alpha bit
+pub fn color(value: u32, depth: u8, alpha: bool) -> (u8, u8, u8, u8) {
+    match depth {
+        15 | 16 => {
+            let channel = |shift: u32| (((value >> shift) & 0x1F) * 255 / 31) as u8;
+            let a: u8 = if depth == 16 && alpha && value & 0x8000 == 0 { 0 } else { u8::MAX };
+
+            (channel(10), channel(5), channel(0), a)
+        },
+        24 => ((value >> 16) as u8, (value >> 8) as u8, value as u8, u8::MAX),
+        _ => ((value >> 16) as u8, (value >> 8) as u8, value as u8, (value >> 24) as u8),
+    }
+}
+
+impl fmt::Display for TGAHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "image_type: {:?}\n", self.image_type)?;
+        write!(f, "rle: {}\n", self.rle)?;
+        write!(f, "color_map: {} entries of {} bits from {}\n", self.color_map_length, self.color_map_entry_size, self.color_map_first_entry)?;
+        write!(f, "origin: ({}, {})\n", self.x_origin, self.y_origin)?;
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "pixel_depth: {}\n", self.pixel_depth)?;
+        write!(f, "alpha_bits: {}\n", self.alpha_bits)?;
+        write!(f, "right_to_left: {}\n", self.right_to_left)?;
+        write!(f, "top_to_bottom: {}\n", self.top_to_bottom)?;
+
+        match &self.extension {
+            Some(extension) => write!(f, "Extension:\n{extension}"),
+            None => write!(f, "extension: none\n"),
+        }
+    }
+}
+
+impl fmt::Display for TGAExtension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [month, day, year, hour, minute, second]: [u16; 6] = self.timestamp;
+
+        write!(f, "author: {}\n", self.author)?;
+        write!(f, "comments: {}\n", self.comments)?;
+        write!(f, "timestamp: {year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}\n")?;
+        write!(f, "job: {}\n", self.job)?;
+        write!(f, "software: {}\n", self.software)?;
+        write!(f, "key_color: {:08X}\n", self.key_color)?;
+        write!(f, "aspect_ratio: {}:{}\n", self.aspect_ratio.0, self.aspect_ratio.1)?;
+        write!(f, "gamma: {}/{}\n", self.gamma.0, self.gamma.1)?;
+        write!(f, "attributes_type: {}\n", self.attributes_type)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use header::{color, little_endian, TGAHeader, TGAImageType};
+use tga_bit_reader::TgaBitReader;
+use super::bmp::BMP;
+use super::Image;
+
+mod header;
+mod tga_bit_reader;
+
+#[derive(Clone, Debug)]
+pub struct TGA {
+    header: TGAHeader,
+    image: BMP,
+}
+
+impl TGA {
+    // Pixel values in the order of the file, run length encoded packets possibly going over several rows
+    fn read_values(reader: &mut TgaBitReader, header: &TGAHeader) -> Result<Vec<u32>> {
+        let count: usize = header.width as usize * header.height as usize;
+        let pixel_size: usize = (header.pixel_depth as usize).div_ceil(8);
+
+        // Fewest bytes the pixels can be stored in, a run length encoded packet holding up to 128 of them
+        let size: u64 = match header.rle {
+            true => count.div_ceil(128) as u64 * (1 + pixel_size) as u64,
+            false => count as u64 * pixel_size as u64,
+        };
+        let remaining: u64 = reader.length()? - reader.position()?;
+
+        if size > remaining {
+            bail!("TGA pixels need at least {size} bytes, {remaining} found");
+        }
+
+        if !header.rle {
+            let bytes: Vec<u8> = reader.read_bytes(count * pixel_size)?;
+            return Ok(bytes.chunks_exact(pixel_size).map(little_endian).collect());
+        }
+
+        let mut values: Vec<u32> = Vec::with_capacity(count);
+
+        while values.len() < count {
+            // High bit tells a run of one value from a packet of raw values
+            let packet: u8 = reader.read_byte()?;
+            let length: usize = (packet & 0x7F) as usize + 1;
+
+            if packet & 0x80 != 0 {
+                let value: u32 = little_endian(&reader.read_bytes(pixel_size)?);
+                values.extend(std::iter::repeat_n(value, length));
+            } else {
+                let bytes: Vec<u8> = reader.read_bytes(length * pixel_size)?;
+                values.extend(bytes.chunks_exact(pixel_size).map(little_endian));
+            }
+        }
+
+        values.truncate(count);
+        Ok(values)
+    }
+
+    fn pixel(header: &TGAHeader, value: u32) -> Result<(u8, u8, u8, u8)> {
+        match header.image_type {
+            TGAImageType::COLORMAPPED => {
+                let Some(&entry) = (value as usize).checked_sub(header.color_map_first_entry as usize).and_then(|index| header.color_map.get(index)) else {
+                    bail!("Color map index {value} is out of the color map");
+                };
+
+                Ok(entry)
+            },
+            TGAImageType::TRUECOLOR => Ok(color(value, header.pixel_depth, header.alpha_bits > 0)),
+            // 16 bits grayscale pixels are followed by their alpha
+            TGAImageType::GRAYSCALE => {
+                let gray: u8 = value as u8;
+                let a: u8 = if header.pixel_depth == 16 { (value >> 8) as u8 } else { u8::MAX };
+
+                Ok((gray, gray, gray, a))
+            },
+        }
+    }
+}
+
+impl Image for TGA {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: TgaBitReader = TgaBitReader::new(stream);
+        let header: TGAHeader = TGAHeader::from_binary(&mut reader)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let values: Vec<u32> = Self::read_values(&mut reader, &header)?;
+        let mut pixels: Vec<(u8, u8, u8, u8)> = Vec::with_capacity(values.len());
+
+        for &value in values.iter() {
+            pixels.push(Self::pixel(&header, value)?);
+        }
+
+        // Alpha channel left to zero by encoders unaware of it is ignored, as it is when the extension area says so
+        if !header.use_alpha() || pixels.iter().all(|&(_, _, _, a)| a == 0) {
+            pixels.iter_mut().for_each(|pixel| pixel.3 = u8::MAX);
+        } else if header.premultiplied() {
+            for (r, g, b, a) in pixels.iter_mut().filter(|pixel| pixel.3 > 0) {
+                let unpremultiply = |color: u8| (color as u32 * 255 / *a as u32).min(255) as u8;
+                (*r, *g, *b) = (unpremultiply(*r), unpremultiply(*g), unpremultiply(*b));
+            }
+        }
+
+        let (width, height): (usize, usize) = (header.width as usize, header.height as usize);
+
+        // Rows go from bottom to top and columns from left to right unless the descriptor says otherwise
+        let image: BMP = BMP::from_rgba_pixels(header.width as u32, header.height as u32, |x, y| {
+            let row: usize = if header.top_to_bottom { y } else { height - 1 - y };
+            let column: usize = if header.right_to_left { width - 1 - x } else { x };
+
+            pixels[row * width + column]
+        });
+
+        Ok(Self {
+            header,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for TGA {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    // Header without image ID, followed by the color map and pixels
+    fn file(color_map: (u8, u16, u8), image_type: u8, (width, height): (u16, u16), depth: u8, descriptor: u8, data: &[u8]) -> Vec<u8> {
+        let (color_map_type, length, entry_size): (u8, u16, u8) = color_map;
+        let mut bytes: Vec<u8> = vec![0, color_map_type, image_type, 0, 0];
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes.push(entry_size);
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&[depth, descriptor]);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        Ok(TGA::from_stream(BufReader::new(source), false)?.image)
+    }
+
+    fn colors(image: &BMP) -> Vec<(u8, u8, u8)> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| image.pixel_color(x, y))).collect()
+    }
+
+    #[test]
+    fn truecolor_rows_go_from_bottom_to_top() {
+        let data: [u8; 12] = [0, 0, 255, 0, 255, 0, 255, 0, 0, 1, 2, 3];
+        let image: BMP = decode(file((0, 0, 0), 2, (2, 2), 24, 0, &data)).unwrap();
+
+        assert_eq!(colors(&image), [(0, 0, 255), (3, 2, 1), (255, 0, 0), (0, 255, 0)]);
+    }
+
+    #[test]
+    fn run_length_packets_go_over_rows() {
+        // Run of 5 pixels then a raw packet of 1, rows from top to bottom
+        let data: [u8; 4] = [0x84, 10, 0x00, 20];
+        let image: BMP = decode(file((0, 0, 0), 11, (3, 2), 8, 0x20, &data)).unwrap();
+        let grays: Vec<u8> = colors(&image).iter().map(|&(r, _, _)| r).collect();
+
+        assert_eq!(grays, [10, 10, 10, 10, 10, 20]);
+    }
+
+    #[test]
+    fn columns_may_go_from_right_to_left() {
+        let data: [u8; 2] = [10, 20];
+        let image: BMP = decode(file((0, 0, 0), 3, (2, 1), 8, 0x30, &data)).unwrap();
+
+        assert_eq!(colors(&image), [(20, 20, 20), (10, 10, 10)]);
+    }
+
+    #[test]
+    fn color_mapped_pixels_use_the_color_map() {
+        let data: [u8; 8] = [255, 0, 0, 0, 0, 255, 1, 0];
+        let image: BMP = decode(file((1, 2, 24), 1, (2, 1), 8, 0x20, &data)).unwrap();
+
+        assert_eq!(colors(&image), [(255, 0, 0), (0, 0, 255)]);
+    }
+
+    #[test]
+    fn unused_color_maps_are_skipped() {
+        let image: BMP = decode(file((1, 3, 0), 3, (1, 1), 8, 0, &[42])).unwrap();
+
+        assert_eq!(colors(&image), [(42, 42, 42)]);
+    }
+
+    #[test]
+    fn invalid_color_map_entries_are_rejected() {
+        let error: String = decode(file((1, 3, 0), 1, (1, 1), 8, 0, &[0])).unwrap_err().to_string();
+
+        assert!(error.contains("color map entries of 0 bits are invalid"), "{error}");
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode(file((0, 0, 0), 2, (u16::MAX, u16::MAX), 24, 0, &[])).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn truncated_pixels_are_rejected() {
+        let error: String = decode(file((0, 0, 0), 2, (2, 2), 24, 0, &[0; 3])).unwrap_err().to_string();
+
+        assert!(error.contains("TGA pixels need at least 12 bytes"), "{error}");
+    }
+}
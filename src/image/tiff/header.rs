@@ -0,0 +1,410 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use crate::image::pixel_count;
+use super::tiff_bit_reader::TiffBitReader;
+
+const MAGIC: u16 = 42;
+const BIGTIFF_MAGIC: u16 = 43;
+
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const FILL_ORDER: u16 = 266;
+const STRIP_OFFSETS: u16 = 273;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const ROWS_PER_STRIP: u16 = 278;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const PLANAR_CONFIGURATION: u16 = 284;
+const PREDICTOR: u16 = 317;
+const COLOR_MAP: u16 = 320;
+const TILE_WIDTH: u16 = 322;
+const TILE_LENGTH: u16 = 323;
+const TILE_OFFSETS: u16 = 324;
+const TILE_BYTE_COUNTS: u16 = 325;
+const INK_SET: u16 = 332;
+const EXTRA_SAMPLES: u16 = 338;
+const SAMPLE_FORMAT: u16 = 339;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ByteOrder {
+    // "II", little endian
+    #[default]
+    INTEL,
+    // "MM", big endian
+    MOTOROLA,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Compression {
+    #[default]
+    NONE,
+    LZW,
+    // Zlib stream, under its Adobe code or the older one
+    DEFLATE,
+    PACKBITS,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Photometric {
+    // Bilevel and grayscale images, zero being white
+    WHITEISZERO,
+    // Bilevel and grayscale images, zero being black
+    #[default]
+    BLACKISZERO,
+    RGB,
+    PALETTE,
+    // Separated images of cyan, magenta, yellow and black inks
+    CMYK,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Alpha {
+    #[default]
+    NONE,
+    // Colors premultiplied by the alpha
+    ASSOCIATED,
+    UNASSOCIATED,
+}
+
+// Byte order and directories of a file, one for each page
+#[derive(Clone, Debug, Default)]
+pub struct TIFFHeader {
+    pub byte_order: ByteOrder,
+    pub directory_offsets: Vec<u32>,
+}
+
+impl TIFFHeader {
+    pub fn from_binary(reader: &mut TiffBitReader) -> Result<Self> {
+        reader.byte_order = match &reader.read_bytes(2)?[..] {
+            b"II" => ByteOrder::INTEL,
+            b"MM" => ByteOrder::MOTOROLA,
+            _ => bail!("Not a TIFF file"),
+        };
+
+        match reader.read_short()? {
+            MAGIC => (),
+            BIGTIFF_MAGIC => bail!("BigTIFF files not supported"),
+            value => bail!("TIFF magic number {value} is invalid"),
+        }
+
+        let length: u64 = reader.length()?;
+        let mut directory_offsets: Vec<u32> = Vec::new();
+        let mut offset: u32 = reader.read_long()?;
+
+        // Directories are chained by the offset following their entries, the chain stopping at its first broken link
+        while offset != 0 && !directory_offsets.contains(&offset) && offset as u64 + 2 <= length {
+            directory_offsets.push(offset);
+            reader.seek(offset as u64)?;
+            let count: u16 = reader.read_short()?;
+            reader.seek(offset as u64 + 2 + 12 * count as u64)?;
+            offset = reader.read_long().unwrap_or(0);
+        }
+
+        if directory_offsets.is_empty() {
+            bail!("TIFF file has no image directory");
+        }
+
+        Ok(Self {
+            byte_order: reader.byte_order,
+            directory_offsets,
+        })
+    }
+}
+
+// Image of a directory, as far as decoding its pixels goes
+#[derive(Clone, Debug, Default)]
+pub struct TIFFPage {
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_sample: u16,
+    pub samples_per_pixel: u16,
+    pub compression: Compression,
+    pub photometric: Photometric,
+    pub alpha: Alpha,
+    // Bits of each byte stored from the least significant one
+    pub reversed_bits: bool,
+    // Each sample in its own strips or tiles, instead of the samples of a pixel following each other
+    pub planar: bool,
+    // Samples stored as differences with the ones of the pixel on their left
+    pub predictor: bool,
+    // Red, then green, then blue values of 16 bits
+    pub color_map: Vec<u16>,
+    pub tiled: bool,
+    // Strips are as wide as the image
+    pub chunk_width: u32,
+    pub chunk_height: u32,
+    pub offsets: Vec<u32>,
+    pub byte_counts: Vec<u32>,
+}
+
+impl TIFFPage {
+    pub fn from_binary(reader: &mut TiffBitReader, offset: u32) -> Result<Self> {
+        let fields: BTreeMap<u16, Vec<u32>> = read_fields(reader, offset)?;
+        let field = |tag: u16| fields.get(&tag).and_then(|values| values.first()).copied();
+
+        let mut page: TIFFPage = TIFFPage::default();
+
+        let (Some(width), Some(height)) = (field(IMAGE_WIDTH), field(IMAGE_LENGTH)) else {
+            bail!("TIFF image has no dimensions");
+        };
+
+        if width == 0 || height == 0 {
+            bail!("TIFF image of {width}x{height} is empty");
+        }
+
+        pixel_count("TIFF", width, height)?;
+
+        (page.width, page.height) = (width, height);
+        page.samples_per_pixel = field(SAMPLES_PER_PIXEL).unwrap_or(1) as u16;
+
+        let bits_per_sample: &[u32] = fields.get(&BITS_PER_SAMPLE).filter(|bits| !bits.is_empty()).map_or(&[1], Vec::as_slice);
+
+        if bits_per_sample.iter().any(|&bits| bits != bits_per_sample[0]) {
+            bail!("TIFF samples of different sizes not supported");
+        }
+
+        page.bits_per_sample = bits_per_sample[0] as u16;
+
+        if let Some(format) = fields.get(&SAMPLE_FORMAT).and_then(|formats| formats.iter().find(|&&format| format != 1)) {
+            bail!("TIFF sample format {format} not supported");
+        }
+
+        page.compression = match field(COMPRESSION).unwrap_or(1) {
+            1 => Compression::NONE,
+            5 => Compression::LZW,
+            8 | 32946 => Compression::DEFLATE,
+            32773 => Compression::PACKBITS,
+            value => bail!("TIFF compression {value} not supported"),
+        };
+
+        // Readers guess the interpretation of images lacking it from their samples
+        let default_photometric: u32 = if page.samples_per_pixel >= 3 { 2 } else { 1 };
+
+        page.photometric = match field(PHOTOMETRIC_INTERPRETATION).unwrap_or(default_photometric) {
+            0 => Photometric::WHITEISZERO,
+            1 => Photometric::BLACKISZERO,
+            2 => Photometric::RGB,
+            3 => Photometric::PALETTE,
+            5 if field(INK_SET).unwrap_or(1) == 1 => Photometric::CMYK,
+            5 => bail!("TIFF separated images of other inks than CMYK not supported"),
+            value => bail!("TIFF photometric interpretation {value} not supported"),
+        };
+
+        // Unspecified extra sample of images with more samples than RGB is taken as alpha, as libtiff does
+        page.alpha = match fields.get(&EXTRA_SAMPLES).and_then(|extra| extra.first()) {
+            Some(1) => Alpha::ASSOCIATED,
+            Some(2) => Alpha::UNASSOCIATED,
+            Some(0) if page.samples_per_pixel > 3 => Alpha::ASSOCIATED,
+            _ => Alpha::NONE,
+        };
+
+        if (page.samples_per_pixel as usize) < page.used_samples() {
+            bail!("TIFF {:?} image of {} samples per pixel is invalid", page.photometric, page.samples_per_pixel);
+        }
+
+        let valid_bits: bool = match page.photometric {
+            Photometric::WHITEISZERO | Photometric::BLACKISZERO => matches!(page.bits_per_sample, 1 | 2 | 4 | 8 | 16),
+            Photometric::PALETTE => matches!(page.bits_per_sample, 1 | 2 | 4 | 8),
+            Photometric::RGB | Photometric::CMYK => matches!(page.bits_per_sample, 8 | 16),
+        };
+
+        if !valid_bits {
+            bail!("{} bits samples not supported for TIFF {:?} images", page.bits_per_sample, page.photometric);
+        }
+
+        if page.photometric == Photometric::PALETTE {
+            page.color_map = fields.get(&COLOR_MAP).map(|map| map.iter().map(|&value| value as u16).collect()).unwrap_or_default();
+
+            if page.color_map.len() < 3 << page.bits_per_sample {
+                bail!("TIFF palette image has a color map of {} values instead of {}", page.color_map.len(), 3 << page.bits_per_sample);
+            }
+
+            // Old writers stored 8 bits values in the color map
+            if page.color_map.iter().all(|&value| value < 256) {
+                page.color_map.iter_mut().for_each(|value| *value *= 257);
+            }
+        }
+
+        page.reversed_bits = field(FILL_ORDER) == Some(2);
+        page.planar = field(PLANAR_CONFIGURATION) == Some(2);
+
+        page.predictor = match field(PREDICTOR).unwrap_or(1) {
+            1 => false,
+            2 => true,
+            value => bail!("TIFF predictor {value} not supported"),
+        };
+
+        if page.predictor && !matches!(page.bits_per_sample, 8 | 16) {
+            bail!("TIFF horizontal predictor of {} bits samples not supported", page.bits_per_sample);
+        }
+
+        page.tiled = fields.contains_key(&TILE_WIDTH);
+
+        let (offsets_tag, byte_counts_tag): (u16, u16) = if page.tiled {
+            (page.chunk_width, page.chunk_height) = (field(TILE_WIDTH).unwrap_or(0), field(TILE_LENGTH).unwrap_or(0));
+            (TILE_OFFSETS, TILE_BYTE_COUNTS)
+        } else {
+            (page.chunk_width, page.chunk_height) = (width, field(ROWS_PER_STRIP).unwrap_or(u32::MAX).min(height));
+            (STRIP_OFFSETS, STRIP_BYTE_COUNTS)
+        };
+
+        if page.chunk_width == 0 || page.chunk_height == 0 {
+            bail!("TIFF {}s of {}x{} are empty", page.chunk_name(), page.chunk_width, page.chunk_height);
+        }
+
+        // Tiles may go past the image, but not by more than the pixels allowed
+        pixel_count("TIFF", page.chunk_width, page.chunk_height)?;
+
+        let count: usize = page.chunk_count();
+        page.offsets = fields.get(&offsets_tag).cloned().unwrap_or_default();
+
+        // Offsets come from the file, bounding the byte counts made up for the missing ones
+        if page.offsets.len() < count {
+            bail!("TIFF image has {} {} offsets instead of {count}", page.offsets.len(), page.chunk_name());
+        }
+
+        // Byte counts are only left out by writers of uncompressed images
+        page.byte_counts = match fields.get(&byte_counts_tag) {
+            Some(byte_counts) => byte_counts.clone(),
+            None if page.compression == Compression::NONE => vec![page.chunk_size() as u32; count],
+            None => bail!("TIFF image has no {} byte counts", page.chunk_name()),
+        };
+
+        if page.byte_counts.len() < count {
+            bail!("TIFF image has {} {} byte counts instead of {count}", page.byte_counts.len(), page.chunk_name());
+        }
+
+        Ok(page)
+    }
+
+    // Samples of a pixel its color and alpha are made of, any other extra sample being ignored
+    pub fn used_samples(&self) -> usize {
+        let color_channels: usize = match self.photometric {
+            Photometric::RGB => 3,
+            Photometric::CMYK => 4,
+            _ => 1,
+        };
+
+        color_channels + if self.alpha == Alpha::NONE { 0 } else { 1 }
+    }
+
+    pub fn chunk_name(&self) -> &'static str {
+        if self.tiled { "tile" } else { "strip" }
+    }
+
+    // Samples of a pixel in each strip or tile
+    pub fn chunk_samples(&self) -> usize {
+        if self.planar { 1 } else { self.samples_per_pixel as usize }
+    }
+
+    // Rows of strips and tiles start on a byte
+    pub fn row_size(&self) -> usize {
+        (self.chunk_width as usize * self.chunk_samples() * self.bits_per_sample as usize).div_ceil(8)
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.row_size() * self.chunk_height as usize
+    }
+
+    pub fn chunks_across(&self) -> usize {
+        (self.width as usize).div_ceil(self.chunk_width as usize)
+    }
+
+    pub fn chunks_down(&self) -> usize {
+        (self.height as usize).div_ceil(self.chunk_height as usize)
+    }
+
+    // Chunks of a plane are in raster order, the planes of planar images following each other
+    pub fn chunk_count(&self) -> usize {
+        let planes: usize = if self.planar { self.samples_per_pixel as usize } else { 1 };
+
+        self.chunks_across() * self.chunks_down() * planes
+    }
+}
+
+// Integer values of the fields of a directory, fields of other types being left out
+fn read_fields(reader: &mut TiffBitReader, offset: u32) -> Result<BTreeMap<u16, Vec<u32>>> {
+    let length: u64 = reader.length()?;
+    reader.seek(offset as u64)?;
+    let count: u16 = reader.read_short()?;
+    let mut entries: Vec<(u16, u16, u32, Vec<u8>)> = Vec::with_capacity(count as usize);
+
+    for _ in 0..count {
+        let tag: u16 = reader.read_short()?;
+        let field_type: u16 = reader.read_short()?;
+        let value_count: u32 = reader.read_long()?;
+        let value: Vec<u8> = reader.read_bytes(4)?;
+
+        entries.push((tag, field_type, value_count, value));
+    }
+
+    let mut fields: BTreeMap<u16, Vec<u32>> = BTreeMap::new();
+
+    for (tag, field_type, value_count, value) in entries {
+        // BYTE, SBYTE and UNDEFINED, SHORT and SSHORT, LONG, SLONG and IFD
+        let size: usize = match field_type {
+            1 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 13 => 4,
+            _ => continue,
+        };
+
+        let total: u64 = size as u64 * value_count as u64;
+
+        // Values that don't fit in the entry are stored at the offset it holds
+        let bytes: Vec<u8> = if total <= 4 {
+            value[..total as usize].to_vec()
+        } else {
+            let value_offset: u64 = read_value(&value, reader.byte_order) as u64;
+
+            if value_offset + total > length {
+                bail!("TIFF field {tag} of {value_count} values is out of the file");
+            }
+
+            reader.seek(value_offset)?;
+            reader.read_bytes(total as usize)?
+        };
+
+        fields.insert(tag, bytes.chunks_exact(size).map(|bytes| read_value(bytes, reader.byte_order)).collect());
+    }
+
+    Ok(fields)
+}
+
+// Value of 1, 2 or 4 bytes in the byte order of the file
+fn read_value(bytes: &[u8], byte_order: ByteOrder) -> u32 {
+    match byte_order {
+        ByteOrder::INTEL => bytes.iter().rev().fold(0, |value, &byte| (value << 8) | byte as u32),
+        ByteOrder::MOTOROLA => bytes.iter().fold(0, |value, &byte| (value << 8) | byte as u32),
+    }
+}
+
+impl fmt::Display for TIFFHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "byte_order: {:?}\n", self.byte_order)?;
+        write!(f, "pages: {}\n", self.directory_offsets.len())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for TIFFPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "bits_per_sample: {}\n", self.bits_per_sample)?;
+        write!(f, "samples_per_pixel: {}\n", self.samples_per_pixel)?;
+        write!(f, "compression: {:?}\n", self.compression)?;
+        write!(f, "photometric: {:?}\n", self.photometric)?;
+        write!(f, "alpha: {:?}\n", self.alpha)?;
+        write!(f, "reversed_bits: {}\n", self.reversed_bits)?;
+        write!(f, "planar: {}\n", self.planar)?;
+        write!(f, "predictor: {}\n", self.predictor)?;
+        write!(f, "{}s: {} of {}x{}\n", self.chunk_name(), self.chunk_count(), self.chunk_width, self.chunk_height)?;
+        Ok(())
+    }
+}
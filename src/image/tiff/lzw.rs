@@ -0,0 +1,154 @@
+use anyhow::{bail, Result};
+
+const CLEAR_CODE: u16 = 256;
+const END_CODE: u16 = 257;
+const MIN_CODE_SIZE: u32 = 9;
+const MAX_CODE_SIZE: u32 = 12;
+const MAX_CODES: usize = 1 << MAX_CODE_SIZE;
+
+// Codes of variable size, packed from the most significant bit of each byte
+struct CodeReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl CodeReader<'_> {
+    fn read_code(&mut self, size: u32) -> Option<u16> {
+        if self.position + size as usize > self.data.len() * 8 {
+            return None;
+        }
+
+        let mut code: u16 = 0;
+
+        for i in self.position..self.position + size as usize {
+            let bit: u8 = (self.data[i / 8] >> (7 - i % 8)) & 1;
+            code = (code << 1) | bit as u16;
+        }
+
+        self.position += size as usize;
+        Some(code)
+    }
+}
+
+// Bytes of a strip or tile, the code size growing one code earlier than in GIF files
+pub fn decode(data: &[u8], size: usize) -> Result<Vec<u8>> {
+    // Old LZW of the first TIFF writers packs codes from the least significant bit
+    if data.len() >= 2 && data[0] == 0 && data[1] & 0x01 != 0 {
+        bail!("TIFF old-style LZW not supported");
+    }
+
+    // Strings of the table are a part of the output, made of the previous string and the byte following it
+    let mut strings: Vec<(usize, usize)> = Vec::with_capacity(MAX_CODES);
+
+    let mut reader: CodeReader = CodeReader {
+        data,
+        position: 0,
+    };
+    let mut output: Vec<u8> = Vec::new();
+    let mut code_size: u32 = MIN_CODE_SIZE;
+    let mut previous: Option<(usize, usize)> = None;
+
+    while output.len() < size {
+        let Some(code) = reader.read_code(code_size) else {
+            break;
+        };
+
+        if code == CLEAR_CODE {
+            strings.clear();
+            code_size = MIN_CODE_SIZE;
+            previous = None;
+            continue;
+        }
+
+        if code == END_CODE {
+            break;
+        }
+
+        let next_code: usize = strings.len() + END_CODE as usize + 1;
+        let start: usize = output.len();
+
+        match (code as usize, previous) {
+            (code, _) if code < CLEAR_CODE as usize => output.push(code as u8),
+            (code, _) if code < next_code => {
+                let (offset, length): (usize, usize) = strings[code - END_CODE as usize - 1];
+                output.extend_from_within(offset..offset + length);
+            },
+            // Code not yet in the table is the previous string followed by its own first byte
+            (code, Some((offset, length))) if code == next_code => {
+                output.extend_from_within(offset..offset + length);
+                output.push(output[offset]);
+            },
+            (code, _) => bail!("LZW code {code} is out of the table of {next_code} codes"),
+        }
+
+        if let Some((offset, length)) = previous {
+            // Table stops growing once full, until the next clear code
+            if next_code < MAX_CODES {
+                strings.push((offset, length + 1));
+
+                if next_code + 2 == 1 << code_size && code_size < MAX_CODE_SIZE {
+                    code_size += 1;
+                }
+            }
+        }
+
+        previous = Some((start, output.len() - start));
+    }
+
+    output.truncate(size);
+    Ok(output)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Codes of 9 bits packed from the most significant bit
+    pub(crate) fn codes(codes: &[u16]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![0; (codes.len() * 9).div_ceil(8)];
+
+        for (index, &code) in codes.iter().enumerate() {
+            for bit in 0..9 {
+                let position: usize = index * 9 + bit;
+                bytes[position / 8] |= (((code >> (8 - bit)) & 1) as u8) << (7 - position % 8);
+            }
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn codes_not_yet_in_the_table_repeat_the_previous_string() {
+        let data: Vec<u8> = codes(&[CLEAR_CODE, 97, 258, 259, END_CODE]);
+
+        assert_eq!(decode(&data, 100).unwrap(), b"aaaaaa");
+    }
+
+    #[test]
+    fn strings_are_made_of_the_previous_one_and_a_byte() {
+        let data: Vec<u8> = codes(&[CLEAR_CODE, 1, 2, 258, 259, CLEAR_CODE, 3, 3, END_CODE]);
+
+        assert_eq!(decode(&data, 100).unwrap(), [1, 2, 1, 2, 2, 1, 3, 3]);
+    }
+
+    #[test]
+    fn output_stops_at_the_size_expected() {
+        let data: Vec<u8> = codes(&[CLEAR_CODE, 97, 258, 259, 260, END_CODE]);
+
+        assert_eq!(decode(&data, 4).unwrap(), b"aaaa");
+    }
+
+    #[test]
+    fn codes_out_of_the_table_are_rejected() {
+        let error: String = decode(&codes(&[CLEAR_CODE, 97, 300]), 100).unwrap_err().to_string();
+
+        assert!(error.contains("LZW code 300 is out of the table of 258 codes"), "{error}");
+    }
+
+    #[test]
+    fn old_style_codes_are_rejected() {
+        let error: String = decode(&[0x00, 0x01, 0x02], 100).unwrap_err().to_string();
+
+        assert!(error.contains("old-style LZW"), "{error}");
+    }
+}
@@ -0,0 +1,396 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use header::{Alpha, ByteOrder, Compression, Photometric, TIFFHeader, TIFFPage};
+use tiff_bit_reader::TiffBitReader;
+use super::bmp::BMP;
+use super::inflate::zlib_decompress;
+use super::{DecodeOptions, Image};
+
+mod header;
+mod lzw;
+mod tiff_bit_reader;
+
+#[derive(Clone, Debug)]
+pub struct TIFF {
+    header: TIFFHeader,
+    page_index: usize,
+    page: TIFFPage,
+    image: BMP,
+}
+
+impl TIFF {
+    pub fn from_stream_with(stream: Stream, options: DecodeOptions, debug: bool) -> Result<Self> {
+        let mut reader: TiffBitReader = TiffBitReader::new(stream);
+        let header: TIFFHeader = TIFFHeader::from_binary(&mut reader)?;
+        let pages: usize = header.directory_offsets.len();
+
+        let page_index: usize = match options.entry {
+            Some(index) if index >= pages => bail!("No page {index}, the file has {pages} pages"),
+            Some(index) => index,
+            None => 0,
+        };
+
+        let page: TIFFPage = TIFFPage::from_binary(&mut reader, header.directory_offsets[page_index])?;
+
+        if debug {
+            println!("Header:\n{header}Page {page_index}:\n{page}");
+        }
+
+        let samples: Vec<u16> = Self::read_samples(&mut reader, &page)?;
+        let (width, used_samples): (usize, usize) = (page.width as usize, page.used_samples());
+
+        let image: BMP = BMP::from_rgba_pixels(page.width, page.height, |x, y| {
+            let start: usize = (y * width + x) * used_samples;
+            pixel(&page, &samples[start..start + used_samples])
+        });
+
+        Ok(Self {
+            header,
+            page_index,
+            page,
+            image,
+        })
+    }
+
+    // Samples of the pixels in raster order, the used ones of a pixel following each other
+    fn read_samples(reader: &mut TiffBitReader, page: &TIFFPage) -> Result<Vec<u16>> {
+        let (width, height): (usize, usize) = (page.width as usize, page.height as usize);
+        let (chunk_width, chunk_height): (usize, usize) = (page.chunk_width as usize, page.chunk_height as usize);
+        let used_samples: usize = page.used_samples();
+        let chunk_samples: usize = page.chunk_samples();
+        let row_size: usize = page.row_size();
+        let length: u64 = reader.length()?;
+
+        let plane = |index: usize| index / (page.chunks_across() * page.chunks_down());
+        let chunk_y = |index: usize| index / page.chunks_across() % page.chunks_down() * chunk_height;
+        // Last strip only holds the rows left, while tiles go past the image
+        let rows = |index: usize| if page.tiled { chunk_height } else { chunk_height.min(height - chunk_y(index)) };
+        // Planes of the extra samples are left alone
+        let chunks: Vec<usize> = (0..page.chunk_count()).filter(|&index| plane(index) < used_samples).collect();
+
+        // Byte counts are checked before the samples are allocated, strips and tiles having to hold their rows once decompressed
+        for &index in chunks.iter() {
+            let (offset, size): (u64, u64) = (page.offsets[index] as u64, page.byte_counts[index] as u64);
+
+            if offset + size > length {
+                bail!("TIFF {} {index} of {size} bytes at {offset} is out of the file", page.chunk_name());
+            }
+
+            if size * max_expansion(page.compression) < (row_size * rows(index)) as u64 {
+                bail!("TIFF {} {index} of {size} bytes can't hold {} rows of {row_size} bytes", page.chunk_name(), rows(index));
+            }
+        }
+
+        let mut samples: Vec<u16> = vec![0; width * height * used_samples];
+
+        for index in chunks {
+            let (plane, chunk_x, chunk_y, rows): (usize, usize, usize, usize) = (plane(index), index % page.chunks_across() * chunk_width, chunk_y(index), rows(index));
+
+            reader.seek(page.offsets[index] as u64)?;
+            let mut data: Vec<u8> = reader.read_bytes(page.byte_counts[index] as usize)?;
+
+            if page.reversed_bits {
+                data.iter_mut().for_each(|byte| *byte = byte.reverse_bits());
+            }
+
+            let bytes: Vec<u8> = decompress(&data, page.compression, row_size * rows)?;
+
+            if bytes.len() < row_size * rows {
+                bail!("TIFF {} {index} holds {} bytes instead of {}", page.chunk_name(), bytes.len(), row_size * rows);
+            }
+
+            for (row, row_bytes) in bytes.chunks_exact(row_size).take(rows.min(height - chunk_y)).enumerate() {
+                let mut values: Vec<u16> = unpack_row(row_bytes, chunk_width * chunk_samples, page.bits_per_sample, reader.byte_order);
+
+                // Differences go across the whole row, samples of a pixel being added to those on their left
+                if page.predictor {
+                    let mask: u32 = (1 << page.bits_per_sample) - 1;
+
+                    for i in chunk_samples..values.len() {
+                        values[i] = ((values[i] as u32 + values[i - chunk_samples] as u32) & mask) as u16;
+                    }
+                }
+
+                let y: usize = chunk_y + row;
+
+                for (column, pixel) in values.chunks_exact(chunk_samples).take(width - chunk_x).enumerate() {
+                    let start: usize = (y * width + chunk_x + column) * used_samples + plane;
+                    let count: usize = chunk_samples.min(used_samples);
+                    samples[start..start + count].copy_from_slice(&pixel[..count]);
+                }
+            }
+        }
+
+        Ok(samples)
+    }
+}
+
+// Most bytes a compressed byte decodes to
+fn max_expansion(compression: Compression) -> u64 {
+    match compression {
+        Compression::NONE => 1,
+        // Run of 128 bytes in 2 bytes
+        Compression::PACKBITS => 64,
+        // Length of 258 bytes and distance of a bit each
+        Compression::DEFLATE => 1032,
+        // Strings of up to 4096 bytes in codes of at least 9 bits
+        Compression::LZW => 4096 * 8 / 9,
+    }
+}
+
+// Bytes of a strip or tile, which may be longer than the size expected
+fn decompress(data: &[u8], compression: Compression, size: usize) -> Result<Vec<u8>> {
+    match compression {
+        Compression::NONE => Ok(data.to_vec()),
+        Compression::LZW => lzw::decode(data, size),
+        Compression::DEFLATE => zlib_decompress(data),
+        Compression::PACKBITS => Ok(unpack_bits(data, size)),
+    }
+}
+
+// Runs of one byte repeated, or of bytes copied, each following its signed length
+fn unpack_bits(data: &[u8], size: usize) -> Vec<u8> {
+    let mut output: Vec<u8> = Vec::new();
+    let mut position: usize = 0;
+
+    while position < data.len() && output.len() < size {
+        let header: i8 = data[position] as i8;
+        position += 1;
+
+        match header {
+            0..=127 => {
+                let end: usize = (position + header as usize + 1).min(data.len());
+                output.extend_from_slice(&data[position..end]);
+                position = end;
+            },
+            // No operation
+            -128 => (),
+            _ => {
+                if let Some(&byte) = data.get(position) {
+                    output.extend(std::iter::repeat_n(byte, (1 - header as isize) as usize));
+                }
+
+                position += 1;
+            },
+        }
+    }
+
+    output
+}
+
+// Samples of a row, packed from the most significant bit, 16 bits ones being in the byte order of the file
+fn unpack_row(bytes: &[u8], count: usize, bits: u16, byte_order: ByteOrder) -> Vec<u16> {
+    match (bits, byte_order) {
+        (8, _) => bytes[..count].iter().map(|&byte| byte as u16).collect(),
+        (16, ByteOrder::INTEL) => bytes.chunks_exact(2).take(count).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect(),
+        (16, ByteOrder::MOTOROLA) => bytes.chunks_exact(2).take(count).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect(),
+        _ => {
+            let bits: usize = bits as usize;
+            let mask: u8 = (1 << bits) - 1;
+
+            (0..count).map(|i| {
+                let position: usize = i * bits;
+                ((bytes[position / 8] >> (8 - bits - position % 8)) & mask) as u16
+            }).collect()
+        },
+    }
+}
+
+// Color of a pixel from its samples, colors and alpha being scaled to 8 bits like libtiff does
+fn pixel(page: &TIFFPage, samples: &[u16]) -> (u8, u8, u8, u8) {
+    let bits: u16 = page.bits_per_sample;
+    let maximum: u32 = (1 << bits) - 1;
+
+    let scale = |value: u16| match bits {
+        16 => ((value as u32 + 128) / 257) as u8,
+        8 => value as u8,
+        _ => (value as u32 * 255 / maximum) as u8,
+    };
+
+    // 16 bits grayscale keeps its upper byte
+    let gray = |value: u16| if bits == 16 { (value >> 8) as u8 } else { scale(value) };
+
+    let (r, g, b, alpha_index): (u8, u8, u8, usize) = match page.photometric {
+        Photometric::WHITEISZERO => {
+            let gray: u8 = u8::MAX - gray(samples[0]);
+            (gray, gray, gray, 1)
+        },
+        Photometric::BLACKISZERO => {
+            let gray: u8 = gray(samples[0]);
+            (gray, gray, gray, 1)
+        },
+        Photometric::RGB => (scale(samples[0]), scale(samples[1]), scale(samples[2]), 3),
+        Photometric::PALETTE => {
+            let entries: usize = 1 << bits;
+            let index: usize = samples[0] as usize;
+            let color = |value: u16| (value >> 8) as u8;
+
+            (color(page.color_map[index]), color(page.color_map[entries + index]), color(page.color_map[2 * entries + index]), 1)
+        },
+        Photometric::CMYK => {
+            let black: u32 = u8::MAX as u32 - scale(samples[3]) as u32;
+            let ink = |value: u16| (black * (u8::MAX - scale(value)) as u32 / 255) as u8;
+
+            (ink(samples[0]), ink(samples[1]), ink(samples[2]), 4)
+        },
+    };
+
+    match page.alpha {
+        Alpha::NONE => (r, g, b, u8::MAX),
+        Alpha::UNASSOCIATED => (r, g, b, scale(samples[alpha_index])),
+        Alpha::ASSOCIATED => {
+            let a: u8 = scale(samples[alpha_index]);
+            let unpremultiply = |color: u8| if a == 0 { 0 } else { (color as u32 * 255 / a as u32).min(255) as u8 };
+
+            (unpremultiply(r), unpremultiply(g), unpremultiply(b), a)
+        },
+    }
+}
+
+impl Image for TIFF {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        Self::from_stream_with(stream, DecodeOptions::default(), debug)
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for TIFF {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)?;
+        write!(f, "Page {}:\n{}", self.page_index, self.page)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::lzw::tests::codes;
+    use super::*;
+
+    // Little endian file of the data followed by a directory of LONG fields, given by tag
+    fn file(data: &[u8], fields: &[(u16, &[u32])]) -> Vec<u8> {
+        let directory: usize = 8 + data.len().next_multiple_of(2);
+        let mut values: usize = directory + 2 + fields.len() * 12 + 4;
+
+        let mut bytes: Vec<u8> = b"II".to_vec();
+        bytes.extend_from_slice(&42u16.to_le_bytes());
+        bytes.extend_from_slice(&(directory as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes.resize(directory, 0);
+        bytes.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+
+        for (tag, field) in fields {
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&4u16.to_le_bytes());
+            bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+
+            // Several values are stored after the directory
+            let value: u32 = if field.len() == 1 { field[0] } else { values as u32 };
+            bytes.extend_from_slice(&value.to_le_bytes());
+            values += if field.len() == 1 { 0 } else { field.len() * 4 };
+        }
+
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+
+        for (_, field) in fields.iter().filter(|(_, field)| field.len() > 1) {
+            field.iter().for_each(|value| bytes.extend_from_slice(&value.to_le_bytes()));
+        }
+
+        bytes
+    }
+
+    // Grayscale image of 8 bits samples, as strips of the given rows, compression and byte counts
+    fn gray(data: &[u8], (width, height): (u32, u32), compression: u32, strips: (&[u32], &[u32])) -> Vec<u8> {
+        let (offsets, byte_counts): (&[u32], &[u32]) = strips;
+        let rows: u32 = height.div_ceil(offsets.len() as u32);
+
+        file(data, &[(256, &[width]), (257, &[height]), (258, &[8]), (259, &[compression]), (262, &[1]), (273, offsets), (278, &[rows]), (279, byte_counts)])
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<BMP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        Ok(TIFF::from_stream_with(BufReader::new(source), DecodeOptions::default(), false)?.image)
+    }
+
+    fn grays(image: &BMP) -> Vec<u8> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| image.pixel_color(x, y).0)).collect()
+    }
+
+    #[test]
+    fn strips_are_read_in_order() {
+        let image: BMP = decode(gray(&[1, 2, 3, 4, 5, 6], (3, 2), 1, (&[8, 11], &[3, 3]))).unwrap();
+
+        assert_eq!(grays(&image), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn lzw_strips_are_decompressed() {
+        let data: Vec<u8> = codes(&[256, 97, 258, 259, 257]);
+        let image: BMP = decode(gray(&data, (3, 2), 5, (&[8], &[data.len() as u32]))).unwrap();
+
+        assert_eq!(grays(&image), [97; 6]);
+    }
+
+    #[test]
+    fn packbits_strips_are_decompressed() {
+        // Run of 3 bytes, no operation, then 3 bytes copied
+        let data: [u8; 7] = [0xFE, 9, 0x80, 0x02, 1, 2, 3];
+        let image: BMP = decode(gray(&data, (3, 2), 32773, (&[8], &[7]))).unwrap();
+
+        assert_eq!(grays(&image), [9, 9, 9, 1, 2, 3]);
+    }
+
+    #[test]
+    fn tiles_go_past_the_image() {
+        let data: Vec<u8> = (0..=255).collect();
+        let fields: [(u16, &[u32]); 8] = [(256, &[2]), (257, &[2]), (258, &[8]), (262, &[1]), (322, &[16]), (323, &[16]), (324, &[8]), (325, &[256])];
+        let image: BMP = decode(file(&data, &fields)).unwrap();
+
+        assert_eq!(grays(&image), [0, 1, 16, 17]);
+    }
+
+    #[test]
+    fn extra_samples_are_ignored() {
+        let fields: [(u16, &[u32]); 8] = [(256, &[2]), (257, &[1]), (258, &[8, 8]), (262, &[1]), (273, &[8]), (277, &[2]), (278, &[1]), (279, &[4])];
+        let image: BMP = decode(file(&[10, 99, 20, 99], &fields)).unwrap();
+
+        assert_eq!(grays(&image), [10, 20]);
+        assert_eq!(image.pixel_alpha(1, 0), u8::MAX);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode(gray(&[0], (100_000, 100_000), 1, (&[8], &[1]))).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn strips_too_short_for_their_rows_are_rejected() {
+        let error: String = decode(gray(&[1, 2, 3, 4], (2, 2), 1, (&[8], &[2]))).unwrap_err().to_string();
+
+        assert!(error.contains("strip 0 of 2 bytes can't hold 2 rows of 2 bytes"), "{error}");
+    }
+
+    #[test]
+    fn tiles_too_short_for_their_rows_are_rejected() {
+        let fields: [(u16, &[u32]); 9] = [(256, &[16]), (257, &[16]), (258, &[8]), (259, &[32773]), (262, &[1]), (322, &[16]), (323, &[1 << 22]), (324, &[8]), (325, &[16])];
+        let error: String = decode(file(&[0; 16], &fields)).unwrap_err().to_string();
+
+        assert!(error.contains("tile 0 of 16 bytes can't hold 4194304 rows of 16 bytes"), "{error}");
+    }
+
+    #[test]
+    fn strips_out_of_the_file_are_rejected() {
+        let error: String = decode(gray(&[1, 2], (2, 1), 1, (&[8], &[1000]))).unwrap_err().to_string();
+
+        assert!(error.contains("strip 0 of 1000 bytes at 8 is out of the file"), "{error}");
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use super::header::ByteOrder;
+
+#[derive(Debug)]
+pub struct TiffBitReader {
+    next_bit: usize,
+    current_byte: u8,
+    stream: Stream,
+    // Set once the header is read
+    pub byte_order: ByteOrder,
+}
+
+impl TiffBitReader {
+    pub fn read_short(&mut self) -> Result<u16> {
+        let value: u16 = self.read_word()?;
+
+        match self.byte_order {
+            ByteOrder::INTEL => Ok(value.swap_bytes()),
+            ByteOrder::MOTOROLA => Ok(value),
+        }
+    }
+
+    pub fn read_long(&mut self) -> Result<u32> {
+        let value: u32 = self.read_double()?;
+
+        match self.byte_order {
+            ByteOrder::INTEL => Ok(value.swap_bytes()),
+            ByteOrder::MOTOROLA => Ok(value),
+        }
+    }
+}
+
+impl BitReader for TiffBitReader {
+    fn new(stream: Stream) -> Self {
+        Self {
+            next_bit: 0,
+            current_byte: 0,
+            stream,
+            byte_order: ByteOrder::INTEL,
+        }
+    }
+
+    fn read_bit(&mut self) -> Result<u8> {
+        if self.next_bit == 0 {
+            self.current_byte = self.read_byte()?;
+        }
+
+        let bit: u8 = (self.current_byte >> (7 - self.next_bit)) & 0x1;
+        self.next_bit += 1;
+        self.next_bit %= 8;
+
+        Ok(bit)
+    }
+
+    fn set_next_bit(&mut self, next_bit: usize) -> () {
+        self.next_bit = next_bit;
+    }
+
+    fn stream(&mut self) -> &mut Stream {
+        &mut self.stream
+    }
+}
@@ -0,0 +1,58 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+// Operator bringing the linear colors of high dynamic range images to the 8 bits of the display
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub enum ToneMapping {
+    // Colors clipped once scaled by the exposure
+    EXPOSURE,
+    // Luminance L mapped to L / (1 + L), keeping highlights from clipping
+    #[default]
+    REINHARD,
+    // Curve fitted to the ACES filmic transform, with a toe and a shoulder
+    FILMIC,
+}
+
+impl ToneMapping {
+    // Color in the sRGB transfer of the display, from a linear color scaled by 2 to the power of the exposure
+    pub fn map(&self, color: (f32, f32, f32), exposure: f32) -> (u8, u8, u8) {
+        let scale: f32 = exposure.exp2();
+        let (r, g, b): (f32, f32, f32) = (color.0 * scale, color.1 * scale, color.2 * scale);
+
+        let (r, g, b): (f32, f32, f32) = match self {
+            ToneMapping::EXPOSURE => (r, g, b),
+            ToneMapping::REINHARD => {
+                let luminance: f32 = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+                if luminance <= 0.0 {
+                    (0.0, 0.0, 0.0)
+                } else {
+                    let ratio: f32 = 1.0 / (1.0 + luminance);
+                    (r * ratio, g * ratio, b * ratio)
+                }
+            },
+            ToneMapping::FILMIC => (filmic(r), filmic(g), filmic(b)),
+        };
+
+        (encode_srgb(r), encode_srgb(g), encode_srgb(b))
+    }
+}
+
+// Krzysztof Narkowicz's fit of the ACES reference rendering transform
+fn filmic(value: f32) -> f32 {
+    let value: f32 = value * 0.6;
+
+    (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+}
+
+fn encode_srgb(value: f32) -> u8 {
+    let value: f32 = value.clamp(0.0, 1.0);
+
+    let encoded: f32 = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded * 255.0).round() as u8
+}
@@ -0,0 +1,112 @@
+use anyhow::{bail, Result};
+
+use super::lossless::{decode_stream, LosslessReader};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaFilter {
+    NONE,
+    HORIZONTAL,
+    VERTICAL,
+    GRADIENT,
+}
+
+// Alpha values of a lossy frame, either raw or as the green channel of a headerless lossless stream
+pub fn decode_alpha(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
+    let Some((&header, payload)) = data.split_first() else {
+        bail!("ALPH chunk is empty");
+    };
+
+    let filter: AlphaFilter = match (header >> 2) & 0x03 {
+        0 => AlphaFilter::NONE,
+        1 => AlphaFilter::HORIZONTAL,
+        2 => AlphaFilter::VERTICAL,
+        _ => AlphaFilter::GRADIENT,
+    };
+
+    let mut alpha: Vec<u8> = match header & 0x03 {
+        0 => {
+            if payload.len() < width * height {
+                bail!("ALPH chunk holds {} values for {width}x{height} pixels", payload.len());
+            }
+
+            payload[..width * height].to_vec()
+        },
+        1 => {
+            let mut reader: LosslessReader = LosslessReader::new(payload);
+            decode_stream(&mut reader, width, height)?.into_iter().map(|pixel| (pixel >> 8) as u8).collect()
+        },
+        value => bail!("ALPH compression method {value} is invalid"),
+    };
+
+    unfilter(&mut alpha, width, height, filter);
+
+    Ok(alpha)
+}
+
+// Values are stored as differences to a prediction from their already decoded neighbours
+fn unfilter(alpha: &mut [u8], width: usize, height: usize, filter: AlphaFilter) {
+    if filter == AlphaFilter::NONE {
+        return;
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            let position: usize = y * width + x;
+
+            // First row is predicted from the left and first column from the top, whatever the filter
+            let prediction: u8 = match (x, y) {
+                (0, 0) => 0,
+                (_, 0) => alpha[position - 1],
+                (0, _) => alpha[position - width],
+                _ => match filter {
+                    AlphaFilter::HORIZONTAL => alpha[position - 1],
+                    AlphaFilter::VERTICAL => alpha[position - width],
+                    _ => {
+                        let (left, top, top_left): (i32, i32, i32) = (alpha[position - 1] as i32, alpha[position - width] as i32, alpha[position - width - 1] as i32);
+                        (left + top - top_left).clamp(0, 255) as u8
+                    },
+                },
+            };
+
+            alpha[position] = alpha[position].wrapping_add(prediction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::lossless::tests::{bits, simple_code};
+    use super::*;
+
+    #[test]
+    fn filters_are_undone() {
+        let data: [u8; 6] = [10, 5, 5, 1, 2, 3];
+
+        for (filter, expected) in [(0x04, [10, 15, 20, 11, 13, 16]), (0x08, [10, 15, 20, 11, 17, 23]), (0x0C, [10, 15, 20, 11, 18, 26])] {
+            let alpha: Vec<u8> = decode_alpha(&[&[filter][..], &data].concat(), 3, 2).unwrap();
+
+            assert_eq!(alpha, expected, "{filter}");
+        }
+    }
+
+    #[test]
+    fn compressed_alpha_is_the_green_channel() {
+        // No transform, color cache nor meta prefix codes
+        let mut fields: Vec<(u32, u32)> = vec![(0, 1), (0, 1), (0, 1)];
+
+        for symbols in [&[77], &[1], &[2], &[3], &[0]] {
+            fields.extend(simple_code(symbols));
+        }
+
+        let alpha: Vec<u8> = decode_alpha(&[&[0x01][..], &bits(&fields)].concat(), 2, 2).unwrap();
+
+        assert_eq!(alpha, [77; 4]);
+    }
+
+    #[test]
+    fn short_raw_alpha_is_rejected() {
+        let error: String = decode_alpha(&[0, 1, 2, 3], 2, 2).unwrap_err().to_string();
+
+        assert!(error.contains("holds 3 values for 2x2 pixels"), "{error}");
+    }
+}
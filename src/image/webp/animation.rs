@@ -0,0 +1,326 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::io::{BufReader, Cursor};
+use std::time::Duration;
+
+use crate::image::bit_reader::{BitReader, Source};
+use crate::image::bmp::BMP;
+use crate::image::pixel_count;
+use super::chunk::Chunk;
+use super::header::{read_u24, VP8XHeader};
+use super::webp_bit_reader::WebpBitReader;
+use super::WEBP;
+
+#[derive(Clone, Debug)]
+pub struct AnimationFrame {
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub width: u32,
+    pub height: u32,
+    pub duration: Duration,
+    // Alpha blended over the canvas, instead of replacing it
+    pub blend: bool,
+    // Area of the frame cleared to transparent once displayed
+    pub dispose: bool,
+    pub has_alpha: bool,
+    // Drawn over a transparent canvas, the previous frames having no effect on it
+    pub key_frame: bool,
+    bitstream: Chunk,
+    alpha: Option<Chunk>,
+}
+
+impl AnimationFrame {
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        let data: &[u8] = &chunk.data;
+
+        if data.len() < 16 {
+            bail!("ANMF chunk of {} bytes is too short", data.len());
+        }
+
+        // Frame data is made of chunks, read like the ones of the file
+        let source: Box<dyn Source> = Box::new(Cursor::new(data[16..].to_vec()));
+        let mut reader: WebpBitReader = WebpBitReader::new(BufReader::new(source));
+        let chunks: Vec<Chunk> = Chunk::read_all(&mut reader, data.len() as u64 - 16)?;
+
+        let Some(position) = chunks.iter().position(|chunk| matches!(&chunk.fourcc, b"VP8 " | b"VP8L")) else {
+            bail!("ANMF chunk has no VP8 or VP8L chunk");
+        };
+
+        let bitstream: Chunk = chunks[position].clone();
+        // Lossless frames carry their own alpha
+        let alpha: Option<Chunk> = match &bitstream.fourcc {
+            b"VP8 " => chunks[..position].iter().rfind(|chunk| &chunk.fourcc == b"ALPH").cloned(),
+            _ => None,
+        };
+
+        // Lossless header hints whether alpha is used, after its 28 bits of dimensions
+        let has_alpha: bool = match &bitstream.fourcc {
+            b"VP8L" => bitstream.data.get(4).is_some_and(|byte| byte & 0x10 != 0),
+            _ => alpha.is_some(),
+        };
+
+        Ok(Self {
+            x_offset: read_u24(&data[0..3]) * 2,
+            y_offset: read_u24(&data[3..6]) * 2,
+            width: read_u24(&data[6..9]) + 1,
+            height: read_u24(&data[9..12]) + 1,
+            duration: Duration::from_millis(read_u24(&data[12..15]) as u64),
+            blend: data[15] & 0x02 == 0,
+            dispose: data[15] & 0x01 != 0,
+            has_alpha,
+            key_frame: false,
+            bitstream,
+            alpha,
+        })
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x_offset as usize..(self.x_offset + self.width) as usize).contains(&x) && (self.y_offset as usize..(self.y_offset + self.height) as usize).contains(&y)
+    }
+
+    fn is_full(&self, width: u32, height: u32) -> bool {
+        (self.width, self.height) == (width, height)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    // Hint only, the canvas starting transparent as browsers display it
+    pub background_color: (u8, u8, u8, u8),
+    // Zero stands for forever
+    pub loop_count: u16,
+    pub frames: Vec<AnimationFrame>,
+    pub canvas: Vec<(u8, u8, u8, u8)>,
+    // Canvas with the last frame drawn disposed of, the next one being drawn over it
+    disposed_canvas: Vec<(u8, u8, u8, u8)>,
+}
+
+impl Animation {
+    pub fn from_chunks(extended: &VP8XHeader, chunks: &[Chunk]) -> Result<Self> {
+        let Some(anim) = chunks.iter().find(|chunk| &chunk.fourcc == b"ANIM") else {
+            bail!("Animated WebP has no ANIM chunk");
+        };
+
+        if anim.data.len() < 6 {
+            bail!("ANIM chunk of {} bytes is too short", anim.data.len());
+        }
+
+        let (width, height): (u32, u32) = (extended.canvas_width, extended.canvas_height);
+        let pixels: usize = pixel_count("WebP", width, height)?;
+        let mut frames: Vec<AnimationFrame> = Vec::new();
+
+        for chunk in chunks.iter().filter(|chunk| &chunk.fourcc == b"ANMF") {
+            let mut frame: AnimationFrame = AnimationFrame::from_chunk(chunk)?;
+
+            if frame.x_offset + frame.width > width || frame.y_offset + frame.height > height {
+                bail!("WebP frame of {}x{} at ({}, {}) is out of the {}x{} canvas", frame.width, frame.height, frame.x_offset, frame.y_offset, width, height);
+            }
+
+            // Frames replacing the whole canvas, or drawn after it was cleared, don't depend on the previous ones
+            frame.key_frame = match frames.last() {
+                None => true,
+                Some(_) if (!frame.has_alpha || !frame.blend) && frame.is_full(width, height) => true,
+                Some(previous) => previous.dispose && (previous.is_full(width, height) || previous.key_frame),
+            };
+
+            frames.push(frame);
+        }
+
+        if frames.is_empty() {
+            bail!("Animated WebP has no ANMF chunk");
+        }
+
+        let [blue, green, red, alpha]: [u8; 4] = [anim.data[0], anim.data[1], anim.data[2], anim.data[3]];
+
+        Ok(Self {
+            width,
+            height,
+            background_color: (red, green, blue, alpha),
+            loop_count: u16::from_le_bytes([anim.data[4], anim.data[5]]),
+            frames,
+            canvas: vec![(0, 0, 0, 0); pixels],
+            disposed_canvas: vec![(0, 0, 0, 0); pixels],
+        })
+    }
+
+    // Draws the frame over the canvas, the frames before it having been drawn in order since the last key frame
+    pub fn draw_frame(&mut self, index: usize, debug: bool) -> Result<()> {
+        let frame: &AnimationFrame = &self.frames[index];
+        let image: BMP = WEBP::decode_bitstream(&frame.bitstream, frame.alpha.as_ref(), debug)?;
+
+        if (image.width(), image.height()) != (frame.width, frame.height) {
+            bail!("WebP frame image of {}x{} doesn't match its frame of {}x{}", image.width(), image.height(), frame.width, frame.height);
+        }
+
+        if frame.key_frame {
+            self.canvas.fill((0, 0, 0, 0));
+        } else {
+            self.canvas.clone_from(&self.disposed_canvas);
+        }
+
+        // Area of the previous frame cleared to transparent is replaced instead of blended
+        let previous: Option<&AnimationFrame> = index.checked_sub(1).map(|previous| &self.frames[previous]).filter(|previous| previous.dispose);
+        let width: usize = self.width as usize;
+
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let (canvas_x, canvas_y): (usize, usize) = ((frame.x_offset + x) as usize, (frame.y_offset + y) as usize);
+                let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+                let source: (u8, u8, u8, u8) = (r, g, b, image.pixel_alpha(x, y));
+
+                let blend: bool = frame.blend && !frame.key_frame && source.3 != u8::MAX && !previous.is_some_and(|previous| previous.contains(canvas_x, canvas_y));
+
+                self.canvas[canvas_y * width + canvas_x] = match blend {
+                    true => blend_pixel(source, self.disposed_canvas[canvas_y * width + canvas_x]),
+                    false => source,
+                };
+            }
+        }
+
+        self.disposed_canvas.clone_from(&self.canvas);
+
+        if frame.dispose {
+            for y in frame.y_offset as usize..(frame.y_offset + frame.height) as usize {
+                self.disposed_canvas[y * width + frame.x_offset as usize..y * width + (frame.x_offset + frame.width) as usize].fill((0, 0, 0, 0));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_image(&self) -> BMP {
+        let width: usize = self.width as usize;
+
+        BMP::from_rgba_pixels(self.width, self.height, |x, y| self.canvas[y * width + x])
+    }
+}
+
+// Source over destination without premultiplied alpha, rounded like libwebp
+fn blend_pixel(source: (u8, u8, u8, u8), destination: (u8, u8, u8, u8)) -> (u8, u8, u8, u8) {
+    let source_alpha: u32 = source.3 as u32;
+
+    if source_alpha == 0 {
+        return destination;
+    }
+
+    let destination_alpha: u32 = (destination.3 as u32 * (256 - source_alpha)) >> 8;
+    let alpha: u32 = source_alpha + destination_alpha;
+    let scale: u32 = (1 << 24) / alpha;
+
+    let channel = |source: u8, destination: u8| (((source as u32 * source_alpha + destination as u32 * destination_alpha) * scale) >> 24) as u8;
+
+    (channel(source.0, destination.0), channel(source.1, destination.1), channel(source.2, destination.2), alpha as u8)
+}
+
+impl fmt::Display for Animation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "background_color: {:?}\n", self.background_color)?;
+        write!(f, "loop_count: {}\n", self.loop_count)?;
+        write!(f, "frames: {}\n", self.frames.len())?;
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            write!(f, "Frame {index}:\n")?;
+            write!(f, "{frame}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for AnimationFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offset: ({}, {})\n", self.x_offset, self.y_offset)?;
+        write!(f, "size: {}x{}\n", self.width, self.height)?;
+        write!(f, "duration: {:?}\n", self.duration)?;
+        write!(f, "bitstream: {}\n", self.bitstream.name())?;
+        write!(f, "has_alpha: {}\n", self.has_alpha)?;
+        write!(f, "blend: {}\n", self.blend)?;
+        write!(f, "dispose: {}\n", self.dispose)?;
+        write!(f, "key_frame: {}\n", self.key_frame)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::lossless::tests::vp8l;
+    use super::super::tests::{decode, riff, vp8x};
+    use super::*;
+
+    // ANMF chunk of a VP8L image of a single color, its offset being stored halved
+    fn frame((x, y): (u32, u32), (width, height): (u32, u32), flags: u8, (r, g, b, a): (u32, u32, u32, u32)) -> (&'static [u8; 4], Vec<u8>) {
+        let mut data: Vec<u8> = Vec::new();
+
+        for value in [x / 2, y / 2, width - 1, height - 1, 100] {
+            data.extend_from_slice(&value.to_le_bytes()[..3]);
+        }
+
+        data.push(flags);
+        let bitstream: Vec<u8> = vp8l(width, height, &[], (&[g], r, b, a), &[]);
+        data.extend_from_slice(b"VP8L");
+        data.extend_from_slice(&(bitstream.len() as u32).to_le_bytes());
+        data.extend_from_slice(&bitstream);
+
+        if bitstream.len() % 2 == 1 {
+            data.push(0);
+        }
+
+        (b"ANMF", data)
+    }
+
+    fn animation(width: u32, height: u32, frames: &[(&'static [u8; 4], Vec<u8>)]) -> Result<WEBP> {
+        let mut chunks: Vec<(&[u8; 4], Vec<u8>)> = vec![(b"VP8X", vp8x(0x12, width, height)), (b"ANIM", vec![0; 6])];
+        chunks.extend_from_slice(frames);
+        decode(riff(&chunks))
+    }
+
+    #[test]
+    fn frames_are_blended_over_the_canvas() {
+        let mut webp: WEBP = animation(4, 1, &[frame((0, 0), (4, 1), 0, (255, 0, 0, 255)), frame((2, 0), (2, 1), 0, (0, 0, 255, 128))]).unwrap();
+        let animation: &mut Animation = webp.animation.as_mut().unwrap();
+
+        assert_eq!(animation.frames.len(), 2);
+        assert_eq!(animation.frames[0].duration, Duration::from_millis(100));
+        assert_eq!(animation.canvas, [(255, 0, 0, 255); 4]);
+
+        animation.draw_frame(1, false).unwrap();
+
+        assert_eq!(animation.canvas, [(255, 0, 0, 255), (255, 0, 0, 255), (126, 0, 127, 255), (126, 0, 127, 255)]);
+    }
+
+    #[test]
+    fn disposed_frames_are_cleared() {
+        // First frame is disposed of, the second one not blended
+        let mut webp: WEBP = animation(4, 1, &[frame((0, 0), (2, 1), 0x01, (255, 0, 0, 255)), frame((2, 0), (2, 1), 0x02, (0, 0, 255, 128))]).unwrap();
+        let animation: &mut Animation = webp.animation.as_mut().unwrap();
+
+        assert_eq!(animation.canvas, [(255, 0, 0, 255), (255, 0, 0, 255), (0, 0, 0, 0), (0, 0, 0, 0)]);
+
+        animation.draw_frame(1, false).unwrap();
+
+        assert!(animation.frames[1].key_frame);
+        assert_eq!(animation.canvas, [(0, 0, 0, 0), (0, 0, 0, 0), (0, 0, 255, 128), (0, 0, 255, 128)]);
+    }
+
+    #[test]
+    fn frames_out_of_the_canvas_are_rejected() {
+        let error: String = animation(4, 1, &[frame((2, 0), (4, 1), 0, (0, 0, 0, 255))]).unwrap_err().to_string();
+
+        assert!(error.contains("is out of the 4x1 canvas"), "{error}");
+    }
+
+    #[test]
+    fn oversized_canvases_are_rejected() {
+        let extended: VP8XHeader = VP8XHeader {
+            animation: true,
+            canvas_width: 1 << 24,
+            canvas_height: 1 << 24,
+            ..VP8XHeader::default()
+        };
+        let error: String = Animation::from_chunks(&extended, &[Chunk { fourcc: *b"ANIM", data: vec![0; 6] }]).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{bail, Result};
+
+// Arithmetic decoder of the VP8 partitions, each bit coming with its probability of being zero out of 256
+pub struct BoolDecoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    // Two bytes window, the upper one being compared to the split
+    value: u32,
+    range: u32,
+    bit_count: u32,
+}
+
+impl<'a> BoolDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        let mut decoder: Self = Self {
+            data,
+            position: 0,
+            value: 0,
+            range: 255,
+            bit_count: 0,
+        };
+
+        decoder.value = (decoder.next_byte() << 8) | decoder.next_byte();
+
+        decoder
+    }
+
+    // Bytes past the end are read as zeros, the caller checking the partition wasn't overread
+    fn next_byte(&mut self) -> u32 {
+        let byte: u8 = self.data.get(self.position).copied().unwrap_or(0);
+        self.position += 1;
+        byte as u32
+    }
+
+    pub fn read_bool(&mut self, probability: u8) -> bool {
+        let split: u32 = 1 + (((self.range - 1) * probability as u32) >> 8);
+        let big_split: u32 = split << 8;
+
+        let bit: bool = self.value >= big_split;
+
+        if bit {
+            self.range -= split;
+            self.value -= big_split;
+        } else {
+            self.range = split;
+        }
+
+        while self.range < 128 {
+            self.value <<= 1;
+            self.range <<= 1;
+            self.bit_count += 1;
+
+            if self.bit_count == 8 {
+                self.bit_count = 0;
+                self.value |= self.next_byte();
+            }
+        }
+
+        bit
+    }
+
+    pub fn read_flag(&mut self) -> bool {
+        self.read_bool(128)
+    }
+
+    // Unsigned value of even probability bits, most significant first
+    pub fn read_literal(&mut self, length: u32) -> u32 {
+        (0..length).fold(0, |value, _| (value << 1) | self.read_flag() as u32)
+    }
+
+    // Magnitude followed by its sign
+    pub fn read_signed(&mut self, length: u32) -> i32 {
+        let value: i32 = self.read_literal(length) as i32;
+
+        if self.read_flag() { -value } else { value }
+    }
+
+    // Literal present only when its flag is set
+    pub fn read_optional_signed(&mut self, length: u32) -> i32 {
+        if self.read_flag() { self.read_signed(length) } else { 0 }
+    }
+
+    // Decoder keeps two bytes ahead of the bits it returns
+    pub fn check_end(&self, name: &str) -> Result<()> {
+        if self.position > self.data.len() + 2 {
+            bail!("VP8 {name} truncated");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Arithmetic encoder the decoder is the inverse of
+    pub(crate) struct BoolEncoder {
+        pub(crate) data: Vec<u8>,
+        range: u32,
+        bottom: u32,
+        bit_count: u32,
+    }
+
+    impl BoolEncoder {
+        pub(crate) fn new() -> Self {
+            Self {
+                data: Vec::new(),
+                range: 255,
+                bottom: 0,
+                bit_count: 24,
+            }
+        }
+
+        pub(crate) fn write_bool(&mut self, probability: u8, bit: bool) {
+            let split: u32 = 1 + (((self.range - 1) * probability as u32) >> 8);
+
+            if bit {
+                self.bottom += split;
+                self.range -= split;
+            } else {
+                self.range = split;
+            }
+
+            while self.range < 128 {
+                self.range <<= 1;
+
+                // Carry goes back through the bytes already written
+                if self.bottom & (1 << 31) != 0 {
+                    for byte in self.data.iter_mut().rev() {
+                        let (value, overflow): (u8, bool) = byte.overflowing_add(1);
+                        *byte = value;
+
+                        if !overflow {
+                            break;
+                        }
+                    }
+                }
+
+                self.bottom <<= 1;
+                self.bit_count -= 1;
+
+                if self.bit_count == 0 {
+                    self.data.push((self.bottom >> 24) as u8);
+                    self.bottom &= (1 << 24) - 1;
+                    self.bit_count = 8;
+                }
+            }
+        }
+
+        pub(crate) fn write_literal(&mut self, value: u32, length: u32) {
+            for bit in (0..length).rev() {
+                self.write_bool(128, (value >> bit) & 1 == 1);
+            }
+        }
+
+        pub(crate) fn finish(mut self) -> Vec<u8> {
+            for _ in 0..32 {
+                self.write_bool(128, false);
+            }
+
+            self.data
+        }
+    }
+
+    #[test]
+    fn bools_round_trip() {
+        // Pseudo random bits and probabilities, some of them unlikely
+        let mut state: u32 = 1;
+        let bools: Vec<(u8, bool)> = (0..2000).map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let probability: u8 = (state >> 16) as u8;
+            (probability.max(1), (state >> 8) as u8 >= probability)
+        }).collect();
+
+        let mut encoder: BoolEncoder = BoolEncoder::new();
+        bools.iter().for_each(|&(probability, bit)| encoder.write_bool(probability, bit));
+        encoder.write_literal(0x5A, 8);
+        let data: Vec<u8> = encoder.finish();
+
+        let mut decoder: BoolDecoder = BoolDecoder::new(&data);
+        let decoded: Vec<(u8, bool)> = bools.iter().map(|&(probability, _)| (probability, decoder.read_bool(probability))).collect();
+
+        assert_eq!(decoded, bools);
+        assert_eq!(decoder.read_literal(8), 0x5A);
+        assert!(decoder.check_end("partition").is_ok());
+    }
+
+    #[test]
+    fn signed_values_follow_their_magnitude() {
+        let mut encoder: BoolEncoder = BoolEncoder::new();
+        encoder.write_literal(6, 4);
+        encoder.write_bool(128, true);
+        encoder.write_bool(128, false);
+        let data: Vec<u8> = encoder.finish();
+        let mut decoder: BoolDecoder = BoolDecoder::new(&data);
+
+        assert_eq!(decoder.read_signed(4), -6);
+        assert_eq!(decoder.read_optional_signed(3), 0);
+    }
+
+    #[test]
+    fn overread_partitions_are_truncated() {
+        let mut decoder: BoolDecoder = BoolDecoder::new(&[0xFF]);
+        decoder.read_literal(32);
+
+        assert!(decoder.check_end("partition").is_err());
+    }
+}
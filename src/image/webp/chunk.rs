@@ -0,0 +1,51 @@
+use anyhow::{bail, Result};
+
+use crate::image::bit_reader::BitReader;
+use super::webp_bit_reader::WebpBitReader;
+
+#[derive(Clone, Debug)]
+pub struct Chunk {
+    pub fourcc: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+impl Chunk {
+    pub fn from_binary(reader: &mut WebpBitReader) -> Result<Self> {
+        let bytes: Vec<u8> = reader.read_bytes(4)?;
+        let fourcc: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        let size: u32 = reader.read_double()?.swap_bytes();
+
+        // Sizes are only trusted as far as the file goes
+        if size as u64 > reader.length()? - reader.position()? {
+            bail!("WebP chunk {} of {size} bytes runs past the end of the file", String::from_utf8_lossy(&fourcc));
+        }
+
+        let data: Vec<u8> = reader.read_bytes(size as usize)?;
+
+        // Chunks of odd size are padded to an even one, the padding of the last chunk being sometimes left out
+        if size % 2 == 1 && reader.position()? < reader.length()? {
+            reader.read_byte()?;
+        }
+
+        Ok(Self {
+            fourcc,
+            data,
+        })
+    }
+
+    // Chunks following each other up to the end of a RIFF file or of an ANMF chunk
+    pub fn read_all(reader: &mut WebpBitReader, end: u64) -> Result<Vec<Self>> {
+        let mut chunks: Vec<Self> = Vec::new();
+
+        // Chunk headers are 8 bytes long, anything shorter being trailing garbage
+        while reader.position()? + 8 <= end {
+            chunks.push(Self::from_binary(reader)?);
+        }
+
+        Ok(chunks)
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.fourcc).into_owned()
+    }
+}
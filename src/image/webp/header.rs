@@ -0,0 +1,127 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::pixel_count;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WEBPFormat {
+    // Single VP8 chunk
+    #[default]
+    LOSSY,
+    // Single VP8L chunk
+    LOSSLESS,
+    // VP8X chunk followed by the image, its alpha and metadata, or by animation frames
+    EXTENDED,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WEBPHeader {
+    pub file_size: u32,
+    pub format: WEBPFormat,
+    pub extended: Option<VP8XHeader>,
+    pub chunks: Vec<String>,
+}
+
+impl WEBPHeader {
+    pub fn from_chunk_names(file_size: u32, chunks: Vec<String>) -> Self {
+        let format: WEBPFormat = match chunks.first().map(String::as_str) {
+            Some("VP8L") => WEBPFormat::LOSSLESS,
+            Some("VP8X") => WEBPFormat::EXTENDED,
+            _ => WEBPFormat::LOSSY,
+        };
+
+        Self {
+            file_size,
+            format,
+            extended: None,
+            chunks,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VP8XHeader {
+    pub icc_profile: bool,
+    pub alpha: bool,
+    pub exif: bool,
+    pub xmp: bool,
+    pub animation: bool,
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+}
+
+impl VP8XHeader {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() < 10 {
+            bail!("VP8X chunk of {} bytes is too short", data.len());
+        }
+
+        let flags: u8 = data[0];
+        let (canvas_width, canvas_height): (u32, u32) = (read_u24(&data[4..7]) + 1, read_u24(&data[7..10]) + 1);
+
+        pixel_count("WebP", canvas_width, canvas_height)?;
+
+        Ok(Self {
+            icc_profile: flags & 0x20 != 0,
+            alpha: flags & 0x10 != 0,
+            exif: flags & 0x08 != 0,
+            xmp: flags & 0x04 != 0,
+            animation: flags & 0x02 != 0,
+            canvas_width,
+            canvas_height,
+        })
+    }
+}
+
+// Dimensions of a VP8 key frame, the only kind of frame found in WebP files
+pub fn vp8_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    if data.len() < 10 {
+        bail!("VP8 chunk of {} bytes is too short", data.len());
+    }
+
+    if data[0] & 0x01 != 0 {
+        bail!("VP8 frame is not a key frame");
+    }
+
+    if data[3..6] != [0x9D, 0x01, 0x2A] {
+        bail!("VP8 start code is invalid");
+    }
+
+    // Upper 2 bits are an upscaling hint
+    let width: u32 = u16::from_le_bytes([data[6], data[7]]) as u32 & 0x3FFF;
+    let height: u32 = u16::from_le_bytes([data[8], data[9]]) as u32 & 0x3FFF;
+
+    Ok((width, height))
+}
+
+// 24 bits little endian value, as found in VP8X and animation chunks
+pub fn read_u24(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16
+}
+
+impl fmt::Display for WEBPHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "file_size: {}\n", self.file_size)?;
+        write!(f, "format: {:?}\n", self.format)?;
+        write!(f, "chunks: {}\n", self.chunks.join(", "))?;
+
+        if let Some(extended) = &self.extended {
+            write!(f, "{extended}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for VP8XHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "icc_profile: {}\n", self.icc_profile)?;
+        write!(f, "alpha: {}\n", self.alpha)?;
+        write!(f, "exif: {}\n", self.exif)?;
+        write!(f, "xmp: {}\n", self.xmp)?;
+        write!(f, "animation: {}\n", self.animation)?;
+        write!(f, "canvas_width: {}\n", self.canvas_width)?;
+        write!(f, "canvas_height: {}\n", self.canvas_height)?;
+        Ok(())
+    }
+}
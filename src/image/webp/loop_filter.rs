@@ -0,0 +1,167 @@
+// Strengths of the filter of a macroblock, a zero limit turning it off
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FilterStrength {
+    pub limit: i32,
+    pub interior_limit: i32,
+    pub hev_threshold: i32,
+    // Edges between the 4x4 blocks are filtered only when they may differ
+    pub inner: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FilterType {
+    #[default]
+    NONE,
+    // Luma only, on the two pixels closest to the edge
+    SIMPLE,
+    NORMAL,
+}
+
+// Luma or chroma plane of whole macroblocks, filtered in place
+pub struct Plane<'a> {
+    pub pixels: &'a mut [u8],
+    pub stride: usize,
+}
+
+fn signed_clip(value: i32) -> i32 {
+    value.clamp(-128, 127)
+}
+
+fn clip(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+// Pixels p3 p2 p1 p0 | q0 q1 q2 q3 across the edge at the position
+fn taps(pixels: &[u8], position: usize, step: usize) -> [i32; 8] {
+    let start: usize = position - 4 * step;
+
+    [0, 1, 2, 3, 4, 5, 6, 7].map(|index| pixels[start + index * step] as i32)
+}
+
+// Two pixels changed, when the edge has high variance
+fn filter2(pixels: &mut [u8], position: usize, step: usize) {
+    let [_, _, p1, p0, q0, q1, _, _]: [i32; 8] = taps(pixels, position, step);
+    let a: i32 = 3 * (q0 - p0) + signed_clip(p1 - q1);
+    let a1: i32 = ((a + 4) >> 3).clamp(-16, 15);
+    let a2: i32 = ((a + 3) >> 3).clamp(-16, 15);
+
+    pixels[position - step] = clip(p0 + a2);
+    pixels[position] = clip(q0 - a1);
+}
+
+// Four pixels changed, on the edges between 4x4 blocks
+fn filter4(pixels: &mut [u8], position: usize, step: usize) {
+    let [_, _, p1, p0, q0, q1, _, _]: [i32; 8] = taps(pixels, position, step);
+    let a: i32 = 3 * (q0 - p0);
+    let a1: i32 = ((a + 4) >> 3).clamp(-16, 15);
+    let a2: i32 = ((a + 3) >> 3).clamp(-16, 15);
+    let a3: i32 = (a1 + 1) >> 1;
+
+    pixels[position - 2 * step] = clip(p1 + a3);
+    pixels[position - step] = clip(p0 + a2);
+    pixels[position] = clip(q0 - a1);
+    pixels[position + step] = clip(q1 - a3);
+}
+
+// Six pixels changed, on the edges between macroblocks
+fn filter6(pixels: &mut [u8], position: usize, step: usize) {
+    let [_, p2, p1, p0, q0, q1, q2, _]: [i32; 8] = taps(pixels, position, step);
+    let a: i32 = signed_clip(3 * (q0 - p0) + signed_clip(p1 - q1));
+    let a1: i32 = (27 * a + 63) >> 7;
+    let a2: i32 = (18 * a + 63) >> 7;
+    let a3: i32 = (9 * a + 63) >> 7;
+
+    pixels[position - 3 * step] = clip(p2 + a3);
+    pixels[position - 2 * step] = clip(p1 + a2);
+    pixels[position - step] = clip(p0 + a1);
+    pixels[position] = clip(q0 - a1);
+    pixels[position + step] = clip(q1 - a2);
+    pixels[position + 2 * step] = clip(q2 - a3);
+}
+
+fn edge_difference(p1: i32, p0: i32, q0: i32, q1: i32) -> i32 {
+    4 * (p0 - q0).abs() + (p1 - q1).abs()
+}
+
+fn simple_edge(pixels: &mut [u8], position: usize, step: usize, threshold: i32) {
+    let [_, _, p1, p0, q0, q1, _, _]: [i32; 8] = taps(pixels, position, step);
+
+    if edge_difference(p1, p0, q0, q1) <= threshold {
+        filter2(pixels, position, step);
+    }
+}
+
+fn normal_edge(pixels: &mut [u8], position: usize, step: usize, threshold: i32, strength: &FilterStrength, macroblock_edge: bool) {
+    let [p3, p2, p1, p0, q0, q1, q2, q3]: [i32; 8] = taps(pixels, position, step);
+
+    if edge_difference(p1, p0, q0, q1) > threshold {
+        return;
+    }
+
+    let interior: i32 = strength.interior_limit;
+
+    if [p3 - p2, p2 - p1, p1 - p0, q3 - q2, q2 - q1, q1 - q0].iter().any(|difference| difference.abs() > interior) {
+        return;
+    }
+
+    let high_variance: bool = (p1 - p0).abs() > strength.hev_threshold || (q1 - q0).abs() > strength.hev_threshold;
+
+    match (high_variance, macroblock_edge) {
+        (true, _) => filter2(pixels, position, step),
+        (false, true) => filter6(pixels, position, step),
+        (false, false) => filter4(pixels, position, step),
+    }
+}
+
+impl Plane<'_> {
+    // Edge between columns, at the left of the pixel at the position, over `length` rows
+    fn vertical_edge(&mut self, position: usize, length: usize, filter_type: FilterType, strength: &FilterStrength, macroblock_edge: bool) {
+        let limit: i32 = if macroblock_edge { strength.limit + 4 } else { strength.limit };
+        let threshold: i32 = 2 * limit + 1;
+
+        for line in 0..length {
+            match filter_type {
+                FilterType::SIMPLE => simple_edge(self.pixels, position + line * self.stride, 1, threshold),
+                _ => normal_edge(self.pixels, position + line * self.stride, 1, threshold, strength, macroblock_edge),
+            }
+        }
+    }
+
+    // Edge between rows, above the pixel at the position, over `length` columns
+    fn horizontal_edge(&mut self, position: usize, length: usize, filter_type: FilterType, strength: &FilterStrength, macroblock_edge: bool) {
+        let limit: i32 = if macroblock_edge { strength.limit + 4 } else { strength.limit };
+        let threshold: i32 = 2 * limit + 1;
+
+        for line in 0..length {
+            match filter_type {
+                FilterType::SIMPLE => simple_edge(self.pixels, position + line, self.stride, threshold),
+                _ => normal_edge(self.pixels, position + line, self.stride, threshold, strength, macroblock_edge),
+            }
+        }
+    }
+
+    // Left edge, inner vertical edges, top edge then inner horizontal edges of a block of `size` pixels
+    pub fn filter_block(&mut self, x: usize, y: usize, size: usize, filter_type: FilterType, strength: &FilterStrength) {
+        let origin: usize = y * self.stride + x;
+
+        if x > 0 {
+            self.vertical_edge(origin, size, filter_type, strength, true);
+        }
+
+        if strength.inner {
+            for offset in (4..size).step_by(4) {
+                self.vertical_edge(origin + offset, size, filter_type, strength, false);
+            }
+        }
+
+        if y > 0 {
+            self.horizontal_edge(origin, size, filter_type, strength, true);
+        }
+
+        if strength.inner {
+            for offset in (4..size).step_by(4) {
+                self.horizontal_edge(origin + offset * self.stride, size, filter_type, strength, false);
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::pixel_count;
+use super::prefix_code::PrefixGroup;
+use super::transform::{Transform, TransformType};
+
+pub const SIGNATURE: u8 = 0x2F;
+
+const LENGTH_PREFIX_START: u32 = 256;
+const CACHE_START: u32 = 256 + 24;
+const CACHE_MULTIPLIER: u32 = 0x1E35A7BD;
+
+// Horizontal and vertical offsets of the 120 first distance codes, nearest pixels coming first
+const DISTANCE_OFFSETS: [(i8, i8); 120] = [
+    (0, 1), (1, 0), (1, 1), (-1, 1), (0, 2), (2, 0), (1, 2), (-1, 2),
+    (2, 1), (-2, 1), (2, 2), (-2, 2), (0, 3), (3, 0), (1, 3), (-1, 3),
+    (3, 1), (-3, 1), (2, 3), (-2, 3), (3, 2), (-3, 2), (0, 4), (4, 0),
+    (1, 4), (-1, 4), (4, 1), (-4, 1), (3, 3), (-3, 3), (2, 4), (-2, 4),
+    (4, 2), (-4, 2), (0, 5), (3, 4), (-3, 4), (4, 3), (-4, 3), (5, 0),
+    (1, 5), (-1, 5), (5, 1), (-5, 1), (2, 5), (-2, 5), (5, 2), (-5, 2),
+    (4, 4), (-4, 4), (3, 5), (-3, 5), (5, 3), (-5, 3), (0, 6), (6, 0),
+    (1, 6), (-1, 6), (6, 1), (-6, 1), (2, 6), (-2, 6), (6, 2), (-6, 2),
+    (4, 5), (-4, 5), (5, 4), (-5, 4), (3, 6), (-3, 6), (6, 3), (-6, 3),
+    (0, 7), (7, 0), (1, 7), (-1, 7), (5, 5), (-5, 5), (7, 1), (-7, 1),
+    (4, 6), (-4, 6), (6, 4), (-6, 4), (2, 7), (-2, 7), (7, 2), (-7, 2),
+    (3, 7), (-3, 7), (7, 3), (-7, 3), (5, 6), (-5, 6), (6, 5), (-6, 5),
+    (8, 0), (4, 7), (-4, 7), (7, 4), (-7, 4), (8, 1), (8, 2), (6, 6),
+    (-6, 6), (8, 3), (5, 7), (-5, 7), (7, 5), (-7, 5), (8, 4), (6, 7),
+    (-6, 7), (7, 6), (-7, 6), (8, 5), (7, 7), (-7, 7), (8, 6), (8, 7),
+];
+
+// Lossless streams are read from the least significant bit of each byte
+pub struct LosslessReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+}
+
+impl<'a> LosslessReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    // Bytes past the end are read as zeros, consuming them being an error
+    fn fill(&mut self) {
+        while self.count <= 56 {
+            let byte: u8 = self.data.get(self.position).copied().unwrap_or(0);
+            self.buffer |= (byte as u64) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+    }
+
+    pub fn peek(&mut self, length: u32) -> u32 {
+        if self.count < length {
+            self.fill();
+        }
+
+        (self.buffer & ((1u64 << length) - 1)) as u32
+    }
+
+    pub fn consume(&mut self, length: u32) -> Result<()> {
+        self.buffer >>= length;
+        self.count -= length;
+
+        if self.position * 8 - self.count as usize > self.data.len() * 8 {
+            bail!("VP8L stream truncated");
+        }
+
+        Ok(())
+    }
+
+    pub fn read_bits(&mut self, length: u32) -> Result<u32> {
+        let value: u32 = self.peek(length);
+        self.consume(length)?;
+        Ok(value)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VP8LHeader {
+    pub width: u32,
+    pub height: u32,
+    // Hint only, the decoded alpha being used either way
+    pub alpha_is_used: bool,
+    pub version: u8,
+}
+
+impl fmt::Display for VP8LHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "alpha_is_used: {}\n", self.alpha_is_used)?;
+        write!(f, "version: {}\n", self.version)?;
+        Ok(())
+    }
+}
+
+// ARGB pixels of the VP8L chunk of a WebP file
+pub fn decode(data: &[u8]) -> Result<(VP8LHeader, Vec<u32>)> {
+    let mut reader: LosslessReader = LosslessReader::new(data);
+
+    if reader.read_bits(8)? != SIGNATURE as u32 {
+        bail!("VP8L signature is invalid");
+    }
+
+    let header: VP8LHeader = VP8LHeader {
+        width: reader.read_bits(14)? + 1,
+        height: reader.read_bits(14)? + 1,
+        alpha_is_used: reader.read_bits(1)? == 1,
+        version: reader.read_bits(3)? as u8,
+    };
+
+    if header.version != 0 {
+        bail!("VP8L version {} not supported", header.version);
+    }
+
+    pixel_count("VP8L", header.width, header.height)?;
+
+    let pixels: Vec<u32> = decode_stream(&mut reader, header.width as usize, header.height as usize)?;
+
+    Ok((header, pixels))
+}
+
+// Image stream without header, as found in the ALPH chunks whose dimensions come from the frame
+pub fn decode_stream(reader: &mut LosslessReader, width: usize, height: usize) -> Result<Vec<u32>> {
+    let mut transforms: Vec<Transform> = Vec::new();
+    // Color indexing packs several pixels into one, making the following images narrower
+    let mut packed_width: usize = width;
+
+    while reader.read_bits(1)? == 1 {
+        let transform: Transform = Transform::from_binary(reader, packed_width, height)?;
+
+        if transforms.iter().any(|other| other.transform_type == transform.transform_type) {
+            bail!("VP8L transform {:?} used twice", transform.transform_type);
+        }
+
+        if transform.transform_type == TransformType::COLORINDEXING {
+            packed_width = packed_width.div_ceil(1 << transform.bits);
+        }
+
+        transforms.push(transform);
+    }
+
+    let mut pixels: Vec<u32> = decode_entropy_image(reader, packed_width, height, true)?;
+
+    // Transforms are undone in the reverse order of their reading
+    for transform in transforms.iter().rev() {
+        pixels = transform.apply(pixels, height);
+    }
+
+    Ok(pixels)
+}
+
+// Prefix coded pixels, only the main image being split into blocks of their own prefix codes
+pub fn decode_entropy_image(reader: &mut LosslessReader, width: usize, height: usize, main: bool) -> Result<Vec<u32>> {
+    let mut cache_bits: u32 = 0;
+
+    if reader.read_bits(1)? == 1 {
+        cache_bits = reader.read_bits(4)?;
+
+        if !(1..=11).contains(&cache_bits) {
+            bail!("VP8L color cache of {cache_bits} bits is invalid");
+        }
+    }
+
+    let mut prefix_bits: u32 = 0;
+    let mut entropy_image: Vec<u32> = Vec::new();
+
+    if main && reader.read_bits(1)? == 1 {
+        prefix_bits = reader.read_bits(3)? + 2;
+        entropy_image = decode_entropy_image(reader, width.div_ceil(1 << prefix_bits), height.div_ceil(1 << prefix_bits), false)?;
+    }
+
+    let cache_size: usize = if cache_bits > 0 { 1 << cache_bits } else { 0 };
+    let group_count: usize = entropy_image.iter().map(|&pixel| ((pixel >> 8) & 0xFFFF) as usize + 1).max().unwrap_or(1);
+    let mut groups: Vec<PrefixGroup> = Vec::with_capacity(group_count);
+
+    for _ in 0..group_count {
+        groups.push(PrefixGroup::from_binary(reader, cache_size)?);
+    }
+
+    let count: usize = width * height;
+    let blocks_width: usize = width.div_ceil(1 << prefix_bits);
+    let mut pixels: Vec<u32> = Vec::with_capacity(count);
+    let mut cache: Vec<u32> = vec![0; cache_size];
+    let mut cached: usize = 0;
+
+    while pixels.len() < count {
+        let position: usize = pixels.len();
+
+        let group: &PrefixGroup = match entropy_image.is_empty() {
+            true => &groups[0],
+            false => {
+                let (x, y): (usize, usize) = (position % width, position / width);
+                &groups[((entropy_image[(y >> prefix_bits) * blocks_width + (x >> prefix_bits)] >> 8) & 0xFFFF) as usize]
+            },
+        };
+
+        let symbol: u32 = group.green.decode(reader)? as u32;
+
+        if symbol < LENGTH_PREFIX_START {
+            let red: u32 = group.red.decode(reader)? as u32;
+            let blue: u32 = group.blue.decode(reader)? as u32;
+            let alpha: u32 = group.alpha.decode(reader)? as u32;
+            pixels.push((alpha << 24) | (red << 16) | (symbol << 8) | blue);
+        } else if symbol < CACHE_START {
+            let length: usize = prefix_value(reader, symbol - LENGTH_PREFIX_START)?;
+            let distance_symbol: u32 = group.distance.decode(reader)? as u32;
+            let distance: usize = plane_distance(prefix_value(reader, distance_symbol)?, width);
+
+            if distance > position || length > count - position {
+                bail!("Backward reference of {length} pixels at distance {distance} goes out of the image");
+            }
+
+            // Copies may overlap their own output
+            for _ in 0..length {
+                pixels.push(pixels[pixels.len() - distance]);
+            }
+        } else {
+            // Cache only holds pixels decoded so far, which are added lazily
+            for &pixel in pixels[cached..].iter() {
+                cache[(CACHE_MULTIPLIER.wrapping_mul(pixel) >> (32 - cache_bits)) as usize] = pixel;
+            }
+
+            cached = pixels.len();
+            pixels.push(cache[(symbol - CACHE_START) as usize]);
+        }
+    }
+
+    Ok(pixels)
+}
+
+// Lengths and distances are a prefix followed by extra bits
+fn prefix_value(reader: &mut LosslessReader, prefix: u32) -> Result<usize> {
+    if prefix < 4 {
+        return Ok(prefix as usize + 1);
+    }
+
+    let extra_bits: u32 = (prefix - 2) >> 1;
+    let offset: u32 = (2 + (prefix & 1)) << extra_bits;
+
+    Ok((offset + reader.read_bits(extra_bits)?) as usize + 1)
+}
+
+// Small distance codes stand for pixels close in two dimensions, the others being linear distances shifted by 120
+fn plane_distance(code: usize, width: usize) -> usize {
+    if code > DISTANCE_OFFSETS.len() {
+        return code - DISTANCE_OFFSETS.len();
+    }
+
+    let (x, y): (i8, i8) = DISTANCE_OFFSETS[code - 1];
+    let distance: isize = x as isize + y as isize * width as isize;
+
+    distance.max(1) as usize
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Values of the given number of bits, packed from the least significant bit
+    pub(crate) fn bits(fields: &[(u32, u32)]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut count: usize = 0;
+
+        for &(value, length) in fields {
+            for bit in 0..length {
+                if count.is_multiple_of(8) {
+                    bytes.push(0);
+                }
+
+                *bytes.last_mut().unwrap() |= (((value >> bit) & 1) as u8) << (count % 8);
+                count += 1;
+            }
+        }
+
+        bytes
+    }
+
+    // Simple prefix code of one or two symbols of 8 bits
+    pub(crate) fn simple_code(symbols: &[u32]) -> Vec<(u32, u32)> {
+        let mut fields: Vec<(u32, u32)> = vec![(1, 1), (symbols.len() as u32 - 1, 1), (1, 1)];
+        fields.extend(symbols.iter().map(|&symbol| (symbol, 8)));
+        fields
+    }
+
+    // VP8L chunk of a single group of simple codes, followed by the bits of the pixels
+    pub(crate) fn vp8l(width: u32, height: u32, transforms: &[(u32, u32)], (green, red, blue, alpha): (&[u32], u32, u32, u32), pixels: &[(u32, u32)]) -> Vec<u8> {
+        let mut fields: Vec<(u32, u32)> = vec![(SIGNATURE as u32, 8), (width - 1, 14), (height - 1, 14), (1, 1), (0, 3)];
+        fields.extend_from_slice(transforms);
+        // End of the transforms, no color cache and no meta prefix codes
+        fields.extend_from_slice(&[(0, 1), (0, 1), (0, 1)]);
+
+        for symbols in [green, &[red], &[blue], &[alpha], &[0]] {
+            fields.extend(simple_code(symbols));
+        }
+
+        fields.extend_from_slice(pixels);
+        bits(&fields)
+    }
+
+    #[test]
+    fn single_symbol_codes_take_no_bits() {
+        let (header, pixels): (VP8LHeader, Vec<u32>) = decode(&vp8l(3, 2, &[], (&[100], 50, 25, 255), &[])).unwrap();
+
+        assert_eq!((header.width, header.height), (3, 2));
+        assert_eq!(pixels, [0xFF326419; 6]);
+    }
+
+    #[test]
+    fn two_symbols_codes_take_a_bit() {
+        let (_, pixels): (VP8LHeader, Vec<u32>) = decode(&vp8l(2, 2, &[], (&[10, 200], 0, 0, 255), &[(0, 1), (1, 1), (1, 1), (0, 1)])).unwrap();
+
+        assert_eq!(pixels, [0xFF000A00, 0xFF00C800, 0xFF00C800, 0xFF000A00]);
+    }
+
+    #[test]
+    fn subtract_green_transform_is_undone() {
+        let (_, pixels): (VP8LHeader, Vec<u32>) = decode(&vp8l(1, 1, &[(1, 1), (2, 2)], (&[100], 50, 25, 255), &[])).unwrap();
+
+        assert_eq!(pixels, [0xFF96647D]);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode(&vp8l(16384, 16384, &[], (&[0], 0, 0, 0), &[])).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn invalid_signatures_are_rejected() {
+        let error: String = decode(&[0x2E, 0, 0, 0, 0]).unwrap_err().to_string();
+
+        assert!(error.contains("signature is invalid"), "{error}");
+    }
+}
@@ -0,0 +1,892 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::pixel_count;
+use super::bool_decoder::BoolDecoder;
+use super::header::vp8_dimensions;
+use super::loop_filter::{FilterStrength, FilterType, Plane};
+use super::predict::{inverse_transform, inverse_walsh_hadamard, predict_block, predict_subblock, PredictionMode, STRIDE};
+use super::tables::{AC_QUANTIZERS, BANDS, COEFFICIENT_PROBABILITIES, COEFFICIENT_UPDATE_PROBABILITIES, DC_QUANTIZERS, LARGE_VALUE_PROBABILITIES, SUBBLOCK_MODE_PROBABILITIES, ZIGZAG};
+
+const SEGMENTS: usize = 4;
+
+// Coefficients of the 16 luma blocks then of the 4 U and 4 V blocks
+const LUMA_COEFFICIENTS: usize = 16 * 16;
+const U_COEFFICIENTS: usize = LUMA_COEFFICIENTS;
+const V_COEFFICIENTS: usize = LUMA_COEFFICIENTS + 4 * 16;
+
+// Plane types selecting the coefficient probabilities
+const LUMA_AFTER_Y2: usize = 0;
+const Y2: usize = 1;
+const CHROMA: usize = 2;
+const LUMA: usize = 3;
+
+type CoefficientProbabilities = [[[u8; 11]; 3]; 8];
+// Rows of RGB pixels going from top to bottom
+type Pixels = Vec<(u8, u8, u8)>;
+
+#[derive(Clone, Debug, Default)]
+pub struct VP8Header {
+    pub width: u32,
+    pub height: u32,
+    // Upscaling hints, ignored
+    pub horizontal_scale: u8,
+    pub vertical_scale: u8,
+    pub profile: u8,
+    pub color_space: u8,
+    pub clamping_type: u8,
+    pub segmentation: bool,
+    pub filter_type: FilterType,
+    pub filter_level: u8,
+    pub sharpness: u8,
+    pub partitions: usize,
+    pub quantizer: u8,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct SegmentHeader {
+    update_map: bool,
+    absolute_values: bool,
+    quantizers: [i32; SEGMENTS],
+    filter_levels: [i32; SEGMENTS],
+    probabilities: [u8; 3],
+}
+
+// Dequantization factors of the DC and AC coefficients
+#[derive(Clone, Copy, Debug, Default)]
+struct Quantizers {
+    luma: (i32, i32),
+    y2: (i32, i32),
+    chroma: (i32, i32),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Macroblock {
+    segment: usize,
+    skip: bool,
+    // Luma predicted as 16 blocks of 4x4 pixels rather than as a whole
+    subblocks: bool,
+    luma: PredictionMode,
+    subblock_modes: [PredictionMode; 16],
+    chroma: PredictionMode,
+}
+
+// Whether the blocks along the edge of a macroblock had non-zero coefficients
+#[derive(Clone, Copy, Debug, Default)]
+struct NonZero {
+    luma: [bool; 4],
+    u: [bool; 2],
+    v: [bool; 2],
+    y2: bool,
+}
+
+struct LossyDecoder {
+    header: VP8Header,
+    segment_header: SegmentHeader,
+    quantizers: [Quantizers; SEGMENTS],
+    // Per segment, for macroblocks predicted whole and split in subblocks
+    filter_strengths: [[FilterStrength; 2]; SEGMENTS],
+    probabilities: [CoefficientProbabilities; 4],
+    skip_probability: Option<u8>,
+    macroblocks_width: usize,
+    macroblocks_height: usize,
+    // Prediction modes of the subblocks along the bottom of the row above and the right of the previous macroblock
+    top_modes: Vec<PredictionMode>,
+    left_modes: [PredictionMode; 4],
+    top_non_zero: Vec<NonZero>,
+    left_non_zero: NonZero,
+    filters: Vec<FilterStrength>,
+    luma: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+// RGB pixels of the VP8 chunk of a WebP file, its only frame being a key frame
+pub fn decode(data: &[u8]) -> Result<(VP8Header, Pixels)> {
+    let (width, height): (u32, u32) = vp8_dimensions(data)?;
+    let tag: u32 = data[0] as u32 | (data[1] as u32) << 8 | (data[2] as u32) << 16;
+
+    let mut header: VP8Header = VP8Header {
+        width,
+        height,
+        horizontal_scale: data[7] >> 6,
+        vertical_scale: data[9] >> 6,
+        profile: ((tag >> 1) & 0x07) as u8,
+        ..VP8Header::default()
+    };
+
+    if header.profile > 3 {
+        bail!("VP8 profile {} is invalid", header.profile);
+    }
+
+    if (tag >> 4) & 0x01 == 0 {
+        bail!("VP8 frame is not meant to be shown");
+    }
+
+    if width == 0 || height == 0 {
+        bail!("VP8 frame of {width}x{height} is empty");
+    }
+
+    pixel_count("VP8", width, height)?;
+
+    let first_partition_length: usize = (tag >> 5) as usize;
+
+    if 10 + first_partition_length > data.len() {
+        bail!("VP8 first partition of {first_partition_length} bytes goes past the chunk");
+    }
+
+    let mut first_partition: BoolDecoder = BoolDecoder::new(&data[10..10 + first_partition_length]);
+    header.color_space = first_partition.read_flag() as u8;
+    header.clamping_type = first_partition.read_flag() as u8;
+
+    let mut decoder: LossyDecoder = LossyDecoder::new(header);
+    decoder.read_segment_header(&mut first_partition);
+    decoder.read_filter_header(&mut first_partition);
+
+    let mut partitions: Vec<BoolDecoder> = split_partitions(&mut first_partition, &data[10 + first_partition_length..])?;
+    decoder.header.partitions = partitions.len();
+
+    decoder.read_quantizers(&mut first_partition);
+
+    // Refresh of the probabilities, meaningless without following frames
+    first_partition.read_flag();
+    decoder.read_probabilities(&mut first_partition);
+    first_partition.check_end("first partition")?;
+
+    decoder.decode_macroblocks(&mut first_partition, &mut partitions)?;
+    decoder.filter();
+
+    let pixels: Vec<(u8, u8, u8)> = decoder.to_rgb();
+
+    Ok((decoder.header, pixels))
+}
+
+// Coefficient partitions follow the first one, preceded by the 3 bytes sizes of all but the last
+fn split_partitions<'a>(first_partition: &mut BoolDecoder, data: &'a [u8]) -> Result<Vec<BoolDecoder<'a>>> {
+    let count: usize = 1 << first_partition.read_literal(2);
+    let sizes_length: usize = 3 * (count - 1);
+
+    if data.len() < sizes_length {
+        bail!("VP8 partition sizes go past the chunk");
+    }
+
+    let mut partitions: Vec<BoolDecoder> = Vec::with_capacity(count);
+    let mut start: usize = sizes_length;
+
+    for index in 0..count - 1 {
+        let size: usize = data[3 * index] as usize | (data[3 * index + 1] as usize) << 8 | (data[3 * index + 2] as usize) << 16;
+        // Sizes too large are cut to the data left, the decoding failing later if it was needed
+        let end: usize = (start + size).min(data.len());
+        partitions.push(BoolDecoder::new(&data[start..end]));
+        start = end;
+    }
+
+    partitions.push(BoolDecoder::new(&data[start..]));
+
+    Ok(partitions)
+}
+
+impl LossyDecoder {
+    fn new(header: VP8Header) -> Self {
+        let macroblocks_width: usize = (header.width as usize).div_ceil(16);
+        let macroblocks_height: usize = (header.height as usize).div_ceil(16);
+
+        Self {
+            header,
+            segment_header: SegmentHeader {
+                absolute_values: true,
+                probabilities: [255; 3],
+                ..SegmentHeader::default()
+            },
+            quantizers: [Quantizers::default(); SEGMENTS],
+            filter_strengths: [[FilterStrength::default(); 2]; SEGMENTS],
+            probabilities: COEFFICIENT_PROBABILITIES,
+            skip_probability: None,
+            macroblocks_width,
+            macroblocks_height,
+            top_modes: vec![PredictionMode::DC; 4 * macroblocks_width],
+            left_modes: [PredictionMode::DC; 4],
+            top_non_zero: vec![NonZero::default(); macroblocks_width],
+            left_non_zero: NonZero::default(),
+            filters: Vec::with_capacity(macroblocks_width * macroblocks_height),
+            luma: vec![0; macroblocks_width * 16 * macroblocks_height * 16],
+            u: vec![0; macroblocks_width * 8 * macroblocks_height * 8],
+            v: vec![0; macroblocks_width * 8 * macroblocks_height * 8],
+        }
+    }
+
+    // Macroblocks may be split in 4 segments, each with its own quantizer and filter level
+    fn read_segment_header(&mut self, decoder: &mut BoolDecoder) {
+        self.header.segmentation = decoder.read_flag();
+
+        if !self.header.segmentation {
+            return;
+        }
+
+        let segment_header: &mut SegmentHeader = &mut self.segment_header;
+        segment_header.update_map = decoder.read_flag();
+
+        if decoder.read_flag() {
+            segment_header.absolute_values = decoder.read_flag();
+
+            for quantizer in segment_header.quantizers.iter_mut() {
+                *quantizer = decoder.read_optional_signed(7);
+            }
+
+            for filter_level in segment_header.filter_levels.iter_mut() {
+                *filter_level = decoder.read_optional_signed(6);
+            }
+        }
+
+        if segment_header.update_map {
+            for probability in segment_header.probabilities.iter_mut() {
+                *probability = if decoder.read_flag() { decoder.read_literal(8) as u8 } else { 255 };
+            }
+        }
+    }
+
+    fn read_filter_header(&mut self, decoder: &mut BoolDecoder) {
+        let simple: bool = decoder.read_flag();
+        let level: i32 = decoder.read_literal(6) as i32;
+        let sharpness: i32 = decoder.read_literal(3) as i32;
+        let mut reference_delta: i32 = 0;
+        let mut mode_delta: i32 = 0;
+
+        if decoder.read_flag() && decoder.read_flag() {
+            // Only the deltas of intra frames and of 4x4 prediction apply to a key frame
+            let mut reference_deltas: [i32; 4] = [0; 4];
+            let mut mode_deltas: [i32; 4] = [0; 4];
+
+            for delta in reference_deltas.iter_mut().chain(mode_deltas.iter_mut()) {
+                *delta = decoder.read_optional_signed(6);
+            }
+
+            (reference_delta, mode_delta) = (reference_deltas[0], mode_deltas[0]);
+        }
+
+        self.header.filter_level = level as u8;
+        self.header.sharpness = sharpness as u8;
+
+        // Frame level turns the filter off whatever the segment levels
+        self.header.filter_type = match (level, simple) {
+            (0, _) => FilterType::NONE,
+            (_, true) => FilterType::SIMPLE,
+            (_, false) => FilterType::NORMAL,
+        };
+
+        for segment in 0..SEGMENTS {
+            let mut base_level: i32 = level;
+
+            if self.header.segmentation {
+                base_level = self.segment_header.filter_levels[segment];
+
+                if !self.segment_header.absolute_values {
+                    base_level += level;
+                }
+            }
+
+            for subblocks in [false, true] {
+                let level: i32 = (base_level + reference_delta + if subblocks { mode_delta } else { 0 }).clamp(0, 63);
+                let mut strength: FilterStrength = FilterStrength {
+                    inner: subblocks,
+                    ..FilterStrength::default()
+                };
+
+                if level > 0 {
+                    let mut interior_limit: i32 = level;
+
+                    if sharpness > 0 {
+                        interior_limit >>= if sharpness > 4 { 2 } else { 1 };
+                        interior_limit = interior_limit.min(9 - sharpness);
+                    }
+
+                    strength.interior_limit = interior_limit.max(1);
+                    strength.limit = 2 * level + strength.interior_limit;
+                    strength.hev_threshold = match level {
+                        40.. => 2,
+                        15.. => 1,
+                        _ => 0,
+                    };
+                }
+
+                self.filter_strengths[segment][subblocks as usize] = strength;
+            }
+        }
+    }
+
+    fn read_quantizers(&mut self, decoder: &mut BoolDecoder) {
+        let base: i32 = decoder.read_literal(7) as i32;
+        let luma_dc_delta: i32 = decoder.read_optional_signed(4);
+        let y2_dc_delta: i32 = decoder.read_optional_signed(4);
+        let y2_ac_delta: i32 = decoder.read_optional_signed(4);
+        let chroma_dc_delta: i32 = decoder.read_optional_signed(4);
+        let chroma_ac_delta: i32 = decoder.read_optional_signed(4);
+
+        self.header.quantizer = base as u8;
+
+        for segment in 0..SEGMENTS {
+            let mut quantizer: i32 = base;
+
+            if self.header.segmentation {
+                quantizer = self.segment_header.quantizers[segment];
+
+                if !self.segment_header.absolute_values {
+                    quantizer += base;
+                }
+            }
+
+            let index = |delta: i32, maximum: i32| (quantizer + delta).clamp(0, maximum) as usize;
+
+            self.quantizers[segment] = Quantizers {
+                luma: (DC_QUANTIZERS[index(luma_dc_delta, 127)], AC_QUANTIZERS[index(0, 127)]),
+                // Multiplied by 155 / 100
+                y2: (DC_QUANTIZERS[index(y2_dc_delta, 127)] * 2, ((AC_QUANTIZERS[index(y2_ac_delta, 127)] * 101581) >> 16).max(8)),
+                chroma: (DC_QUANTIZERS[index(chroma_dc_delta, 117)], AC_QUANTIZERS[index(chroma_ac_delta, 127)]),
+            };
+        }
+    }
+
+    fn read_probabilities(&mut self, decoder: &mut BoolDecoder) {
+        for (plane_type, bands) in self.probabilities.iter_mut().enumerate() {
+            for (band, contexts) in bands.iter_mut().enumerate() {
+                for (context, probabilities) in contexts.iter_mut().enumerate() {
+                    for (node, probability) in probabilities.iter_mut().enumerate() {
+                        if decoder.read_bool(COEFFICIENT_UPDATE_PROBABILITIES[plane_type][band][context][node]) {
+                            *probability = decoder.read_literal(8) as u8;
+                        }
+                    }
+                }
+            }
+        }
+
+        if decoder.read_flag() {
+            self.skip_probability = Some(decoder.read_literal(8) as u8);
+        }
+    }
+
+    // Modes come from the first partition, coefficients from the partition of the macroblock row
+    fn decode_macroblocks(&mut self, first_partition: &mut BoolDecoder, partitions: &mut [BoolDecoder]) -> Result<()> {
+        let partition_count: usize = partitions.len();
+
+        for macroblock_y in 0..self.macroblocks_height {
+            let partition: &mut BoolDecoder = &mut partitions[macroblock_y & (partition_count - 1)];
+            self.left_modes = [PredictionMode::DC; 4];
+            self.left_non_zero = NonZero::default();
+
+            for macroblock_x in 0..self.macroblocks_width {
+                let macroblock: Macroblock = self.read_modes(first_partition, macroblock_x);
+                let mut coefficients: [i16; 384] = [0; 384];
+                let mut non_zero: bool = false;
+
+                if macroblock.skip {
+                    // Second order block contexts are kept by the macroblocks without one
+                    let top: &mut NonZero = &mut self.top_non_zero[macroblock_x];
+                    *top = NonZero { y2: macroblock.subblocks && top.y2, ..NonZero::default() };
+                    self.left_non_zero = NonZero { y2: macroblock.subblocks && self.left_non_zero.y2, ..NonZero::default() };
+                } else {
+                    non_zero = self.read_residuals(partition, macroblock_x, &macroblock, &mut coefficients);
+                }
+
+                let mut strength: FilterStrength = self.filter_strengths[macroblock.segment][macroblock.subblocks as usize];
+                strength.inner |= non_zero;
+                self.filters.push(strength);
+
+                self.reconstruct(macroblock_x, macroblock_y, &macroblock, &coefficients);
+            }
+
+            first_partition.check_end("first partition")?;
+            partition.check_end("coefficient partition")?;
+        }
+
+        Ok(())
+    }
+
+    fn read_modes(&mut self, decoder: &mut BoolDecoder, macroblock_x: usize) -> Macroblock {
+        let mut macroblock: Macroblock = Macroblock::default();
+
+        if self.segment_header.update_map {
+            let probabilities: [u8; 3] = self.segment_header.probabilities;
+
+            macroblock.segment = match decoder.read_bool(probabilities[0]) {
+                false => decoder.read_bool(probabilities[1]) as usize,
+                true => decoder.read_bool(probabilities[2]) as usize + 2,
+            };
+        }
+
+        if let Some(probability) = self.skip_probability {
+            macroblock.skip = decoder.read_bool(probability);
+        }
+
+        macroblock.subblocks = !decoder.read_bool(145);
+        let top_modes: &mut [PredictionMode] = &mut self.top_modes[4 * macroblock_x..4 * macroblock_x + 4];
+
+        if !macroblock.subblocks {
+            macroblock.luma = match decoder.read_bool(156) {
+                true => if decoder.read_bool(128) { PredictionMode::TM } else { PredictionMode::HE },
+                false => if decoder.read_bool(163) { PredictionMode::VE } else { PredictionMode::DC },
+            };
+
+            // Subblocks of the following macroblocks see the whole block mode as theirs
+            top_modes.fill(macroblock.luma);
+            self.left_modes.fill(macroblock.luma);
+        } else {
+            for y in 0..4 {
+                let mut mode: PredictionMode = self.left_modes[y];
+
+                for x in 0..4 {
+                    mode = read_subblock_mode(decoder, &SUBBLOCK_MODE_PROBABILITIES[top_modes[x] as usize][mode as usize]);
+                    top_modes[x] = mode;
+                    macroblock.subblock_modes[y * 4 + x] = mode;
+                }
+
+                self.left_modes[y] = mode;
+            }
+        }
+
+        macroblock.chroma = match decoder.read_bool(142) {
+            false => PredictionMode::DC,
+            true if !decoder.read_bool(114) => PredictionMode::VE,
+            true => if decoder.read_bool(183) { PredictionMode::TM } else { PredictionMode::HE },
+        };
+
+        macroblock
+    }
+
+    // Dequantized coefficients of the macroblock, returning whether any of them isn't zero
+    fn read_residuals(&mut self, decoder: &mut BoolDecoder, macroblock_x: usize, macroblock: &Macroblock, coefficients: &mut [i16; 384]) -> bool {
+        let quantizers: Quantizers = self.quantizers[macroblock.segment];
+        let top: &mut NonZero = &mut self.top_non_zero[macroblock_x];
+        let left: &mut NonZero = &mut self.left_non_zero;
+        let mut non_zero: bool = false;
+
+        // Luma DC coefficients of whole block predictions are coded apart in a second order block
+        let (first, luma_type): (usize, usize) = match macroblock.subblocks {
+            true => (0, LUMA),
+            false => {
+                let mut y2: [i16; 16] = [0; 16];
+                let context: usize = top.y2 as usize + left.y2 as usize;
+                let count: usize = read_coefficients(decoder, &self.probabilities[Y2], context, quantizers.y2, 0, &mut y2);
+
+                top.y2 = count > 0;
+                left.y2 = count > 0;
+                inverse_walsh_hadamard(&y2, coefficients);
+
+                (1, LUMA_AFTER_Y2)
+            },
+        };
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let block: &mut [i16] = &mut coefficients[(y * 4 + x) * 16..(y * 4 + x + 1) * 16];
+                let context: usize = top.luma[x] as usize + left.luma[y] as usize;
+                let count: usize = read_coefficients(decoder, &self.probabilities[luma_type], context, quantizers.luma, first, block);
+
+                top.luma[x] = count > first;
+                left.luma[y] = count > first;
+                non_zero |= count > 1 || block[0] != 0;
+            }
+        }
+
+        for (offset, top, left) in [(U_COEFFICIENTS, &mut top.u, &mut left.u), (V_COEFFICIENTS, &mut top.v, &mut left.v)] {
+            // 2x2 blocks in raster order
+            for index in 0..4 {
+                let (x, y): (usize, usize) = (index % 2, index / 2);
+                let start: usize = offset + index * 16;
+                let block: &mut [i16] = &mut coefficients[start..start + 16];
+                let context: usize = top[x] as usize + left[y] as usize;
+                let count: usize = read_coefficients(decoder, &self.probabilities[CHROMA], context, quantizers.chroma, 0, block);
+
+                top[x] = count > 0;
+                left[y] = count > 0;
+                non_zero |= count > 1 || block[0] != 0;
+            }
+        }
+
+        non_zero
+    }
+
+    // Predicts the macroblock from its unfiltered neighbours and adds the residuals
+    fn reconstruct(&mut self, macroblock_x: usize, macroblock_y: usize, macroblock: &Macroblock, coefficients: &[i16; 384]) {
+        let origin: usize = STRIDE + 1;
+        let mut workspace: [u8; STRIDE * 17] = [0; STRIDE * 17];
+        let stride: usize = self.macroblocks_width * 16;
+        let last_column: bool = macroblock_x == self.macroblocks_width - 1;
+
+        load_edges(&mut workspace, &self.luma, stride, macroblock_x * 16, macroblock_y * 16, 16);
+
+        // Right column of subblocks reuses the pixels above and to the right of the macroblock
+        if macroblock_y > 0 {
+            let above: usize = (macroblock_y * 16 - 1) * stride + macroblock_x * 16;
+
+            for x in 0..4 {
+                workspace[origin - STRIDE + 16 + x] = match last_column {
+                    true => self.luma[above + 15],
+                    false => self.luma[above + 16 + x],
+                };
+            }
+        }
+
+        for row in [3, 7, 11] {
+            workspace.copy_within(origin - STRIDE + 16..origin - STRIDE + 20, origin + row * STRIDE + 16);
+        }
+
+        let (has_top, has_left): (bool, bool) = (macroblock_y > 0, macroblock_x > 0);
+
+        if !macroblock.subblocks {
+            predict_block(&mut workspace, origin, 16, macroblock.luma, has_top, has_left);
+        }
+
+        for (index, block) in coefficients[..LUMA_COEFFICIENTS].chunks_exact(16).enumerate() {
+            let block_origin: usize = origin + (index / 4) * 4 * STRIDE + (index % 4) * 4;
+
+            if macroblock.subblocks {
+                predict_subblock(&mut workspace, block_origin, macroblock.subblock_modes[index]);
+            }
+
+            if block.iter().any(|&coefficient| coefficient != 0) {
+                inverse_transform(block, &mut workspace, block_origin);
+            }
+        }
+
+        store_block(&workspace, &mut self.luma, stride, macroblock_x * 16, macroblock_y * 16, 16);
+
+        let stride: usize = self.macroblocks_width * 8;
+
+        for (offset, plane) in [(U_COEFFICIENTS, &mut self.u), (V_COEFFICIENTS, &mut self.v)] {
+            let mut workspace: [u8; STRIDE * 9] = [0; STRIDE * 9];
+            load_edges(&mut workspace, plane, stride, macroblock_x * 8, macroblock_y * 8, 8);
+            predict_block(&mut workspace, origin, 8, macroblock.chroma, has_top, has_left);
+
+            for (index, block) in coefficients[offset..offset + 4 * 16].chunks_exact(16).enumerate() {
+                if block.iter().any(|&coefficient| coefficient != 0) {
+                    inverse_transform(block, &mut workspace, origin + (index / 2) * 4 * STRIDE + (index % 2) * 4);
+                }
+            }
+
+            store_block(&workspace, plane, stride, macroblock_x * 8, macroblock_y * 8, 8);
+        }
+    }
+
+    // Loop filter runs over the whole frame, predictions having used the unfiltered pixels
+    fn filter(&mut self) {
+        if self.header.filter_type == FilterType::NONE {
+            return;
+        }
+
+        let filter_type: FilterType = self.header.filter_type;
+        let mut luma: Plane = Plane {
+            pixels: &mut self.luma,
+            stride: self.macroblocks_width * 16,
+        };
+        let mut u: Plane = Plane {
+            pixels: &mut self.u,
+            stride: self.macroblocks_width * 8,
+        };
+        let mut v: Plane = Plane {
+            pixels: &mut self.v,
+            stride: self.macroblocks_width * 8,
+        };
+
+        for (index, strength) in self.filters.iter().enumerate() {
+            if strength.limit == 0 {
+                continue;
+            }
+
+            let (x, y): (usize, usize) = (index % self.macroblocks_width, index / self.macroblocks_width);
+            luma.filter_block(x * 16, y * 16, 16, filter_type, strength);
+
+            if filter_type == FilterType::NORMAL {
+                u.filter_block(x * 8, y * 8, 8, filter_type, strength);
+                v.filter_block(x * 8, y * 8, 8, filter_type, strength);
+            }
+        }
+    }
+
+    // Chroma is upsampled by weighting the 4 closest samples 9, 3, 3 and 1
+    fn to_rgb(&self) -> Vec<(u8, u8, u8)> {
+        let (width, height): (usize, usize) = (self.header.width as usize, self.header.height as usize);
+        let (chroma_width, chroma_height): (usize, usize) = (width.div_ceil(2), height.div_ceil(2));
+        let chroma_stride: usize = self.macroblocks_width * 8;
+        let mut pixels: Vec<(u8, u8, u8)> = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            let near_row: usize = y >> 1;
+            let far_row: usize = if y & 1 == 1 { (near_row + 1).min(chroma_height - 1) } else { near_row.saturating_sub(1) };
+
+            for x in 0..width {
+                let near_column: usize = x >> 1;
+                let far_column: Option<usize> = match x & 1 {
+                    1 if near_column + 1 < chroma_width => Some(near_column + 1),
+                    0 if x > 0 => Some(near_column - 1),
+                    _ => None,
+                };
+
+                let upsample = |plane: &[u8]| {
+                    let sample = |row: usize, column: usize| plane[row * chroma_stride + column] as u32;
+                    let near: u32 = sample(near_row, near_column);
+                    let vertical: u32 = sample(far_row, near_column);
+
+                    match far_column {
+                        None => ((3 * near + vertical + 2) >> 2) as i32,
+                        Some(far_column) => {
+                            let horizontal: u32 = sample(near_row, far_column);
+                            let diagonal: u32 = sample(far_row, far_column);
+                            ((((near + diagonal + 3 * (horizontal + vertical) + 8) >> 3) + near) >> 1) as i32
+                        },
+                    }
+                };
+
+                pixels.push(yuv_to_rgb(self.luma[y * self.macroblocks_width * 16 + x] as i32, upsample(&self.u), upsample(&self.v)));
+            }
+        }
+
+        pixels
+    }
+}
+
+// Edges of the block at the position, or their defaults outside the frame
+fn load_edges(workspace: &mut [u8], plane: &[u8], stride: usize, x: usize, y: usize, size: usize) {
+    let origin: usize = STRIDE + 1;
+
+    if y == 0 {
+        workspace[..origin + size + 4].fill(127);
+    } else {
+        let above: usize = (y - 1) * stride + x;
+        workspace[origin - STRIDE - 1] = if x == 0 { 129 } else { plane[above - 1] };
+        workspace[origin - STRIDE..origin - STRIDE + size].copy_from_slice(&plane[above..above + size]);
+    }
+
+    for row in 0..size {
+        workspace[origin + row * STRIDE - 1] = if x == 0 { 129 } else { plane[(y + row) * stride + x - 1] };
+    }
+}
+
+fn store_block(workspace: &[u8], plane: &mut [u8], stride: usize, x: usize, y: usize, size: usize) {
+    let origin: usize = STRIDE + 1;
+
+    for row in 0..size {
+        let start: usize = (y + row) * stride + x;
+        plane[start..start + size].copy_from_slice(&workspace[origin + row * STRIDE..origin + row * STRIDE + size]);
+    }
+}
+
+fn read_subblock_mode(decoder: &mut BoolDecoder, probabilities: &[u8; 9]) -> PredictionMode {
+    if !decoder.read_bool(probabilities[0]) {
+        return PredictionMode::DC;
+    }
+
+    if !decoder.read_bool(probabilities[1]) {
+        return PredictionMode::TM;
+    }
+
+    if !decoder.read_bool(probabilities[2]) {
+        return PredictionMode::VE;
+    }
+
+    match decoder.read_bool(probabilities[3]) {
+        false if !decoder.read_bool(probabilities[4]) => PredictionMode::HE,
+        false => if decoder.read_bool(probabilities[5]) { PredictionMode::VR } else { PredictionMode::RD },
+        true if !decoder.read_bool(probabilities[6]) => PredictionMode::LD,
+        true if !decoder.read_bool(probabilities[7]) => PredictionMode::VL,
+        true => if decoder.read_bool(probabilities[8]) { PredictionMode::HU } else { PredictionMode::HD },
+    }
+}
+
+// Tokens of a 4x4 block from its first coefficient, returning the position after the last non-zero one
+fn read_coefficients(decoder: &mut BoolDecoder, probabilities: &CoefficientProbabilities, context: usize, quantizers: (i32, i32), first: usize, block: &mut [i16]) -> usize {
+    let mut position: usize = first;
+    let mut node: &[u8; 11] = &probabilities[BANDS[position]][context];
+
+    while position < 16 {
+        if !decoder.read_bool(node[0]) {
+            return position;
+        }
+
+        // Zeros are coded without end of block check, as one can't follow them
+        while !decoder.read_bool(node[1]) {
+            position += 1;
+
+            if position == 16 {
+                return 16;
+            }
+
+            node = &probabilities[BANDS[position]][0];
+        }
+
+        let value: i32 = match decoder.read_bool(node[2]) {
+            false => {
+                node = &probabilities[BANDS[position + 1]][1];
+                1
+            },
+            true => {
+                let value: i32 = read_large_value(decoder, node);
+                node = &probabilities[BANDS[position + 1]][2];
+                value
+            },
+        };
+
+        let quantizer: i32 = if position > 0 { quantizers.1 } else { quantizers.0 };
+        let value: i32 = if decoder.read_flag() { -value } else { value };
+        block[ZIGZAG[position]] = (value * quantizer) as i16;
+        position += 1;
+    }
+
+    16
+}
+
+// Values above 1, the largest ones being categories followed by extra bits
+fn read_large_value(decoder: &mut BoolDecoder, node: &[u8; 11]) -> i32 {
+    if !decoder.read_bool(node[3]) {
+        if !decoder.read_bool(node[4]) {
+            return 2;
+        }
+
+        return 3 + decoder.read_bool(node[5]) as i32;
+    }
+
+    if !decoder.read_bool(node[6]) {
+        if !decoder.read_bool(node[7]) {
+            return 5 + decoder.read_bool(159) as i32;
+        }
+
+        return 7 + 2 * decoder.read_bool(165) as i32 + decoder.read_bool(145) as i32;
+    }
+
+    let high: usize = decoder.read_bool(node[8]) as usize;
+    let category: usize = 2 * high + decoder.read_bool(node[9 + high]) as usize;
+    let extra: i32 = LARGE_VALUE_PROBABILITIES[category].iter().fold(0, |value, &probability| 2 * value + decoder.read_bool(probability) as i32);
+
+    3 + (8 << category) + extra
+}
+
+// BT.601 limited range conversion in 14 bits fixed point
+fn yuv_to_rgb(y: i32, u: i32, v: i32) -> (u8, u8, u8) {
+    let multiply = |value: i32, coefficient: i32| (value * coefficient) >> 8;
+    let clip = |value: i32| match value {
+        0..=16383 => (value >> 6) as u8,
+        ..0 => 0,
+        _ => 255,
+    };
+
+    let luma: i32 = multiply(y, 19077);
+
+    (
+        clip(luma + multiply(v, 26149) - 14234),
+        clip(luma - multiply(u, 6419) - multiply(v, 13320) + 8708),
+        clip(luma + multiply(u, 33050) - 17685),
+    )
+}
+
+impl fmt::Display for VP8Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "width: {}", self.width)?;
+        writeln!(f, "height: {}", self.height)?;
+        writeln!(f, "horizontal_scale: {}", self.horizontal_scale)?;
+        writeln!(f, "vertical_scale: {}", self.vertical_scale)?;
+        writeln!(f, "profile: {}", self.profile)?;
+        writeln!(f, "color_space: {}", self.color_space)?;
+        writeln!(f, "clamping_type: {}", self.clamping_type)?;
+        writeln!(f, "segmentation: {}", self.segmentation)?;
+        writeln!(f, "filter_type: {:?}", self.filter_type)?;
+        writeln!(f, "filter_level: {}", self.filter_level)?;
+        writeln!(f, "sharpness: {}", self.sharpness)?;
+        writeln!(f, "partitions: {}", self.partitions)?;
+        writeln!(f, "quantizer: {}", self.quantizer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::bool_decoder::tests::BoolEncoder;
+    use super::*;
+
+    // Key frame of whole block predicted macroblocks without coefficients, in the order of the given luma modes
+    pub(crate) fn key_frame(width: u16, height: u16, modes: &[PredictionMode]) -> Vec<u8> {
+        let mut encoder: BoolEncoder = BoolEncoder::new();
+        // Color space, clamping type, segmentation, filter type, level and sharpness, filter deltas and partitions
+        encoder.write_literal(0, 3);
+        encoder.write_literal(0, 1 + 6 + 3 + 1 + 2);
+        // Base quantizer without deltas and refresh of the probabilities
+        encoder.write_literal(10, 7);
+        encoder.write_literal(0, 5 + 1);
+
+        for probability in COEFFICIENT_UPDATE_PROBABILITIES.iter().flatten().flatten().flatten() {
+            encoder.write_bool(*probability, false);
+        }
+
+        // Every macroblock is skipped
+        encoder.write_bool(128, true);
+        encoder.write_literal(1, 8);
+
+        for mode in modes {
+            encoder.write_bool(1, true);
+            encoder.write_bool(145, true);
+
+            let (first, second): (bool, bool) = match mode {
+                PredictionMode::DC => (false, false),
+                PredictionMode::VE => (false, true),
+                PredictionMode::HE => (true, false),
+                _ => (true, true),
+            };
+            encoder.write_bool(156, first);
+            encoder.write_bool(if first { 128 } else { 163 }, second);
+            encoder.write_bool(142, false);
+        }
+
+        let partition: Vec<u8> = encoder.finish();
+        // Key frame shown, followed by the length of its first partition
+        let tag: u32 = 0x10 | (partition.len() as u32) << 5;
+
+        let mut data: Vec<u8> = tag.to_le_bytes()[..3].to_vec();
+        data.extend_from_slice(&[0x9D, 0x01, 0x2A]);
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&partition);
+        data
+    }
+
+    #[test]
+    fn predictions_start_from_the_frame_edges() {
+        // Top edge is 127, left edge 129 and both of them missing 128, converted from limited range
+        for (mode, gray) in [(PredictionMode::DC, 130), (PredictionMode::VE, 129), (PredictionMode::HE, 132)] {
+            let (header, pixels): (VP8Header, Pixels) = decode(&key_frame(16, 16, &[mode])).unwrap();
+
+            assert_eq!((header.width, header.height, header.quantizer, header.partitions), (16, 16, 10, 1));
+            assert_eq!(pixels, vec![(gray, gray, gray); 256], "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn frames_are_cropped_from_their_macroblocks() {
+        let (_, pixels): (VP8Header, Pixels) = decode(&key_frame(20, 3, &[PredictionMode::VE, PredictionMode::HE])).unwrap();
+
+        assert_eq!(pixels.len(), 60);
+        assert!(pixels.iter().all(|&pixel| pixel == (129, 129, 129)));
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let error: String = decode(&key_frame(16383, 16383, &[])).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn interframes_are_rejected() {
+        let mut data: Vec<u8> = key_frame(16, 16, &[PredictionMode::DC]);
+        data[0] |= 0x01;
+        let error: String = decode(&data).unwrap_err().to_string();
+
+        assert!(error.contains("not a key frame"), "{error}");
+    }
+
+    #[test]
+    fn truncated_partitions_are_rejected() {
+        let mut data: Vec<u8> = key_frame(16, 16, &[PredictionMode::DC]);
+        data.truncate(11);
+        let error: String = decode(&data).unwrap_err().to_string();
+
+        assert!(error.contains("goes past the chunk"), "{error}");
+    }
+}
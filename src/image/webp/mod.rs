@@ -0,0 +1,272 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use alpha::decode_alpha;
+use animation::Animation;
+use chunk::Chunk;
+use header::{VP8XHeader, WEBPHeader};
+use lossless::VP8LHeader;
+use lossy::VP8Header;
+use webp_bit_reader::WebpBitReader;
+use super::bmp::BMP;
+use super::Image;
+
+pub mod alpha;
+pub mod animation;
+mod bool_decoder;
+pub mod chunk;
+pub mod header;
+mod loop_filter;
+pub mod lossless;
+pub mod lossy;
+mod predict;
+mod prefix_code;
+mod tables;
+mod transform;
+pub mod webp_bit_reader;
+
+pub const RIFF_SIGNATURE: &[u8; 4] = b"RIFF";
+pub const WEBP_SIGNATURE: &[u8; 4] = b"WEBP";
+
+#[derive(Clone, Debug)]
+pub struct WEBP {
+    header: WEBPHeader,
+    pub animation: Option<Animation>,
+    image: BMP,
+}
+
+impl WEBP {
+    // Chunks of the RIFF container, whose size may be shorter than the file
+    pub fn read_chunks(reader: &mut WebpBitReader) -> Result<(u32, Vec<Chunk>)> {
+        if reader.read_bytes(4)? != RIFF_SIGNATURE {
+            bail!("Not a RIFF file");
+        }
+
+        let file_size: u32 = reader.read_double()?.swap_bytes();
+
+        if reader.read_bytes(4)? != WEBP_SIGNATURE {
+            bail!("RIFF file is not a WebP file");
+        }
+
+        let end: u64 = reader.length()?.min(8 + file_size as u64);
+        let chunks: Vec<Chunk> = Chunk::read_all(reader, end)?;
+
+        Ok((file_size, chunks))
+    }
+
+    // Pixels of a VP8L chunk, or of a VP8 chunk with the alpha of the ALPH chunk preceding it
+    pub fn decode_bitstream(bitstream: &Chunk, alpha: Option<&Chunk>, debug: bool) -> Result<BMP> {
+        match &bitstream.fourcc {
+            b"VP8L" => {
+                let (header, pixels): (VP8LHeader, Vec<u32>) = lossless::decode(&bitstream.data)?;
+
+                if debug {
+                    println!("VP8L:\n{header}");
+                }
+
+                let width: usize = header.width as usize;
+
+                Ok(BMP::from_rgba_pixels(header.width, header.height, |x, y| {
+                    let pixel: u32 = pixels[y * width + x];
+                    ((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, (pixel >> 24) as u8)
+                }))
+            },
+            b"VP8 " => {
+                let (header, pixels): (VP8Header, Vec<(u8, u8, u8)>) = lossy::decode(&bitstream.data)?;
+
+                if debug {
+                    println!("VP8:\n{header}");
+                }
+
+                let (width, height): (usize, usize) = (header.width as usize, header.height as usize);
+
+                // Opaque without ALPH chunk
+                let alpha: Vec<u8> = match alpha {
+                    Some(alpha) => decode_alpha(&alpha.data, width, height)?,
+                    None => vec![u8::MAX; width * height],
+                };
+
+                Ok(BMP::from_rgba_pixels(header.width, header.height, |x, y| {
+                    let (r, g, b): (u8, u8, u8) = pixels[y * width + x];
+                    (r, g, b, alpha[y * width + x])
+                }))
+            },
+            _ => bail!("WebP chunk {} is not an image", bitstream.name()),
+        }
+    }
+}
+
+impl Image for WEBP {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: WebpBitReader = WebpBitReader::new(stream);
+        let (file_size, chunks): (u32, Vec<Chunk>) = Self::read_chunks(&mut reader)?;
+        let mut header: WEBPHeader = WEBPHeader::from_chunk_names(file_size, chunks.iter().map(Chunk::name).collect());
+
+        let Some(first) = chunks.first() else {
+            bail!("WebP file has no chunk");
+        };
+
+        if &first.fourcc == b"VP8X" {
+            header.extended = Some(VP8XHeader::from_bytes(&first.data)?);
+        }
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        // Animated files are shown as their first frame
+        if let Some(extended) = header.extended.as_ref().filter(|extended| extended.animation) {
+            let mut animation: Animation = Animation::from_chunks(extended, &chunks)?;
+
+            if debug {
+                println!("Animation:\n{animation}");
+            }
+
+            animation.draw_frame(0, debug)?;
+            let image: BMP = animation.to_image();
+
+            return Ok(Self {
+                header,
+                animation: Some(animation),
+                image,
+            });
+        }
+
+        // Alpha of an extended file comes right before its image, metadata chunks being ignored
+        let Some(position) = chunks.iter().position(|chunk| matches!(&chunk.fourcc, b"VP8 " | b"VP8L")) else {
+            bail!("WebP file has no VP8 or VP8L chunk");
+        };
+
+        let alpha: Option<&Chunk> = chunks[..position].iter().rfind(|chunk| &chunk.fourcc == b"ALPH");
+        let image: BMP = Self::decode_bitstream(&chunks[position], alpha, debug)?;
+
+        if let Some(extended) = &header.extended {
+            if (extended.canvas_width, extended.canvas_height) != (image.width(), image.height()) {
+                bail!("WebP image of {}x{} doesn't fill its canvas of {}x{}", image.width(), image.height(), extended.canvas_width, extended.canvas_height);
+            }
+        }
+
+        Ok(Self {
+            header,
+            animation: None,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for WEBP {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)?;
+
+        if let Some(animation) = &self.animation {
+            write!(f, "Animation:\n{animation}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::lossless::tests::vp8l;
+    use super::lossy::tests::key_frame;
+    use super::predict::PredictionMode;
+    use super::*;
+
+    // RIFF container of the given chunks, padded to even sizes
+    pub(crate) fn riff(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut body: Vec<u8> = WEBP_SIGNATURE.to_vec();
+
+        for (fourcc, data) in chunks {
+            body.extend_from_slice(*fourcc);
+            body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            body.extend_from_slice(data);
+
+            if data.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+
+        let mut bytes: Vec<u8> = RIFF_SIGNATURE.to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    // VP8X chunk of the given flags, its canvas size being stored minus one
+    pub(crate) fn vp8x(flags: u8, width: u32, height: u32) -> Vec<u8> {
+        let mut data: Vec<u8> = vec![flags, 0, 0, 0];
+        data.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        data.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        data
+    }
+
+    pub(crate) fn decode(bytes: Vec<u8>) -> Result<WEBP> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        WEBP::from_stream(BufReader::new(source), false)
+    }
+
+    #[test]
+    fn lossless_files_are_decoded() {
+        let webp: WEBP = decode(riff(&[(b"VP8L", vp8l(2, 1, &[], (&[100], 50, 25, 128), &[]))])).unwrap();
+
+        assert_eq!(webp.header.format, header::WEBPFormat::LOSSLESS);
+        assert_eq!(webp.image.pixel_color(1, 0), (50, 100, 25));
+        assert_eq!(webp.image.pixel_alpha(1, 0), 128);
+    }
+
+    #[test]
+    fn extended_files_skip_metadata() {
+        let webp: WEBP = decode(riff(&[(b"VP8X", vp8x(0x10, 2, 1)), (b"EXIF", vec![1, 2, 3]), (b"VP8L", vp8l(2, 1, &[], (&[100], 50, 25, 255), &[]))])).unwrap();
+
+        assert_eq!(webp.image.pixel_color(0, 0), (50, 100, 25));
+    }
+
+    #[test]
+    fn lossy_images_take_the_alpha_before_them() {
+        let alpha: Vec<u8> = [&[0][..], &[200; 256]].concat();
+        let webp: WEBP = decode(riff(&[(b"VP8X", vp8x(0x10, 16, 16)), (b"ALPH", alpha), (b"VP8 ", key_frame(16, 16, &[PredictionMode::DC]))])).unwrap();
+
+        assert_eq!(webp.image.pixel_color(15, 15), (130, 130, 130));
+        assert_eq!(webp.image.pixel_alpha(15, 15), 200);
+    }
+
+    #[test]
+    fn lossy_images_without_alpha_are_opaque() {
+        let webp: WEBP = decode(riff(&[(b"VP8 ", key_frame(16, 16, &[PredictionMode::VE]))])).unwrap();
+
+        assert_eq!(webp.header.format, header::WEBPFormat::LOSSY);
+        assert_eq!(webp.image.pixel_alpha(0, 0), u8::MAX);
+    }
+
+    #[test]
+    fn images_must_fill_their_canvas() {
+        let error: String = decode(riff(&[(b"VP8X", vp8x(0, 3, 1)), (b"VP8L", vp8l(2, 1, &[], (&[0], 0, 0, 255), &[]))])).unwrap_err().to_string();
+
+        assert!(error.contains("doesn't fill its canvas of 3x1"), "{error}");
+    }
+
+    #[test]
+    fn oversized_canvases_are_rejected() {
+        let error: String = decode(riff(&[(b"VP8X", vp8x(0, 1 << 24, 1 << 24))])).unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn chunks_past_the_end_of_the_file_are_rejected() {
+        let mut bytes: Vec<u8> = riff(&[(b"VP8L", vec![0; 4])]);
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error: String = decode(bytes).unwrap_err().to_string();
+
+        assert!(error.contains("runs past the end of the file"), "{error}");
+    }
+}
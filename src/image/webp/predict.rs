@@ -0,0 +1,226 @@
+// Macroblocks are predicted in a workspace holding their top and left edges, rows being STRIDE bytes apart
+pub const STRIDE: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PredictionMode {
+    // Average of the edges, the only mode of 16x16 and chroma blocks along with TM, VE and HE
+    #[default]
+    DC,
+    // True motion, left plus top minus top left
+    TM,
+    // Vertical
+    VE,
+    // Horizontal
+    HE,
+    // Down right
+    RD,
+    // Vertical right
+    VR,
+    // Down left
+    LD,
+    // Vertical left
+    VL,
+    // Horizontal down
+    HD,
+    // Horizontal up
+    HU,
+}
+
+fn average2(a: i32, b: i32) -> u8 {
+    ((a + b + 1) >> 1) as u8
+}
+
+fn average3(a: i32, b: i32, c: i32) -> u8 {
+    ((a + 2 * b + c + 2) >> 2) as u8
+}
+
+fn clip(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+// Whole 16x16 luma or 8x8 chroma block, DC using only the edges inside the frame
+pub fn predict_block(workspace: &mut [u8], origin: usize, size: usize, mode: PredictionMode, has_top: bool, has_left: bool) {
+    let top: Vec<u8> = workspace[origin - STRIDE..origin - STRIDE + size].to_vec();
+    let left: Vec<u8> = (0..size).map(|y| workspace[origin + y * STRIDE - 1]).collect();
+    let top_left: i32 = workspace[origin - STRIDE - 1] as i32;
+    let shift: u32 = size.trailing_zeros();
+
+    let sum = |edge: &[u8]| edge.iter().map(|&value| value as u32).sum::<u32>();
+
+    let dc: u8 = match (has_top, has_left) {
+        (true, true) => ((sum(&top) + sum(&left) + size as u32) >> (shift + 1)) as u8,
+        (true, false) => ((sum(&top) + (size as u32 >> 1)) >> shift) as u8,
+        (false, true) => ((sum(&left) + (size as u32 >> 1)) >> shift) as u8,
+        (false, false) => 0x80,
+    };
+
+    for y in 0..size {
+        for x in 0..size {
+            workspace[origin + y * STRIDE + x] = match mode {
+                PredictionMode::TM => clip(left[y] as i32 + top[x] as i32 - top_left),
+                PredictionMode::VE => top[x],
+                PredictionMode::HE => left[y],
+                _ => dc,
+            };
+        }
+    }
+}
+
+// 4x4 luma block, whose top edge goes 4 pixels past its right side
+pub fn predict_subblock(workspace: &mut [u8], origin: usize, mode: PredictionMode) {
+    let top: Vec<i32> = (0..8).map(|x| workspace[origin - STRIDE + x] as i32).collect();
+    let left: Vec<i32> = (0..4).map(|y| workspace[origin + y * STRIDE - 1] as i32).collect();
+    let top_left: i32 = workspace[origin - STRIDE - 1] as i32;
+
+    let (a, b, c, d, e, f, g, h): (i32, i32, i32, i32, i32, i32, i32, i32) = (top[0], top[1], top[2], top[3], top[4], top[5], top[6], top[7]);
+    let (i, j, k, l, x): (i32, i32, i32, i32, i32) = (left[0], left[1], left[2], left[3], top_left);
+
+    let rows: [[u8; 4]; 4] = match mode {
+        PredictionMode::DC => {
+            let dc: u8 = ((top[..4].iter().sum::<i32>() + left.iter().sum::<i32>() + 4) >> 3) as u8;
+            [[dc; 4]; 4]
+        },
+        PredictionMode::TM => {
+            let mut rows: [[u8; 4]; 4] = [[0; 4]; 4];
+
+            for (row, &left) in rows.iter_mut().zip(left.iter()) {
+                for (pixel, &top) in row.iter_mut().zip(top.iter()) {
+                    *pixel = clip(left + top - top_left);
+                }
+            }
+
+            rows
+        },
+        PredictionMode::VE => [[average3(x, a, b), average3(a, b, c), average3(b, c, d), average3(c, d, e)]; 4],
+        PredictionMode::HE => [
+            [average3(x, i, j); 4],
+            [average3(i, j, k); 4],
+            [average3(j, k, l); 4],
+            [average3(k, l, l); 4],
+        ],
+        PredictionMode::RD => {
+            // Diagonals going down right share the value of their edge pixel
+            let edge: [i32; 9] = [l, k, j, i, x, a, b, c, d];
+            let mut rows: [[u8; 4]; 4] = [[0; 4]; 4];
+
+            for (row_index, row) in rows.iter_mut().enumerate() {
+                for (column, pixel) in row.iter_mut().enumerate() {
+                    let index: usize = column + 3 - row_index;
+                    *pixel = average3(edge[index], edge[index + 1], edge[index + 2]);
+                }
+            }
+
+            rows
+        },
+        PredictionMode::LD => {
+            // Diagonals going down left share the value of their top pixel, the last one being repeated
+            let edge: [i32; 9] = [a, b, c, d, e, f, g, h, h];
+            let mut rows: [[u8; 4]; 4] = [[0; 4]; 4];
+
+            for (row_index, row) in rows.iter_mut().enumerate() {
+                for (column, pixel) in row.iter_mut().enumerate() {
+                    let index: usize = column + row_index;
+                    *pixel = average3(edge[index], edge[index + 1], edge[index + 2]);
+                }
+            }
+
+            rows
+        },
+        PredictionMode::VR => [
+            [average2(x, a), average2(a, b), average2(b, c), average2(c, d)],
+            [average3(i, x, a), average3(x, a, b), average3(a, b, c), average3(b, c, d)],
+            [average3(j, i, x), average2(x, a), average2(a, b), average2(b, c)],
+            [average3(k, j, i), average3(i, x, a), average3(x, a, b), average3(a, b, c)],
+        ],
+        PredictionMode::VL => [
+            [average2(a, b), average2(b, c), average2(c, d), average2(d, e)],
+            [average3(a, b, c), average3(b, c, d), average3(c, d, e), average3(d, e, f)],
+            [average2(b, c), average2(c, d), average2(d, e), average3(e, f, g)],
+            [average3(b, c, d), average3(c, d, e), average3(d, e, f), average3(f, g, h)],
+        ],
+        PredictionMode::HD => [
+            [average2(i, x), average3(i, x, a), average3(x, a, b), average3(a, b, c)],
+            [average2(j, i), average3(j, i, x), average2(i, x), average3(i, x, a)],
+            [average2(k, j), average3(k, j, i), average2(j, i), average3(j, i, x)],
+            [average2(l, k), average3(l, k, j), average2(k, j), average3(k, j, i)],
+        ],
+        PredictionMode::HU => [
+            [average2(i, j), average3(i, j, k), average2(j, k), average3(j, k, l)],
+            [average2(j, k), average3(j, k, l), average2(k, l), average3(k, l, l)],
+            [average2(k, l), average3(k, l, l), l as u8, l as u8],
+            [l as u8; 4],
+        ],
+    };
+
+    for (y, row) in rows.iter().enumerate() {
+        workspace[origin + y * STRIDE..origin + y * STRIDE + 4].copy_from_slice(row);
+    }
+}
+
+// Fixed point products by sqrt(2) * cos(pi / 8) and sqrt(2) * sin(pi / 8), computed wide to survive corrupt coefficients
+fn multiply_cos(value: i32) -> i32 {
+    ((value as i64 * 20091) >> 16) as i32 + value
+}
+
+fn multiply_sin(value: i32) -> i32 {
+    ((value as i64 * 35468) >> 16) as i32
+}
+
+// Adds the inverse DCT of the 16 coefficients to the predicted 4x4 block
+pub fn inverse_transform(coefficients: &[i16], workspace: &mut [u8], origin: usize) {
+    let mut columns: [i32; 16] = [0; 16];
+
+    // Vertical pass, each column of coefficients becoming a row of the intermediate block
+    for i in 0..4 {
+        let input = |row: usize| coefficients[row * 4 + i] as i32;
+
+        let a: i32 = input(0) + input(2);
+        let b: i32 = input(0) - input(2);
+        let c: i32 = multiply_sin(input(1)) - multiply_cos(input(3));
+        let d: i32 = multiply_cos(input(1)) + multiply_sin(input(3));
+
+        columns[i * 4..i * 4 + 4].copy_from_slice(&[a + d, b + c, b - c, a - d]);
+    }
+
+    for i in 0..4 {
+        let dc: i32 = columns[i] + 4;
+        let a: i32 = dc + columns[8 + i];
+        let b: i32 = dc - columns[8 + i];
+        let c: i32 = multiply_sin(columns[4 + i]) - multiply_cos(columns[12 + i]);
+        let d: i32 = multiply_cos(columns[4 + i]) + multiply_sin(columns[12 + i]);
+
+        for (x, value) in [a + d, b + c, b - c, a - d].into_iter().enumerate() {
+            let position: usize = origin + i * STRIDE + x;
+            workspace[position] = clip(workspace[position] as i32 + (value >> 3));
+        }
+    }
+}
+
+// Spreads the second order block to the DC coefficients of the 16 luma blocks
+pub fn inverse_walsh_hadamard(input: &[i16; 16], coefficients: &mut [i16]) {
+    let mut rows: [i32; 16] = [0; 16];
+
+    for i in 0..4 {
+        let a0: i32 = input[i] as i32 + input[12 + i] as i32;
+        let a1: i32 = input[4 + i] as i32 + input[8 + i] as i32;
+        let a2: i32 = input[4 + i] as i32 - input[8 + i] as i32;
+        let a3: i32 = input[i] as i32 - input[12 + i] as i32;
+
+        rows[i] = a0 + a1;
+        rows[8 + i] = a0 - a1;
+        rows[4 + i] = a3 + a2;
+        rows[12 + i] = a3 - a2;
+    }
+
+    for i in 0..4 {
+        let dc: i32 = rows[i * 4] + 3;
+        let a0: i32 = dc + rows[i * 4 + 3];
+        let a1: i32 = rows[i * 4 + 1] + rows[i * 4 + 2];
+        let a2: i32 = rows[i * 4 + 1] - rows[i * 4 + 2];
+        let a3: i32 = dc - rows[i * 4 + 3];
+
+        for (x, value) in [a0 + a1, a3 + a2, a0 - a1, a3 - a2].into_iter().enumerate() {
+            coefficients[(i * 4 + x) * 16] = (value >> 3) as i16;
+        }
+    }
+}
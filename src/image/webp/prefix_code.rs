@@ -0,0 +1,232 @@
+use anyhow::{bail, Result};
+
+use super::lossless::LosslessReader;
+
+const MAX_LENGTH: usize = 15;
+// Codes up to this length are looked up at once, longer ones being decoded bit by bit
+const TABLE_BITS: u32 = 10;
+
+// Order in which the code length code lengths are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+const LITERAL_COUNT: usize = 256;
+const LENGTH_PREFIX_COUNT: usize = 24;
+const DISTANCE_PREFIX_COUNT: usize = 40;
+
+// Canonical prefix code, as in deflate streams
+#[derive(Clone, Debug, Default)]
+pub struct PrefixCode {
+    // Symbol and code length, indexed by the reversed codes
+    table: Vec<(u16, u8)>,
+    bits: u32,
+    // Number of codes of each length, then symbols sorted by code
+    counts: [u16; MAX_LENGTH + 1],
+    symbols: Vec<u16>,
+    // Codes of a single symbol take no bits
+    single: Option<u16>,
+}
+
+impl PrefixCode {
+    fn from_lengths(lengths: &[u8]) -> Result<Self> {
+        let mut code: PrefixCode = PrefixCode::default();
+
+        for &length in lengths {
+            code.counts[length as usize] += 1;
+        }
+
+        code.counts[0] = 0;
+        let used: Vec<u16> = (0..lengths.len() as u16).filter(|&symbol| lengths[symbol as usize] > 0).collect();
+
+        match used[..] {
+            [] => bail!("Prefix code without any symbol"),
+            [symbol] => {
+                code.single = Some(symbol);
+                return Ok(code);
+            },
+            _ => (),
+        }
+
+        // Every code of the longest length must be taken, as libwebp requires
+        let mut left: i32 = 1;
+
+        for length in 1..=MAX_LENGTH {
+            left = (left << 1) - code.counts[length] as i32;
+
+            if left < 0 {
+                bail!("Over-subscribed prefix code");
+            }
+        }
+
+        if left != 0 {
+            bail!("Incomplete prefix code");
+        }
+
+        let mut next_codes: [u32; MAX_LENGTH + 1] = [0; MAX_LENGTH + 1];
+        let mut offsets: [usize; MAX_LENGTH + 1] = [0; MAX_LENGTH + 1];
+        let mut value: u32 = 0;
+
+        for length in 1..=MAX_LENGTH {
+            value = (value + code.counts[length - 1] as u32) << 1;
+            next_codes[length] = value;
+            offsets[length] = offsets[length - 1] + code.counts[length - 1] as usize;
+        }
+
+        code.bits = lengths.iter().copied().max().unwrap_or(0).min(TABLE_BITS as u8) as u32;
+        code.table = vec![(0, 0); 1 << code.bits];
+        code.symbols = vec![0; used.len()];
+
+        for symbol in used {
+            let length: u8 = lengths[symbol as usize];
+            let value: u32 = next_codes[length as usize];
+            next_codes[length as usize] += 1;
+
+            code.symbols[offsets[length as usize]] = symbol;
+            offsets[length as usize] += 1;
+
+            if length as u32 > code.bits {
+                continue;
+            }
+
+            let reversed: usize = (value.reverse_bits() >> (32 - length as u32)) as usize;
+
+            for index in (reversed..code.table.len()).step_by(1 << length) {
+                code.table[index] = (symbol, length);
+            }
+        }
+
+        Ok(code)
+    }
+
+    pub fn from_binary(reader: &mut LosslessReader, alphabet_size: usize) -> Result<Self> {
+        let mut lengths: Vec<u8> = vec![0; alphabet_size];
+
+        // Simple codes list their one or two symbols
+        if reader.read_bits(1)? == 1 {
+            let count: u32 = reader.read_bits(1)? + 1;
+            let first_bits: u32 = if reader.read_bits(1)? == 1 { 8 } else { 1 };
+            let mut symbols: Vec<usize> = vec![reader.read_bits(first_bits)? as usize];
+
+            if count == 2 {
+                symbols.push(reader.read_bits(8)? as usize);
+            }
+
+            for symbol in symbols {
+                if symbol >= alphabet_size {
+                    bail!("Symbol {symbol} is out of an alphabet of {alphabet_size}");
+                }
+
+                lengths[symbol] = 1;
+            }
+
+            return Self::from_lengths(&lengths);
+        }
+
+        let mut code_lengths: [u8; 19] = [0; 19];
+        let count: usize = reader.read_bits(4)? as usize + 4;
+
+        for &index in CODE_LENGTH_ORDER.iter().take(count) {
+            code_lengths[index] = reader.read_bits(3)? as u8;
+        }
+
+        let code_lengths_code: PrefixCode = Self::from_lengths(&code_lengths)?;
+
+        // Code lengths may stop before the end of the alphabet, the others being zero
+        let mut max_symbol: usize = alphabet_size;
+
+        if reader.read_bits(1)? == 1 {
+            let length_bits: u32 = 2 + 2 * reader.read_bits(3)?;
+            max_symbol = 2 + reader.read_bits(length_bits)? as usize;
+
+            if max_symbol > alphabet_size {
+                bail!("{max_symbol} code lengths for an alphabet of {alphabet_size}");
+            }
+        }
+
+        let mut symbol: usize = 0;
+        let mut previous: u8 = 8;
+
+        while symbol < alphabet_size && max_symbol > 0 {
+            max_symbol -= 1;
+
+            let (value, repeat): (u8, usize) = match code_lengths_code.decode(reader)? {
+                length @ 0..=15 => {
+                    if length != 0 {
+                        previous = length as u8;
+                    }
+
+                    (length as u8, 1)
+                },
+                16 => (previous, 3 + reader.read_bits(2)? as usize),
+                17 => (0, 3 + reader.read_bits(3)? as usize),
+                _ => (0, 11 + reader.read_bits(7)? as usize),
+            };
+
+            if symbol + repeat > alphabet_size {
+                bail!("Code lengths overflow an alphabet of {alphabet_size}");
+            }
+
+            lengths[symbol..symbol + repeat].fill(value);
+            symbol += repeat;
+        }
+
+        Self::from_lengths(&lengths)
+    }
+
+    pub fn decode(&self, reader: &mut LosslessReader) -> Result<u16> {
+        if let Some(symbol) = self.single {
+            return Ok(symbol);
+        }
+
+        let (symbol, length): (u16, u8) = self.table[reader.peek(self.bits) as usize];
+
+        if length != 0 {
+            reader.consume(length as u32)?;
+            return Ok(symbol);
+        }
+
+        // Codes are stored from their most significant bit
+        let bits: u32 = reader.peek(MAX_LENGTH as u32);
+        let mut value: u32 = 0;
+        let mut first: u32 = 0;
+        let mut index: usize = 0;
+
+        for length in 1..=MAX_LENGTH {
+            value |= (bits >> (length - 1)) & 1;
+            let count: u32 = self.counts[length] as u32;
+
+            if value < first + count {
+                reader.consume(length as u32)?;
+                return Ok(self.symbols[index + (value - first) as usize]);
+            }
+
+            index += count as usize;
+            first = (first + count) << 1;
+            value <<= 1;
+        }
+
+        bail!("Invalid prefix code")
+    }
+}
+
+// Codes of the pixels of one group of blocks
+#[derive(Clone, Debug)]
+pub struct PrefixGroup {
+    // Green channel, backward reference lengths and color cache indexes share a code
+    pub green: PrefixCode,
+    pub red: PrefixCode,
+    pub blue: PrefixCode,
+    pub alpha: PrefixCode,
+    pub distance: PrefixCode,
+}
+
+impl PrefixGroup {
+    pub fn from_binary(reader: &mut LosslessReader, cache_size: usize) -> Result<Self> {
+        Ok(Self {
+            green: PrefixCode::from_binary(reader, LITERAL_COUNT + LENGTH_PREFIX_COUNT + cache_size)?,
+            red: PrefixCode::from_binary(reader, LITERAL_COUNT)?,
+            blue: PrefixCode::from_binary(reader, LITERAL_COUNT)?,
+            alpha: PrefixCode::from_binary(reader, LITERAL_COUNT)?,
+            distance: PrefixCode::from_binary(reader, DISTANCE_PREFIX_COUNT)?,
+        })
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::Result;
+
+use super::lossless::{decode_entropy_image, LosslessReader};
+
+const BLACK: u32 = 0xFF000000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransformType {
+    PREDICTOR,
+    COLOR,
+    SUBTRACTGREEN,
+    COLORINDEXING,
+}
+
+#[derive(Clone, Debug)]
+pub struct Transform {
+    pub transform_type: TransformType,
+    // Width of the image the transform is undone on
+    pub width: usize,
+    // Block size of the predictor and color transforms, pixels packed in one for color indexing
+    pub bits: u32,
+    // Block modes and multipliers, or the color table
+    pub data: Vec<u32>,
+}
+
+impl Transform {
+    pub fn from_binary(reader: &mut LosslessReader, width: usize, height: usize) -> Result<Self> {
+        let mut transform: Transform = Transform {
+            transform_type: TransformType::SUBTRACTGREEN,
+            width,
+            bits: 0,
+            data: Vec::new(),
+        };
+
+        match reader.read_bits(2)? {
+            kind @ (0 | 1) => {
+                transform.transform_type = if kind == 0 { TransformType::PREDICTOR } else { TransformType::COLOR };
+                transform.bits = reader.read_bits(3)? + 2;
+                transform.data = decode_entropy_image(reader, width.div_ceil(1 << transform.bits), height.div_ceil(1 << transform.bits), false)?;
+            },
+            2 => (),
+            _ => {
+                transform.transform_type = TransformType::COLORINDEXING;
+                let size: usize = reader.read_bits(8)? as usize + 1;
+
+                // Colors are stored as differences to the previous one
+                transform.data = decode_entropy_image(reader, size, 1, false)?;
+
+                for index in 1..size {
+                    transform.data[index] = add_pixels(transform.data[index], transform.data[index - 1]);
+                }
+
+                transform.bits = match size {
+                    1..=2 => 3,
+                    3..=4 => 2,
+                    5..=16 => 1,
+                    _ => 0,
+                };
+            },
+        }
+
+        Ok(transform)
+    }
+
+    pub fn apply(&self, pixels: Vec<u32>, height: usize) -> Vec<u32> {
+        match self.transform_type {
+            TransformType::PREDICTOR => self.predict(pixels, height),
+            TransformType::COLOR => self.transform_colors(pixels),
+            TransformType::SUBTRACTGREEN => pixels.into_iter().map(add_green).collect(),
+            TransformType::COLORINDEXING => self.index_colors(&pixels, height),
+        }
+    }
+
+    // Mode of the block of a pixel, or its multipliers
+    fn block(&self, x: usize, y: usize) -> u32 {
+        let blocks_width: usize = self.width.div_ceil(1 << self.bits);
+
+        self.data[(y >> self.bits) * blocks_width + (x >> self.bits)]
+    }
+
+    fn predict(&self, mut pixels: Vec<u32>, height: usize) -> Vec<u32> {
+        let width: usize = self.width;
+
+        for y in 0..height {
+            for x in 0..width {
+                let position: usize = y * width + x;
+
+                // First row is predicted from the left, first column from the top
+                let prediction: u32 = match (x, y) {
+                    (0, 0) => BLACK,
+                    (_, 0) => pixels[position - 1],
+                    (0, _) => pixels[position - width],
+                    _ => {
+                        let left: u32 = pixels[position - 1];
+                        let top: u32 = pixels[position - width];
+                        let top_left: u32 = pixels[position - width - 1];
+                        // Rightmost pixels use the leftmost pixel of their own row
+                        let top_right: u32 = pixels[position - width + 1];
+
+                        match (self.block(x, y) >> 8) & 0x0F {
+                            1 => left,
+                            2 => top,
+                            3 => top_right,
+                            4 => top_left,
+                            5 => average(average(left, top_right), top),
+                            6 => average(left, top_left),
+                            7 => average(left, top),
+                            8 => average(top_left, top),
+                            9 => average(top, top_right),
+                            10 => average(average(left, top_left), average(top, top_right)),
+                            11 => select(left, top, top_left),
+                            12 => clamp_add_subtract_full(left, top, top_left),
+                            13 => clamp_add_subtract_half(average(left, top), top_left),
+                            _ => BLACK,
+                        }
+                    },
+                };
+
+                pixels[position] = add_pixels(pixels[position], prediction);
+            }
+        }
+
+        pixels
+    }
+
+    // Red and blue are predicted from green, and blue from red, by signed 3.5 fixed point multipliers
+    fn transform_colors(&self, mut pixels: Vec<u32>) -> Vec<u32> {
+        let width: usize = self.width;
+
+        for (position, pixel) in pixels.iter_mut().enumerate() {
+            let multipliers: u32 = self.block(position % width, position / width);
+            let (green_to_red, green_to_blue, red_to_blue): (i8, i8, i8) = (multipliers as i8, (multipliers >> 8) as i8, (multipliers >> 16) as i8);
+            let delta = |multiplier: i8, color: u8| ((multiplier as i32 * color as i8 as i32) >> 5) as u8;
+
+            let green: u8 = (*pixel >> 8) as u8;
+            let red: u8 = ((*pixel >> 16) as u8).wrapping_add(delta(green_to_red, green));
+            let blue: u8 = (*pixel as u8).wrapping_add(delta(green_to_blue, green)).wrapping_add(delta(red_to_blue, red));
+
+            *pixel = (*pixel & 0xFF00FF00) | ((red as u32) << 16) | blue as u32;
+        }
+
+        pixels
+    }
+
+    // Green channel holds indexes in the color table, several of them in one pixel for small tables
+    fn index_colors(&self, pixels: &[u32], height: usize) -> Vec<u32> {
+        let packed_width: usize = self.width.div_ceil(1 << self.bits);
+        let index_bits: u32 = 8 >> self.bits;
+        let mask: u32 = (1 << index_bits) - 1;
+        let mut output: Vec<u32> = Vec::with_capacity(self.width * height);
+
+        for y in 0..height {
+            for x in 0..self.width {
+                let green: u32 = (pixels[y * packed_width + (x >> self.bits)] >> 8) & 0xFF;
+                let shift: u32 = (x as u32 & ((1 << self.bits) - 1)) * index_bits;
+                let index: usize = ((green >> shift) & mask) as usize;
+
+                // Indexes past the table stand for transparent black
+                output.push(self.data.get(index).copied().unwrap_or(0));
+            }
+        }
+
+        output
+    }
+}
+
+fn add_pixels(a: u32, b: u32) -> u32 {
+    let alpha_green: u32 = (a & 0xFF00FF00).wrapping_add(b & 0xFF00FF00) & 0xFF00FF00;
+    let red_blue: u32 = (a & 0x00FF00FF).wrapping_add(b & 0x00FF00FF) & 0x00FF00FF;
+
+    alpha_green | red_blue
+}
+
+fn add_green(pixel: u32) -> u32 {
+    let green: u32 = (pixel >> 8) & 0xFF;
+
+    add_pixels(pixel, (green << 16) | green)
+}
+
+fn channel(pixel: u32, index: u32) -> i32 {
+    ((pixel >> (index * 8)) & 0xFF) as i32
+}
+
+fn clamp_add_subtract_full(a: u32, b: u32, c: u32) -> u32 {
+    (0..4).fold(0, |pixel, index| pixel | ((channel(a, index) + channel(b, index) - channel(c, index)).clamp(0, 255) as u32) << (index * 8))
+}
+
+fn clamp_add_subtract_half(a: u32, b: u32) -> u32 {
+    (0..4).fold(0, |pixel, index| pixel | ((channel(a, index) + (channel(a, index) - channel(b, index)) / 2).clamp(0, 255) as u32) << (index * 8))
+}
+
+fn average(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xFEFEFEFE) >> 1) + (a & b)
+}
+
+// Left or top pixel, whichever is closer to the gradient estimate left + top - top left
+fn select(left: u32, top: u32, top_left: u32) -> u32 {
+    let distance = |a: u32, b: u32| (0..4).map(|index| (channel(a, index) - channel(b, index)).abs()).sum::<i32>();
+
+    // Distance of the estimate to left is the one of top to top left, and the other way round
+    if distance(top, top_left) < distance(left, top_left) { left } else { top }
+}
@@ -0,0 +1,92 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::pixel_count;
+
+#[derive(Clone, Debug, Default)]
+pub struct XBMHeader {
+    // Prefix of the defines and of the array
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub hotspot: Option<(u32, u32)>,
+    // X10 bitmaps are arrays of 16 bits values
+    pub x10: bool,
+}
+
+impl XBMHeader {
+    // Header made of the defines of the C source and the declaration of its array, with the position of its values
+    pub fn from_text(text: &str) -> Result<(Self, usize)> {
+        let mut header: XBMHeader = XBMHeader::default();
+        let (mut width, mut height, mut x_hot, mut y_hot): (Option<u32>, Option<u32>, Option<u32>, Option<u32>) = (None, None, None, None);
+
+        let Some(bits_start) = text.find("_bits") else {
+            bail!("Not an XBM file");
+        };
+
+        for line in text[..bits_start].lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            let ["#define", name, value] = fields[..] else {
+                continue;
+            };
+
+            let Ok(value) = value.parse::<u32>() else {
+                bail!("XBM define {name} of \"{value}\" is invalid");
+            };
+
+            if let Some(prefix) = name.strip_suffix("_width") {
+                header.name = prefix.to_owned();
+                width = Some(value);
+            } else if name.ends_with("_height") {
+                height = Some(value);
+            } else if name.ends_with("_x_hot") {
+                x_hot = Some(value);
+            } else if name.ends_with("_y_hot") {
+                y_hot = Some(value);
+            }
+        }
+
+        let (Some(width), Some(height)) = (width, height) else {
+            bail!("XBM file defines no width or height");
+        };
+
+        (header.width, header.height) = (width, height);
+
+        if let (Some(x), Some(y)) = (x_hot, y_hot) {
+            header.hotspot = Some((x, y));
+        }
+
+        if width == 0 || height == 0 {
+            bail!("XBM image of {width}x{height} is empty");
+        }
+
+        pixel_count("XBM", width, height)?;
+
+        let Some(values_start) = text[bits_start..].find('{') else {
+            bail!("XBM array has no values");
+        };
+
+        // Declaration of the array goes from the end of the line of the last define to its values
+        let declaration_start: usize = text[..bits_start].rfind('\n').map_or(0, |position| position + 1);
+        header.x10 = text[declaration_start..bits_start + values_start].contains("short");
+
+        Ok((header, bits_start + values_start + 1))
+    }
+
+    // Bytes of a row, X10 ones being padded to 16 bits
+    pub fn row_size(&self) -> usize {
+        if self.x10 { (self.width as usize).div_ceil(16) * 2 } else { (self.width as usize).div_ceil(8) }
+    }
+}
+
+impl fmt::Display for XBMHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "name: {}\n", self.name)?;
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "hotspot: {:?}\n", self.hotspot)?;
+        write!(f, "x10: {}\n", self.x10)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use header::XBMHeader;
+use xbm_bit_reader::XbmBitReader;
+use super::bmp::BMP;
+use super::Image;
+
+mod header;
+mod xbm_bit_reader;
+
+#[derive(Clone, Debug)]
+pub struct XBM {
+    header: XBMHeader,
+    image: BMP,
+}
+
+impl XBM {
+    // Bytes of the array, X10 values of 16 bits being split from their lower byte
+    fn read_values(text: &str, x10: bool) -> Result<Vec<u8>> {
+        let Some(end) = text.find('}') else {
+            bail!("XBM array is not closed");
+        };
+
+        let mut bytes: Vec<u8> = Vec::new();
+
+        for token in text[..end].split(',').map(str::trim).filter(|token| !token.is_empty()) {
+            let value: Result<u16, _> = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+                Some(digits) => u16::from_str_radix(digits, 16),
+                None => token.parse::<u16>(),
+            };
+
+            let Ok(value) = value else {
+                bail!("XBM value \"{token}\" is invalid");
+            };
+
+            if x10 {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            } else if let Ok(byte) = u8::try_from(value) {
+                bytes.push(byte);
+            } else {
+                bail!("XBM value \"{token}\" is larger than a byte");
+            }
+        }
+
+        Ok(bytes)
+    }
+}
+
+impl Image for XBM {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: XbmBitReader = XbmBitReader::new(stream);
+        let length: u64 = reader.length()?;
+        let text: String = String::from_utf8_lossy(&reader.read_bytes(length as usize)?).into_owned();
+        let (header, start): (XBMHeader, usize) = XBMHeader::from_text(&text)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let bytes: Vec<u8> = Self::read_values(&text[start..], header.x10)?;
+        let row_size: usize = header.row_size();
+
+        if bytes.len() < row_size * header.height as usize {
+            bail!("XBM array holds {} bytes instead of {}", bytes.len(), row_size * header.height as usize);
+        }
+
+        // Least significant bit of each byte is the leftmost, set bits being black
+        let image: BMP = BMP::from_pixels(header.width, header.height, |x, y| {
+            let bit: u8 = (bytes[y * row_size + x / 8] >> (x % 8)) & 0x1;
+            let level: u8 = if bit == 1 { 0 } else { u8::MAX };
+            (level, level, level)
+        });
+
+        Ok(Self {
+            header,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for XBM {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn decode(text: &str) -> Result<XBM> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(text.as_bytes().to_vec()));
+        XBM::from_stream(BufReader::new(source), false)
+    }
+
+    fn levels(image: &BMP) -> Vec<u8> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| image.pixel_color(x, y).0)).collect()
+    }
+
+    #[test]
+    fn set_bits_are_black_from_the_lowest() {
+        let xbm: XBM = decode("#define cross_width 3\n#define cross_height 2\n#define cross_x_hot 1\n#define cross_y_hot 0\nstatic unsigned char cross_bits[] = {\n   0x05, 0X02 };\n").unwrap();
+
+        assert_eq!(xbm.header.name, "cross");
+        assert_eq!(xbm.header.hotspot, Some((1, 0)));
+        assert_eq!(levels(&xbm.image), [0, 255, 0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn x10_values_hold_16_pixels() {
+        let xbm: XBM = decode("#define wide_width 10\n#define wide_height 1\nstatic short wide_bits[] = { 0x0201 };").unwrap();
+
+        assert!(xbm.header.x10);
+        assert_eq!(levels(&xbm.image), [0, 255, 255, 255, 255, 255, 255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode("#define big_width 100000\n#define big_height 100000\nstatic char big_bits[] = { 0 };").unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn short_arrays_are_rejected() {
+        let error: String = decode("#define tiny_width 9\n#define tiny_height 2\nstatic char tiny_bits[] = { 1, 2, 3 };").unwrap_err().to_string();
+
+        assert!(error.contains("XBM array holds 3 bytes instead of 4"), "{error}");
+    }
+
+    #[test]
+    fn values_larger_than_a_byte_are_rejected() {
+        let error: String = decode("#define b_width 8\n#define b_height 1\nstatic char b_bits[] = { 0x100 };").unwrap_err().to_string();
+
+        assert!(error.contains("is larger than a byte"), "{error}");
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::image::pixel_count;
+use super::color_names::COLOR_NAMES;
+
+// Keys of a color definition, from the most to the least preferred for display
+const COLOR_KEYS: [&str; 4] = ["c", "g", "g4", "m"];
+const SYMBOL_KEY: &str = "s";
+
+#[derive(Clone, Debug, Default)]
+pub struct XPMHeader {
+    // XPM2 files are made of lines instead of C strings
+    pub version: u8,
+    pub width: u32,
+    pub height: u32,
+    pub colors: u32,
+    pub chars_per_pixel: u32,
+    pub hotspot: Option<(u32, u32)>,
+    pub extensions: bool,
+    // Color of the characters of each pixel, None being transparent
+    pub color_table: HashMap<Vec<u8>, Option<(u8, u8, u8)>>,
+    // Symbolic names given to colors
+    pub symbols: Vec<(String, Vec<u8>)>,
+}
+
+impl XPMHeader {
+    // Values line and color definitions, the lines of the pixels following them
+    pub fn from_lines(lines: &[Vec<u8>], version: u8) -> Result<Self> {
+        let mut header: XPMHeader = XPMHeader {
+            version,
+            ..XPMHeader::default()
+        };
+
+        let Some(values) = lines.first() else {
+            bail!("XPM file has no values");
+        };
+
+        let values: String = String::from_utf8_lossy(values).into_owned();
+        let fields: Vec<&str> = values.split_whitespace().collect();
+        let numbers: Vec<u32> = fields.iter().map_while(|field| field.parse::<u32>().ok()).collect();
+
+        let [width, height, colors, chars_per_pixel, ..] = numbers[..] else {
+            bail!("XPM values \"{values}\" are invalid");
+        };
+
+        (header.width, header.height, header.colors, header.chars_per_pixel) = (width, height, colors, chars_per_pixel);
+
+        if let [_, _, _, _, x, y] = numbers[..] {
+            header.hotspot = Some((x, y));
+        }
+
+        header.extensions = fields.last() == Some(&"XPMEXT");
+
+        if width == 0 || height == 0 || chars_per_pixel == 0 {
+            bail!("XPM image of {width}x{height} and {chars_per_pixel} characters per pixel is empty");
+        }
+
+        pixel_count("XPM", width, height)?;
+
+        if lines.len() <= colors as usize {
+            bail!("XPM file holds {} color definitions instead of {colors}", lines.len() - 1);
+        }
+
+        for line in &lines[1..=colors as usize] {
+            let Some(characters) = line.get(..chars_per_pixel as usize) else {
+                bail!("XPM color definition \"{}\" is too short", String::from_utf8_lossy(line));
+            };
+
+            let definition: String = String::from_utf8_lossy(&line[chars_per_pixel as usize..]).into_owned();
+            let (color, symbol): (String, Option<String>) = parse_definition(&definition)?;
+
+            if let Some(symbol) = symbol {
+                header.symbols.push((symbol, characters.to_vec()));
+            }
+
+            header.color_table.insert(characters.to_vec(), parse_color(&color)?);
+        }
+
+        Ok(header)
+    }
+}
+
+// Color of a definition made of keys each followed by a value of one or more words, and its symbolic name
+fn parse_definition(definition: &str) -> Result<(String, Option<String>)> {
+    let mut values: Vec<(&str, String)> = Vec::new();
+
+    for word in definition.split_whitespace() {
+        match values.last_mut() {
+            Some((_, value)) if value.is_empty() || !(COLOR_KEYS.contains(&word) || word == SYMBOL_KEY) => {
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+
+                value.push_str(word);
+            },
+            _ if COLOR_KEYS.contains(&word) || word == SYMBOL_KEY => values.push((word, String::new())),
+            _ => bail!("XPM color definition \"{definition}\" is invalid"),
+        }
+    }
+
+    let symbol: Option<String> = values.iter().find(|(key, _)| *key == SYMBOL_KEY).map(|(_, value)| value.clone());
+
+    let Some((_, value)) = COLOR_KEYS.iter().find_map(|key| values.iter().find(|(other, _)| other == key)) else {
+        bail!("XPM color definition \"{definition}\" has no color");
+    };
+
+    Ok((value.clone(), symbol))
+}
+
+// Color given as hexadecimal digits or by its X11 name, None being transparent
+fn parse_color(value: &str) -> Result<Option<(u8, u8, u8)>> {
+    if value.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+
+    if let Some(digits) = value.strip_prefix('#') {
+        // 1 to 4 digits for each channel, the upper 8 bits being kept
+        let size: usize = digits.len() / 3;
+
+        if !matches!(size, 1..=4) || digits.len() % 3 != 0 || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            bail!("XPM color \"{value}\" is invalid");
+        }
+
+        let channel = |index: usize| {
+            let channel: u32 = u32::from_str_radix(&digits[index * size..(index + 1) * size], 16).unwrap_or_default();
+            if size == 1 { (channel * 17) as u8 } else { (channel >> (4 * (size - 2))) as u8 }
+        };
+
+        return Ok(Some((channel(0), channel(1), channel(2))));
+    }
+
+    let name: String = value.to_ascii_lowercase().replace(' ', "");
+
+    let Ok(index) = COLOR_NAMES.binary_search_by_key(&name.as_str(), |(name, _)| name) else {
+        bail!("XPM color name \"{value}\" is unknown");
+    };
+
+    Ok(Some(COLOR_NAMES[index].1))
+}
+
+impl fmt::Display for XPMHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "version: {}\n", self.version)?;
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+        write!(f, "colors: {}\n", self.colors)?;
+        write!(f, "chars_per_pixel: {}\n", self.chars_per_pixel)?;
+        write!(f, "hotspot: {:?}\n", self.hotspot)?;
+        write!(f, "extensions: {}\n", self.extensions)?;
+
+        for (symbol, characters) in &self.symbols {
+            write!(f, "symbol: {symbol} for \"{}\"\n", String::from_utf8_lossy(characters))?;
+        }
+
+        Ok(())
+    }
+}
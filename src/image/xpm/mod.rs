@@ -0,0 +1,194 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use header::XPMHeader;
+use xpm_bit_reader::XpmBitReader;
+use super::bmp::BMP;
+use super::Image;
+
+mod color_names;
+mod header;
+mod xpm_bit_reader;
+
+const XPM3_MAGIC: &[u8] = b"/* XPM */";
+const XPM2_MAGIC: &[u8] = b"! XPM2";
+
+#[derive(Clone, Debug)]
+pub struct XPM {
+    header: XPMHeader,
+    image: BMP,
+}
+
+impl XPM {
+    // Strings of the C source, comments between them being skipped
+    fn read_strings(text: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let mut strings: Vec<Vec<u8>> = Vec::new();
+        let mut position: usize = 0;
+
+        while position < text.len() {
+            if text[position..].starts_with(b"/*") {
+                let Some(end) = text[position + 2..].windows(2).position(|pair| pair == b"*/") else {
+                    bail!("XPM comment is not closed");
+                };
+
+                position += end + 4;
+                continue;
+            }
+
+            if text[position] != b'"' {
+                position += 1;
+                continue;
+            }
+
+            let mut string: Vec<u8> = Vec::new();
+            position += 1;
+
+            loop {
+                match text.get(position) {
+                    Some(b'"') => break,
+                    // Escaped quotes and backslashes stand for themselves
+                    Some(b'\\') if matches!(text.get(position + 1), Some(b'"' | b'\\')) => {
+                        string.push(text[position + 1]);
+                        position += 1;
+                    },
+                    Some(&byte) => string.push(byte),
+                    None => bail!("XPM string is not closed"),
+                }
+
+                position += 1;
+            }
+
+            strings.push(string);
+            position += 1;
+        }
+
+        Ok(strings)
+    }
+}
+
+impl Image for XPM {
+    fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: XpmBitReader = XpmBitReader::new(stream);
+        let length: u64 = reader.length()?;
+        let text: Vec<u8> = reader.read_bytes(length as usize)?;
+        let start: &[u8] = text.trim_ascii_start();
+
+        let (lines, version): (Vec<Vec<u8>>, u8) = if start.starts_with(XPM3_MAGIC) {
+            (Self::read_strings(&start[XPM3_MAGIC.len()..])?, 3)
+        } else if start.starts_with(XPM2_MAGIC) {
+            let lines: Vec<Vec<u8>> = start.split(|&byte| byte == b'\n').skip(1).map(|line| line.strip_suffix(b"\r").unwrap_or(line).to_vec()).collect();
+            (lines, 2)
+        } else {
+            bail!("Not an XPM file");
+        };
+
+        let header: XPMHeader = XPMHeader::from_lines(&lines, version)?;
+
+        if debug {
+            println!("Header:\n{header}");
+        }
+
+        let first_row: usize = 1 + header.colors as usize;
+        let (width, chars_per_pixel): (usize, usize) = (header.width as usize, header.chars_per_pixel as usize);
+
+        let Some(rows) = lines.get(first_row..first_row + header.height as usize) else {
+            bail!("XPM file holds {} rows of pixels instead of {}", lines.len() - first_row, header.height);
+        };
+
+        let mut pixels: Vec<(u8, u8, u8, u8)> = Vec::new();
+
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() < width * chars_per_pixel {
+                bail!("XPM row {y} holds {} characters instead of {}", row.len(), width * chars_per_pixel);
+            }
+
+            for characters in row.chunks_exact(chars_per_pixel).take(width) {
+                let Some(&color) = header.color_table.get(characters) else {
+                    bail!("XPM pixel \"{}\" of row {y} has no color", String::from_utf8_lossy(characters));
+                };
+
+                pixels.push(match color {
+                    Some((r, g, b)) => (r, g, b, u8::MAX),
+                    None => (0, 0, 0, 0),
+                });
+            }
+        }
+
+        let image: BMP = BMP::from_rgba_pixels(header.width, header.height, |x, y| pixels[y * width + x]);
+
+        Ok(Self {
+            header,
+            image,
+        })
+    }
+
+    fn to_bmp(self: Box<Self>) -> Box<BMP> {
+        Box::new(self.image)
+    }
+}
+
+impl fmt::Display for XPM {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn decode(text: &str) -> Result<XPM> {
+        let source: Box<dyn Source> = Box::new(Cursor::new(text.as_bytes().to_vec()));
+        XPM::from_stream(BufReader::new(source), false)
+    }
+
+    fn pixels(image: &BMP) -> Vec<(u8, u8, u8, u8)> {
+        (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| (x, y))).map(|(x, y)| {
+            let (r, g, b): (u8, u8, u8) = image.pixel_color(x, y);
+            (r, g, b, image.pixel_alpha(x, y))
+        }).collect()
+    }
+
+    #[test]
+    fn c_strings_skip_comments() {
+        let text: &str = "/* XPM */\nstatic char *icon[] = {\n/* values */\n\"2 2 3 2 0 1\",\n\"aa c #FF0000 s red\",\n\"b. c None\",\n\"cc m white c dark slate gray\",\n\"aab.\",\n/* \"cccc\" */\n\"ccaa\"\n};\n";
+        let xpm: XPM = decode(text).unwrap();
+
+        assert_eq!(xpm.header.hotspot, Some((0, 1)));
+        assert_eq!(xpm.header.symbols, [("red".to_owned(), b"aa".to_vec())]);
+        assert_eq!(pixels(&xpm.image), [(255, 0, 0, 255), (0, 0, 0, 0), (47, 79, 79, 255), (255, 0, 0, 255)]);
+    }
+
+    #[test]
+    fn xpm2_files_are_made_of_lines() {
+        let xpm: XPM = decode("! XPM2\r\n2 1 2 1\r\n# c #123456789ABC\r\n- c #fff\r\n#-\r\n").unwrap();
+
+        assert_eq!(xpm.header.version, 2);
+        assert_eq!(pixels(&xpm.image), [(0x12, 0x56, 0x9A, 255), (255, 255, 255, 255)]);
+    }
+
+    #[test]
+    fn pixels_without_color_are_rejected() {
+        let error: String = decode("! XPM2\n1 1 1 1\n# c black\nx\n").unwrap_err().to_string();
+
+        assert!(error.contains("XPM pixel \"x\" of row 0 has no color"), "{error}");
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let error: String = decode("! XPM2\n100000 100000 1 1\n# c black\n").unwrap_err().to_string();
+
+        assert!(error.contains("too large"), "{error}");
+    }
+
+    #[test]
+    fn missing_rows_are_rejected() {
+        let error: String = decode("! XPM2\n1 3 1 1\n# c black\n#\n").unwrap_err().to_string();
+
+        assert!(error.contains("XPM file holds 2 rows of pixels instead of 3"), "{error}");
+    }
+}
@@ -1,32 +1,87 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
+use rustix::fd::OwnedFd;
+use rustix::io::dup;
+use rustix::stdio::dup2_stdout;
+use std::io::{stderr, stdout, Write};
 use std::path::PathBuf;
+use std::time::Duration;
+use terminal_size::{Width, Height, terminal_size};
 
 use image::bmp::BMP;
-use image::{from_file, ImageType};
-use term_drawer::drawer::{draw, ScalingLevel};
+use image::bmp::writer::{BMPPixels, BMPVersion, BMPWriteOptions};
+use image::mcu::{ColorMatrix, ColorRange};
+use image::tone_mapping::ToneMapping;
+use image::{from_file, to_file, DecodeOptions, ImageType, OutputType};
+use term_drawer::drawer::{draw, Background, ScalingLevel};
+use term_drawer::player::{play, PlaybackStats};
+use video::extractor::{extract, FrameSelection};
+use video::yuv::{PixelFormat, YUVFormat};
+use video::{Video, VideoType};
 
 mod image;
 mod term_drawer;
+mod video;
 
 /// TermViewer
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// File
+    /// File, "-" reading an image, but not a video, from the standard input
     filepath: PathBuf,
 
     /// Type of the file to process
-    image_type: ImageType,
+    #[clap(required_unless_present = "video_type")]
+    image_type: Option<ImageType>,
+
+    /// Type of the video to process, instead of an image type
+    #[clap(long, conflicts_with = "image_type")]
+    video_type: Option<VideoType>,
+
+    /// Image to display from an icon file, the largest one fitting in the terminal by default, page of a TIFF file or array slice of a DDS texture, the first one by default
+    #[clap(long)]
+    entry: Option<usize>,
+
+    /// Mip level of a DDS texture, the full size one by default
+    #[clap(long, default_value="0")]
+    mip_level: usize,
+
+    /// Operator bringing the colors of a high dynamic range image to the terminal
+    #[clap(long, default_value="reinhard")]
+    tone_mapping: ToneMapping,
+
+    /// Exposure of a high dynamic range image, in stops added before its tone mapping
+    #[clap(long, default_value="0", allow_negative_numbers = true)]
+    exposure: f32,
 
     /// Type of the file to process
     #[clap(short, long, default_value="level2")]
     scaling_level: ScalingLevel,
 
+    /// Color shown behind transparent pixels, as RRGGBB or "checkerboard"
+    #[clap(long, default_value="checkerboard")]
+    background: Background,
+
     /// Save intermediate BMP file
     #[clap(long)]
     save_bmp: bool,
 
+    /// Header of the saved BMP file
+    #[clap(long, requires = "save_bmp", default_value="core")]
+    bmp_version: BMPVersion,
+
+    /// Pixel format of the saved BMP file
+    #[clap(long, requires = "save_bmp", default_value="rgb24")]
+    bmp_pixels: BMPPixels,
+
+    /// Also save the decoded image next to the file in this format
+    #[clap(long)]
+    save_as: Option<OutputType>,
+
+    /// Path of the saved image instead of next to the file, "-" writing it to the standard output without rendering
+    #[clap(long, requires = "save_as")]
+    output: Option<PathBuf>,
+
     /// Enable debug
     #[clap(long)]
     debug: bool,
@@ -34,6 +89,58 @@ struct Args {
     /// Disable rendering
     #[clap(long)]
     no_render: bool,
+
+    /// Width of the raw video frames or of the raw RGB and RGBA images
+    #[clap(long, requires = "height")]
+    width: Option<u16>,
+
+    /// Height of the raw video frames or of the raw RGB and RGBA images
+    #[clap(long, requires = "width")]
+    height: Option<u16>,
+
+    /// Pixel format of the raw video frames
+    #[clap(long, default_value="i420")]
+    pixel_format: PixelFormat,
+
+    /// Frame rate of the raw video
+    #[clap(long, default_value="25")]
+    frame_rate: f32,
+
+    /// YCbCr to RGB conversion matrix of the raw video
+    #[clap(long, default_value="bt601")]
+    color_matrix: ColorMatrix,
+
+    /// YCbCr range of the raw video
+    #[clap(long, default_value="limited")]
+    color_range: ColorRange,
+
+    /// Start the video at the frame displayed at this timestamp, in seconds
+    #[clap(long, requires = "video_type", conflicts_with = "seek_frame")]
+    seek_time: Option<f32>,
+
+    /// Start the video at the frame at this index
+    #[clap(long, requires = "video_type")]
+    seek_frame: Option<usize>,
+
+    /// Extract the frame at this index instead of playing the video
+    #[clap(long, requires = "video_type", group = "extract")]
+    extract_frame: Option<usize>,
+
+    /// Extract the frame displayed at this timestamp, in seconds
+    #[clap(long, requires = "video_type", group = "extract")]
+    extract_time: Option<f32>,
+
+    /// Extract every Nth frame
+    #[clap(long, requires = "video_type", group = "extract")]
+    extract_every: Option<usize>,
+
+    /// Filename pattern of the extracted frames, "%05d" being replaced by the frame index, "-" writing them all to the standard output [default: frame_%05d.<output type>]
+    #[clap(long)]
+    output_pattern: Option<String>,
+
+    /// Format of the extracted frames
+    #[clap(long, default_value="bmp")]
+    output_type: OutputType,
 }
 
 // TODO: Be able to decode video stream
@@ -41,24 +148,134 @@ struct Args {
 // https://koushtav.me/jpeg/tutorial/c++/decoder/2019/03/02/lets-write-a-simple-jpeg-library-part-2/#detailed-description-of-the-markers
 // https://imrannazar.com/series/lets-build-a-jpeg-decoder/huffman-tables
 
+// Timestamp given in seconds on the command line, which can't be negative
+fn timestamp(seconds: f32) -> Result<Duration> {
+    let Ok(timestamp) = Duration::try_from_secs_f32(seconds) else {
+        bail!("Timestamp of {seconds} seconds is invalid");
+    };
+
+    Ok(timestamp)
+}
+
 fn main() -> Result<()> {
     let args: Args = Args::parse();
     let filepath: PathBuf = args.filepath;
 
-    assert!(filepath.exists());
-    assert!(filepath.is_file());
+    let from_stdin: bool = filepath.as_os_str() == "-";
+
+    assert!(from_stdin || filepath.exists());
+    assert!(from_stdin || filepath.is_file());
+
+    if let Some(video_type) = args.video_type {
+        if from_stdin {
+            bail!("Videos can't be read from the standard input");
+        }
+
+        let yuv_format: Option<YUVFormat> = match (args.width, args.height) {
+            (Some(width), Some(height)) => Some(YUVFormat {
+                width,
+                height,
+                pixel_format: args.pixel_format,
+                frame_rate: args.frame_rate,
+                color_matrix: args.color_matrix,
+                color_range: args.color_range,
+            }),
+            _ => None,
+        };
+
+        let mut video: Box<dyn Video> = video::from_file(&filepath, video_type, yuv_format, args.debug)?;
+
+        if let Some(seconds) = args.seek_time {
+            video.seek_time(timestamp(seconds)?)?;
+        } else if let Some(index) = args.seek_frame {
+            video.seek_frame(index)?;
+        }
+
+        let selection: Option<FrameSelection> = match (args.extract_frame, args.extract_time, args.extract_every) {
+            (Some(index), _, _) => Some(FrameSelection::Index(index)),
+            (_, Some(seconds), _) => Some(FrameSelection::Timestamp(timestamp(seconds)?)),
+            (_, _, Some(step)) => Some(FrameSelection::Every(step)),
+            _ => None,
+        };
+
+        if let Some(selection) = selection {
+            let pattern: String = args.output_pattern.unwrap_or(format!("frame_%05d.{}", args.output_type.extension()));
+            let written: usize = extract(video, selection, &pattern, args.output_type)?;
+
+            if pattern != "-" {
+                println!("{written} frames extracted");
+            }
+
+            return Ok(());
+        }
+
+        if args.no_render {
+            while video.next_frame()?.is_some() {}
+        } else {
+            let stats: PlaybackStats = play(video, args.scaling_level, args.background)?;
+            println!("{stats}");
+        }
 
-    let bmp: Box<BMP> = from_file(&filepath, args.image_type, args.debug)?.to_bmp();
+        return Ok(());
+    }
+
+    let image_type: ImageType = args.image_type.expect("Required without a video type");
+    let options: DecodeOptions = DecodeOptions {
+        entry: args.entry,
+        mip_level: args.mip_level,
+        max_size: terminal_size().map(|(Width(width), Height(height))| (width as u32 / 2, height as u32)),
+        tone_mapping: args.tone_mapping,
+        exposure: args.exposure,
+        raw_size: args.width.zip(args.height).map(|(width, height)| (width as u32, height as u32)),
+    };
+
+    // Rendering and messages would mix with the image piped to the standard output
+    let to_stdout: bool = args.output.as_ref().is_some_and(|output| output.as_os_str() == "-");
+
+    if from_stdin && args.save_bmp {
+        bail!("Intermediate BMP file can't be saved next to the standard input");
+    }
+
+    // Debug output and messages go to the standard error until the image is written
+    let piped_stdout: Option<OwnedFd> = if to_stdout {
+        stdout().flush()?;
+        let piped_stdout: OwnedFd = dup(stdout())?;
+        dup2_stdout(stderr())?;
+        Some(piped_stdout)
+    } else {
+        None
+    };
+
+    let bmp: Box<BMP> = from_file(&filepath, image_type, options, args.debug)?.to_bmp();
 
     if args.save_bmp {
         let mut bmp_filepath: String = filepath.to_str().unwrap().to_owned();
         bmp_filepath.push_str(".bmp");
         println!("Saving intermediate BMP file as \"{bmp_filepath}\"");
-        bmp.write_to_file(PathBuf::from(bmp_filepath))?;
+        let write_options: BMPWriteOptions = BMPWriteOptions {
+            version: args.bmp_version,
+            pixels: args.bmp_pixels,
+        };
+        bmp.write_to_file_with(PathBuf::from(bmp_filepath), write_options)?;
+    }
+
+    if let Some(piped_stdout) = piped_stdout {
+        stdout().flush()?;
+        dup2_stdout(piped_stdout)?;
+    }
+
+    if let Some(output_type) = args.save_as {
+        let output_filepath: PathBuf = args.output.unwrap_or(PathBuf::from(format!("{}.{}", filepath.to_str().unwrap(), output_type.extension())));
+
+        if !to_stdout {
+            println!("Saving image as \"{}\"", output_filepath.display());
+        }
+
+        to_file(&bmp, output_filepath, output_type)?;
     }
 
-    if !args.no_render {
-        draw(bmp, args.scaling_level)?;
+    if !args.no_render && !to_stdout {
+        draw(bmp, args.scaling_level, args.background)?;
     }
 
     Ok(())
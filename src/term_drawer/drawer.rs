@@ -1,7 +1,8 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::ValueEnum;
 use serde::Serialize;
 use std::io::{stdout, Write};
+use std::str::FromStr;
 use terminal_size::{Width, Height, terminal_size};
 
 use crate::image::bmp::BMP;
@@ -13,138 +14,213 @@ pub enum ScalingLevel {
     LEVEL2,
 }
 
-pub fn level1(image: Box<BMP>, terminal_width: usize, terminal_height: usize) -> Result<()> {
-    let image_height: usize = image.height() as usize;
-    let image_width: usize = image.width() as usize;
-    let mcus: &Vec<MCU> = image.mcus();
-    let mcu_width: usize = (image_width as usize + 7) / 8;
-
-    let ratio_width: usize = image_width / terminal_width;
-    let ratio_height: usize = image_height / terminal_height;
-    let step: usize = ratio_height.max(ratio_width);
+// Color shown behind the transparent pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Background {
+    Color(u8, u8, u8),
+    // Squares of one cell
+    Checkerboard,
+}
 
-    let mut image_row: usize = 0;
-    let mut image_column: usize = 0;
+impl Background {
+    fn color(&self, row: usize, column: usize) -> (u8, u8, u8) {
+        match self {
+            Background::Color(r, g, b) => (*r, *g, *b),
+            Background::Checkerboard if (row + column).is_multiple_of(2) => (0x99, 0x99, 0x99),
+            Background::Checkerboard => (0x66, 0x66, 0x66),
+        }
+    }
+}
 
-    let size_per_pixel: usize = background(u8::MAX, u8::MAX, u8::MAX).len() + "  ".len();
-    let size_per_row: usize = size_per_pixel * (image_width / step + 1) + goto(0, 0).len();
-    let output_size: usize = size_per_row * (image_height / step + 1) + reset().len();
-    let mut output: String = String::with_capacity(output_size);
+// "checkerboard" or a "RRGGBB" hexadecimal color, optionally preceded by '#'
+impl FromStr for Background {
+    type Err = anyhow::Error;
 
-    loop {
-        let mcu_row: usize = image_row / 8;
-        let pixel_row: usize = image_row % 8;
-        let mcu_column: usize = image_column / 8;
-        let pixel_column: usize = image_column % 8;
-        let mcu_index: usize = mcu_row * mcu_width + mcu_column;
-        let pixel_index: usize = pixel_row * 8 + pixel_column;
+    fn from_str(value: &str) -> Result<Self> {
+        if value == "checkerboard" {
+            return Ok(Background::Checkerboard);
+        }
 
-        if let Some(mcu) = mcus.get(mcu_index) {
-            let r = mcu.component(0).expect("Should exist")[pixel_index] as u8;
-            let g = mcu.component(1).expect("Should exist")[pixel_index] as u8;
-            let b = mcu.component(2).expect("Should exist")[pixel_index] as u8;
+        let hex: &str = value.strip_prefix('#').unwrap_or(value);
 
-            output.push_str(&background(r, g, b));
-            output.push_str(&"  ");
+        if hex.len() != 6 || !hex.is_ascii() {
+            bail!("Background \"{value}\" is neither \"checkerboard\" nor a RRGGBB color");
         }
 
-        image_column += step;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+
+        Ok(Background::Color(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+// Colors of the terminal cells, each cell being two characters wide
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<(u8, u8, u8)>,
+}
+
+impl Grid {
+    pub fn new(image: &BMP, scaling_level: &ScalingLevel, background: Background, terminal_width: usize, terminal_height: usize) -> Self {
+        let image_height: usize = image.height() as usize;
+        let image_width: usize = image.width() as usize;
+
+        let ratio_width: usize = image_width.div_ceil((terminal_width / 2).max(1));
+        let ratio_height: usize = image_height.div_ceil(terminal_height.max(1));
+        let step: usize = ratio_height.max(ratio_width).max(1);
+
+        let width: usize = image_width.div_ceil(step);
+        let height: usize = image_height.div_ceil(step);
+        let mut cells: Vec<(u8, u8, u8)> = Vec::with_capacity(width * height);
 
-        if image_column >= image_width {
-            image_column = 0;
-            image_row += step;
-            output.push_str(&goto(image_row / step, 0));
+        for row in 0..height {
+            for column in 0..width {
+                let cell_background: (u8, u8, u8) = background.color(row, column);
+
+                cells.push(match scaling_level {
+                    ScalingLevel::LEVEL1 => level1(image, row * step, column * step, cell_background),
+                    ScalingLevel::LEVEL2 => level2(image, row * step, column * step, step, cell_background),
+                });
+            }
         }
 
-        if image_row >= image_height {
-            break;
+        Self {
+            width,
+            height,
+            cells,
         }
     }
 
-    output.push_str(&reset());
-    Ok(stdout().write_all(output.as_bytes())?)
-}
+    pub fn width(&self) -> usize {
+        self.width
+    }
 
-fn average(mcus: &Vec<MCU>, mcu_width: usize, width: usize, height: usize, x: usize, y: usize, step: usize) -> (u8, u8, u8) {
-    let mut sum_r: usize = 0;
-    let mut sum_g: usize = 0;
-    let mut sum_b: usize = 0;
-    let mut count: usize = 0;
+    pub fn height(&self) -> usize {
+        self.height
+    }
 
-    for row in x..height.min(x + step) {
-        for column in y..width.min(y + step) {
-            let mcu_row: usize = row / 8;
-            let pixel_row: usize = row % 8;
-            let mcu_column: usize = column / 8;
-            let pixel_column: usize = column % 8;
-            let mcu_index: usize = mcu_row * mcu_width + mcu_column;
-            let pixel_index: usize = pixel_row * 8 + pixel_column;
-
-            if let Some(mcu) = mcus.get(mcu_index) {
-                sum_r += mcu.component(0).expect("Should exist")[pixel_index] as usize;
-                sum_g += mcu.component(1).expect("Should exist")[pixel_index] as usize;
-                sum_b += mcu.component(2).expect("Should exist")[pixel_index] as usize;
-                count += 1;
+    fn cell(&self, row: usize, column: usize) -> (u8, u8, u8) {
+        self.cells[row * self.width + column]
+    }
+
+    // Emits the escape sequences painting the grid, skipping the cells unchanged since the previous one
+    pub fn render(&self, previous: Option<&Grid>) -> String {
+        let previous: Option<&Grid> = previous.filter(|previous| previous.width == self.width && previous.height == self.height);
+
+        let size_per_pixel: usize = background(u8::MAX, u8::MAX, u8::MAX).len() + "  ".len();
+        let size_per_row: usize = size_per_pixel * self.width + goto(0, 0).len();
+        let mut output: String = String::with_capacity(size_per_row * self.height + reset().len());
+        let mut current_color: Option<(u8, u8, u8)> = None;
+
+        for row in 0..self.height {
+            // Cursor is never assumed to be well placed at the start of a row
+            let mut cursor_column: Option<usize> = None;
+
+            for column in 0..self.width {
+                let color: (u8, u8, u8) = self.cell(row, column);
+
+                if previous.is_some_and(|previous| previous.cell(row, column) == color) {
+                    continue;
+                }
+
+                if cursor_column != Some(column) {
+                    output.push_str(&goto(row + 1, 2 * column + 1));
+                }
+
+                if current_color != Some(color) {
+                    let (r, g, b): (u8, u8, u8) = color;
+                    output.push_str(&background(r, g, b));
+                    current_color = Some(color);
+                }
+
+                output.push_str("  ");
+                cursor_column = Some(column + 1);
             }
         }
-    }
 
-    let average_r: usize = sum_r / count;
-    let average_g: usize = sum_g / count;
-    let average_b: usize = sum_b / count;
+        output.push_str(&reset());
+        output
+    }
+}
 
-    (average_r as u8, average_g as u8, average_b as u8)
+fn pixel(mcus: &[MCU], mcu_width: usize, row: usize, column: usize) -> Option<(u8, u8, u8, u8)> {
+    let mcu_row: usize = row / 8;
+    let pixel_row: usize = row % 8;
+    let mcu_column: usize = column / 8;
+    let pixel_column: usize = column % 8;
+    let mcu_index: usize = mcu_row * mcu_width + mcu_column;
+    let pixel_index: usize = pixel_row * 8 + pixel_column;
+
+    let mcu: &MCU = mcus.get(mcu_index)?;
+    let r = mcu.component(0).expect("Should exist")[pixel_index] as u8;
+    let g = mcu.component(1).expect("Should exist")[pixel_index] as u8;
+    let b = mcu.component(2).expect("Should exist")[pixel_index] as u8;
+    let a = mcu.alpha(pixel_index);
+
+    Some((r, g, b, a))
 }
 
-pub fn level2(image: Box<BMP>, terminal_width: usize, terminal_height: usize) -> Result<()> {
-    let image_height: usize = image.height() as usize;
-    let image_width: usize = image.width() as usize;
-    let mcus: &Vec<MCU> = image.mcus();
-    let mcu_width: usize = (image_width as usize + 7) / 8;
+// Top left pixel of the area covered by the cell, over the background
+fn level1(image: &BMP, x: usize, y: usize, background: (u8, u8, u8)) -> (u8, u8, u8) {
+    let mcu_width: usize = (image.width() as usize).div_ceil(8);
 
-    let ratio_width: usize = image_width / terminal_width;
-    let ratio_height: usize = image_height / terminal_height;
-    let step: usize = ratio_height.max(ratio_width);
+    let Some((r, g, b, a)) = pixel(image.mcus(), mcu_width, x, y) else {
+        return (0, 0, 0);
+    };
 
-    let mut image_row: usize = 0;
-    let mut image_column: usize = 0;
+    let (background_r, background_g, background_b): (u8, u8, u8) = background;
+    let blend = |color: u8, background: u8| ((color as usize * a as usize + background as usize * (255 - a as usize)) / 255) as u8;
 
-    let size_per_pixel: usize = background(u8::MAX, u8::MAX, u8::MAX).len() + "  ".len();
-    let size_per_row: usize = size_per_pixel * (image_width / step + 1) + goto(0, 0).len();
-    let output_size: usize = size_per_row * (image_height / step + 1) + reset().len();
-    let mut output: String = String::with_capacity(output_size);
+    (blend(r, background_r), blend(g, background_g), blend(b, background_b))
+}
 
-    loop {
-        let (r, g, b): (u8, u8, u8) = average(mcus, mcu_width, image_width, image_height, image_row, image_column, step);
-        output.push_str(&background(r, g, b));
-        output.push_str(&"  ");
+// Average of the pixels of the area covered by the cell, over the background
+fn level2(image: &BMP, x: usize, y: usize, step: usize, background: (u8, u8, u8)) -> (u8, u8, u8) {
+    let height: usize = image.height() as usize;
+    let width: usize = image.width() as usize;
+    let mcu_width: usize = width.div_ceil(8);
 
-        image_column += step;
+    // Colors are summed premultiplied by their alpha
+    let mut sum_r: usize = 0;
+    let mut sum_g: usize = 0;
+    let mut sum_b: usize = 0;
+    let mut sum_a: usize = 0;
+    let mut count: usize = 0;
 
-        if image_column >= image_width {
-            image_column = 0;
-            image_row += step;
-            output.push_str(&goto(image_row / step, 0));
+    for row in x..height.min(x + step) {
+        for column in y..width.min(y + step) {
+            if let Some((r, g, b, a)) = pixel(image.mcus(), mcu_width, row, column) {
+                sum_r += r as usize * a as usize;
+                sum_g += g as usize * a as usize;
+                sum_b += b as usize * a as usize;
+                sum_a += a as usize;
+                count += 1;
+            }
         }
+    }
 
-        if image_row >= image_height {
-            break;
-        }
+    if count == 0 {
+        return (0, 0, 0);
     }
 
-    output.push_str(&reset());
-    Ok(stdout().write_all(output.as_bytes())?)
+    let (background_r, background_g, background_b): (u8, u8, u8) = background;
+    let transparency: usize = 255 * count - sum_a;
+
+    let average_r: usize = (sum_r + background_r as usize * transparency) / (255 * count);
+    let average_g: usize = (sum_g + background_g as usize * transparency) / (255 * count);
+    let average_b: usize = (sum_b + background_b as usize * transparency) / (255 * count);
+
+    (average_r as u8, average_g as u8, average_b as u8)
 }
 
-pub fn draw(image: Box<BMP>, scaling_level: ScalingLevel) -> Result<()> {
+pub fn draw(image: Box<BMP>, scaling_level: ScalingLevel, background: Background) -> Result<()> {
     clean();
     hide_cursor();
 
     if let Some((Width(width), Height(height))) = terminal_size() {
-        match scaling_level {
-            ScalingLevel::LEVEL1 => level1(image, width as usize, height as usize)?,
-            ScalingLevel::LEVEL2 => level2(image, width as usize, height as usize)?,
-        }
+        let grid: Grid = Grid::new(&image, &scaling_level, background, width as usize, height as usize);
+        stdout().write_all(grid.render(None).as_bytes())?;
     }
 
     println!("");
@@ -152,26 +228,26 @@ pub fn draw(image: Box<BMP>, scaling_level: ScalingLevel) -> Result<()> {
     Ok(())
 }
 
-fn background(r: u8, g: u8, b: u8) -> String {
+pub fn background(r: u8, g: u8, b: u8) -> String {
     format!("\u{001b}[48;2;{r};{g};{b}m")
 }
 
-fn reset() -> String {
+pub fn reset() -> String {
     format!("\u{001b}[0m")
 }
 
-fn show_cursor() {
+pub fn show_cursor() {
     print!("\u{001b}[?25h");
 }
 
-fn hide_cursor() {
+pub fn hide_cursor() {
     print!("\u{001b}[?25l");
 }
 
-fn goto(row: usize, column: usize) -> String {
+pub fn goto(row: usize, column: usize) -> String {
     format!("\u{001b}[{row};{column}H")
 }
 
-fn clean() {
+pub fn clean() {
     print!("\u{001b}[2J");
 }
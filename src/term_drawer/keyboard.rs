@@ -0,0 +1,85 @@
+use anyhow::Result;
+use rustix::termios::{isatty, tcgetattr, tcsetattr, LocalModes, OptionalActions, SpecialCodeIndex, Termios};
+use std::io::{stdin, stdout, Write};
+
+use super::drawer::{hide_cursor, reset, show_cursor};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    Quit,
+}
+
+// Puts the terminal in non canonical mode so that key presses can be polled without blocking,
+// and hides the cursor, both being restored when dropped, even on errors
+pub struct Keyboard {
+    original: Option<Termios>,
+}
+
+impl Keyboard {
+    pub fn new() -> Result<Self> {
+        // Built first so that an error below still shows the cursor back
+        let mut keyboard: Keyboard = Self { original: None };
+        hide_cursor();
+
+        if !isatty(stdin()) {
+            return Ok(keyboard);
+        }
+
+        let original: Termios = tcgetattr(stdin())?;
+        let mut raw: Termios = original.clone();
+        // Ctrl-C is read as a key, a signal would kill the process before the terminal is restored
+        raw.local_modes.remove(LocalModes::ICANON | LocalModes::ECHO | LocalModes::ISIG);
+        raw.special_codes[SpecialCodeIndex::VMIN] = 0;
+        raw.special_codes[SpecialCodeIndex::VTIME] = 0;
+        tcsetattr(stdin(), OptionalActions::Now, &raw)?;
+        keyboard.original = Some(original);
+
+        Ok(keyboard)
+    }
+
+    pub fn keys(&mut self) -> Result<Vec<Key>> {
+        if self.original.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let mut buffer: [u8; 64] = [0; 64];
+        let length: usize = rustix::io::read(stdin(), &mut buffer)?;
+        let mut keys: Vec<Key> = Vec::new();
+        let mut i: usize = 0;
+
+        while i < length {
+            match &buffer[i..length] {
+                [0x1B, b'[', b'A', ..] => keys.push(Key::Up),
+                [0x1B, b'[', b'B', ..] => keys.push(Key::Down),
+                [0x1B, b'[', b'C', ..] => keys.push(Key::Right),
+                [0x1B, b'[', b'D', ..] => keys.push(Key::Left),
+                [b'q' | 0x03, ..] => keys.push(Key::Quit),
+                _ => {
+                    i += 1;
+                    continue;
+                },
+            }
+
+            i += if buffer[i] == 0x1B { 3 } else { 1 };
+        }
+
+        Ok(keys)
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        print!("{}", reset());
+        println!();
+        show_cursor();
+        let _ = stdout().flush();
+
+        if let Some(original) = &self.original {
+            let _ = tcsetattr(stdin(), OptionalActions::Now, original);
+        }
+    }
+}
@@ -1 +1,3 @@
 pub mod drawer;
+pub mod keyboard;
+pub mod player;
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use std::fmt;
+use std::io::{stdout, Stdout, Write};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use terminal_size::{Width, Height, terminal_size};
+
+use crate::video::{Frame, Video};
+use super::keyboard::{Key, Keyboard};
+use super::drawer::{clean, goto, reset, Background, Grid, ScalingLevel};
+
+const SHORT_SEEK: Duration = Duration::from_secs(5);
+const LONG_SEEK: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Default)]
+pub struct PlaybackStats {
+    pub rendered_frames: usize,
+    pub dropped_frames: usize,
+    pub elapsed: Duration,
+}
+
+impl PlaybackStats {
+    pub fn fps(&self) -> f32 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+
+        self.rendered_frames as f32 / self.elapsed.as_secs_f32()
+    }
+}
+
+impl fmt::Display for PlaybackStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} frames rendered, {} dropped in {:.2}s ({:.2} fps)", self.rendered_frames, self.dropped_frames, self.elapsed.as_secs_f32(), self.fps())
+    }
+}
+
+pub struct Player {
+    scaling_level: ScalingLevel,
+    background: Background,
+    previous: Option<Grid>,
+    stats: PlaybackStats,
+    // Rendered frames count and instant of the last FPS measure
+    fps_window: (usize, Instant),
+    fps: f32,
+}
+
+impl Player {
+    pub fn new(scaling_level: ScalingLevel, background: Background) -> Self {
+        Self {
+            scaling_level,
+            background,
+            previous: None,
+            stats: PlaybackStats::default(),
+            fps_window: (0, Instant::now()),
+            fps: 0.0,
+        }
+    }
+
+    pub fn play(mut self, mut video: Box<dyn Video>) -> Result<PlaybackStats> {
+        let mut out: Stdout = stdout();
+        let mut keyboard: Keyboard = Keyboard::new()?;
+        clean();
+        out.flush()?;
+
+        let start: Instant = Instant::now();
+        self.fps_window = (0, start);
+        // Playback clock, realigned on the timestamp of the first frame and after every seek
+        let mut clock: Instant = start;
+        let mut clock_origin: Option<Duration> = None;
+        let mut position: Duration = Duration::ZERO;
+        // Looping videos go on from the end of the previous play
+        let mut plays: u32 = 0;
+        let mut play_offset: Duration = Duration::ZERO;
+        let mut play_end: Option<Duration> = None;
+
+        'playback: loop {
+            for key in keyboard.keys()? {
+                let target: Duration = match key {
+                    Key::Quit => break 'playback,
+                    Key::Left => position.saturating_sub(SHORT_SEEK),
+                    Key::Right => position + SHORT_SEEK,
+                    Key::Down => position.saturating_sub(LONG_SEEK),
+                    Key::Up => position + LONG_SEEK,
+                };
+
+                video.seek_time(target)?;
+                clock_origin = None;
+            }
+
+            let Some(frame) = video.next_frame()? else {
+                plays += 1;
+
+                // Play without any frame would start over endlessly
+                let Some(end) = play_end.take() else {
+                    break;
+                };
+
+                if video.plays().is_some_and(|total| plays >= total) {
+                    break;
+                }
+
+                video.seek_frame(0)?;
+                play_offset += end;
+                continue;
+            };
+
+            let Frame { image, timestamp, duration, .. } = frame;
+            position = timestamp;
+            play_end = Some(timestamp + duration);
+
+            let timeline: Duration = play_offset + timestamp;
+            let origin: Duration = *clock_origin.get_or_insert_with(|| {
+                clock = Instant::now();
+                timeline
+            });
+            let due: Duration = timeline.saturating_sub(origin);
+
+            // Frame is already over, drawing it would only delay the next ones
+            if self.previous.is_some() && clock.elapsed() > due + duration {
+                self.stats.dropped_frames += 1;
+                continue;
+            }
+
+            if let Some(remaining) = due.checked_sub(clock.elapsed()) {
+                sleep(remaining);
+            }
+
+            let Some((Width(width), Height(height))) = terminal_size() else {
+                self.stats.dropped_frames += 1;
+                continue;
+            };
+
+            // Last row is kept for the status line
+            let rows: usize = (height as usize).saturating_sub(1).max(1);
+            let grid: Grid = Grid::new(&image, &self.scaling_level, self.background, width as usize, rows);
+
+            let resized: bool = self.previous
+                .as_ref()
+                .is_some_and(|previous| previous.width() != grid.width() || previous.height() != grid.height());
+
+            if resized {
+                clean();
+            }
+
+            let mut output: String = grid.render(self.previous.as_ref());
+            self.stats.rendered_frames += 1;
+            self.update_fps();
+            output.push_str(&self.status_line(height as usize, position));
+
+            out.write_all(output.as_bytes())?;
+            out.flush()?;
+
+            self.previous = Some(grid);
+        }
+
+        self.stats.elapsed = start.elapsed();
+
+        Ok(self.stats)
+    }
+
+    // FPS is measured on windows of about one second
+    fn update_fps(&mut self) {
+        let (frames, since) = self.fps_window;
+        let elapsed: Duration = since.elapsed();
+
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = (self.stats.rendered_frames - frames) as f32 / elapsed.as_secs_f32();
+            self.fps_window = (self.stats.rendered_frames, Instant::now());
+        }
+    }
+
+    fn status_line(&self, height: usize, position: Duration) -> String {
+        format!("{}\u{001b}[2K{:.1}s, {:.1} fps, {} dropped{}", goto(height, 1), position.as_secs_f32(), self.fps, self.stats.dropped_frames, reset())
+    }
+}
+
+pub fn play(video: Box<dyn Video>, scaling_level: ScalingLevel, background: Background) -> Result<PlaybackStats> {
+    Player::new(scaling_level, background).play(video)
+}
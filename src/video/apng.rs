@@ -0,0 +1,447 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::time::Duration;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use crate::image::bmp::BMP;
+use crate::image::png::chunk::Chunk;
+use crate::image::png::header::{ColorType, PNGHeader};
+use crate::image::png::pixels::decode_pixels;
+use crate::image::png::png_bit_reader::PngBitReader;
+use crate::image::png::SIGNATURE;
+use crate::image::pixel_count;
+use super::{Frame, Video};
+
+// What becomes of the area of a frame once it is over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisposeOp {
+    NONE,
+    // Cleared to transparent black
+    BACKGROUND,
+    // Restored to what it was before the frame
+    PREVIOUS,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendOp {
+    // Frame replaces the area
+    SOURCE,
+    // Frame is composited over the area
+    OVER,
+}
+
+// fcTL chunk, placing the next frame on the canvas
+#[derive(Clone, Copy, Debug)]
+pub struct FrameControl {
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay: Duration,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    // Fields following the sequence number
+    fn from_binary(data: &[u8], header: &PNGHeader) -> Result<Self> {
+        if data.len() != 22 {
+            bail!("fcTL chunk of {} bytes is invalid", data.len() + 4);
+        }
+
+        let field = |offset: usize| u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+        let delay_numerator: u16 = u16::from_be_bytes([data[16], data[17]]);
+
+        // Zero denominator stands for hundredths of a second
+        let delay_denominator: u16 = match u16::from_be_bytes([data[18], data[19]]) {
+            0 => 100,
+            value => value,
+        };
+
+        let control: Self = Self {
+            width: field(0),
+            height: field(4),
+            x_offset: field(8),
+            y_offset: field(12),
+            delay: Duration::from_secs(delay_numerator as u64) / delay_denominator as u32,
+            dispose_op: match data[20] {
+                0 => DisposeOp::NONE,
+                1 => DisposeOp::BACKGROUND,
+                2 => DisposeOp::PREVIOUS,
+                value => bail!("APNG dispose op {value} is invalid"),
+            },
+            blend_op: match data[21] {
+                0 => BlendOp::SOURCE,
+                1 => BlendOp::OVER,
+                value => bail!("APNG blend op {value} is invalid"),
+            },
+        };
+
+        if control.width == 0 || control.height == 0
+            || control.x_offset as u64 + control.width as u64 > header.width as u64
+            || control.y_offset as u64 + control.height as u64 > header.height as u64 {
+            bail!("APNG frame of {}x{} at ({}, {}) is out of the {}x{} canvas", control.width, control.height, control.x_offset, control.y_offset, header.width, header.height);
+        }
+
+        Ok(control)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct AnimationFrame {
+    control: FrameControl,
+    // Concatenated IDAT or fdAT chunks
+    data: Vec<u8>,
+    timestamp: Duration,
+}
+
+#[derive(Debug)]
+pub struct APNG {
+    header: PNGHeader,
+    frames: Vec<AnimationFrame>,
+    // None stands for forever
+    plays: Option<u32>,
+    // Default image being the first frame rather than a fallback for viewers unaware of APNG
+    default_image_animated: bool,
+    frame_index: usize,
+    canvas: Vec<(u8, u8, u8, u8)>,
+    // Last frame drawn, disposed of before drawing the next one
+    disposal: Option<FrameControl>,
+    // Area of the last frame as it was before it, when it has to be restored
+    saved_area: Vec<(u8, u8, u8, u8)>,
+}
+
+impl APNG {
+    pub fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: PngBitReader = PngBitReader::new(stream);
+
+        if reader.read_bytes(SIGNATURE.len())? != SIGNATURE {
+            bail!("PNG signature is invalid");
+        }
+
+        let ihdr: Chunk = Chunk::from_binary(&mut reader)?;
+
+        if &ihdr.chunk_type != b"IHDR" {
+            bail!("PNG file starts with chunk {} instead of IHDR", ihdr.name());
+        }
+
+        let mut header: PNGHeader = PNGHeader::from_ihdr(&ihdr.data)?;
+        let mut animation: Option<(u32, u32)> = None;
+        let mut frames: Vec<AnimationFrame> = Vec::new();
+        let mut default_image: Vec<u8> = Vec::new();
+        let mut default_image_animated: bool = false;
+        // fcTL and fdAT chunks share a sequence
+        let mut sequence_number: u32 = 0;
+
+        loop {
+            let chunk: Chunk = Chunk::from_binary(&mut reader)?;
+
+            if matches!(&chunk.chunk_type, b"fcTL" | b"fdAT") {
+                let Some(bytes) = chunk.data.get(0..4) else {
+                    bail!("{} chunk of {} bytes is invalid", chunk.name(), chunk.data.len());
+                };
+
+                let number: u32 = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+                if number != sequence_number {
+                    bail!("{} chunk has sequence number {number} instead of {sequence_number}", chunk.name());
+                }
+
+                sequence_number += 1;
+            }
+
+            match &chunk.chunk_type {
+                b"PLTE" => header.read_palette(&chunk.data)?,
+                b"tRNS" => header.read_transparency(&chunk.data)?,
+                b"gAMA" => header.read_gamma(&chunk.data)?,
+                b"acTL" => {
+                    if chunk.data.len() != 8 {
+                        bail!("acTL chunk of {} bytes is invalid", chunk.data.len());
+                    }
+
+                    let field = |offset: usize| u32::from_be_bytes([chunk.data[offset], chunk.data[offset + 1], chunk.data[offset + 2], chunk.data[offset + 3]]);
+                    animation = Some((field(0), field(4)));
+                },
+                b"fcTL" => {
+                    let control: FrameControl = FrameControl::from_binary(&chunk.data[4..], &header)?;
+
+                    // fcTL before the image data makes the default image the first frame
+                    if frames.is_empty() && default_image.is_empty() {
+                        default_image_animated = true;
+                    }
+
+                    frames.push(AnimationFrame {
+                        control,
+                        data: Vec::new(),
+                        timestamp: Duration::ZERO,
+                    });
+                },
+                b"IDAT" if default_image_animated => frames[0].data.extend_from_slice(&chunk.data),
+                b"IDAT" => default_image.extend_from_slice(&chunk.data),
+                b"fdAT" => match frames.last_mut() {
+                    Some(frame) => frame.data.extend_from_slice(&chunk.data[4..]),
+                    None => bail!("fdAT chunk without fcTL chunk"),
+                },
+                b"IEND" => break,
+                _ if chunk.is_critical() => bail!("Critical PNG chunk {} not supported", chunk.name()),
+                _ => (),
+            }
+        }
+
+        if header.color_type == ColorType::INDEXED && header.palette.is_empty() {
+            bail!("Indexed PNG without PLTE chunk");
+        }
+
+        let plays: Option<u32> = match animation {
+            Some((frames_count, plays)) => {
+                if frames_count as usize != frames.len() {
+                    eprintln!("APNG announces {frames_count} frames but holds {}", frames.len());
+                }
+
+                if plays == 0 { None } else { Some(plays) }
+            },
+            // Images without animation are a single frame
+            None => {
+                frames = vec![AnimationFrame {
+                    control: FrameControl {
+                        width: header.width,
+                        height: header.height,
+                        x_offset: 0,
+                        y_offset: 0,
+                        delay: Duration::ZERO,
+                        dispose_op: DisposeOp::NONE,
+                        blend_op: BlendOp::SOURCE,
+                    },
+                    data: default_image,
+                    timestamp: Duration::ZERO,
+                }];
+                default_image_animated = true;
+
+                Some(1)
+            },
+        };
+
+        if frames.iter().any(|frame| frame.data.is_empty()) {
+            bail!("APNG frame without image data");
+        }
+
+        // Previous canvas of the first frame is the cleared one
+        if let Some(frame) = frames.first_mut().filter(|frame| frame.control.dispose_op == DisposeOp::PREVIOUS) {
+            frame.control.dispose_op = DisposeOp::BACKGROUND;
+        }
+
+        let mut timestamp: Duration = Duration::ZERO;
+
+        for frame in frames.iter_mut() {
+            frame.timestamp = timestamp;
+            timestamp += frame.control.delay;
+        }
+
+        let apng: Self = Self {
+            canvas: vec![(0, 0, 0, 0); pixel_count("APNG", header.width, header.height)?],
+            header,
+            frames,
+            plays,
+            default_image_animated,
+            frame_index: 0,
+            disposal: None,
+            saved_area: Vec::new(),
+        };
+
+        if debug {
+            println!("{apng}");
+        }
+
+        Ok(apng)
+    }
+
+    fn reset(&mut self) {
+        self.canvas.fill((0, 0, 0, 0));
+        self.disposal = None;
+        self.frame_index = 0;
+    }
+
+    // Disposes of the previous frame and draws the frame on the canvas
+    fn compose(&mut self, index: usize) -> Result<()> {
+        let canvas_width: usize = self.header.width as usize;
+
+        if let Some(control) = self.disposal.take() {
+            for y in 0..control.height as usize {
+                let start: usize = (control.y_offset as usize + y) * canvas_width + control.x_offset as usize;
+                let row: &mut [(u8, u8, u8, u8)] = &mut self.canvas[start..start + control.width as usize];
+
+                match control.dispose_op {
+                    DisposeOp::NONE => (),
+                    DisposeOp::BACKGROUND => row.fill((0, 0, 0, 0)),
+                    DisposeOp::PREVIOUS => row.copy_from_slice(&self.saved_area[y * control.width as usize..(y + 1) * control.width as usize]),
+                }
+            }
+        }
+
+        let AnimationFrame { control, data, .. } = &self.frames[index];
+        let control: FrameControl = *control;
+        let pixels: Vec<(u8, u8, u8, u8)> = decode_pixels(&self.header, control.width, control.height, data)?;
+        self.saved_area.clear();
+
+        for y in 0..control.height as usize {
+            let start: usize = (control.y_offset as usize + y) * canvas_width + control.x_offset as usize;
+            let row: &mut [(u8, u8, u8, u8)] = &mut self.canvas[start..start + control.width as usize];
+
+            if control.dispose_op == DisposeOp::PREVIOUS {
+                self.saved_area.extend_from_slice(row);
+            }
+
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let source: (u8, u8, u8, u8) = pixels[y * control.width as usize + x];
+
+                *pixel = match control.blend_op {
+                    BlendOp::SOURCE => source,
+                    BlendOp::OVER => blend_over(source, *pixel),
+                };
+            }
+        }
+
+        self.disposal = Some(control);
+
+        Ok(())
+    }
+}
+
+// Source over destination, neither being premultiplied
+fn blend_over(source: (u8, u8, u8, u8), destination: (u8, u8, u8, u8)) -> (u8, u8, u8, u8) {
+    let source_alpha: u32 = source.3 as u32;
+
+    match source_alpha {
+        255 => return source,
+        0 => return destination,
+        _ => (),
+    }
+
+    let destination_alpha: u32 = destination.3 as u32 * (255 - source_alpha);
+    let alpha: u32 = source_alpha * 255 + destination_alpha;
+    let blend = |source: u8, destination: u8| ((source as u32 * source_alpha * 255 + destination as u32 * destination_alpha) / alpha) as u8;
+
+    (blend(source.0, destination.0), blend(source.1, destination.1), blend(source.2, destination.2), (alpha / 255) as u8)
+}
+
+impl Video for APNG {
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        if self.frame_index >= self.frames.len() {
+            return Ok(None);
+        }
+
+        let index: usize = self.frame_index;
+        self.compose(index)?;
+        self.frame_index += 1;
+
+        let width: usize = self.header.width as usize;
+        let canvas: &[(u8, u8, u8, u8)] = &self.canvas;
+        let image: BMP = BMP::from_rgba_pixels(self.header.width, self.header.height, |x, y| canvas[y * width + x]);
+
+        Ok(Some(Frame {
+            image: Box::new(image),
+            index,
+            timestamp: self.frames[index].timestamp,
+            duration: self.frames[index].control.delay,
+        }))
+    }
+
+    // Frames build on the previous ones, which are drawn again from the first one
+    fn seek_frame(&mut self, index: usize) -> Result<()> {
+        let index: usize = index.min(self.frames.len());
+        self.reset();
+
+        for previous in 0..index {
+            self.compose(previous)?;
+        }
+
+        self.frame_index = index;
+
+        Ok(())
+    }
+
+    fn seek_time(&mut self, timestamp: Duration) -> Result<()> {
+        let index: Option<usize> = self.frames
+            .iter()
+            .position(|frame| timestamp < frame.timestamp + frame.control.delay);
+
+        self.seek_frame(index.unwrap_or(self.frames.len()))
+    }
+
+    fn plays(&self) -> Option<u32> {
+        self.plays
+    }
+}
+
+impl fmt::Display for APNG {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)?;
+        write!(f, "plays: {}\n", self.plays.map_or("forever".to_owned(), |plays| plays.to_string()))?;
+        write!(f, "default_image_animated: {}\n", self.default_image_animated)?;
+        write!(f, "frames: {}\n", self.frames.len())?;
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            let control: FrameControl = frame.control;
+            write!(f, "frame {index}: {}x{} at ({}, {}), {:?}, {:?} {:?}\n", control.width, control.height, control.x_offset, control.y_offset, control.delay, control.dispose_op, control.blend_op)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use crate::image::png::tests::{chunk, ihdr, zlib};
+    use super::*;
+
+    fn fctl(sequence_number: u32, (width, height): (u32, u32), (x, y): (u32, u32), delay: (u16, u16)) -> Vec<u8> {
+        let fields: Vec<u8> = [sequence_number, width, height, x, y].iter().flat_map(|field| field.to_be_bytes()).collect();
+        chunk(b"fcTL", &[fields.as_slice(), &delay.0.to_be_bytes(), &delay.1.to_be_bytes(), &[0, 0]].concat())
+    }
+
+    fn decode(chunks: &[Vec<u8>]) -> Result<APNG> {
+        let bytes: Vec<u8> = [SIGNATURE.to_vec(), chunks.concat(), chunk(b"IEND", &[])].concat();
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        APNG::from_stream(BufReader::new(source), false)
+    }
+
+    #[test]
+    fn frames_are_composed_on_the_canvas() {
+        // Red default image as the first frame, then a green pixel replacing its right half
+        let chunks: [Vec<u8>; 6] = [
+            ihdr(2, 1, 8, 6, false),
+            chunk(b"acTL", &[0, 0, 0, 2, 0, 0, 0, 1]),
+            fctl(0, (2, 1), (0, 0), (1, 4)),
+            chunk(b"IDAT", &zlib(&[0, 255, 0, 0, 255, 255, 0, 0, 255])),
+            fctl(1, (1, 1), (1, 0), (1, 2)),
+            chunk(b"fdAT", &[[0, 0, 0, 2].as_slice(), &zlib(&[0, 0, 255, 0, 255])].concat()),
+        ];
+        let mut apng: APNG = decode(&chunks).unwrap();
+
+        let first: Frame = apng.next_frame().unwrap().unwrap();
+        assert_eq!((first.image.pixel_color(0, 0), first.image.pixel_color(1, 0)), ((255, 0, 0), (255, 0, 0)));
+        assert_eq!(first.duration, Duration::from_millis(250));
+
+        let second: Frame = apng.next_frame().unwrap().unwrap();
+        assert_eq!((second.image.pixel_color(0, 0), second.image.pixel_color(1, 0)), ((255, 0, 0), (0, 255, 0)));
+        assert_eq!(second.timestamp, Duration::from_millis(250));
+
+        assert!(apng.next_frame().unwrap().is_none());
+        assert_eq!(apng.plays(), Some(1));
+    }
+
+    #[test]
+    fn frames_out_of_the_canvas_are_rejected() {
+        let error: String = decode(&[ihdr(2, 1, 8, 6, false), fctl(0, (2, 1), (1, 0), (1, 4))]).unwrap_err().to_string();
+        assert!(error.contains("out of the 2x1 canvas"), "{error}");
+    }
+
+    #[test]
+    fn oversized_canvases_are_rejected() {
+        let error: String = decode(&[ihdr(100000, 100000, 8, 6, false), chunk(b"acTL", &[0, 0, 0, 1, 0, 0, 0, 0])]).unwrap_err().to_string();
+        assert!(error.contains("too large"), "{error}");
+    }
+}
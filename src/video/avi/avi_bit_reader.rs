@@ -0,0 +1,63 @@
+use anyhow::Result;
+
+use crate::image::bit_reader::{BitReader, Stream};
+
+#[derive(Debug)]
+pub struct AviBitReader {
+    next_bit: usize,
+    current_byte: u8,
+    stream: Stream,
+}
+
+impl AviBitReader {
+    pub fn read_fourcc(&mut self) -> Result<[u8; 4]> {
+        let mut fourcc: [u8; 4] = [0; 4];
+        fourcc.copy_from_slice(&self.read_bytes(4)?);
+        Ok(fourcc)
+    }
+
+    // RIFF fields are little endian
+    pub fn read_u16(&mut self) -> Result<u16> {
+        Ok(self.read_word()?.swap_bytes())
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32> {
+        Ok(self.read_double()?.swap_bytes())
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64> {
+        let low: u64 = self.read_u32()? as u64;
+        let high: u64 = self.read_u32()? as u64;
+        Ok((high << 32) | low)
+    }
+}
+
+impl BitReader for AviBitReader {
+    fn new(stream: Stream) -> Self {
+        Self {
+            next_bit: 0,
+            current_byte: 0,
+            stream,
+        }
+    }
+
+    fn read_bit(&mut self) -> Result<u8> {
+        if self.next_bit == 0 {
+            self.current_byte = self.read_byte()?;
+        }
+
+        let bit: u8 = (self.current_byte >> (7 - self.next_bit)) & 0x1;
+        self.next_bit += 1;
+        self.next_bit %= 8;
+
+        Ok(bit)
+    }
+
+    fn set_next_bit(&mut self, next_bit: usize) -> () {
+        self.next_bit = next_bit;
+    }
+
+    fn stream(&mut self) -> &mut Stream {
+        &mut self.stream
+    }
+}
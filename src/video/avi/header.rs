@@ -0,0 +1,191 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::time::Duration;
+
+use crate::image::bit_reader::BitReader;
+use super::avi_bit_reader::AviBitReader;
+use super::index::{read_super_index, SuperIndexEntry};
+
+#[derive(Clone, Debug, Default)]
+pub struct VideoStream {
+    pub number: usize,
+    pub handler: [u8; 4],
+    pub scale: u32,
+    pub rate: u32,
+    pub length: u32,
+    pub compression: [u8; 4],
+    pub width: i32,
+    pub height: i32,
+    pub bits_per_pixel: u16,
+    pub super_index: Vec<SuperIndexEntry>,
+}
+
+impl VideoStream {
+    // Chunks of the stream are named after its number, "00dc" for compressed frames of the first stream
+    pub fn owns_chunk(&self, fourcc: &[u8; 4]) -> bool {
+        let number: [u8; 2] = [b'0' + (self.number / 10) as u8, b'0' + (self.number % 10) as u8];
+
+        fourcc[0..2] == number && (&fourcc[2..4] == b"dc" || &fourcc[2..4] == b"db")
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AVIHeader {
+    pub micro_sec_per_frame: u32,
+    pub total_frames: u32,
+    pub streams: u32,
+    pub width: u32,
+    pub height: u32,
+    // Frames count over every RIFF chunk of OpenDML files
+    pub odml_total_frames: Option<u32>,
+    pub video_stream: Option<VideoStream>,
+}
+
+impl AVIHeader {
+    // Reads the chunks of the "hdrl" list, up to the end position
+    pub fn from_binary(reader: &mut AviBitReader, end: u64) -> Result<Self> {
+        let mut header: AVIHeader = AVIHeader::default();
+        let mut stream_number: usize = 0;
+
+        while reader.position()? + 8 <= end {
+            let fourcc: [u8; 4] = reader.read_fourcc()?;
+            let size: u32 = reader.read_u32()?;
+            let chunk_end: u64 = reader.position()? + size as u64 + (size & 1) as u64;
+
+            match &fourcc {
+                b"avih" => header.read_avih(reader)?,
+                b"LIST" => match &reader.read_fourcc()? {
+                    b"strl" => {
+                        header.read_strl(reader, stream_number, chunk_end)?;
+                        stream_number += 1;
+                    },
+                    b"odml" => header.read_odml(reader, chunk_end)?,
+                    _ => (),
+                },
+                _ => (),
+            }
+
+            reader.seek(chunk_end)?;
+        }
+
+        Ok(header)
+    }
+
+    fn read_avih(&mut self, reader: &mut AviBitReader) -> Result<()> {
+        self.micro_sec_per_frame = reader.read_u32()?;
+        let _max_bytes_per_sec: u32 = reader.read_u32()?;
+        let _padding_granularity: u32 = reader.read_u32()?;
+        let _flags: u32 = reader.read_u32()?;
+        self.total_frames = reader.read_u32()?;
+        let _initial_frames: u32 = reader.read_u32()?;
+        self.streams = reader.read_u32()?;
+        let _suggested_buffer_size: u32 = reader.read_u32()?;
+        self.width = reader.read_u32()?;
+        self.height = reader.read_u32()?;
+
+        Ok(())
+    }
+
+    fn read_strl(&mut self, reader: &mut AviBitReader, number: usize, end: u64) -> Result<()> {
+        let mut stream: VideoStream = VideoStream {
+            number,
+            ..VideoStream::default()
+        };
+        let mut is_video: bool = false;
+
+        while reader.position()? + 8 <= end {
+            let fourcc: [u8; 4] = reader.read_fourcc()?;
+            let size: u32 = reader.read_u32()?;
+            let chunk_end: u64 = reader.position()? + size as u64 + (size & 1) as u64;
+
+            match &fourcc {
+                b"strh" => {
+                    is_video = &reader.read_fourcc()? == b"vids";
+                    stream.handler = reader.read_fourcc()?;
+                    let _flags: u32 = reader.read_u32()?;
+                    let _priority: u16 = reader.read_u16()?;
+                    let _language: u16 = reader.read_u16()?;
+                    let _initial_frames: u32 = reader.read_u32()?;
+                    stream.scale = reader.read_u32()?;
+                    stream.rate = reader.read_u32()?;
+                    let _start: u32 = reader.read_u32()?;
+                    stream.length = reader.read_u32()?;
+                },
+                // BITMAPINFOHEADER for video streams
+                b"strf" if is_video => {
+                    let _header_size: u32 = reader.read_u32()?;
+                    stream.width = reader.read_u32()? as i32;
+                    stream.height = reader.read_u32()? as i32;
+                    let _planes: u16 = reader.read_u16()?;
+                    stream.bits_per_pixel = reader.read_u16()?;
+                    stream.compression = reader.read_fourcc()?;
+                },
+                b"indx" => stream.super_index = read_super_index(reader, size)?,
+                _ => (),
+            }
+
+            reader.seek(chunk_end)?;
+        }
+
+        if is_video && self.video_stream.is_none() {
+            self.video_stream = Some(stream);
+        }
+
+        Ok(())
+    }
+
+    fn read_odml(&mut self, reader: &mut AviBitReader, end: u64) -> Result<()> {
+        while reader.position()? + 8 <= end {
+            let fourcc: [u8; 4] = reader.read_fourcc()?;
+            let size: u32 = reader.read_u32()?;
+            let chunk_end: u64 = reader.position()? + size as u64 + (size & 1) as u64;
+
+            if &fourcc == b"dmlh" {
+                self.odml_total_frames = Some(reader.read_u32()?);
+            }
+
+            reader.seek(chunk_end)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn video_stream(&self) -> Result<&VideoStream> {
+        let Some(stream) = &self.video_stream else {
+            bail!("AVI file has no video stream");
+        };
+
+        Ok(stream)
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        match &self.video_stream {
+            Some(stream) if stream.rate != 0 => Duration::from_secs_f64(stream.scale as f64 / stream.rate as f64),
+            _ => Duration::from_micros(self.micro_sec_per_frame as u64),
+        }
+    }
+}
+
+impl fmt::Display for AVIHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "micro_sec_per_frame: {}\n", self.micro_sec_per_frame)?;
+        write!(f, "total_frames: {}\n", self.total_frames)?;
+        write!(f, "odml_total_frames: {:?}\n", self.odml_total_frames)?;
+        write!(f, "streams: {}\n", self.streams)?;
+        write!(f, "width: {}\n", self.width)?;
+        write!(f, "height: {}\n", self.height)?;
+
+        if let Some(stream) = &self.video_stream {
+            write!(f, "video stream: {}\n", stream.number)?;
+            write!(f, "handler: {}\n", String::from_utf8_lossy(&stream.handler))?;
+            write!(f, "compression: {}\n", String::from_utf8_lossy(&stream.compression))?;
+            write!(f, "scale: {}\n", stream.scale)?;
+            write!(f, "rate: {}\n", stream.rate)?;
+            write!(f, "length: {}\n", stream.length)?;
+            write!(f, "super index entries: {}\n", stream.super_index.len())?;
+            write!(f, "super index frames: {}\n", stream.super_index.iter().map(|entry| entry.duration as u64).sum::<u64>())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{bail, Result};
+
+use crate::image::bit_reader::BitReader;
+use super::avi_bit_reader::AviBitReader;
+use super::header::VideoStream;
+
+const AVIIF_KEYFRAME: u32 = 0x10;
+const AVI_INDEX_OF_INDEXES: u8 = 0x00;
+const AVI_INDEX_OF_CHUNKS: u8 = 0x01;
+
+// Location of the data of a frame, past its chunk header
+#[derive(Clone, Copy, Debug)]
+pub struct IndexEntry {
+    pub offset: u64,
+    pub size: u32,
+    pub keyframe: bool,
+}
+
+// Location of an OpenDML standard index chunk ("ix##")
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SuperIndexEntry {
+    pub offset: u64,
+    pub duration: u32,
+}
+
+// OpenDML "indx" chunk of a stream list, reader being past the chunk header
+pub fn read_super_index(reader: &mut AviBitReader, size: u32) -> Result<Vec<SuperIndexEntry>> {
+    let longs_per_entry: u16 = reader.read_u16()?;
+    let _index_sub_type: u8 = reader.read_byte()?;
+    let index_type: u8 = reader.read_byte()?;
+    let entries_in_use: u32 = reader.read_u32()?;
+    let _chunk_id: [u8; 4] = reader.read_fourcc()?;
+    reader.read_bytes(12)?;
+
+    // Some muxers only write a standard index in place of the super index, which is left for the chunk scan
+    if index_type != AVI_INDEX_OF_INDEXES {
+        return Ok(Vec::new());
+    }
+
+    let remaining: u64 = reader.length()? - reader.position()?;
+
+    if longs_per_entry != 4 || 24 + entries_in_use as u64 * 16 > size as u64 || entries_in_use as u64 * 16 > remaining {
+        bail!("Invalid OpenDML super index");
+    }
+
+    let mut entries: Vec<SuperIndexEntry> = Vec::with_capacity(entries_in_use as usize);
+
+    for _ in 0..entries_in_use {
+        let offset: u64 = reader.read_u64()?;
+        let _size: u32 = reader.read_u32()?;
+        let duration: u32 = reader.read_u32()?;
+
+        entries.push(SuperIndexEntry {
+            offset,
+            duration,
+        });
+    }
+
+    Ok(entries)
+}
+
+// OpenDML "ix##" chunks pointed by the super index
+pub fn read_standard_indexes(reader: &mut AviBitReader, super_index: &[SuperIndexEntry]) -> Result<Vec<IndexEntry>> {
+    let mut entries: Vec<IndexEntry> = Vec::new();
+
+    for super_entry in super_index {
+        reader.seek(super_entry.offset)?;
+
+        let _fourcc: [u8; 4] = reader.read_fourcc()?;
+        let _size: u32 = reader.read_u32()?;
+        let longs_per_entry: u16 = reader.read_u16()?;
+        let _index_sub_type: u8 = reader.read_byte()?;
+        let index_type: u8 = reader.read_byte()?;
+        let entries_in_use: u32 = reader.read_u32()?;
+        let _chunk_id: [u8; 4] = reader.read_fourcc()?;
+        let base_offset: u64 = reader.read_u64()?;
+        let _reserved: u32 = reader.read_u32()?;
+
+        if index_type != AVI_INDEX_OF_CHUNKS || longs_per_entry != 2 {
+            bail!("Invalid OpenDML standard index at {:X}", super_entry.offset);
+        }
+
+        for _ in 0..entries_in_use {
+            let offset: u32 = reader.read_u32()?;
+            let size: u32 = reader.read_u32()?;
+
+            let Some(offset) = base_offset.checked_add(offset as u64) else {
+                bail!("Invalid OpenDML standard index at {:X}", super_entry.offset);
+            };
+
+            // Bit 31 of the size is set for delta frames
+            entries.push(IndexEntry {
+                offset,
+                size: size & 0x7FFFFFFF,
+                keyframe: size & 0x80000000 == 0,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+// Legacy "idx1" chunk, reader being past the chunk header
+// Offsets point to chunk headers, either from the "movi" fourcc or from the start of the file
+pub fn read_idx1(reader: &mut AviBitReader, size: u32, stream: &VideoStream, movi_start: u64) -> Result<Vec<IndexEntry>> {
+    // Chunks declared past the end of the file are cut short by it
+    let remaining: u64 = reader.length()? - reader.position()?;
+    let count: u64 = (size as u64).min(remaining) / 16;
+    let mut raw_entries: Vec<([u8; 4], u32, u32, u32)> = Vec::with_capacity(count as usize);
+
+    for _ in 0..count {
+        raw_entries.push((reader.read_fourcc()?, reader.read_u32()?, reader.read_u32()?, reader.read_u32()?));
+    }
+
+    let Some(&(first_fourcc, _, first_offset, _)) = raw_entries.first() else {
+        return Ok(Vec::new());
+    };
+
+    reader.seek(movi_start + first_offset as u64)?;
+    let base: u64 = match reader.read_fourcc() {
+        Ok(fourcc) if fourcc == first_fourcc => movi_start,
+        _ => 0,
+    };
+
+    Ok(raw_entries
+        .into_iter()
+        .filter(|(fourcc, _, _, _)| stream.owns_chunk(fourcc))
+        .map(|(_, flags, offset, size)| IndexEntry {
+            offset: base + offset as u64 + 8,
+            size,
+            keyframe: flags & AVIIF_KEYFRAME != 0,
+        })
+        .collect())
+}
+
+// Walks the chunks of the "movi" lists when the file has no index
+pub fn scan(reader: &mut AviBitReader, movi_lists: &[(u64, u64)], stream: &VideoStream) -> Result<Vec<IndexEntry>> {
+    let mut entries: Vec<IndexEntry> = Vec::new();
+
+    for &(start, end) in movi_lists {
+        let mut position: u64 = start + 4;
+
+        while position + 8 <= end {
+            reader.seek(position)?;
+            let fourcc: [u8; 4] = reader.read_fourcc()?;
+            let size: u32 = reader.read_u32()?;
+
+            // "rec " lists group chunks played together, their content is walked as well
+            if &fourcc == b"LIST" {
+                position += 12;
+                continue;
+            }
+
+            if stream.owns_chunk(&fourcc) {
+                entries.push(IndexEntry {
+                    offset: position + 8,
+                    size,
+                    keyframe: true,
+                });
+            }
+
+            position += 8 + size as u64 + (size & 1) as u64;
+        }
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,263 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::time::Duration;
+
+use avi_bit_reader::AviBitReader;
+use crate::image::bit_reader::{BitReader, Stream};
+use crate::image::bmp::BMP;
+use crate::image::{from_bytes, ImageType};
+use header::{AVIHeader, VideoStream};
+use index::{read_idx1, read_standard_indexes, scan, IndexEntry};
+use super::{Frame, Video};
+
+mod avi_bit_reader;
+mod header;
+mod index;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum IndexSource {
+    OpenDML,
+    Idx1,
+    Scan,
+}
+
+#[derive(Debug)]
+pub struct AVI {
+    header: AVIHeader,
+    reader: AviBitReader,
+    index: Vec<IndexEntry>,
+    index_source: IndexSource,
+    frame_index: usize,
+    // Zero sized chunks repeat the previous frame
+    previous_image: Option<Box<BMP>>,
+}
+
+impl AVI {
+    pub fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: AviBitReader = AviBitReader::new(stream);
+        let mut header: Option<AVIHeader> = None;
+        let mut idx1: Option<(u64, u32)> = None;
+        // Start ("movi" fourcc) and end of every "movi" list, one per RIFF chunk in OpenDML files
+        let mut movi_lists: Vec<(u64, u64)> = Vec::new();
+
+        let length: u64 = reader.length()?;
+        let mut first_riff: bool = true;
+
+        while reader.position()? + 12 <= length {
+            let riff_start: u64 = reader.position()?;
+
+            if &reader.read_fourcc()? != b"RIFF" {
+                bail!("Expected RIFF chunk at {riff_start:X}");
+            }
+
+            let riff_size: u32 = reader.read_u32()?;
+            let riff_end: u64 = (reader.position()? + riff_size as u64).min(length);
+            let form: [u8; 4] = reader.read_fourcc()?;
+
+            if first_riff && &form != b"AVI " {
+                bail!("Not an AVI file");
+            }
+
+            first_riff = false;
+
+            while reader.position()? + 8 <= riff_end {
+                let fourcc: [u8; 4] = reader.read_fourcc()?;
+                let size: u32 = reader.read_u32()?;
+                let chunk_start: u64 = reader.position()?;
+                let chunk_end: u64 = chunk_start + size as u64 + (size & 1) as u64;
+
+                match &fourcc {
+                    b"LIST" => match &reader.read_fourcc()? {
+                        b"hdrl" => header = Some(AVIHeader::from_binary(&mut reader, chunk_start + size as u64)?),
+                        b"movi" => movi_lists.push((chunk_start, (chunk_start + size as u64).min(length))),
+                        _ => (),
+                    },
+                    b"idx1" => idx1 = Some((chunk_start, size)),
+                    _ => (),
+                }
+
+                reader.seek(chunk_end)?;
+            }
+
+            reader.seek(riff_end + (riff_end & 1))?;
+        }
+
+        let Some(header) = header else {
+            bail!("AVI file has no header list");
+        };
+
+        let stream: &VideoStream = header.video_stream()?;
+
+        if !matches!(stream.compression.to_ascii_uppercase().as_slice(), b"MJPG" | b"JPEG" | b"AVRN") {
+            bail!("AVI video compression {} not supported", String::from_utf8_lossy(&stream.compression));
+        }
+
+        let (index, index_source): (Vec<IndexEntry>, IndexSource) = if !stream.super_index.is_empty() {
+            (read_standard_indexes(&mut reader, &stream.super_index)?, IndexSource::OpenDML)
+        } else if let (Some((start, size)), Some(&(movi_start, _))) = (idx1, movi_lists.first()) {
+            reader.seek(start)?;
+            (read_idx1(&mut reader, size, stream, movi_start)?, IndexSource::Idx1)
+        } else {
+            (scan(&mut reader, &movi_lists, stream)?, IndexSource::Scan)
+        };
+
+        let avi: Self = Self {
+            header,
+            reader,
+            index,
+            index_source,
+            frame_index: 0,
+            previous_image: None,
+        };
+
+        if debug {
+            println!("{avi}");
+        }
+
+        Ok(avi)
+    }
+
+    fn decode(&mut self, entry: IndexEntry) -> Result<Box<BMP>> {
+        if entry.size == 0 {
+            if let Some(image) = &self.previous_image {
+                return Ok(image.clone());
+            }
+
+            bail!("AVI file starts with an empty frame");
+        }
+
+        // Sizes of 31 bits are only trusted as far as the file goes
+        if entry.offset.saturating_add(entry.size as u64) > self.reader.length()? {
+            bail!("AVI frame at {:X} runs past the end of the file", entry.offset);
+        }
+
+        self.reader.seek(entry.offset)?;
+        let bytes: Vec<u8> = self.reader.read_bytes(entry.size as usize)?;
+
+        Ok(from_bytes(bytes, ImageType::JPEG, false)?.to_bmp())
+    }
+}
+
+impl Video for AVI {
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let Some(&entry) = self.index.get(self.frame_index) else {
+            return Ok(None);
+        };
+
+        let image: Box<BMP> = self.decode(entry)?;
+        let duration: Duration = self.header.frame_duration();
+        let index: usize = self.frame_index;
+        self.frame_index += 1;
+        self.previous_image = Some(image.clone());
+
+        Ok(Some(Frame {
+            image,
+            index,
+            timestamp: duration * index as u32,
+            duration,
+        }))
+    }
+
+    fn seek_frame(&mut self, index: usize) -> Result<()> {
+        if index >= self.index.len() {
+            self.frame_index = self.index.len();
+            return Ok(());
+        }
+
+        self.frame_index = (0..=index)
+            .rev()
+            .find(|&i| self.index[i].keyframe && self.index[i].size != 0)
+            .unwrap_or(0);
+        self.previous_image = None;
+
+        Ok(())
+    }
+
+    fn seek_time(&mut self, timestamp: Duration) -> Result<()> {
+        let duration: Duration = self.header.frame_duration();
+
+        if duration.is_zero() {
+            return self.seek_frame(0);
+        }
+
+        self.seek_frame((timestamp.as_secs_f64() / duration.as_secs_f64()) as usize)
+    }
+}
+
+impl fmt::Display for AVI {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Header:\n{}", self.header)?;
+        write!(f, "index: {:?}\n", self.index_source)?;
+        write!(f, "indexed frames: {}\n", self.index.len())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn chunk(fourcc: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = fourcc.to_vec();
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+
+        if data.len() % 2 == 1 {
+            bytes.push(0);
+        }
+
+        bytes
+    }
+
+    fn list(kind: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+        chunk(b"LIST", &[kind.to_vec(), chunks.concat()].concat())
+    }
+
+    // Motion JPEG stream at 25 frames per second, with one frame of 4 bytes and the given index
+    fn avi(frame_size: u32, idx1_size: u32) -> AVI {
+        let mut strh: Vec<u8> = [b"vids".as_slice(), b"MJPG", &[0; 12], &1u32.to_le_bytes(), &25u32.to_le_bytes()].concat();
+        strh.resize(56, 0);
+        let mut strf: Vec<u8> = [40u32.to_le_bytes(), 16u32.to_le_bytes(), 16u32.to_le_bytes()].concat();
+        strf.extend_from_slice(&[1, 0, 24, 0]);
+        strf.extend_from_slice(b"MJPG");
+        strf.resize(40, 0);
+
+        let hdrl: Vec<u8> = list(b"hdrl", &[chunk(b"avih", &[0; 56]), list(b"strl", &[chunk(b"strh", &strh), chunk(b"strf", &strf)])]);
+        let movi: Vec<u8> = list(b"movi", &[chunk(b"00dc", &[0; 4])]);
+        let idx1: Vec<u8> = [b"idx1".as_slice(), &idx1_size.to_le_bytes(), b"00dc", &0x10u32.to_le_bytes(), &4u32.to_le_bytes(), &frame_size.to_le_bytes()].concat();
+
+        let content: Vec<u8> = [b"AVI ".to_vec(), hdrl, movi, idx1].concat();
+        let bytes: Vec<u8> = [b"RIFF".as_slice(), &(content.len() as u32).to_le_bytes(), &content].concat();
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+
+        AVI::from_stream(BufReader::new(source), false).unwrap()
+    }
+
+    #[test]
+    fn index_is_read_from_idx1() {
+        let avi: AVI = avi(4, 16);
+
+        assert_eq!(avi.index_source, IndexSource::Idx1);
+        assert_eq!(avi.index.len(), 1);
+        assert_eq!(avi.index[0].size, 4);
+        assert!(avi.index[0].keyframe);
+    }
+
+    #[test]
+    fn oversized_idx1_is_cut_at_end_of_file() {
+        let avi: AVI = avi(4, 0xFFFFFFF0);
+
+        assert_eq!(avi.index.len(), 1);
+    }
+
+    #[test]
+    fn frames_past_end_of_file_are_rejected() {
+        let mut avi: AVI = avi(0x7FFFFFFF, 16);
+        let error: String = avi.next_frame().unwrap_err().to_string();
+
+        assert!(error.contains("runs past the end of the file"), "{error}");
+    }
+}
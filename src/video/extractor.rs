@@ -0,0 +1,102 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::image::{to_file, OutputType};
+use super::{Frame, Video};
+
+#[derive(Clone, Copy, Debug)]
+pub enum FrameSelection {
+    Index(usize),
+    Timestamp(Duration),
+    Every(usize),
+}
+
+// Expands the printf-like "%d" or "%0Nd" placeholder of the pattern with the frame index
+pub fn format_pattern(pattern: &str, index: usize) -> Result<String> {
+    let Some(start) = pattern.find('%') else {
+        return Ok(pattern.to_owned());
+    };
+
+    let Some(length) = pattern[start + 1..].find('d') else {
+        bail!("Invalid placeholder in pattern \"{pattern}\"");
+    };
+
+    let specifier: &str = &pattern[start + 1..start + 1 + length];
+    let width: usize = match specifier {
+        "" => 0,
+        _ if specifier.starts_with('0') => specifier.parse()?,
+        _ => bail!("Invalid placeholder in pattern \"{pattern}\""),
+    };
+
+    Ok(format!("{}{:0width$}{}", &pattern[..start], index, &pattern[start + length + 2..]))
+}
+
+// Decodes the video up to the selected frames and saves them, returning the number of frames written
+pub fn extract(mut video: Box<dyn Video>, selection: FrameSelection, pattern: &str, output_type: OutputType) -> Result<usize> {
+    match selection {
+        FrameSelection::Index(index) => video.seek_frame(index)?,
+        FrameSelection::Timestamp(timestamp) => video.seek_time(timestamp)?,
+        FrameSelection::Every(_) => (),
+    }
+
+    let mut written: usize = 0;
+    let mut last: Option<(usize, Duration)> = None;
+
+    while let Some(frame) = video.next_frame()? {
+        let Frame { image, index, timestamp, duration } = frame;
+
+        // Seeking lands on the previous frame that can be decoded on its own
+        let selected: bool = match selection {
+            FrameSelection::Index(target) => index == target,
+            FrameSelection::Timestamp(target) => target < timestamp + duration,
+            FrameSelection::Every(step) => index % step.max(1) == 0,
+        };
+
+        if selected {
+            let filepath: String = format_pattern(pattern, index)?;
+
+            // Frames piped to the standard output are left alone
+            if filepath != "-" {
+                println!("Saving frame {index} as \"{filepath}\"");
+            }
+
+            to_file(&image, PathBuf::from(filepath), output_type)?;
+            written += 1;
+
+            if !matches!(selection, FrameSelection::Every(_)) {
+                return Ok(written);
+            }
+        }
+
+        last = Some((index, timestamp + duration));
+    }
+
+    if written == 0 {
+        match last {
+            Some((index, end)) => bail!("Selected frame is out of the video ({} frames, {:.2}s)", index + 1, end.as_secs_f32()),
+            None => bail!("Selected frame is out of the video"),
+        }
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_expanded() {
+        assert_eq!(format_pattern("frame_%05d.bmp", 42).unwrap(), "frame_00042.bmp");
+        assert_eq!(format_pattern("frame_%d.bmp", 42).unwrap(), "frame_42.bmp");
+        assert_eq!(format_pattern("still.bmp", 42).unwrap(), "still.bmp");
+        assert_eq!(format_pattern("-", 42).unwrap(), "-");
+    }
+
+    #[test]
+    fn invalid_placeholders_are_rejected() {
+        assert!(format_pattern("frame_%5d.bmp", 0).is_err());
+        assert!(format_pattern("frame_%.bmp", 0).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use std::fmt;
+use std::time::Duration;
+
+use crate::image::bit_reader::Stream;
+use crate::image::gif::frame::{DisposalMethod, GIFFrame};
+use crate::image::gif::GIF;
+use super::{Frame, Video};
+
+#[derive(Debug)]
+pub struct AnimatedGIF {
+    gif: GIF,
+    // Start of each frame
+    timestamps: Vec<Duration>,
+    frame_index: usize,
+    // Last frame drawn, disposed of before drawing the next one
+    disposal: Option<usize>,
+    // Canvas as it was before the last frame, when it has to be restored
+    saved_canvas: Vec<(u8, u8, u8, u8)>,
+}
+
+impl AnimatedGIF {
+    pub fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let gif: GIF = GIF::from_binary(stream)?;
+        let mut timestamps: Vec<Duration> = Vec::with_capacity(gif.frames.len());
+        let mut timestamp: Duration = Duration::ZERO;
+
+        for frame in gif.frames.iter() {
+            timestamps.push(timestamp);
+            timestamp += frame.control.delay;
+        }
+
+        let animated_gif: Self = Self {
+            gif,
+            timestamps,
+            frame_index: 0,
+            disposal: None,
+            saved_canvas: Vec::new(),
+        };
+
+        if debug {
+            println!("{animated_gif}");
+        }
+
+        Ok(animated_gif)
+    }
+
+    fn reset(&mut self) {
+        self.gif.canvas.fill((0, 0, 0, 0));
+        self.disposal = None;
+        self.frame_index = 0;
+    }
+
+    // Disposes of the previous frame and draws the frame on the canvas
+    fn compose(&mut self, index: usize) -> Result<()> {
+        if let Some(previous) = self.disposal.take() {
+            let frame: &GIFFrame = &self.gif.frames[previous];
+            let width: usize = self.gif.header.width as usize;
+            let height: usize = self.gif.header.height as usize;
+
+            for y in (frame.top as usize..frame.top as usize + frame.height as usize).take_while(|&y| y < height) {
+                for x in (frame.left as usize..frame.left as usize + frame.width as usize).take_while(|&x| x < width) {
+                    match frame.control.disposal {
+                        DisposalMethod::NONE => (),
+                        DisposalMethod::BACKGROUND => self.gif.canvas[y * width + x] = (0, 0, 0, 0),
+                        DisposalMethod::PREVIOUS => self.gif.canvas[y * width + x] = self.saved_canvas[y * width + x],
+                    }
+                }
+            }
+        }
+
+        if self.gif.frames[index].control.disposal == DisposalMethod::PREVIOUS {
+            self.saved_canvas.clone_from(&self.gif.canvas);
+        }
+
+        self.gif.draw_frame(index)?;
+        self.disposal = Some(index);
+
+        Ok(())
+    }
+}
+
+impl Video for AnimatedGIF {
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        if self.frame_index >= self.gif.frames.len() {
+            return Ok(None);
+        }
+
+        let index: usize = self.frame_index;
+        self.compose(index)?;
+        self.frame_index += 1;
+
+        Ok(Some(Frame {
+            image: Box::new(self.gif.to_image()),
+            index,
+            timestamp: self.timestamps[index],
+            duration: self.gif.frames[index].control.delay,
+        }))
+    }
+
+    // Frames build on the previous ones, which are drawn again from the first one
+    fn seek_frame(&mut self, index: usize) -> Result<()> {
+        let index: usize = index.min(self.gif.frames.len());
+        self.reset();
+
+        for previous in 0..index {
+            self.compose(previous)?;
+        }
+
+        self.frame_index = index;
+
+        Ok(())
+    }
+
+    fn seek_time(&mut self, timestamp: Duration) -> Result<()> {
+        let index: Option<usize> = self.gif.frames
+            .iter()
+            .zip(self.timestamps.iter())
+            .position(|(frame, &start)| timestamp < start + frame.control.delay);
+
+        self.seek_frame(index.unwrap_or(self.gif.frames.len()))
+    }
+
+    // Loop count is the number of repetitions following the first play
+    fn plays(&self) -> Option<u32> {
+        match self.gif.loop_count {
+            None => Some(1),
+            Some(0) => None,
+            Some(loop_count) => Some(loop_count as u32 + 1),
+        }
+    }
+}
+
+impl fmt::Display for AnimatedGIF {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.gif)
+    }
+}
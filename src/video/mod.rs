@@ -0,0 +1,101 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use apng::APNG;
+use avi::AVI;
+use gif::AnimatedGIF;
+use mp4::MP4;
+use webp::AnimatedWebP;
+use crate::image::bit_reader::Source;
+use crate::image::bmp::BMP;
+use yuv::{YUVFormat, YUV};
+
+pub mod apng;
+pub mod avi;
+pub mod extractor;
+pub mod gif;
+pub mod mp4;
+pub mod webp;
+pub mod yuv;
+
+#[derive(ValueEnum, Clone, Debug, Serialize)]
+pub enum VideoType {
+    // Animated PNG
+    APNG,
+    AVI,
+    GIF,
+    MP4,
+    // Animated WebP
+    WEBP,
+    YUV,
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    pub image: Box<BMP>,
+    pub index: usize,
+    pub timestamp: Duration,
+    pub duration: Duration,
+}
+
+pub trait Video {
+    fn next_frame(&mut self) -> Result<Option<Frame>>;
+
+    // Next frame will be the closest one at or before the index that can be decoded on its own
+    // Seeking past the last frame ends the video
+    fn seek_frame(&mut self, index: usize) -> Result<()>;
+
+    // Same as seek_frame, with the frame displayed at the timestamp
+    fn seek_time(&mut self, timestamp: Duration) -> Result<()>;
+
+    // Number of times the video is meant to be played in a row, None standing for forever
+    fn plays(&self) -> Option<u32> {
+        Some(1)
+    }
+}
+
+pub fn from_file(filepath: &PathBuf, video_type: VideoType, yuv_format: Option<YUVFormat>, debug: bool) -> Result<Box<dyn Video>> {
+    let file: File = File::open(filepath)?;
+
+    match video_type {
+        VideoType::APNG => {
+            let file: Box<dyn Source> = Box::new(file);
+
+            Ok(Box::new(APNG::from_stream(BufReader::new(file), debug)?))
+        },
+        VideoType::AVI => {
+            let file: Box<dyn Source> = Box::new(file);
+
+            Ok(Box::new(AVI::from_stream(BufReader::new(file), debug)?))
+        },
+        VideoType::GIF => {
+            let file: Box<dyn Source> = Box::new(file);
+
+            Ok(Box::new(AnimatedGIF::from_stream(BufReader::new(file), debug)?))
+        },
+        VideoType::MP4 => {
+            let file: Box<dyn Source> = Box::new(file);
+
+            Ok(Box::new(MP4::from_stream(BufReader::new(file), debug)?))
+        },
+        VideoType::WEBP => {
+            let file: Box<dyn Source> = Box::new(file);
+
+            Ok(Box::new(AnimatedWebP::from_stream(BufReader::new(file), debug)?))
+        },
+        VideoType::YUV => {
+            let Some(format) = yuv_format else {
+                bail!("Raw YUV input needs a width, a height and a pixel format");
+            };
+
+            let length: u64 = file.metadata()?.len();
+
+            Ok(Box::new(YUV::from_stream(BufReader::new(file), length, format, debug)?))
+        },
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::image::bit_reader::BitReader;
+use super::mp4_bit_reader::Mp4BitReader;
+use super::sample_table::SampleTable;
+
+// Reads a box header, returning the box type and the end position of the box
+pub fn read_box(reader: &mut Mp4BitReader, parent_end: u64) -> Result<([u8; 4], u64)> {
+    let start: u64 = reader.position()?;
+    let size: u32 = reader.read_double()?;
+    let box_type: [u8; 4] = reader.read_fourcc()?;
+
+    let end: u64 = match size {
+        0 => parent_end,
+        1 => match start.checked_add(reader.read_u64()?) {
+            Some(end) => end,
+            None => bail!("Box {} at {start:X} has an invalid size", String::from_utf8_lossy(&box_type)),
+        },
+        _ => start + size as u64,
+    };
+
+    if end > parent_end || end < reader.position()? {
+        bail!("Box {} at {start:X} overflows its parent", String::from_utf8_lossy(&box_type));
+    }
+
+    Ok((box_type, end))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Track {
+    pub id: u32,
+    pub handler: [u8; 4],
+    pub timescale: u32,
+    pub duration: u64,
+    // Type of the first sample entry of the "stsd" box
+    pub codec: [u8; 4],
+    pub width: u16,
+    pub height: u16,
+    pub depth: u16,
+    pub sample_table: SampleTable,
+}
+
+impl Track {
+    pub fn is_video(&self) -> bool {
+        &self.handler == b"vide"
+    }
+
+    fn from_binary(reader: &mut Mp4BitReader, end: u64) -> Result<Self> {
+        let mut track: Track = Track::default();
+        track.read_children(reader, end)?;
+        Ok(track)
+    }
+
+    // Walks the containers down to the boxes describing the samples
+    fn read_children(&mut self, reader: &mut Mp4BitReader, end: u64) -> Result<()> {
+        while reader.position()? + 8 <= end {
+            let (box_type, box_end) = read_box(reader, end)?;
+
+            match &box_type {
+                b"mdia" | b"minf" | b"stbl" => self.read_children(reader, box_end)?,
+                b"tkhd" => self.read_tkhd(reader)?,
+                b"mdhd" => self.read_mdhd(reader)?,
+                b"hdlr" => {
+                    reader.read_bytes(8)?;
+                    self.handler = reader.read_fourcc()?;
+                },
+                b"stsd" => self.read_stsd(reader)?,
+                b"stsz" => self.sample_table.read_stsz(reader)?,
+                b"stco" => self.sample_table.read_stco(reader, false)?,
+                b"co64" => self.sample_table.read_stco(reader, true)?,
+                b"stsc" => self.sample_table.read_stsc(reader)?,
+                b"stts" => self.sample_table.read_stts(reader)?,
+                b"stss" => self.sample_table.read_stss(reader)?,
+                _ => (),
+            }
+
+            reader.seek(box_end)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_tkhd(&mut self, reader: &mut Mp4BitReader) -> Result<()> {
+        let version: u8 = reader.read_byte()?;
+        reader.read_bytes(3)?;
+
+        // Creation and modification times
+        reader.read_bytes(if version == 1 { 16 } else { 8 })?;
+        self.id = reader.read_double()?;
+
+        Ok(())
+    }
+
+    fn read_mdhd(&mut self, reader: &mut Mp4BitReader) -> Result<()> {
+        let version: u8 = reader.read_byte()?;
+        reader.read_bytes(3)?;
+
+        if version == 1 {
+            reader.read_bytes(16)?;
+            self.timescale = reader.read_double()?;
+            self.duration = reader.read_u64()?;
+        } else {
+            reader.read_bytes(8)?;
+            self.timescale = reader.read_double()?;
+            self.duration = reader.read_double()? as u64;
+        }
+
+        Ok(())
+    }
+
+    // Visual sample entry, only the first one is used
+    fn read_stsd(&mut self, reader: &mut Mp4BitReader) -> Result<()> {
+        reader.read_bytes(4)?;
+        let entry_count: u32 = reader.read_double()?;
+
+        if entry_count == 0 {
+            return Ok(());
+        }
+
+        let _size: u32 = reader.read_double()?;
+        self.codec = reader.read_fourcc()?;
+
+        // Reserved bytes, data reference index, version, revision, vendor, temporal and spatial qualities
+        reader.read_bytes(6 + 2 + 16)?;
+        self.width = reader.read_word()?;
+        self.height = reader.read_word()?;
+
+        // Resolutions, data size, frame count and compressor name
+        reader.read_bytes(4 + 4 + 4 + 2 + 32)?;
+        self.depth = reader.read_word()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MP4Header {
+    pub brand: [u8; 4],
+    pub tracks: Vec<Track>,
+}
+
+impl MP4Header {
+    pub fn from_binary(reader: &mut Mp4BitReader) -> Result<Self> {
+        let mut header: MP4Header = MP4Header::default();
+        let length: u64 = reader.length()?;
+        let mut found_moov: bool = false;
+
+        while reader.position()? + 8 <= length {
+            let (box_type, box_end) = read_box(reader, length)?;
+
+            match &box_type {
+                b"ftyp" => header.brand = reader.read_fourcc()?,
+                b"moov" => {
+                    header.read_moov(reader, box_end)?;
+                    found_moov = true;
+                },
+                _ => (),
+            }
+
+            reader.seek(box_end)?;
+        }
+
+        if !found_moov {
+            bail!("No movie box found, fragmented files are not supported");
+        }
+
+        Ok(header)
+    }
+
+    fn read_moov(&mut self, reader: &mut Mp4BitReader, end: u64) -> Result<()> {
+        while reader.position()? + 8 <= end {
+            let (box_type, box_end) = read_box(reader, end)?;
+
+            if &box_type == b"trak" {
+                self.tracks.push(Track::from_binary(reader, box_end)?);
+            }
+
+            reader.seek(box_end)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for MP4Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "brand: {}\n", String::from_utf8_lossy(&self.brand))?;
+
+        for track in self.tracks.iter() {
+            write!(f, "track: {}\n", track.id)?;
+            write!(f, "handler: {}\n", String::from_utf8_lossy(&track.handler))?;
+            write!(f, "codec: {}\n", String::from_utf8_lossy(&track.codec))?;
+            write!(f, "timescale: {}\n", track.timescale)?;
+            write!(f, "duration: {}\n", track.duration)?;
+            write!(f, "width: {}\n", track.width)?;
+            write!(f, "height: {}\n", track.height)?;
+            write!(f, "depth: {}\n", track.depth)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn open(bytes: Vec<u8>) -> Mp4BitReader {
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        Mp4BitReader::new(BufReader::new(source))
+    }
+
+    #[test]
+    fn box_sizes_are_read() {
+        let mut bytes: Vec<u8> = [16u32.to_be_bytes().as_slice(), b"free", &[0; 8]].concat();
+        bytes.extend_from_slice(&[1u32.to_be_bytes().as_slice(), b"mdat", &24u64.to_be_bytes(), &[0; 8]].concat());
+        let mut reader: Mp4BitReader = open(bytes);
+
+        assert_eq!(read_box(&mut reader, 40).unwrap(), (*b"free", 16));
+        reader.seek(16).unwrap();
+        assert_eq!(read_box(&mut reader, 40).unwrap(), (*b"mdat", 40));
+    }
+
+    #[test]
+    fn boxes_overflowing_their_parent_are_rejected() {
+        let mut reader: Mp4BitReader = open([32u32.to_be_bytes().as_slice(), b"free", &[0; 8]].concat());
+        assert!(read_box(&mut reader, 16).is_err());
+
+        let mut reader: Mp4BitReader = open([16u32.to_be_bytes().as_slice(), b"skip", &[0; 8], &1u32.to_be_bytes(), b"mdat", &u64::MAX.to_be_bytes()].concat());
+        reader.seek(16).unwrap();
+        assert!(read_box(&mut reader, 32).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::time::Duration;
+
+use crate::image::bit_reader::{BitReader, Stream};
+use crate::image::bmp::BMP;
+use crate::image::{from_bytes, ImageType};
+use header::{MP4Header, Track};
+use mp4_bit_reader::Mp4BitReader;
+use sample_table::Sample;
+use super::{Frame, Video};
+
+mod header;
+mod mp4_bit_reader;
+mod sample_table;
+
+#[derive(Debug)]
+pub struct MP4 {
+    reader: Mp4BitReader,
+    track: Track,
+    samples: Vec<Sample>,
+    sample_index: usize,
+}
+
+impl MP4 {
+    pub fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let mut reader: Mp4BitReader = Mp4BitReader::new(stream);
+        let header: MP4Header = MP4Header::from_binary(&mut reader)?;
+
+        if debug {
+            println!("{header}");
+        }
+
+        let Some(track) = header.tracks
+            .iter()
+            .find(|track| track.is_video() && matches!(&track.codec, b"jpeg" | b"mjpa" | b"raw ")) else {
+            let codecs: Vec<String> = header.tracks
+                .iter()
+                .filter(|track| track.is_video())
+                .map(|track| String::from_utf8_lossy(&track.codec).into_owned())
+                .collect();
+            bail!("No Motion JPEG or raw video track, video codecs found: {codecs:?}");
+        };
+
+        let track: Track = track.clone();
+        let samples: Vec<Sample> = track.sample_table.samples()?;
+
+        let mp4: Self = Self {
+            reader,
+            track,
+            samples,
+            sample_index: 0,
+        };
+
+        if debug {
+            println!("{mp4}");
+        }
+
+        Ok(mp4)
+    }
+
+    fn decode(&mut self, sample: Sample) -> Result<Box<BMP>> {
+        if sample.offset.saturating_add(sample.size as u64) > self.reader.length()? {
+            bail!("MP4 sample at {:X} runs past the end of the file", sample.offset);
+        }
+
+        self.reader.seek(sample.offset)?;
+        let bytes: Vec<u8> = self.reader.read_bytes(sample.size as usize)?;
+
+        match &self.track.codec {
+            b"raw " => self.decode_raw(&bytes),
+            _ => Ok(from_bytes(bytes, ImageType::JPEG, false)?.to_bmp()),
+        }
+    }
+
+    // Uncompressed QuickTime frames, 24 bits RGB or 32 bits ARGB, rows being possibly padded
+    fn decode_raw(&self, bytes: &[u8]) -> Result<Box<BMP>> {
+        let width: usize = self.track.width as usize;
+        let height: usize = self.track.height as usize;
+
+        let bytes_per_pixel: usize = match self.track.depth {
+            24 => 3,
+            32 => 4,
+            depth => bail!("Raw video depth {depth} not supported"),
+        };
+
+        if height == 0 || bytes.len() / height < width * bytes_per_pixel {
+            bail!("Raw video sample too small for a {width}x{height} frame");
+        }
+
+        let stride: usize = bytes.len() / height;
+        let offset: usize = bytes_per_pixel - 3;
+
+        Ok(Box::new(BMP::from_pixels(self.track.width as u32, self.track.height as u32, |x, y| {
+            let index: usize = y * stride + x * bytes_per_pixel + offset;
+            (bytes[index], bytes[index + 1], bytes[index + 2])
+        })))
+    }
+}
+
+impl Video for MP4 {
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let Some(&sample) = self.samples.get(self.sample_index) else {
+            return Ok(None);
+        };
+
+        let index: usize = self.sample_index;
+        self.sample_index += 1;
+
+        Ok(Some(Frame {
+            image: self.decode(sample)?,
+            index,
+            timestamp: sample.timestamp(self.track.timescale),
+            duration: sample.length(self.track.timescale),
+        }))
+    }
+
+    fn seek_frame(&mut self, index: usize) -> Result<()> {
+        if index >= self.samples.len() {
+            self.sample_index = self.samples.len();
+            return Ok(());
+        }
+
+        self.sample_index = (0..=index)
+            .rev()
+            .find(|&i| self.samples[i].sync)
+            .unwrap_or(0);
+
+        Ok(())
+    }
+
+    fn seek_time(&mut self, timestamp: Duration) -> Result<()> {
+        let time: u64 = (timestamp.as_secs_f64() * self.track.timescale as f64) as u64;
+
+        // Last sample starting at or before the time
+        let index: usize = self.samples
+            .partition_point(|sample| sample.time <= time)
+            .saturating_sub(1);
+
+        match self.samples.last() {
+            Some(last) if time >= last.time + last.duration as u64 => self.seek_frame(self.samples.len()),
+            _ => self.seek_frame(index),
+        }
+    }
+}
+
+impl fmt::Display for MP4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "played track: {}\n", self.track.id)?;
+        write!(f, "samples: {}\n", self.samples.len())?;
+        write!(f, "sync samples: {}\n", self.samples.iter().filter(|sample| sample.sync).count())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::{bail, Result};
+use std::time::Duration;
+
+use crate::image::bit_reader::BitReader;
+use super::mp4_bit_reader::Mp4BitReader;
+
+// Location and timing of a sample, times being in the track timescale
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    pub offset: u64,
+    pub size: u32,
+    pub time: u64,
+    pub duration: u32,
+    pub sync: bool,
+}
+
+impl Sample {
+    pub fn timestamp(&self, timescale: u32) -> Duration {
+        Duration::from_secs_f64(self.time as f64 / timescale.max(1) as f64)
+    }
+
+    pub fn length(&self, timescale: u32) -> Duration {
+        Duration::from_secs_f64(self.duration as f64 / timescale.max(1) as f64)
+    }
+}
+
+// Content of the "stbl" box
+#[derive(Clone, Debug, Default)]
+pub struct SampleTable {
+    sample_sizes: Vec<u32>,
+    chunk_offsets: Vec<u64>,
+    // First chunk (1-based) and samples per chunk
+    sample_to_chunk: Vec<(u32, u32)>,
+    // Samples count and sample duration
+    time_to_sample: Vec<(u32, u32)>,
+    // Sync samples (1-based), every sample being a sync one when the box is absent
+    sync_samples: Option<Vec<u32>>,
+}
+
+impl SampleTable {
+    // Full boxes start with a version and flags
+    fn read_version(reader: &mut Mp4BitReader) -> Result<u8> {
+        let version: u8 = reader.read_byte()?;
+        reader.read_bytes(3)?;
+        Ok(version)
+    }
+
+    pub fn read_stsz(&mut self, reader: &mut Mp4BitReader) -> Result<()> {
+        Self::read_version(reader)?;
+        let sample_size: u32 = reader.read_double()?;
+        let sample_count: u32 = reader.read_double()?;
+
+        // Samples of a single size are not listed, so their count is only checked against the file
+        if sample_count as u64 * sample_size as u64 > reader.length()? {
+            bail!("{sample_count} samples of {sample_size} bytes don't fit in the file");
+        }
+
+        self.sample_sizes = match sample_size {
+            0 => (0..sample_count).map(|_| reader.read_double()).collect::<Result<_>>()?,
+            _ => vec![sample_size; sample_count as usize],
+        };
+
+        Ok(())
+    }
+
+    pub fn read_stco(&mut self, reader: &mut Mp4BitReader, large: bool) -> Result<()> {
+        Self::read_version(reader)?;
+        let entry_count: u32 = reader.read_double()?;
+
+        self.chunk_offsets = (0..entry_count)
+            .map(|_| if large { reader.read_u64() } else { reader.read_double().map(|offset| offset as u64) })
+            .collect::<Result<_>>()?;
+
+        Ok(())
+    }
+
+    pub fn read_stsc(&mut self, reader: &mut Mp4BitReader) -> Result<()> {
+        Self::read_version(reader)?;
+        let entry_count: u32 = reader.read_double()?;
+
+        for _ in 0..entry_count {
+            let first_chunk: u32 = reader.read_double()?;
+            let samples_per_chunk: u32 = reader.read_double()?;
+            let _sample_description_index: u32 = reader.read_double()?;
+            self.sample_to_chunk.push((first_chunk, samples_per_chunk));
+        }
+
+        Ok(())
+    }
+
+    pub fn read_stts(&mut self, reader: &mut Mp4BitReader) -> Result<()> {
+        Self::read_version(reader)?;
+        let entry_count: u32 = reader.read_double()?;
+
+        for _ in 0..entry_count {
+            let sample_count: u32 = reader.read_double()?;
+            let sample_delta: u32 = reader.read_double()?;
+            self.time_to_sample.push((sample_count, sample_delta));
+        }
+
+        Ok(())
+    }
+
+    pub fn read_stss(&mut self, reader: &mut Mp4BitReader) -> Result<()> {
+        Self::read_version(reader)?;
+        let entry_count: u32 = reader.read_double()?;
+
+        self.sync_samples = Some((0..entry_count).map(|_| reader.read_double()).collect::<Result<_>>()?);
+
+        Ok(())
+    }
+
+    pub fn samples(&self) -> Result<Vec<Sample>> {
+        let mut samples: Vec<Sample> = Vec::with_capacity(self.sample_sizes.len());
+
+        // Sample offsets, from the chunk offsets and the number of samples in each chunk
+        for (chunk, &chunk_offset) in self.chunk_offsets.iter().enumerate() {
+            let chunk_number: u32 = chunk as u32 + 1;
+            let Some(&(_, samples_per_chunk)) = self.sample_to_chunk
+                .iter()
+                .rev()
+                .find(|(first_chunk, _)| *first_chunk <= chunk_number) else {
+                bail!("Chunk {chunk_number} is missing from the sample to chunk table");
+            };
+
+            let mut offset: u64 = chunk_offset;
+
+            for _ in 0..samples_per_chunk {
+                let Some(&size) = self.sample_sizes.get(samples.len()) else {
+                    break;
+                };
+
+                samples.push(Sample {
+                    offset,
+                    size,
+                    time: 0,
+                    duration: 0,
+                    sync: self.sync_samples.is_none(),
+                });
+                offset = offset.saturating_add(size as u64);
+            }
+        }
+
+        if samples.len() != self.sample_sizes.len() {
+            bail!("Chunks hold {} samples out of {}", samples.len(), self.sample_sizes.len());
+        }
+
+        // Sample times, from the run lengths of sample durations
+        let mut time: u64 = 0;
+        let mut durations = self.time_to_sample
+            .iter()
+            .flat_map(|&(count, delta)| std::iter::repeat_n(delta, count as usize));
+
+        for sample in samples.iter_mut() {
+            let duration: u32 = durations.next().unwrap_or(0);
+            sample.time = time;
+            sample.duration = duration;
+            time += duration as u64;
+        }
+
+        for &number in self.sync_samples.iter().flatten() {
+            if let Some(sample) = number.checked_sub(1).and_then(|i| samples.get_mut(i as usize)) {
+                sample.sync = true;
+            }
+        }
+
+        Ok(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::image::bit_reader::Source;
+    use super::*;
+
+    fn reader(values: &[u32]) -> Mp4BitReader {
+        let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_be_bytes()).collect();
+        let source: Box<dyn Source> = Box::new(Cursor::new(bytes));
+        Mp4BitReader::new(BufReader::new(source))
+    }
+
+    #[test]
+    fn samples_are_located_in_chunks() {
+        let mut table: SampleTable = SampleTable::default();
+        table.read_stsz(&mut reader(&[0, 0, 3, 10, 20, 30])).unwrap();
+        table.read_stco(&mut reader(&[0, 2, 100, 500]), false).unwrap();
+        table.read_stsc(&mut reader(&[0, 2, 1, 2, 1, 2, 1, 1])).unwrap();
+        table.read_stts(&mut reader(&[0, 1, 3, 512])).unwrap();
+        table.read_stss(&mut reader(&[0, 1, 3])).unwrap();
+
+        let samples: Vec<Sample> = table.samples().unwrap();
+        let layout: Vec<(u64, u32, u64, bool)> = samples.iter().map(|sample| (sample.offset, sample.size, sample.time, sample.sync)).collect();
+
+        assert_eq!(layout, [(100, 10, 0, false), (110, 20, 512, false), (500, 30, 1024, true)]);
+        assert_eq!(samples[2].timestamp(1024), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn fixed_sample_sizes_larger_than_the_file_are_rejected() {
+        let mut table: SampleTable = SampleTable::default();
+
+        assert!(table.read_stsz(&mut reader(&[0, 1000, u32::MAX])).is_err());
+        assert!(table.read_stsz(&mut reader(&[0, 1, 4])).is_ok());
+        assert_eq!(table.sample_sizes, [1; 4]);
+    }
+}
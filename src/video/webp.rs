@@ -0,0 +1,97 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::time::Duration;
+
+use crate::image::bit_reader::Stream;
+use crate::image::webp::animation::{Animation, AnimationFrame};
+use crate::image::webp::WEBP;
+use crate::image::Image;
+use super::{Frame, Video};
+
+#[derive(Debug)]
+pub struct AnimatedWebP {
+    animation: Animation,
+    // Start of each frame
+    timestamps: Vec<Duration>,
+    frame_index: usize,
+}
+
+impl AnimatedWebP {
+    pub fn from_stream(stream: Stream, debug: bool) -> Result<Self> {
+        let webp: WEBP = WEBP::from_stream(stream, debug)?;
+
+        let Some(animation) = webp.animation else {
+            bail!("WebP file is not animated");
+        };
+
+        let mut timestamps: Vec<Duration> = Vec::with_capacity(animation.frames.len());
+        let mut timestamp: Duration = Duration::ZERO;
+
+        for frame in animation.frames.iter() {
+            timestamps.push(timestamp);
+            timestamp += frame.duration;
+        }
+
+        Ok(Self {
+            animation,
+            timestamps,
+            frame_index: 0,
+        })
+    }
+}
+
+impl Video for AnimatedWebP {
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        if self.frame_index >= self.animation.frames.len() {
+            return Ok(None);
+        }
+
+        let index: usize = self.frame_index;
+        self.animation.draw_frame(index, false)?;
+        self.frame_index += 1;
+
+        Ok(Some(Frame {
+            image: Box::new(self.animation.to_image()),
+            index,
+            timestamp: self.timestamps[index],
+            duration: self.animation.frames[index].duration,
+        }))
+    }
+
+    // Frames build on the previous ones, which are drawn again from the last key frame
+    fn seek_frame(&mut self, index: usize) -> Result<()> {
+        let index: usize = index.min(self.animation.frames.len());
+        let start: usize = (0..index).rev().find(|&previous| self.animation.frames[previous].key_frame).unwrap_or(index);
+
+        for previous in start..index {
+            self.animation.draw_frame(previous, false)?;
+        }
+
+        self.frame_index = index;
+
+        Ok(())
+    }
+
+    fn seek_time(&mut self, timestamp: Duration) -> Result<()> {
+        let index: Option<usize> = self.animation.frames
+            .iter()
+            .zip(self.timestamps.iter())
+            .position(|(frame, &start): (&AnimationFrame, &Duration)| timestamp < start + frame.duration);
+
+        self.seek_frame(index.unwrap_or(self.animation.frames.len()))
+    }
+
+    // Loop count is the number of plays, unlike the one of GIF files
+    fn plays(&self) -> Option<u32> {
+        match self.animation.loop_count {
+            0 => None,
+            loop_count => Some(loop_count as u32),
+        }
+    }
+}
+
+impl fmt::Display for AnimatedWebP {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.animation)
+    }
+}
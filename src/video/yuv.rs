@@ -0,0 +1,265 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use crate::image::bmp::BMP;
+use crate::image::bmp::header::BMPHeader;
+use crate::image::mcu::{ColorMatrix, ColorRange, MCU};
+use super::{Frame, Video};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize)]
+pub enum PixelFormat {
+    I420,
+    NV12,
+    YUYV,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct YUVFormat {
+    pub width: u16,
+    pub height: u16,
+    pub pixel_format: PixelFormat,
+    pub frame_rate: f32,
+    pub color_matrix: ColorMatrix,
+    pub color_range: ColorRange,
+}
+
+impl YUVFormat {
+    fn chroma_width(&self) -> usize {
+        (self.width as usize).div_ceil(2)
+    }
+
+    fn chroma_height(&self) -> usize {
+        (self.height as usize).div_ceil(2)
+    }
+
+    pub fn frame_size(&self) -> usize {
+        let luma_size: usize = self.width as usize * self.height as usize;
+
+        match self.pixel_format {
+            PixelFormat::I420 |
+            PixelFormat::NV12 => luma_size + 2 * self.chroma_width() * self.chroma_height(),
+            PixelFormat::YUYV => 4 * self.chroma_width() * self.height as usize,
+        }
+    }
+
+    // Returns the Y, U and V samples of the pixel
+    fn sample(&self, frame: &[u8], x: usize, y: usize) -> (u8, u8, u8) {
+        let width: usize = self.width as usize;
+        let luma_size: usize = width * self.height as usize;
+        let chroma_index: usize = (y / 2) * self.chroma_width() + x / 2;
+
+        match self.pixel_format {
+            PixelFormat::I420 => {
+                let chroma_size: usize = self.chroma_width() * self.chroma_height();
+
+                (frame[y * width + x], frame[luma_size + chroma_index], frame[luma_size + chroma_size + chroma_index])
+            },
+            PixelFormat::NV12 => {
+                (frame[y * width + x], frame[luma_size + 2 * chroma_index], frame[luma_size + 2 * chroma_index + 1])
+            },
+            PixelFormat::YUYV => {
+                let macropixel: usize = y * 4 * self.chroma_width() + (x / 2) * 4;
+
+                (frame[macropixel + (x % 2) * 2], frame[macropixel + 1], frame[macropixel + 3])
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct YUV {
+    format: YUVFormat,
+    reader: BufReader<File>,
+    frame_count: usize,
+    frame_index: usize,
+    frame_duration: Duration,
+}
+
+impl YUV {
+    pub fn from_stream(reader: BufReader<File>, length: u64, format: YUVFormat, debug: bool) -> Result<Self> {
+        if format.width == 0 || format.height == 0 {
+            bail!("Raw YUV frames can't be empty");
+        }
+
+        if !format.frame_rate.is_finite() || format.frame_rate <= 0.0 {
+            bail!("Raw YUV frame rate must be positive");
+        }
+
+        // Frame rates so high or so low that a frame can't last a representable time are rejected as well
+        let frame_duration: Duration = match Duration::try_from_secs_f32(1.0 / format.frame_rate) {
+            Ok(duration) if !duration.is_zero() => duration,
+            _ => bail!("Raw YUV frame rate of {} is invalid", format.frame_rate),
+        };
+
+        let frame_size: u64 = format.frame_size() as u64;
+
+        if !length.is_multiple_of(frame_size) {
+            eprintln!("Raw YUV file has {} trailing bytes, ignoring them", length % frame_size);
+        }
+
+        let yuv: Self = Self {
+            format,
+            reader,
+            frame_count: (length / frame_size) as usize,
+            frame_index: 0,
+            frame_duration,
+        };
+
+        if debug {
+            println!("{yuv}");
+        }
+
+        Ok(yuv)
+    }
+
+    fn to_mcus(&self, frame: &[u8]) -> Vec<MCU> {
+        let width: usize = self.format.width as usize;
+        let height: usize = self.format.height as usize;
+        let mcu_width: usize = width.div_ceil(8);
+        let mcu_height: usize = height.div_ceil(8);
+        let mut mcus: Vec<MCU> = vec![MCU::default(); mcu_width * mcu_height];
+
+        for y in 0..height {
+            for x in 0..width {
+                let mcu_index: usize = (y / 8) * mcu_width + x / 8;
+                let pixel_index: usize = (y % 8) * 8 + x % 8;
+                let (luma, u, v): (u8, u8, u8) = self.format.sample(frame, x, y);
+                let mcu: &mut MCU = &mut mcus[mcu_index];
+
+                // Same level shift as the one undone by the JPEG inverse DCT
+                mcu.component_mut(0).expect("Should exist")[pixel_index] = luma as i32 - 128;
+                mcu.component_mut(1).expect("Should exist")[pixel_index] = u as i32 - 128;
+                mcu.component_mut(2).expect("Should exist")[pixel_index] = v as i32 - 128;
+            }
+        }
+
+        for mcu in mcus.iter_mut() {
+            mcu.ycbcr_to_rgb_with(self.format.color_matrix, self.format.color_range);
+        }
+
+        mcus
+    }
+}
+
+impl Video for YUV {
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        if self.frame_index >= self.frame_count {
+            return Ok(None);
+        }
+
+        let mut frame: Vec<u8> = vec![0u8; self.format.frame_size()];
+        self.reader.read_exact(&mut frame)?;
+
+        let header: BMPHeader = BMPHeader::new(self.format.width as u32, self.format.height as u32);
+        let duration: Duration = self.frame_duration;
+        let index: usize = self.frame_index;
+        self.frame_index += 1;
+
+        Ok(Some(Frame {
+            image: Box::new(BMP::new(header, self.to_mcus(&frame))),
+            index,
+            timestamp: duration * index as u32,
+            duration,
+        }))
+    }
+
+    fn seek_frame(&mut self, index: usize) -> Result<()> {
+        self.frame_index = index.min(self.frame_count);
+        self.reader.seek(SeekFrom::Start((self.frame_index * self.format.frame_size()) as u64))?;
+
+        Ok(())
+    }
+
+    fn seek_time(&mut self, timestamp: Duration) -> Result<()> {
+        self.seek_frame((timestamp.as_secs_f64() * self.format.frame_rate as f64) as usize)
+    }
+}
+
+impl fmt::Display for YUV {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "width: {}\n", self.format.width)?;
+        write!(f, "height: {}\n", self.format.height)?;
+        write!(f, "pixel_format: {:?}\n", self.format.pixel_format)?;
+        write!(f, "frame_rate: {}\n", self.format.frame_rate)?;
+        write!(f, "color_matrix: {:?}\n", self.format.color_matrix)?;
+        write!(f, "color_range: {:?}\n", self.format.color_range)?;
+        write!(f, "frame_size: {}\n", self.format.frame_size())?;
+        write!(f, "frame_count: {}\n", self.frame_count)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn format(pixel_format: PixelFormat, frame_rate: f32) -> YUVFormat {
+        YUVFormat {
+            width: 3,
+            height: 3,
+            pixel_format,
+            frame_rate,
+            color_matrix: ColorMatrix::BT601,
+            color_range: ColorRange::FULL,
+        }
+    }
+
+    fn open(name: &str, bytes: &[u8]) -> (BufReader<File>, u64) {
+        let path: PathBuf = std::env::temp_dir().join(format!("term_viewer_{}_{name}.yuv", std::process::id()));
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+
+        let file: File = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        (BufReader::new(file), bytes.len() as u64)
+    }
+
+    #[test]
+    fn chroma_of_odd_sizes_is_rounded_up() {
+        assert_eq!(format(PixelFormat::I420, 25.0).frame_size(), 9 + 2 * 4);
+        assert_eq!(format(PixelFormat::NV12, 25.0).frame_size(), 9 + 2 * 4);
+        assert_eq!(format(PixelFormat::YUYV, 25.0).frame_size(), 4 * 2 * 3);
+    }
+
+    #[test]
+    fn samples_are_read_from_each_layout() {
+        let frame: Vec<u8> = (0..24).collect();
+
+        // Last pixel of the second row shares the chroma of the second column of macropixels
+        assert_eq!(format(PixelFormat::I420, 25.0).sample(&frame, 2, 1), (5, 10, 14));
+        assert_eq!(format(PixelFormat::NV12, 25.0).sample(&frame, 2, 1), (5, 11, 12));
+        assert_eq!(format(PixelFormat::YUYV, 25.0).sample(&frame, 2, 1), (12, 13, 15));
+    }
+
+    #[test]
+    fn frames_are_converted_to_rgb() {
+        let mut bytes: Vec<u8> = vec![128; 2 * 17];
+        bytes[17..26].fill(255);
+        let (reader, length): (BufReader<File>, u64) = open("frames", &bytes);
+        let mut yuv: YUV = YUV::from_stream(reader, length, format(PixelFormat::I420, 4.0), false).unwrap();
+
+        let first: Frame = yuv.next_frame().unwrap().unwrap();
+        let second: Frame = yuv.next_frame().unwrap().unwrap();
+
+        assert_eq!(first.image.pixel_color(1, 1), (128, 128, 128));
+        assert_eq!(second.image.pixel_color(1, 1), (255, 255, 255));
+        assert_eq!(second.timestamp, Duration::from_millis(250));
+        assert!(yuv.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_frame_rates_are_rejected() {
+        for frame_rate in [0.0, -1.0, f32::NAN, f32::INFINITY, 1e30, 1e-30] {
+            let (reader, length): (BufReader<File>, u64) = open("rates", &[128; 17]);
+            assert!(YUV::from_stream(reader, length, format(PixelFormat::I420, frame_rate), false).is_err());
+        }
+    }
+}